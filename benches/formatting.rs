@@ -0,0 +1,192 @@
+//! Parsing and formatting of large payloads, e.g. pushes of hundreds of commits or PRs with a huge
+//! description.
+
+use std::fmt::Write;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde_json::{json, Value};
+
+use prololo::{
+    bot::{
+        format_event,
+        message_builder::{MessageBuilder, MessageBuilderPool},
+    },
+    config::GitHubConfig,
+    webhooks::{Event, GitHubEventType, RawDelivery, RawEvent},
+};
+
+fn user() -> Value {
+    json!({
+        "login": "alice",
+        "id": 42,
+        "html_url": "https://github.com/alice",
+        "avatar_url": "https://avatars.githubusercontent.com/u/42",
+        "type": "User",
+    })
+}
+
+fn repository() -> Value {
+    json!({
+        "name": "site",
+        "full_name": "prologin/site",
+        "html_url": "https://github.com/prologin/site",
+    })
+}
+
+/// Some Markdown, with characters escaped in HTML
+fn long_text(paragraphs: usize) -> String {
+    "Fixes the **<main>** loop & the `parse_payload` function, see [the docs](https://prologin.org/docs) \
+     and _the \"issue\"_ for details.\n\n"
+        .repeat(paragraphs)
+}
+
+fn push_payload(commits: usize) -> String {
+    let commit = |i: usize| {
+        json!({
+            "id": format!("{:040x}", i),
+            "url": format!("https://github.com/prologin/site/commit/{:040x}", i),
+            "distinct": true,
+            "message": format!("Commit number {}\n\n{}", i, long_text(5)),
+            "modified": ["prologin/settings.py", "prologin/urls.py"],
+        })
+    };
+
+    json!({
+        "repository": repository(),
+        "sender": user(),
+        "commits": (0..commits).map(commit).collect::<Vec<_>>(),
+        "head_commit": commit(commits - 1),
+        "forced": false,
+        "created": false,
+        "ref": "refs/heads/main",
+        "before": format!("{:040x}", 0),
+        "after": format!("{:040x}", commits - 1),
+        "compare": "https://github.com/prologin/site/compare/0000000...000012b",
+    })
+    .to_string()
+}
+
+fn pull_request_payload(body: &str) -> String {
+    json!({
+        "repository": repository(),
+        "sender": user(),
+        "action": "opened",
+        "assignee": null,
+        "pull_request": {
+            "number": 42,
+            "html_url": "https://github.com/prologin/site/pull/42",
+            "title": "Rewrite the forum",
+            "body": body,
+            "user": user(),
+            "requested_reviewers": [],
+            "base": { "ref": "main" },
+            "head": { "ref": "forum" },
+        },
+    })
+    .to_string()
+}
+
+fn parsing(c: &mut Criterion) {
+    let push = push_payload(300);
+    c.bench_function("parse push of 300 commits", |b| {
+        b.iter_batched(
+            || push.clone(),
+            |push| GitHubEventType::Push.parse_payload(push).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("parse delivery of a push of 300 commits", |b| {
+        b.iter_batched(
+            || RawDelivery {
+                id: None,
+                event: RawEvent::GitHub {
+                    event_type: GitHubEventType::Push,
+                    payload: push.clone(),
+                },
+            },
+            |delivery| delivery.parse(&[]).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    let pull_request = pull_request_payload(&long_text(5000));
+    c.bench_function("parse pull request with a huge body", |b| {
+        b.iter_batched(
+            || pull_request.clone(),
+            |pull_request| {
+                GitHubEventType::PullRequest
+                    .parse_payload(pull_request)
+                    .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn formatting(c: &mut Criterion) {
+    let config = GitHubConfig::default();
+    let push = push_payload(300);
+    c.bench_function("format push of 300 commits", |b| {
+        b.iter_batched(
+            || Event::GitHub(GitHubEventType::Push.parse_payload(push.clone()).unwrap()),
+            |event| format_event(event, &config).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    let text = long_text(5000);
+    c.bench_function("render huge markdown text", |b| {
+        b.iter(|| {
+            let mut message = MessageBuilder::new();
+            message.markdown(&text);
+            message.into_bodies()
+        })
+    });
+    c.bench_function("escape huge text", |b| {
+        b.iter(|| {
+            let mut message = MessageBuilder::new();
+            write!(message, "{}", text).unwrap();
+            message.into_bodies()
+        })
+    });
+}
+
+fn pooling(c: &mut Criterion) {
+    let url = "https://github.com/prologin/site/pull/42".parse().unwrap();
+    let build = |message: &mut MessageBuilder, i: usize| {
+        message.tag("site", None);
+        write!(message, " ").unwrap();
+        message.main_link(format_args!("PR #{}: Rewrite the forum", i), &url);
+        write!(message, " opened by alice").unwrap();
+    };
+
+    c.bench_function("format 1000 messages", |b| {
+        b.iter(|| {
+            (0..1000)
+                .map(|i| {
+                    let mut message = MessageBuilder::new();
+                    build(&mut message, i);
+                    message.into_bodies().0.len()
+                })
+                .sum::<usize>()
+        })
+    });
+    let mut pool = MessageBuilderPool::default();
+    c.bench_function("format 1000 messages with a pool", |b| {
+        b.iter(|| {
+            (0..1000)
+                .map(|i| {
+                    let mut message = pool.take();
+                    build(&mut message, i);
+                    let len = message.bodies().0.len();
+                    pool.give_back(message);
+                    len
+                })
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, parsing, formatting, pooling);
+criterion_main!(benches);
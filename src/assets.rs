@@ -0,0 +1,53 @@
+//! Files shipped inside the binary: the default message templates and the locales. Each of them
+//! can be replaced by a file with the same path in [`ProloloConfig::assets_dir`], so that a
+//! deployment stays a single binary and its configuration file.
+//!
+//! [`ProloloConfig::assets_dir`]: crate::config::ProloloConfig::assets_dir
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Embedded;
+
+/// Reads an asset, e.g. `templates/incident.txt`, from `dir` if it has a file with this path
+pub fn load(dir: Option<&Path>, path: &str) -> anyhow::Result<String> {
+    if let Some(dir) = dir {
+        let file = dir.join(path);
+        if file.is_file() {
+            return fs::read_to_string(&file)
+                .with_context(|| format!("couldn't read asset {}", file.display()));
+        }
+    }
+
+    let file = Embedded::get(path).with_context(|| format!("unknown asset {}", path))?;
+    String::from_utf8(file.data.into_owned())
+        .with_context(|| format!("asset {} isn't valid UTF-8", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("prololo-assets-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let embedded = load(Some(&dir), "templates/incident.txt").unwrap();
+        assert!(embedded.starts_with("Incident opened by {sender}"));
+
+        fs::create_dir_all(dir.join("templates")).unwrap();
+        fs::write(dir.join("templates/incident.txt"), "{title}").unwrap();
+        assert_eq!(
+            load(Some(&dir), "templates/incident.txt").unwrap(),
+            "{title}"
+        );
+        assert!(load(Some(&dir), "templates/missing.txt").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+//! Snapshots of everything the bot stores on disk, to migrate it to another host without losing
+//! its Matrix session and device.
+//!
+//! The bot must be stopped while backing up or restoring, the Matrix store can't be copied while
+//! it is in use.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use tracing::info;
+
+/// Name of the copy of the Matrix session file inside a backup
+const BACKUP_SESSION: &str = "matrix-session.yaml";
+/// Name of the copy of the state directory inside a backup
+const BACKUP_STATE: &str = "state";
+
+fn copy_recursively(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)
+            .with_context(|| format!("couldn't copy {} to {}", from.display(), to.display()))?;
+    }
+
+    Ok(())
+}
+
+/// A sibling of `path` used to prepare it, renamed to `path` once complete so that an interrupted
+/// copy can't be mistaken for a complete one.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Where the backup of a bot goes: `path` itself if it is the only one of the configuration,
+/// otherwise a subdirectory named after its path prefix.
+pub fn profile_backup_path(path: &Path, prefix: &str, single: bool) -> PathBuf {
+    let name = prefix.trim_matches('/').replace('/', "-");
+    if single {
+        path.to_path_buf()
+    } else if name.is_empty() {
+        path.join("root")
+    } else {
+        path.join(name)
+    }
+}
+
+/// Copies the session file and the state directory to the new directory `destination`.
+pub fn backup(session_file: &Path, state_dir: &Path, destination: &Path) -> anyhow::Result<()> {
+    if destination.exists() {
+        bail!("{} already exists", destination.display());
+    }
+    if !session_file.is_file() {
+        bail!(
+            "no session to back up, {} is missing",
+            session_file.display()
+        );
+    }
+
+    let tmp = tmp_path(destination);
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    fs::create_dir_all(&tmp)?;
+    fs::copy(session_file, tmp.join(BACKUP_SESSION))?;
+    copy_recursively(state_dir, &tmp.join(BACKUP_STATE))?;
+    fs::rename(&tmp, destination)?;
+
+    info!("backed up the bot to {}", destination.display());
+    Ok(())
+}
+
+/// Restores a backup made by [`backup`], refusing to overwrite an existing session or state.
+pub fn restore(source: &Path, session_file: &Path, state_dir: &Path) -> anyhow::Result<()> {
+    let backup_session = source.join(BACKUP_SESSION);
+    let backup_state = source.join(BACKUP_STATE);
+    if !backup_session.is_file() || !backup_state.is_dir() {
+        bail!("{} isn't a backup of the bot", source.display());
+    }
+    for existing in [session_file, state_dir] {
+        if existing.exists() {
+            bail!(
+                "{} already exists, move it away before restoring",
+                existing.display()
+            );
+        }
+    }
+
+    let tmp = tmp_path(state_dir);
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    copy_recursively(&backup_state, &tmp)?;
+    fs::rename(&tmp, state_dir)?;
+
+    // the session goes last, the bot would otherwise start with a session but an empty store
+    let tmp = tmp_path(session_file);
+    fs::copy(&backup_session, &tmp)?;
+    fs::rename(&tmp, session_file)?;
+
+    info!("restored the bot from {}", source.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_restore() {
+        let root = std::env::temp_dir().join(format!("prololo-backup-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let session = root.join("old").join("matrix-session.yaml");
+        let state_dir = root.join("old").join("state");
+        fs::create_dir_all(state_dir.join("store")).unwrap();
+        fs::write(&session, "user_id: '@prololo:prologin.org'").unwrap();
+        fs::write(state_dir.join("state.yaml"), "incident: ~").unwrap();
+        fs::write(state_dir.join("store").join("db"), "sled").unwrap();
+
+        let destination = root.join("backup");
+        backup(&session, &state_dir, &destination).unwrap();
+        assert!(backup(&session, &state_dir, &destination).is_err());
+
+        let new_session = root.join("new").join("matrix-session.yaml");
+        let new_state_dir = root.join("new").join("state");
+        fs::create_dir_all(root.join("new")).unwrap();
+        restore(&destination, &new_session, &new_state_dir).unwrap();
+        assert_eq!(
+            fs::read_to_string(&new_session).unwrap(),
+            "user_id: '@prololo:prologin.org'"
+        );
+        assert_eq!(
+            fs::read_to_string(new_state_dir.join("store").join("db")).unwrap(),
+            "sled"
+        );
+        assert!(restore(&destination, &new_session, &new_state_dir).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,42 +1,164 @@
 use std::{
+    collections::HashMap,
+    fmt::Write,
     fs::File,
     io::{BufReader, BufWriter},
-    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
+use chrono::Utc;
 use matrix_sdk::{
     room::Room,
     ruma::{
-        events::{room::member::MemberEventContent, AnyMessageEventContent, StrippedStateEvent},
-        RoomId,
+        events::{
+            room::{member::MemberEventContent, message::MessageEventContent},
+            AnyMessageEventContent, StrippedStateEvent, SyncMessageEvent, SyncStateEvent,
+        },
+        EventId, RoomId, UserId,
     },
-    Client, ClientConfig, Session, SyncSettings,
+    uuid::Uuid,
+    Client, ClientConfig, LoopCtrl, RequestConfig, Session, SyncSettings,
 };
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{mpsc::UnboundedReceiver, oneshot, Mutex};
 use tracing::{debug, error, info, trace, warn};
 
-use crate::{config::ProloloConfig, webhooks::Event};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[cfg(any(test, feature = "chaos"))]
+use crate::chaos::{self, ChaosSender};
+use crate::{
+    config::{GitHubConfig, ProloloConfig},
+    github_api::GitHubApi,
+    webhooks::{rules::Overrides, Delivery, Event},
+};
+
+pub mod activity;
+use activity::Activity;
+
+pub(crate) mod archive;
+use archive::{compact_archive, Archive, ArchivedKind, ArchivedMessage};
+
+mod args;
+
+mod audit_log;
+use audit_log::relay_audit_log;
+
+mod breaker;
+use breaker::Breakers;
+
+mod canary;
+
+mod codeowners;
+
+mod debounce;
+use debounce::Debouncer;
+
+mod deliveries;
+use deliveries::monitor_deliveries;
+
+mod deployments;
+
+mod duplicates;
+use duplicates::Duplicates;
 
 mod emoji;
 
+mod enrich;
+use enrich::enrich_event;
+
+mod filter;
+use filter::Filters;
+
+mod find;
+
 mod github;
 use github::handle_github_event;
 
 mod handlers;
-use handlers::autojoin_authorized_rooms;
+use handlers::{archive_acks, autojoin_authorized_rooms, handle_commands, welcome_new_members};
+
+mod hooks;
+use hooks::Hooks;
+
+mod images;
+use images::attach_image;
+
+mod mail;
+use mail::handle_mail_event;
+
+mod incidents;
+
+mod leaderboard;
+use leaderboard::post_monthly_leaderboard;
 
 mod prolosite;
 use prolosite::handle_prolosite_event;
 
-mod message_builder;
+pub mod message_builder;
+
+mod pings;
+use pings::Pings;
+
+mod preferences;
 use message_builder::MessageBuilder;
 
+mod mentions;
+use mentions::process_mentions;
+
+pub mod metrics;
+use metrics::persist_metrics;
+
+mod mirrors;
+use mirrors::Mirrors;
+
+mod releases;
+use releases::remind_release_trains;
+
+mod reviews;
+use reviews::remind_pending_reviews;
+
+mod search;
+
+mod stale;
+use stale::post_weekly_stale_lists;
+
+pub mod state;
+use state::{SharedState, StateStore};
+
+mod timeline;
+
+mod trials;
+use trials::{Trial, Trials};
+
+mod two_factor;
+use two_factor::post_weekly_two_factor_reports;
+
 pub(crate) mod utils;
+use utils::transaction_id;
+
+/// Time during which a repo made public isn't announced again
+const PUBLICIZED_WINDOW: Duration = Duration::from_secs(10 * 60);
 
+#[derive(Clone)]
 pub struct Prololo {
     client: Client,
     config: ProloloConfig,
+    state: SharedState,
+    mirrors: Arc<Mirrors>,
+    filters: Arc<Filters>,
+    pings: Arc<Pings>,
+    duplicates: Arc<Duplicates>,
+    hooks: Arc<Hooks>,
+    trials: Arc<Trials>,
+    github: Arc<GitHubApi>,
+    archive: Arc<Archive>,
+    breakers: Arc<Breakers>,
+    activity: Arc<Activity>,
+    /// When the repos were last made public, GitHub announces it with two different events
+    publicized: Arc<std::sync::Mutex<HashMap<String, Instant>>>,
 }
 
 impl Prololo {
@@ -45,10 +167,69 @@ impl Prololo {
     ///
     /// The [`Client`] is only initialized, not ready to be used yet.
     pub fn new(config: ProloloConfig) -> anyhow::Result<Self> {
-        let client_config = ClientConfig::new().store_path(config.matrix_state_dir.join("store"));
+        let state = StateStore::load(config.matrix_state_dir.join("state.yaml"))
+            .context("couldn't load bot state")?;
+        Self::with_state(config, state)
+    }
+
+    /// Like [`Prololo::new`], with a state kept somewhere else than in the state directory.
+    pub fn with_state(config: ProloloConfig, state: StateStore) -> anyhow::Result<Self> {
+        // without a retry timeout, the requests to an unreachable homeserver are retried forever
+        let request_config = RequestConfig::new()
+            .timeout(config.timeouts.matrix_request())
+            .retry_timeout(config.timeouts.matrix());
+        let client_config = ClientConfig::new()
+            .store_path(config.matrix_state_dir.join("store"))
+            .request_config(request_config);
         let client = Client::new_with_config(config.matrix_homeserver.clone(), client_config)?;
 
-        Ok(Self { client, config })
+        let activity = Arc::new(Activity::new(Utc::now(), state.state.metrics.clone()));
+        let state = Arc::new(Mutex::new(state));
+
+        let mirrors = Arc::new(Mirrors::from_config(&config)?);
+        let filters = Arc::new(Filters::from_config(&config)?);
+        let pings = Arc::new(Pings::from_config(&config));
+        let duplicates = Arc::new(Duplicates::from_config(&config));
+        let hooks = Arc::new(Hooks::from_config(&config).context("couldn't load room hooks")?);
+        let trials = Arc::new(Trials::from_config(&config)?);
+        let github = Arc::new(
+            GitHubApi::new(&config.github, config.timeouts.http())
+                .context("couldn't build GitHub API client")?,
+        );
+        let archive = Arc::new(Archive::new(config.matrix_state_dir.join("archive.jsonl")));
+        let breakers = Arc::new(Breakers::new(config.circuit_breaker.clone()));
+
+        Ok(Self {
+            client,
+            config,
+            state,
+            mirrors,
+            filters,
+            pings,
+            duplicates,
+            hooks,
+            trials,
+            github,
+            archive,
+            breakers,
+            activity,
+            publicized: Default::default(),
+        })
+    }
+
+    /// The archive of the messages sent by the bot, shared with the admin endpoints.
+    pub fn archive(&self) -> Arc<Archive> {
+        self.archive.clone()
+    }
+
+    /// What the bot has been doing, shared with the status page.
+    pub fn activity(&self) -> Arc<Activity> {
+        self.activity.clone()
+    }
+
+    /// The state of the bot, saved to its [`state::StateBackend`].
+    pub fn state(&self) -> SharedState {
+        self.state.clone()
     }
 
     /// Loads session information from file, or creates it if no previous session is found.
@@ -65,12 +246,66 @@ impl Prololo {
             .values()
             .map(|room| room.id.clone())
             .collect();
+        let dm_users: Vec<UserId> = self.config.user_mapping.values().cloned().collect();
 
         self.client
             .register_event_handler({
                 move |ev: StrippedStateEvent<MemberEventContent>, client: Client, room: Room| {
                     let authorized_rooms = authorized_rooms.clone();
-                    async move { autojoin_authorized_rooms(ev, client, room, authorized_rooms).await }
+                    let dm_users = dm_users.clone();
+                    async move {
+                        autojoin_authorized_rooms(ev, client, room, authorized_rooms, dm_users)
+                            .await
+                    }
+                }
+            })
+            .await;
+
+        let config = self.config.clone();
+        let state = self.state.clone();
+        let archive = self.archive.clone();
+        let breakers = self.breakers.clone();
+        let activity = self.activity.clone();
+        let github = self.github.clone();
+        self.client
+            .register_event_handler({
+                move |ev: SyncMessageEvent<MessageEventContent>, client: Client, room: Room| {
+                    let config = config.clone();
+                    let state = state.clone();
+                    let archive = archive.clone();
+                    let breakers = breakers.clone();
+                    let activity = activity.clone();
+                    let github = github.clone();
+                    async move {
+                        handle_commands(
+                            ev, client, room, config, state, archive, breakers, activity, github,
+                        )
+                        .await
+                    }
+                }
+            })
+            .await;
+
+        let state = self.state.clone();
+        let archive = self.archive.clone();
+        self.client
+            .register_event_handler({
+                move |ev: SyncMessageEvent<MessageEventContent>, client: Client, room: Room| {
+                    let state = state.clone();
+                    let archive = archive.clone();
+                    async move { archive_acks(ev, client, room, state, archive).await }
+                }
+            })
+            .await;
+
+        let config = self.config.clone();
+        let state = self.state.clone();
+        self.client
+            .register_event_handler({
+                move |ev: SyncStateEvent<MemberEventContent>, client: Client, room: Room| {
+                    let config = config.clone();
+                    let state = state.clone();
+                    async move { welcome_new_members(ev, client, room, config, state).await }
                 }
             })
             .await;
@@ -82,48 +317,299 @@ impl Prololo {
     ///
     /// [`Prololo::init`] **must** be called before this function, otherwise the [`Client`] isn't
     /// logged in.
-    pub async fn run(&self, events: UnboundedReceiver<Event>) {
+    pub async fn run(&self, events: UnboundedReceiver<Delivery>) {
         debug!("running...");
 
-        let client = self.client.clone();
-        let config = self.config.clone();
-        tokio::task::spawn(async move { Self::receive_events(events, client, config).await });
+        let prololo = self.clone();
+        tokio::task::spawn(async move { prololo.receive_events(events).await });
 
-        self.client.sync(SyncSettings::default()).await
+        {
+            let activity = self.activity.clone();
+            let state = self.state.clone();
+            tokio::task::spawn(async move { persist_metrics(activity, state).await });
+        }
+
+        if self.config.leaderboard.monthly_post {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            let state = self.state.clone();
+            tokio::task::spawn(
+                async move { post_monthly_leaderboard(client, config, state).await },
+            );
+        }
+
+        if self.config.reviews.is_some() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            let state = self.state.clone();
+            let github = self.github.clone();
+            tokio::task::spawn(async move {
+                remind_pending_reviews(client, config, state, github).await
+            });
+        }
+
+        if self.config.stale.is_some() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            let github = self.github.clone();
+            tokio::task::spawn(
+                async move { post_weekly_stale_lists(client, config, github).await },
+            );
+        }
+
+        if self.config.audit_log.is_some() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            let state = self.state.clone();
+            tokio::task::spawn(async move { relay_audit_log(client, config, state).await });
+        }
+
+        if self.config.deliveries.is_some() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            tokio::task::spawn(async move { monitor_deliveries(client, config).await });
+        }
+
+        if self.config.two_factor.is_some() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            tokio::task::spawn(async move { post_weekly_two_factor_reports(client, config).await });
+        }
+
+        if !self.config.release_trains.is_empty() {
+            let client = self.client.clone();
+            let config = self.config.clone();
+            let github = self.github.clone();
+            tokio::task::spawn(async move { remind_release_trains(client, config, github).await });
+        }
+
+        if let Some(retention) = self.config.retention.clone() {
+            let archive = self.archive.clone();
+            let state_dir = self.config.matrix_state_dir.clone();
+            tokio::task::spawn(async move { compact_archive(archive, retention, state_dir).await });
+        }
+
+        #[cfg(any(test, feature = "chaos"))]
+        let chaos = self.config.chaos.as_ref();
+        let activity = &self.activity;
+        loop {
+            // the sync token is kept, the events aren't lost when the loop restarts
+            self.client
+                .sync_with_callback(SyncSettings::default(), |_| async move {
+                    activity.synced(Utc::now());
+                    #[cfg(any(test, feature = "chaos"))]
+                    if chaos::drop_sync(chaos) {
+                        return LoopCtrl::Break;
+                    }
+                    LoopCtrl::Continue
+                })
+                .await;
+        }
     }
 
-    async fn receive_events(
-        mut events: UnboundedReceiver<Event>,
-        client: Client,
-        config: ProloloConfig,
-    ) {
+    async fn receive_events(&self, mut events: UnboundedReceiver<Delivery>) {
+        let mut debouncer = Debouncer::new(&self.config.github);
         loop {
-            let event = match events.recv().await {
-                Some(event) => event,
+            let received = tokio::select! {
+                received = events.recv() => received,
+                _ = debouncer.next_close() => {
+                    for delivery in debouncer.flush(Some(tokio::time::Instant::now())) {
+                        self.receive_event(delivery).await;
+                    }
+                    continue;
+                }
+            };
+            let delivery = match received {
+                Some(delivery) => delivery,
                 None => {
+                    for delivery in debouncer.flush(None) {
+                        self.receive_event(delivery).await;
+                    }
                     info!("all channel senders were dropped, exiting receive loop");
                     break;
                 }
             };
-            debug!("received event: {:?}", event);
+            debug!("received event: {:?}", delivery);
+            self.activity.received(&delivery.event, Utc::now());
 
-            if let Err(e) = Self::handle_event(event, &client, &config).await {
-                warn!("encountered error while handling event: {}", e);
+            if let Some(delivery) = debouncer.push(delivery) {
+                self.receive_event(delivery).await;
             }
         }
     }
 
+    async fn receive_event(&self, mut delivery: Delivery) {
+        // the queue, if any, retries the failed events: they are only done once handled
+        let mut done = delivery.done.take();
+        let id = delivery.id.clone();
+        let result = self.handle_event(delivery, &mut done).await;
+        self.activity.handled(result.as_ref().err(), Utc::now());
+        match result {
+            Ok(()) => {
+                if let Some(done) = done {
+                    let _ = done.send(());
+                }
+            }
+            Err(e) => {
+                warn!("encountered error while handling event: {:#}", e);
+                if let Err(e) = self.report_error(id.as_deref(), &e).await {
+                    warn!("couldn't report error: {:#}", e);
+                }
+            }
+        }
+    }
+
+    /// Tells the errors room, if any, that an event couldn't be handled
+    async fn report_error(&self, id: Option<&str>, error: &anyhow::Error) -> anyhow::Result<()> {
+        let mut message = MessageBuilder::new();
+        message.tag("error", Some(emoji::WARNING));
+        write!(message, " couldn't handle event").unwrap();
+        if let Some(id) = id {
+            write!(message, " {}", id).unwrap();
+        }
+        write!(message, ": ").unwrap();
+        message.code();
+        write!(message, "{:#}", error).unwrap();
+        message.close_last();
+
+        self.send_to_errors_room(message).await
+    }
+
+    /// Tells the errors room, if any, that an event was about to be announced twice in a room,
+    /// which usually means that it has several webhooks
+    async fn report_duplicate(&self, room_id: &RoomId, plain: &str) -> anyhow::Result<()> {
+        let room_name = self
+            .config
+            .matrix_rooms
+            .iter()
+            .find(|(_, room)| &room.id == room_id)
+            .map_or_else(|| room_id.to_string(), |(name, _)| name.clone());
+
+        let mut message = MessageBuilder::new();
+        message.tag("duplicate", Some(emoji::WARNING));
+        write!(
+            message,
+            " the same message was about to be sent twice to room {} within {}s, check that its \
+             events aren't delivered by several webhooks (e.g. on the organization and on the \
+             repo). Further duplicates are dropped silently: ",
+            room_name,
+            self.duplicates.window().as_secs()
+        )
+        .unwrap();
+        message.code();
+        write!(message, "{}", plain.lines().next().unwrap_or_default()).unwrap();
+        message.close_last();
+
+        self.send_to_errors_room(message).await
+    }
+
+    async fn send_to_errors_room(&self, message: MessageBuilder) -> anyhow::Result<()> {
+        let room_name = match &self.config.errors_room {
+            Some(room_name) => room_name,
+            None => return Ok(()),
+        };
+        let room_id = self.config.room_by_name(room_name)?;
+        let room = self
+            .client
+            .get_joined_room(room_id)
+            .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+
+        room.send(AnyMessageEventContent::RoomMessage(message.build()), None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Handles an event, `done` is taken if its message is held back by a circuit breaker
     async fn handle_event(
-        event: Event,
-        client: &Client,
-        config: &ProloloConfig,
+        &self,
+        delivery: Delivery,
+        done: &mut Option<oneshot::Sender<()>>,
     ) -> anyhow::Result<()> {
-        let response = match event {
-            Event::GitHub(event) => handle_github_event(event)?,
-            Event::ProloSite(event) => handle_prolosite_event(event)?,
+        let Self {
+            client,
+            config,
+            state,
+            mirrors,
+            filters,
+            pings,
+            duplicates,
+            hooks,
+            trials,
+            github,
+            archive,
+            breakers,
+            publicized,
+            ..
+        } = self;
+        let Delivery {
+            id,
+            mut event,
+            overrides,
+            canary,
+            ..
+        } = delivery;
+
+        let mut review_request = None;
+        if let Event::GitHub(event) = &event {
+            if let Some(repo) = github::publicized_repo(event) {
+                let mut publicized = publicized.lock().unwrap();
+                publicized.retain(|_, time| time.elapsed() < PUBLICIZED_WINDOW);
+                if publicized
+                    .insert(repo.to_string(), Instant::now())
+                    .is_some()
+                {
+                    debug!("repo {} was already announced as publicized", repo);
+                    return Ok(());
+                }
+            }
+
+            leaderboard::record_event(event, state).await?;
+            if deployments::record_event(event, state).await? {
+                if let Err(e) = deployments::update_status_message(client, config, state).await {
+                    warn!("couldn't update deployments status message: {:#}", e);
+                }
+            }
+            if config.reviews.is_some() {
+                review_request = reviews::record_event(event, state).await?;
+            }
+        }
+        enrich_event(&mut event, github, &config.github).await;
+
+        let author = match &event {
+            Event::GitHub(event) => event.sender().map(|sender| sender.login.clone()),
+            _ => None,
         };
 
-        let Response { message, repo } = match response {
+        // the trial rooms format the event again, with their own settings
+        let copy = if trials.is_empty() {
+            None
+        } else {
+            Some((event.clone(), overrides.clone()))
+        };
+        let response = format_event(event, &config.github)?;
+        if let (Some(response), Some(canary_config), Some(verdict)) =
+            (&response, &config.canary, canary)
+        {
+            let reviewed = canary::review(
+                client,
+                config,
+                canary_config,
+                id.as_deref(),
+                response,
+                &overrides,
+                verdict,
+            );
+            if let Err(e) = reviewed.await {
+                warn!("couldn't post canary diff: {:#}", e);
+            }
+        }
+        let response = response.map(|mut response| {
+            overrides.apply(&mut response);
+            response
+        });
+
+        let response = match response {
             Some(response) => response,
             // event doesn't need a message from the bot
             None => {
@@ -131,27 +617,185 @@ impl Prololo {
                 return Ok(());
             }
         };
+        let room_id = route(&response, config)?.clone();
+        if let Some((event, overrides)) = copy {
+            for trial in trials.copying(&room_id) {
+                let sent = self
+                    .send_trial_copy(trial, event.clone(), overrides.clone(), id.as_deref())
+                    .await;
+                if let Err(e) = sent {
+                    warn!("couldn't send copy to trial room `{}`: {:#}", trial.name, e);
+                }
+            }
+        }
+        let Response {
+            message,
+            repo,
+            severity,
+            image,
+            ..
+        } = response;
+
+        trace!("sending message `{}` to room `{}`", message.plain, room_id);
+        let (plain, html) = message.into_bodies();
+        let (plain, html) = process_mentions(config.mentions, &config.github.web_url, plain, html);
+        let (plain, html) = filters.apply(&room_id, plain, html);
+        let (plain, html) = match hooks.run(&room_id, severity, plain, html).await? {
+            Some(bodies) => bodies,
+            None => {
+                debug!("message vetoed by the hook of room `{}`", &room_id);
+                return Ok(());
+            }
+        };
+        if duplicates.is_duplicate(&room_id, &plain, Instant::now()) {
+            debug!("dropping duplicate message in room `{}`", &room_id);
+            if duplicates.should_report(&room_id) {
+                if let Err(e) = self.report_duplicate(&room_id, &plain).await {
+                    warn!("couldn't report duplicate message: {:#}", e);
+                }
+            }
+            return Ok(());
+        }
+        // the pings only concern this room, not the archive nor the mirrors
+        let (pinged_plain, pinged_html) = pings.apply(
+            &room_id,
+            severity,
+            plain.clone(),
+            html.clone(),
+            Instant::now(),
+        );
+        let message = MessageEventContent::text_html(pinged_plain, pinged_html);
+        let txn_id = id.as_deref().map(transaction_id);
+        let sender = client;
+        #[cfg(any(test, feature = "chaos"))]
+        let sender = &ChaosSender {
+            sender,
+            chaos: config.chaos.as_ref(),
+        };
+        let event_id = match breakers
+            .send(
+                sender,
+                &room_id,
+                message,
+                txn_id,
+                done,
+                config.timeouts.matrix(),
+            )
+            .await?
+        {
+            Some(event_id) => event_id,
+            None => {
+                debug!(
+                    "message held back by the circuit breaker of room `{}`",
+                    &room_id
+                );
+                return Ok(());
+            }
+        };
 
-        let room = repo
-            // get room id for current repo, or use default room
-            .map_or_else(|| config.default_room(), |repo| config.find_room_for(repo))
-            // find that joined room in the Matrix client
-            .and_then(|room_id| {
-                client.get_joined_room(room_id).ok_or_else(|| {
-                    anyhow!(
-                        "room with id {} isn't joined yet, can't send message",
-                        room_id
-                    )
-                })
-            })?;
+        let archived = ArchivedMessage {
+            time: Utc::now(),
+            room: room_id.clone(),
+            event_id: event_id.clone(),
+            kind: ArchivedKind::Announcement {
+                repo,
+                author,
+                severity,
+            },
+            body: plain.clone(),
+        };
+        if let Err(e) = archive.append(&archived) {
+            warn!("couldn't archive message: {:#}", e);
+        }
+
+        if let Some(pr) = review_request {
+            reviews::track(state, pr, room_id.clone(), event_id).await?;
+        }
+
+        mirrors.mirror(&room_id, severity, &plain);
+        for (name, mirror) in mirrors.rooms(&room_id) {
+            let (plain, html) = filters.apply(mirror, plain.clone(), html.clone());
+            // the transaction IDs of the copies must differ from the one of the original message
+            let txn_id = id
+                .as_deref()
+                .map(|id| transaction_id(&format!("{}:{}", id, name)));
+            let content = MessageEventContent::text_html(plain, html);
+            let timeout = config.timeouts.matrix();
+            if let Err(e) = breakers
+                .send(sender, mirror, content, txn_id, &mut None, timeout)
+                .await
+            {
+                warn!("couldn't mirror message to room `{}`: {:#}", name, e);
+            }
+        }
+        if severity == Severity::High {
+            if let Err(e) = incidents::mirror_alert(client, state, &room_id, &plain, &html).await {
+                warn!("couldn't mirror message to the incident room: {:#}", e);
+            }
+        }
+
+        if let (Some(image), Some(images)) = (image, &config.images) {
+            let attached = match client.get_joined_room(&room_id) {
+                Some(room) => attach_image(&room, &image, images, config.timeouts.http()).await,
+                None => Err(anyhow!("room {} was left", room_id)),
+            };
+            if let Err(e) = attached {
+                warn!("couldn't attach image to message: {:#}", e);
+            }
+        }
 
-        trace!(
-            "sending message `{}` to room `{}`",
-            message.plain,
-            room.room_id()
+        Ok(())
+    }
+
+    /// Sends the message of an event formatted with the settings of a trial room. The copies aren't
+    /// archived nor mirrored.
+    async fn send_trial_copy(
+        &self,
+        trial: &Trial,
+        event: Event,
+        overrides: Overrides,
+        id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut response = match format_event(event, &trial.github)? {
+            Some(response) => response,
+            None => return Ok(()),
+        };
+        overrides.apply(&mut response);
+
+        let (plain, html) = response.message.into_bodies();
+        let (plain, html) = process_mentions(
+            self.config.mentions,
+            &self.config.github.web_url,
+            plain,
+            html,
         );
-        let message = AnyMessageEventContent::RoomMessage(message.into());
-        room.send(message, None).await?;
+        let (plain, html) = self.filters.apply(&trial.room_id, plain, html);
+        let (plain, html) = match self
+            .hooks
+            .run(&trial.room_id, response.severity, plain, html)
+            .await?
+        {
+            Some(bodies) => bodies,
+            None => return Ok(()),
+        };
+        // the transaction IDs of the copies must differ from the one of the original message
+        let txn_id = id.map(|id| transaction_id(&format!("{}:{}", id, trial.name)));
+        let sender = &self.client;
+        #[cfg(any(test, feature = "chaos"))]
+        let sender = &ChaosSender {
+            sender,
+            chaos: self.config.chaos.as_ref(),
+        };
+        self.breakers
+            .send(
+                sender,
+                &trial.room_id,
+                MessageEventContent::text_html(plain, html),
+                txn_id,
+                &mut None,
+                self.config.timeouts.matrix(),
+            )
+            .await?;
 
         Ok(())
     }
@@ -160,36 +804,42 @@ impl Prololo {
     /// file is found, then login using username and password, and save the new session information on
     /// disk.
     async fn load_or_init_session(&self) -> anyhow::Result<()> {
-        let session_file = PathBuf::from("matrix-session.yaml");
+        let session_file = &self.config.matrix_session_file;
 
         if session_file.is_file() {
-            let reader = BufReader::new(File::open(&session_file)?);
+            let reader = BufReader::new(File::open(session_file)?);
             let session: Session = serde_yaml::from_reader(reader)?;
 
             self.client.restore_login(session.clone()).await?;
 
             // Check that the session restored session is valid
-            match self.client.sync_once(SyncSettings::default()).await {
-                Ok(_) => {
+            let sync = self.client.sync_once(SyncSettings::default());
+            match tokio::time::timeout(self.config.timeouts.matrix(), sync).await {
+                Ok(Ok(_)) => {
                     info!("Reused session: {}, {}", session.user_id, session.device_id);
                     return Ok(());
                 }
-                Err(e) => error!(
+                Ok(Err(e)) => error!(
                     "Unable to reuse session: {}, {}: {}",
                     session.user_id, session.device_id, e
                 ),
+                Err(_) => bail!(
+                    "timed out checking session: {}, {}",
+                    session.user_id,
+                    session.device_id
+                ),
             };
         }
 
-        let response = self
-            .client
-            .login(
-                &self.config.matrix_username,
-                &self.config.matrix_password,
-                None,
-                Some("autojoin bot"),
-            )
-            .await?;
+        let login = self.client.login(
+            &self.config.matrix_username,
+            &self.config.matrix_password,
+            None,
+            Some("autojoin bot"),
+        );
+        let response = tokio::time::timeout(self.config.timeouts.matrix(), login)
+            .await
+            .context("timed out logging in")??;
 
         info!("Logged in as {}", self.config.matrix_username);
 
@@ -199,14 +849,143 @@ impl Prololo {
             device_id: response.device_id,
         };
 
-        let writer = BufWriter::new(File::create(&session_file)?);
+        let writer = BufWriter::new(File::create(session_file)?);
         serde_yaml::to_writer(writer, &session)?;
 
         Ok(())
     }
 }
 
+/// Builds the message announcing an event, without sending it anywhere.
+pub fn format_event(
+    event: Event,
+    github_config: &GitHubConfig,
+) -> anyhow::Result<Option<Response>> {
+    match event {
+        Event::GitHub(event) => handle_github_event(event, github_config),
+        Event::ProloSite(event) => handle_prolosite_event(event),
+        Event::Mail(event) => handle_mail_event(event),
+    }
+}
+
+/// Finds the room receiving the message of a response: the room chosen by the handler or by a
+/// transformation rule, else the destination matching the repo, else the default room.
+pub fn route<'a>(response: &Response, config: &'a ProloloConfig) -> anyhow::Result<&'a RoomId> {
+    match (&response.room, &response.repo) {
+        (Some(room), _) => config.room_by_name(room),
+        (None, Some(repo)) => config.find_room_for(repo.clone()),
+        (None, None) => config.default_room(),
+    }
+}
+
+/// Sends the messages of the bot to Matrix
+#[rocket::async_trait]
+pub trait MessageSender: Sync {
+    /// Sends a message to a joined room, returning the ID of the new event
+    async fn send_message(
+        &self,
+        room_id: &RoomId,
+        content: MessageEventContent,
+        txn_id: Option<Uuid>,
+    ) -> anyhow::Result<EventId>;
+}
+
+#[rocket::async_trait]
+impl MessageSender for Client {
+    async fn send_message(
+        &self,
+        room_id: &RoomId,
+        content: MessageEventContent,
+        txn_id: Option<Uuid>,
+    ) -> anyhow::Result<EventId> {
+        let room = self.get_joined_room(room_id).ok_or_else(|| {
+            anyhow!(
+                "room with id {} isn't joined yet, can't send message",
+                room_id
+            )
+        })?;
+        let response = room
+            .send(AnyMessageEventContent::RoomMessage(content), txn_id)
+            .await?;
+
+        Ok(response.event_id)
+    }
+}
+
+#[derive(Clone)]
 pub struct Response {
     pub message: MessageBuilder,
     pub repo: Option<String>,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving the message, overriding
+    /// the repo based routing
+    pub room: Option<String>,
+    pub severity: Severity,
+    /// An image illustrating the event, attached after the message if enabled
+    pub image: Option<Url>,
+}
+
+/// How urgent a message is, used to decide who should be notified about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Normal,
+    High,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Normal => write!(f, "normal"),
+            Self::High => write!(f, "high"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            "{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_state_dir: state, \
+             matrix_rooms: {main: {id: '!main:prologin.org', default: true}, \
+             site: {id: '!site:prologin.org'}, ops: {id: '!ops:prologin.org'}}, \
+             destinations: [{room: site, regex: '^prologin/site$'}], \
+             github_secret: secret, prolosite_secret: secret}",
+        )
+        .unwrap();
+        let route = |repo: Option<&str>, room: Option<&str>| {
+            let response = Response {
+                message: MessageBuilder::new(),
+                repo: repo.map(str::to_string),
+                room: room.map(str::to_string),
+                severity: Severity::Normal,
+                image: None,
+            };
+            route(&response, &config).map(|room_id| room_id.to_string())
+        };
+
+        assert_eq!(route(None, None).unwrap(), "!main:prologin.org");
+        assert_eq!(
+            route(Some("prologin/site"), None).unwrap(),
+            "!site:prologin.org"
+        );
+        assert_eq!(
+            route(Some("prologin/stechec2"), None).unwrap(),
+            "!main:prologin.org"
+        );
+        assert_eq!(
+            route(Some("prologin/site"), Some("ops")).unwrap(),
+            "!ops:prologin.org"
+        );
+        assert!(route(None, Some("unknown")).is_err());
+    }
 }
@@ -0,0 +1,285 @@
+//! What the bot has been doing lately, for `!status`: when each source last sent an event, how many
+//! events are waiting to be handled, how many failed recently and how late the Matrix sync is.
+//! The same information is served on the status page, along with the uptime and the last errors.
+//! The daily counters of [`Metrics`] are updated along the way.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    bot::{message_builder::MessageBuilder, metrics::Metrics},
+    webhooks::Event,
+};
+
+/// The sources of events, in the order they are listed
+pub(super) const SOURCES: &[&str] = &["github", "prolosite", "mail"];
+
+/// How far back the failed events are counted, in minutes
+const ERROR_WINDOW_MINUTES: i64 = 60;
+
+/// Number of errors shown on the status page
+const LAST_ERRORS: usize = 5;
+
+fn source(event: &Event) -> &'static str {
+    match event {
+        Event::GitHub(_) => "github",
+        Event::ProloSite(_) => "prolosite",
+        Event::Mail(_) => "mail",
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    /// When each source last sent an event
+    last_events: Vec<(&'static str, DateTime<Utc>)>,
+    /// How many events each source sent since the start
+    events: BTreeMap<&'static str, u64>,
+    /// Events received but not handled yet, including the ones held by the debouncer
+    queued: usize,
+    /// When the recent events failed, the oldest first
+    errors: VecDeque<DateTime<Utc>>,
+    /// The messages of the last errors, the newest first
+    last_errors: VecDeque<(DateTime<Utc>, String)>,
+    last_sync: Option<DateTime<Utc>>,
+    metrics: Metrics,
+}
+
+impl Counters {
+    /// The rows of the table shown by `!status` and the status page
+    fn rows(&self, now: DateTime<Utc>) -> Vec<(String, String)> {
+        let mut rows = Vec::new();
+        for source in SOURCES {
+            let last_event = self
+                .last_events
+                .iter()
+                .find(|(other, _)| other == source)
+                .map(|(_, time)| {
+                    format!(
+                        "{} ({} ago)",
+                        time.format("%Y-%m-%d %H:%M"),
+                        ago(now - *time)
+                    )
+                });
+            rows.push((
+                format!("last {} event", source),
+                last_event.unwrap_or_else(|| "never".to_string()),
+            ));
+        }
+        rows.push(("queued events".to_string(), self.queued.to_string()));
+        let window = Duration::minutes(ERROR_WINDOW_MINUTES);
+        let errors = self
+            .errors
+            .iter()
+            .filter(|time| now - **time <= window)
+            .count();
+        rows.push(("errors".to_string(), format!("{} in the last hour", errors)));
+        let sync = self.last_sync.map_or_else(
+            || "not synced yet".to_string(),
+            |time| format!("last synced {} ago", ago(now - time)),
+        );
+        rows.push(("matrix sync".to_string(), sync));
+
+        rows
+    }
+}
+
+pub struct Activity {
+    started: DateTime<Utc>,
+    counters: Mutex<Counters>,
+}
+
+impl Activity {
+    /// Starts a new run, `metrics` being the ones persisted by the previous runs
+    pub fn new(started: DateTime<Utc>, mut metrics: Metrics) -> Self {
+        metrics.start(started);
+        Self {
+            started,
+            counters: Mutex::new(Counters {
+                metrics,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn received(&self, event: &Event, now: DateTime<Utc>) {
+        let source = source(event);
+        let mut counters = self.counters.lock().unwrap();
+        counters.last_events.retain(|(other, _)| *other != source);
+        counters.last_events.push((source, now));
+        *counters.events.entry(source).or_default() += 1;
+        counters.metrics.record_event(source, now);
+        counters.queued += 1;
+    }
+
+    pub fn handled(&self, error: Option<&anyhow::Error>, now: DateTime<Utc>) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.queued = counters.queued.saturating_sub(1);
+        if let Some(error) = error {
+            counters.errors.push_back(now);
+            counters.metrics.record_error(now);
+            counters
+                .last_errors
+                .push_front((now, format!("{:#}", error)));
+            counters.last_errors.truncate(LAST_ERRORS);
+        }
+        let window = Duration::minutes(ERROR_WINDOW_MINUTES);
+        while matches!(counters.errors.front(), Some(time) if now - *time > window) {
+            counters.errors.pop_front();
+        }
+    }
+
+    /// Called after each sync with the homeserver
+    pub fn synced(&self, now: DateTime<Utc>) {
+        self.counters.lock().unwrap().last_sync = Some(now);
+    }
+
+    /// Marks the run as still going, and returns the metrics to persist
+    pub fn checkpoint(&self, now: DateTime<Utc>) -> Metrics {
+        let mut counters = self.counters.lock().unwrap();
+        counters.metrics.seen(now);
+        counters.metrics.clone()
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.counters.lock().unwrap().metrics.clone()
+    }
+
+    /// A table of the activity, for `!status`
+    pub fn status_message(&self, now: DateTime<Utc>) -> MessageBuilder {
+        let rows = self.counters.lock().unwrap().rows(now);
+
+        let mut message = MessageBuilder::new();
+        write_table(&mut message, &rows);
+        message
+    }
+
+    /// An HTML page with the activity since the start and the last errors, to check the bot is
+    /// alive from a browser. It only shows counters and error messages, nothing from the config.
+    /// `{title}` and `{body}` are replaced in the template
+    pub fn status_page(&self, template: &str, name: &str, now: DateTime<Utc>) -> String {
+        let counters = self.counters.lock().unwrap();
+
+        let mut rows = vec![(
+            "uptime".to_string(),
+            format!(
+                "{} (since {})",
+                ago(now - self.started),
+                self.started.format("%Y-%m-%d %H:%M")
+            ),
+        )];
+        for source in SOURCES {
+            let count = counters.events.get(source).copied().unwrap_or(0);
+            rows.push((format!("{} events", source), count.to_string()));
+        }
+        rows.extend(counters.rows(now));
+
+        let mut body = MessageBuilder::new();
+        write_table(&mut body, &rows);
+        if !counters.last_errors.is_empty() {
+            write!(body, "Last errors:").unwrap();
+            body.list();
+            for (time, error) in &counters.last_errors {
+                body.list_item();
+                write!(body, "{} ", time.format("%Y-%m-%d %H:%M")).unwrap();
+                body.code();
+                write!(body, "{}", error).unwrap();
+                body.close_last();
+                body.close_last();
+            }
+            body.close_last();
+        }
+
+        let mut title = MessageBuilder::new();
+        write!(title, "{} status", name).unwrap();
+
+        template
+            .replace("{title}", &title.html)
+            .replace("{body}", &body.html)
+    }
+}
+
+/// Writes rows of names and values as an aligned table
+fn write_table(message: &mut MessageBuilder, rows: &[(String, String)]) {
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    message.preformatted();
+    for (i, (name, value)) in rows.iter().enumerate() {
+        if i > 0 {
+            writeln!(message).unwrap();
+        }
+        write!(message, "{:width$}  {}", name, value, width = width).unwrap();
+    }
+    message.close_last();
+}
+
+/// Formats a duration in its largest unit, e.g. `3m` or `2d`
+pub(super) fn ago(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=172_799 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{bot::Severity, mail::MailEvent};
+
+    #[test]
+    fn test_status_message() {
+        let start = Utc.ymd(2021, 10, 3).and_hms(14, 0, 0);
+        let activity = Activity::new(start, Metrics::default());
+        let error = anyhow::anyhow!("couldn't find room <dev>");
+
+        activity.synced(start);
+        activity.received(&mail_event(), start);
+        activity.handled(Some(&error), start);
+        activity.received(&mail_event(), start + Duration::minutes(70));
+        activity.handled(Some(&error), start + Duration::minutes(70));
+        activity.received(&mail_event(), start + Duration::minutes(80));
+
+        let message = activity.status_message(start + Duration::minutes(90));
+        assert_eq!(
+            message.plain,
+            "last github event     never\n\
+             last prolosite event  never\n\
+             last mail event       2021-10-03 15:20 (10m ago)\n\
+             queued events         1\n\
+             errors                1 in the last hour\n\
+             matrix sync           last synced 1h ago"
+        );
+        assert!(message.html.starts_with("<pre>last github event"));
+        assert!(message.html.ends_with("1h ago</pre>"));
+
+        let template = crate::assets::load(None, "templates/status.html").unwrap();
+        let page = activity.status_page(&template, "prololo", start + Duration::minutes(90));
+        assert!(page.contains("<title>prololo status</title>"));
+        assert!(page.contains(
+            "<pre>uptime                1h (since 2021-10-03 14:00)\n\
+             github events         0\n\
+             prolosite events      0\n\
+             mail events           3\n\
+             last github event     never\n"
+        ));
+        assert!(page.contains("<li>2021-10-03 15:10 <code>couldn&#39;t find room &lt;dev&gt;</code></li><li>2021-10-03 14:00 "));
+    }
+
+    fn mail_event() -> Event {
+        Event::Mail(MailEvent {
+            sender: "root@prologin.org".to_string(),
+            subject: "Disk full".to_string(),
+            body: String::new(),
+            room: "dev".to_string(),
+            severity: Severity::Normal,
+        })
+    }
+}
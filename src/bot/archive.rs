@@ -0,0 +1,260 @@
+//! An append-only log of the messages sent by the bot and of the chat acknowledgements they got,
+//! stored as JSON lines next to the bot's state.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration as StdDuration,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use matrix_sdk::ruma::{EventId, RoomId, UserId};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{bot::Severity, config::RetentionConfig};
+
+/// Time between two compactions of the archive
+const COMPACTION_INTERVAL: StdDuration = StdDuration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ArchivedKind {
+    /// A message sent by the bot
+    Announcement {
+        repo: Option<String>,
+        /// The GitHub login of who triggered the announced event
+        #[serde(default)]
+        author: Option<String>,
+        severity: Severity,
+    },
+    /// A human answer to the bot, or a message in the incident room
+    Chat { sender: UserId },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ArchivedMessage {
+    pub time: DateTime<Utc>,
+    pub room: RoomId,
+    pub event_id: EventId,
+    #[serde(flatten)]
+    pub kind: ArchivedKind,
+    /// The plain text body of the message
+    pub body: String,
+}
+
+pub struct Archive {
+    path: PathBuf,
+    /// Appends from concurrent tasks must not interleave
+    lock: Mutex<()>,
+}
+
+impl Archive {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn append(&self, message: &ArchivedMessage) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Removes the messages sent before `oldest`, then the oldest ones until the archive is at most
+    /// `max_size` bytes. Returns the number of removed messages.
+    pub fn compact(
+        &self,
+        oldest: Option<DateTime<Utc>>,
+        max_size: Option<u64>,
+    ) -> anyhow::Result<usize> {
+        let _guard = self.lock.lock().unwrap();
+        if !self.path.is_file() {
+            return Ok(0);
+        }
+
+        let mut lines = Vec::new();
+        let mut removed = 0;
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            match serde_json::from_str::<ArchivedMessage>(&line) {
+                Ok(message) if oldest.map_or(true, |oldest| message.time >= oldest) => {
+                    lines.push(line)
+                }
+                // invalid lines are dropped too
+                _ => removed += 1,
+            }
+        }
+
+        let mut size: u64 = lines.iter().map(|line| line.len() as u64 + 1).sum();
+        let mut first = 0;
+        if let Some(max_size) = max_size {
+            while size > max_size && first < lines.len() {
+                size -= lines[first].len() as u64 + 1;
+                first += 1;
+            }
+        }
+        removed += first;
+
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        // same as the state, a crash can't leave a truncated archive behind
+        let tmp_path = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        for line in &lines[first..] {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(removed)
+    }
+
+    /// Returns all the archived messages, oldest first.
+    pub fn all(&self) -> anyhow::Result<Vec<ArchivedMessage>> {
+        self.read(|_| true)
+    }
+
+    /// Returns the archived messages sent at or after `since`, oldest first.
+    pub fn since(&self, since: DateTime<Utc>) -> anyhow::Result<Vec<ArchivedMessage>> {
+        self.read(|message| message.time >= since)
+    }
+
+    fn read(
+        &self,
+        filter: impl Fn(&ArchivedMessage) -> bool,
+    ) -> anyhow::Result<Vec<ArchivedMessage>> {
+        let _guard = self.lock.lock().unwrap();
+        if !self.path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        let mut messages = Vec::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            match serde_json::from_str::<ArchivedMessage>(&line) {
+                Ok(message) if filter(&message) => messages.push(message),
+                Ok(_) => {}
+                Err(e) => warn!("skipping invalid archive line `{}`: {}", line, e),
+            }
+        }
+
+        Ok(messages)
+    }
+}
+
+/// Size in bytes of a file, or of all the files of a directory
+fn disk_usage(path: &Path) -> u64 {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Applies the retention limits to the archive every hour, and logs the size of the stores.
+pub async fn compact_archive(archive: Arc<Archive>, config: RetentionConfig, state_dir: PathBuf) {
+    let mut interval = tokio::time::interval(COMPACTION_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let oldest = config
+            .max_age_days
+            .map(|days| Utc::now() - Duration::days(days as i64));
+        match archive.compact(oldest, config.max_size) {
+            Ok(0) => {}
+            Ok(removed) => info!("removed {} messages from the archive", removed),
+            Err(e) => warn!("couldn't compact the archive: {:#}", e),
+        }
+
+        info!(
+            "store sizes: archive {} bytes, state {} bytes, matrix store {} bytes",
+            disk_usage(&archive.path),
+            disk_usage(&state_dir.join("state.yaml")),
+            disk_usage(&state_dir.join("store")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    #[test]
+    fn test_archive() {
+        let path =
+            std::env::temp_dir().join(format!("prololo-archive-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let archive = Archive::new(path.clone());
+
+        let message = |time: &str, kind: ArchivedKind| ArchivedMessage {
+            time: DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc),
+            room: RoomId::try_from("!abc:prologin.org").unwrap(),
+            event_id: EventId::try_from("$event:prologin.org").unwrap(),
+            kind,
+            body: "site is down".to_string(),
+        };
+        let old = message(
+            "2021-12-14T08:00:00Z",
+            ArchivedKind::Announcement {
+                repo: None,
+                author: None,
+                severity: Severity::High,
+            },
+        );
+        let ack = message(
+            "2021-12-14T09:20:00Z",
+            ArchivedKind::Chat {
+                sender: UserId::try_from("@alice:prologin.org").unwrap(),
+            },
+        );
+        archive.append(&old).unwrap();
+        archive.append(&ack).unwrap();
+
+        let since = DateTime::parse_from_rfc3339("2021-12-14T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(archive.since(since).unwrap(), vec![ack.clone()]);
+
+        assert_eq!(archive.compact(Some(since), None).unwrap(), 1);
+        assert_eq!(archive.all().unwrap(), vec![ack]);
+        assert_eq!(archive.compact(Some(since), None).unwrap(), 0);
+        assert_eq!(archive.compact(None, Some(10)).unwrap(), 1);
+        assert!(archive.all().unwrap().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+//! Parsing of the arguments of chat commands, shared by all commands so that they behave the same.
+//!
+//! Arguments are separated by whitespace, and can be quoted with `"` or `'` to contain spaces.
+//! Unquoted arguments of the form `key=value` are flags, e.g. `since=2h`, their value can be quoted
+//! too. Durations (e.g. `2h30m`) are parsed by [`parse_duration`].
+
+use anyhow::anyhow;
+use chrono::Duration;
+
+use crate::bot::utils::parse_duration;
+
+/// The arguments of a command
+#[derive(Debug)]
+pub struct Args {
+    /// Shown in the parsing errors, e.g. `!find <regex> [room] [since]`
+    usage: &'static str,
+    positional: Vec<String>,
+    flags: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Parses the arguments following the name of the command
+    pub fn parse(input: &str, usage: &'static str) -> anyhow::Result<Self> {
+        let mut args = Self {
+            usage,
+            positional: Vec::new(),
+            flags: Vec::new(),
+        };
+
+        let mut rest = input.trim_start();
+        while !rest.is_empty() {
+            let (flag, value) = match flag_name(rest) {
+                Some(name) => (Some(name), &rest[name.len() + 1..]),
+                None => (None, rest),
+            };
+            let (value, remaining) = match split_token(value) {
+                Some(split) => split,
+                None => return Err(args.error("unterminated quote")),
+            };
+
+            match flag {
+                Some(name) => args.flags.push((name.to_string(), value)),
+                None => args.positional.push(value),
+            }
+            rest = remaining.trim_start();
+        }
+
+        Ok(args)
+    }
+
+    /// An error explaining what is wrong, followed by the usage of the command
+    pub fn error(&self, reason: impl std::fmt::Display) -> anyhow::Error {
+        anyhow!("{}, usage: {}", reason, self.usage)
+    }
+
+    /// Checks that there are between `min` and `max` positional arguments
+    pub fn expect_positional(&self, min: usize, max: usize) -> anyhow::Result<&[String]> {
+        match self.positional.len() {
+            count if count < min => Err(self.error("missing arguments")),
+            count if count > max => Err(self.error("too many arguments")),
+            _ => Ok(&self.positional),
+        }
+    }
+
+    /// Takes the value of a flag, the last one if it is given several times
+    pub fn flag(&mut self, name: &str) -> Option<String> {
+        let mut value = None;
+        self.flags.retain(|(flag, flag_value)| {
+            if flag == name {
+                value = Some(flag_value.clone());
+            }
+            flag != name
+        });
+        value
+    }
+
+    /// Takes the value of a flag holding a duration
+    pub fn duration_flag(&mut self, name: &str) -> anyhow::Result<Option<Duration>> {
+        match self.flag(name) {
+            Some(value) => parse_duration(&value)
+                .map(Some)
+                .map_err(|e| self.error(format_args!("invalid `{}`: {}", name, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks that the command took all the flags it was given
+    pub fn finish(self) -> anyhow::Result<()> {
+        match self.flags.first() {
+            Some((name, _)) => Err(self.error(format_args!("unknown flag `{}`", name))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The name of the flag starting `text`, if it starts with one
+fn flag_name(text: &str) -> Option<&str> {
+    let end = text.find('=')?;
+    let name = &text[..end];
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    Some(name).filter(|_| valid)
+}
+
+/// Splits the token starting `text`, unquoted, from the text after it. Returns `None` if a quote
+/// isn't terminated.
+fn split_token(text: &str) -> Option<(String, &str)> {
+    match text.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let inner = &text[1..];
+            let end = inner.find(quote)?;
+            Some((inner[..end].to_string(), &inner[end + 1..]))
+        }
+        _ => {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            Some((text[..end].to_string(), &text[end..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USAGE: &str = "!find <regex> [room=<room>] [since=<duration>]";
+
+    #[test]
+    fn test_parse() {
+        let mut args = Args::parse(
+            r#" "deployed site"  'it is' room=dev since="2h30m" "a=b""#,
+            USAGE,
+        )
+        .unwrap();
+        assert_eq!(
+            args.expect_positional(3, 3).unwrap(),
+            ["deployed site", "it is", "a=b"]
+        );
+        assert_eq!(args.flag("room").as_deref(), Some("dev"));
+        assert_eq!(args.flag("room"), None);
+        assert_eq!(
+            args.duration_flag("since").unwrap(),
+            Some(Duration::minutes(150))
+        );
+        args.finish().unwrap();
+
+        let args = Args::parse("", USAGE).unwrap();
+        assert!(args.expect_positional(0, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = Args::parse(r#""deployed site"#, USAGE).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unterminated quote, usage: !find <regex> [room=<room>] [since=<duration>]"
+        );
+
+        let args = Args::parse("site rom=dev", USAGE).unwrap();
+        assert_eq!(
+            args.expect_positional(2, 2).unwrap_err().to_string(),
+            "missing arguments, usage: !find <regex> [room=<room>] [since=<duration>]"
+        );
+        assert_eq!(
+            args.finish().unwrap_err().to_string(),
+            "unknown flag `rom`, usage: !find <regex> [room=<room>] [since=<duration>]"
+        );
+
+        let mut args = Args::parse("since=2w", USAGE).unwrap();
+        assert_eq!(
+            args.duration_flag("since").unwrap_err().to_string(),
+            "invalid `since`: invalid duration `2w`, expected e.g. 30m, 2h or 1h30m, usage: !find <regex> [room=<room>] [since=<duration>]"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Critical entries of the organization's audit log, relayed to the security room. GitHub sends no
+//! webhook for several of them (e.g. disabled two-factor authentications), so the audit log is
+//! polled instead.
+//!
+//! The entries logged before the first poll aren't relayed, the bot state remembers the latest
+//! relayed entry afterwards.
+
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use matrix_sdk::{ruma::events::AnyMessageEventContent, Client};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder, state::SharedState},
+    config::{GitHubConfig, ProloloConfig},
+    github_api::{AuditLogEntry, GitHubApi},
+};
+
+/// What an audit log action means, for the actions relayed by default
+fn describe(action: &str) -> Option<&'static str> {
+    Some(match action {
+        "oauth_authorization.create" => "authorized an OAuth app",
+        "org.oauth_app_access_approved" => "approved the access of an OAuth app",
+        "two_factor_authentication.disabled" => "disabled two-factor authentication",
+        "org.disable_two_factor_requirement" => {
+            "disabled the two-factor authentication requirement"
+        }
+        "org.enable_two_factor_requirement" => "required two-factor authentication",
+        "public_key.create" => "added a deploy key",
+        _ => return None,
+    })
+}
+
+fn entry_message(org: &str, entry: &AuditLogEntry) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    message.tag(org, Some(emoji::SHIELD));
+    let actor = entry.actor.as_deref().unwrap_or("someone");
+    match describe(&entry.action) {
+        Some(description) => write!(message, " {} {}", actor, description).unwrap(),
+        None => {
+            write!(message, " {} performed ", actor).unwrap();
+            message.code();
+            write!(message, "{}", entry.action).unwrap();
+            message.close_last();
+        }
+    }
+    if let Some(user) = entry.user.as_deref().filter(|user| *user != actor) {
+        write!(message, " for {}", user).unwrap();
+    }
+    if let Some(repo) = &entry.repo {
+        write!(message, " on {}", repo).unwrap();
+    }
+
+    message
+}
+
+/// Keeps the entries logged after `cursor`, oldest first
+fn new_entries(mut entries: Vec<AuditLogEntry>, cursor: i64) -> Vec<AuditLogEntry> {
+    entries.retain(|entry| entry.timestamp > cursor);
+    entries.sort_by_key(|entry| entry.timestamp);
+    entries
+}
+
+async fn relay_entries(
+    client: &Client,
+    config: &ProloloConfig,
+    state: &SharedState,
+    api: &GitHubApi,
+) -> anyhow::Result<()> {
+    let audit_log = config.audit_log.as_ref().expect("audit log is enabled");
+
+    let mut entries = Vec::new();
+    for action in &audit_log.actions {
+        entries.extend(api.audit_log(&audit_log.org, action).await?);
+    }
+    let latest = entries.iter().map(|entry| entry.timestamp).max();
+
+    let cursor = state.lock().await.state.audit_log_cursor;
+    let cursor = match cursor {
+        Some(cursor) => cursor,
+        None => {
+            debug!("first poll of the audit log, not relaying older entries");
+            let mut store = state.lock().await;
+            store.state.audit_log_cursor = Some(latest.unwrap_or(0));
+            return store.save();
+        }
+    };
+
+    // the entries can be sensitive, they are never sent to the default room
+    let room = config
+        .github
+        .security_room
+        .as_ref()
+        .ok_or_else(|| anyhow!("no security room to relay the audit log to"))?;
+    let room_id = config.room_by_name(room)?;
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+
+    for entry in new_entries(entries, cursor) {
+        let message = entry_message(&audit_log.org, &entry);
+        room.send(AnyMessageEventContent::RoomMessage(message.into()), None)
+            .await?;
+
+        // the entry isn't relayed again if the next ones fail
+        let mut store = state.lock().await;
+        store.state.audit_log_cursor = Some(entry.timestamp);
+        store.save()?;
+    }
+
+    Ok(())
+}
+
+/// Polls the audit log of the organization, and relays its critical entries.
+pub async fn relay_audit_log(client: Client, config: ProloloConfig, state: SharedState) {
+    let audit_log = match &config.audit_log {
+        Some(audit_log) => audit_log,
+        None => return,
+    };
+    let github = GitHubConfig {
+        api_token: Some(audit_log.token.clone()),
+        ..config.github.clone()
+    };
+    let api = match GitHubApi::new(&github, config.timeouts.http()) {
+        Ok(api) => api,
+        Err(e) => {
+            warn!("couldn't build audit log client: {:#}", e);
+            return;
+        }
+    };
+
+    loop {
+        if let Err(e) = relay_entries(&client, &config, &state, &api).await {
+            warn!("couldn't relay audit log entries: {:#}", e);
+        }
+        tokio::time::sleep(audit_log.interval()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64, action: &str, actor: &str, user: Option<&str>) -> AuditLogEntry {
+        AuditLogEntry {
+            timestamp,
+            action: action.to_string(),
+            actor: Some(actor.to_string()),
+            user: user.map(str::to_string),
+            repo: None,
+        }
+    }
+
+    #[test]
+    fn test_entry_message() {
+        let message = entry_message(
+            "prologin",
+            &entry(
+                1,
+                "two_factor_authentication.disabled",
+                "alice",
+                Some("alice"),
+            ),
+        );
+        assert_eq!(
+            message.plain,
+            "[🛡 prologin] alice disabled two-factor authentication"
+        );
+
+        let mut deploy_key = entry(1, "public_key.create", "bob", None);
+        deploy_key.repo = Some("prologin/site".to_string());
+        let message = entry_message("prologin", &deploy_key);
+        assert_eq!(
+            message.plain,
+            "[🛡 prologin] bob added a deploy key on prologin/site"
+        );
+
+        let message = entry_message("prologin", &entry(1, "org.add_member", "bob", Some("eve")));
+        assert_eq!(
+            message.html,
+            "<b>[🛡 prologin]</b> bob performed <code>org.add_member</code> for eve"
+        );
+    }
+
+    #[test]
+    fn test_new_entries() {
+        let entries = vec![
+            entry(30, "public_key.create", "alice", None),
+            entry(10, "public_key.create", "alice", None),
+            entry(20, "oauth_authorization.create", "bob", None),
+        ];
+
+        let timestamps: Vec<_> = new_entries(entries, 10)
+            .iter()
+            .map(|entry| entry.timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![20, 30]);
+    }
+}
@@ -0,0 +1,343 @@
+//! Circuit breakers for the rooms where sending keeps failing (e.g. the bot lost its permission to
+//! post): their messages are held back for a while instead of delaying the other rooms.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use matrix_sdk::{
+    ruma::{events::room::message::MessageEventContent, EventId, RoomId},
+    uuid::Uuid,
+};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use crate::{
+    bot::{message_builder::MessageBuilder, MessageSender},
+    config::{CircuitBreakerConfig, HeldMessages, ProloloConfig},
+};
+
+/// A message held back while the circuit of its room is open
+struct HeldMessage {
+    content: MessageEventContent,
+    txn_id: Option<Uuid>,
+    /// Tells the queue that the event of the message is done, once it is sent or dropped
+    done: Option<oneshot::Sender<()>>,
+}
+
+impl HeldMessage {
+    fn done(self) {
+        if let Some(done) = self.done {
+            let _ = done.send(());
+        }
+    }
+}
+
+#[derive(Default)]
+struct Circuit {
+    /// Consecutive failed sends
+    failures: u32,
+    /// When the circuit last opened, `None` while it is closed
+    opened: Option<Instant>,
+    held: VecDeque<HeldMessage>,
+}
+
+pub struct Breakers {
+    config: Option<CircuitBreakerConfig>,
+    circuits: Mutex<HashMap<RoomId, Circuit>>,
+}
+
+impl Breakers {
+    pub fn new(config: Option<CircuitBreakerConfig>) -> Self {
+        Self {
+            config,
+            circuits: Default::default(),
+        }
+    }
+
+    /// Sends a message unless the circuit of the room is open. Returns `None` if the message was
+    /// held back, and sends the held messages first once the cooldown is over.
+    ///
+    /// The held messages are sent as is: they aren't archived nor mirrored. A held message takes
+    /// `done`, sent once the message is sent or dropped, so that its event stays in the queue
+    /// until then.
+    pub async fn send(
+        &self,
+        sender: &impl MessageSender,
+        room_id: &RoomId,
+        content: MessageEventContent,
+        txn_id: Option<Uuid>,
+        done: &mut Option<oneshot::Sender<()>>,
+        timeout: Duration,
+    ) -> anyhow::Result<Option<EventId>> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => {
+                return send(sender, room_id, content, txn_id, timeout)
+                    .await
+                    .map(Some)
+            }
+        };
+
+        let held = {
+            let mut circuits = self.circuits.lock().unwrap();
+            let circuit = circuits.entry(room_id.clone()).or_default();
+            match circuit.opened {
+                Some(opened) if opened.elapsed() < config.cooldown() => {
+                    let message = HeldMessage {
+                        content,
+                        txn_id,
+                        done: done.take(),
+                    };
+                    hold(circuit, config, room_id, message);
+                    return Ok(None);
+                }
+                _ => std::mem::take(&mut circuit.held),
+            }
+        };
+
+        // the oldest messages go first, the new one is held with them if one of them fails
+        let mut pending = held;
+        pending.push_back(HeldMessage {
+            content,
+            txn_id,
+            done: done.take(),
+        });
+        let mut event_id = None;
+        while let Some(message) = pending.pop_front() {
+            let result = send(
+                sender,
+                room_id,
+                message.content.clone(),
+                message.txn_id,
+                timeout,
+            )
+            .await;
+            let mut circuits = self.circuits.lock().unwrap();
+            let circuit = circuits.entry(room_id.clone()).or_default();
+            match result {
+                Ok(id) => {
+                    circuit.failures = 0;
+                    event_id = Some(id);
+                    // the new message is done once its event is handled
+                    if pending.is_empty() {
+                        *done = message.done;
+                    } else {
+                        message.done();
+                    }
+                }
+                Err(e) => {
+                    circuit.failures += 1;
+                    if circuit.failures < config.failures && circuit.opened.is_none() {
+                        *done = message.done;
+                        return Err(e);
+                    }
+                    warn!("circuit of room {} opened: {:#}", room_id, e);
+                    circuit.opened = Some(Instant::now());
+                    hold(circuit, config, room_id, message);
+                    for message in pending {
+                        hold(circuit, config, room_id, message);
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(circuit) = circuits.get_mut(room_id) {
+            if circuit.opened.take().is_some() {
+                info!("circuit of room {} closed", room_id);
+            }
+        }
+
+        Ok(event_id)
+    }
+
+    /// Writes the state of the open circuits on a new line, for `!status`
+    pub fn write_status(&self, message: &mut MessageBuilder, config: &ProloloConfig) {
+        message.line_break();
+        let breaker = match &self.config {
+            Some(breaker) => breaker,
+            None => {
+                write!(message, "circuit breakers are disabled").unwrap();
+                return;
+            }
+        };
+
+        let circuits = self.circuits.lock().unwrap();
+        let mut open: Vec<_> = circuits
+            .iter()
+            .filter_map(|(room_id, circuit)| Some((room_id, circuit, circuit.opened?)))
+            .collect();
+        if open.is_empty() {
+            write!(message, "all rooms are flowing").unwrap();
+            return;
+        }
+        open.sort_by_key(|(_, _, opened)| *opened);
+
+        write!(message, "open circuits:").unwrap();
+        for (room_id, circuit, opened) in open {
+            let name = config
+                .matrix_rooms
+                .iter()
+                .find(|(_, room)| &room.id == room_id)
+                .map_or_else(|| room_id.to_string(), |(name, _)| name.clone());
+            write!(message, "\n- {}: {} failures, ", name, circuit.failures).unwrap();
+            match breaker.cooldown().checked_sub(opened.elapsed()) {
+                Some(left) => write!(message, "retrying in {}s", left.as_secs()).unwrap(),
+                None => write!(message, "retrying with the next message").unwrap(),
+            }
+            if !circuit.held.is_empty() {
+                write!(message, ", {} held messages", circuit.held.len()).unwrap();
+            }
+        }
+    }
+}
+
+async fn send(
+    sender: &impl MessageSender,
+    room_id: &RoomId,
+    content: MessageEventContent,
+    txn_id: Option<Uuid>,
+    timeout: Duration,
+) -> anyhow::Result<EventId> {
+    tokio::time::timeout(timeout, sender.send_message(room_id, content, txn_id))
+        .await
+        .context("timed out sending message")?
+}
+
+fn hold(
+    circuit: &mut Circuit,
+    config: &CircuitBreakerConfig,
+    room_id: &RoomId,
+    message: HeldMessage,
+) {
+    // the dropped messages are done, retrying their events would only drop them again
+    match config.held_messages {
+        HeldMessages::Drop => {
+            info!("dropping message to room {}, its circuit is open", room_id);
+            message.done();
+        }
+        HeldMessages::Buffer => {
+            if circuit.held.len() >= config.buffer_size {
+                warn!(
+                    "buffer of room {} is full, dropping its oldest message",
+                    room_id
+                );
+                if let Some(dropped) = circuit.held.pop_front() {
+                    dropped.done();
+                }
+            }
+            circuit.held.push_back(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::TryFrom,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use matrix_sdk::ruma::events::room::message::MessageType;
+
+    use super::*;
+
+    /// Fails while `down` is set, and records the bodies of the sent messages
+    #[derive(Default)]
+    struct FlakySender {
+        down: AtomicBool,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[rocket::async_trait]
+    impl MessageSender for FlakySender {
+        async fn send_message(
+            &self,
+            _room_id: &RoomId,
+            content: MessageEventContent,
+            _txn_id: Option<Uuid>,
+        ) -> anyhow::Result<EventId> {
+            if self.down.load(Ordering::SeqCst) {
+                anyhow::bail!("M_FORBIDDEN");
+            }
+            if let MessageType::Text(text) = content.msgtype {
+                self.sent.lock().unwrap().push(text.body);
+            }
+            Ok(EventId::try_from("$event:prologin.org")?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_breaker() {
+        let breakers = Breakers::new(Some(CircuitBreakerConfig {
+            failures: 2,
+            cooldown_seconds: 60,
+            held_messages: HeldMessages::Buffer,
+            buffer_size: 2,
+        }));
+        let sender = FlakySender::default();
+        let room_id = RoomId::try_from("!main:prologin.org").unwrap();
+        let send = |body: &str| {
+            let (done, acknowledged) = oneshot::channel();
+            let body = body.to_string();
+            let breakers = &breakers;
+            let sender = &sender;
+            let room_id = &room_id;
+            async move {
+                let mut done = Some(done);
+                let sent = breakers
+                    .send(
+                        sender,
+                        room_id,
+                        MessageEventContent::text_plain(body),
+                        None,
+                        &mut done,
+                        Duration::from_secs(1),
+                    )
+                    .await;
+                // the caller is done with the messages that weren't held
+                if let Some(done) = done {
+                    done.send(()).unwrap();
+                }
+                (sent, acknowledged)
+            }
+        };
+
+        sender.down.store(true, Ordering::SeqCst);
+        assert!(send("first").await.0.is_err());
+        // the second failure opens the circuit
+        let (sent, mut second) = send("second").await;
+        assert!(sent.unwrap().is_none());
+        let (sent, mut third) = send("third").await;
+        assert!(sent.unwrap().is_none());
+        let (sent, mut fourth) = send("fourth").await;
+        assert!(sent.unwrap().is_none());
+        // the oldest held message was dropped when the buffer was full
+        assert!(second.try_recv().is_ok());
+        assert!(third.try_recv().is_err());
+        assert!(fourth.try_recv().is_err());
+
+        // end of the cooldown
+        sender.down.store(false, Ordering::SeqCst);
+        breakers
+            .circuits
+            .lock()
+            .unwrap()
+            .get_mut(&room_id)
+            .unwrap()
+            .opened = Instant::now().checked_sub(Duration::from_secs(61));
+        assert!(send("fifth").await.0.unwrap().is_some());
+        assert!(third.try_recv().is_ok());
+        assert!(fourth.try_recv().is_ok());
+        assert_eq!(
+            *sender.sent.lock().unwrap(),
+            vec!["third", "fourth", "fifth"]
+        );
+    }
+}
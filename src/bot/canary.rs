@@ -0,0 +1,166 @@
+//! Comparison of the messages formatted with the current transformation rules and with the rules of
+//! the canary, see [`CanaryConfig`]. Only the differences are posted, to the review room: the
+//! announcements themselves are sent once, with the current rules.
+
+use std::fmt::Write;
+
+use anyhow::Context;
+
+use crate::{
+    bot::{message_builder::MessageBuilder, route, MessageSender, Response, Severity},
+    config::{CanaryConfig, ProloloConfig},
+    webhooks::rules::{Overrides, Verdict},
+};
+
+/// How an event is announced
+#[derive(Debug, PartialEq)]
+struct Announcement {
+    room: String,
+    severity: Severity,
+    body: String,
+}
+
+impl Announcement {
+    fn new(
+        mut response: Response,
+        overrides: Overrides,
+        config: &ProloloConfig,
+    ) -> anyhow::Result<Self> {
+        overrides.apply(&mut response);
+        let room_id = route(&response, config)?;
+        let room = config
+            .matrix_rooms
+            .iter()
+            .find(|(_, room)| &room.id == room_id)
+            .map_or_else(|| room_id.to_string(), |(name, _)| name.clone());
+        let (body, _) = response.message.into_bodies();
+
+        Ok(Self {
+            room,
+            severity: response.severity,
+            body,
+        })
+    }
+}
+
+/// Describes how the canary rules change the announcement of an event, `None` if they don't
+fn diff_message(
+    id: Option<&str>,
+    current: &Announcement,
+    canary: Option<&Announcement>,
+) -> Option<MessageBuilder> {
+    if canary == Some(current) {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+    message.tag("canary", None);
+    write!(message, " event {}", id.unwrap_or("without ID")).unwrap();
+    let canary = match canary {
+        Some(canary) => canary,
+        None => {
+            write!(message, " would be dropped, it is announced as:").unwrap();
+            message.list();
+            message.list_item();
+            write!(message, "{}", current.body).unwrap();
+            message.close_styles();
+            return Some(message);
+        }
+    };
+
+    write!(message, " would be announced differently:").unwrap();
+    message.list();
+    if canary.room != current.room {
+        message.list_item();
+        write!(message, "room: {} → {}", current.room, canary.room).unwrap();
+        message.close_last();
+    }
+    if canary.severity != current.severity {
+        message.list_item();
+        write!(
+            message,
+            "severity: {} → {}",
+            current.severity, canary.severity
+        )
+        .unwrap();
+        message.close_last();
+    }
+    if canary.body != current.body {
+        message.list_item();
+        write!(message, "before: {}", current.body).unwrap();
+        message.close_last();
+        message.list_item();
+        write!(message, "after: {}", canary.body).unwrap();
+        message.close_last();
+    }
+    message.close_styles();
+
+    Some(message)
+}
+
+/// Posts to the review room how the canary rules would change the announcement of an event. The
+/// `response` is the message of the bot before any rule applied.
+pub async fn review(
+    sender: &impl MessageSender,
+    config: &ProloloConfig,
+    canary: &CanaryConfig,
+    id: Option<&str>,
+    response: &Response,
+    overrides: &Overrides,
+    verdict: Verdict,
+) -> anyhow::Result<()> {
+    let current = Announcement::new(response.clone(), overrides.clone(), config)?;
+    let canary_announcement = match verdict {
+        Verdict::Keep(overrides) => Some(Announcement::new(response.clone(), overrides, config)?),
+        Verdict::Drop => None,
+    };
+
+    let message = match diff_message(id, &current, canary_announcement.as_ref()) {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let room_id = config.room_by_name(&canary.review_room)?;
+    tokio::time::timeout(
+        config.timeouts.matrix(),
+        sender.send_message(room_id, message.build(), None),
+    )
+    .await
+    .context("timed out sending canary diff")??;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(room: &str, severity: Severity, body: &str) -> Announcement {
+        Announcement {
+            room: room.to_string(),
+            severity,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_message() {
+        let current = announcement("dev", Severity::Normal, "alice pushed 1 commit");
+        assert!(diff_message(Some("42"), &current, Some(&current)).is_none());
+
+        let canary = announcement("ops", Severity::Normal, "push by alice");
+        let mut message = diff_message(Some("42"), &current, Some(&canary)).unwrap();
+        assert_eq!(
+            message.bodies().0,
+            "[canary] event 42 would be announced differently:\n- room: dev → ops\n- before: alice pushed 1 commit\n- after: push by alice"
+        );
+
+        let mut message = diff_message(None, &current, None).unwrap();
+        assert_eq!(
+            message.bodies(),
+            (
+                "[canary] event without ID would be dropped, it is announced as:\n- alice pushed 1 commit",
+                "<b>[canary]</b> event without ID would be dropped, it is announced as:<ul><li>alice pushed 1 commit</li></ul>"
+            )
+        );
+    }
+}
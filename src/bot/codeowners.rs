@@ -0,0 +1,115 @@
+//! Parsing of `CODEOWNERS` files, to find who should review changes to some paths.
+//!
+//! Patterns follow the `.gitignore` syntax supported by GitHub, and the last matching pattern of
+//! the file takes precedence.
+
+use regex::Regex;
+use tracing::warn;
+
+/// Where GitHub looks for the `CODEOWNERS` file, in order
+pub const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+struct Rule {
+    pattern: Regex,
+    /// `@user`, `@org/team` or e-mail addresses, an empty list explicitly removes ownership
+    owners: Vec<String>,
+}
+
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+/// Converts a `CODEOWNERS` pattern to a regex matching the paths it applies to.
+fn pattern_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let directory = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    // patterns with a slash anywhere but at the end are relative to the repo's root
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+
+    let mut re = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    // a pattern matching a directory applies to everything it contains
+    re.push_str(if directory { "/.*$" } else { "(?:/.*)?$" });
+
+    Regex::new(&re)
+}
+
+impl CodeOwners {
+    pub fn parse(content: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next().expect("line isn't empty");
+            let owners = fields
+                .take_while(|owner| !owner.starts_with('#'))
+                .map(str::to_string)
+                .collect();
+
+            match pattern_regex(pattern) {
+                Ok(pattern) => rules.push(Rule { pattern, owners }),
+                Err(e) => warn!("ignoring invalid CODEOWNERS pattern {}: {}", pattern, e),
+            }
+        }
+
+        Self { rules }
+    }
+
+    /// The owners of a file, given its path from the repo's root
+    pub fn owners_of(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.pattern.is_match(path))
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owners_of() {
+        let owners = CodeOwners::parse(
+            r#"
+            # default owners
+            *       @alice
+            *.rs    @bob @prologin/rust  # Rust code
+            /docs/  @carol
+            apps/   @dave
+            docs/generated/**
+            "#,
+        );
+
+        assert_eq!(owners.owners_of("README.md"), ["@alice"]);
+        assert_eq!(owners.owners_of("src/main.rs"), ["@bob", "@prologin/rust"]);
+        assert_eq!(owners.owners_of("docs/index.md"), ["@carol"]);
+        assert_eq!(owners.owners_of("src/docs/index.md"), ["@alice"]);
+        assert_eq!(owners.owners_of("web/apps/login/view.py"), ["@dave"]);
+        assert!(owners.owners_of("docs/generated/api/index.md").is_empty());
+    }
+}
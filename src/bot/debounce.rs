@@ -0,0 +1,255 @@
+//! Combination of the consecutive pushes to a branch, e.g. while a PR is rebased several times in a
+//! row, into a single message.
+//!
+//! The first push to a branch opens a window of [`GitHubConfig::push_debounce_seconds`], the
+//! pushes received in the meantime are announced together when it closes. The buffered events are
+//! only acknowledged once the combined message is handled, so that the queue hands them over again
+//! if the bot stops during the window.
+
+use std::{collections::HashMap, time::Duration};
+
+use tokio::{sync::oneshot, time::Instant};
+use url::Url;
+
+use crate::{
+    config::GitHubConfig,
+    webhooks::{github::PushEvent, Delivery, Event, GitHubEvent},
+};
+
+/// Pushes to a branch waiting for their window to close
+struct Window {
+    closes: Instant,
+    deliveries: Vec<Delivery>,
+}
+
+pub struct Debouncer {
+    window: Option<Duration>,
+    /// Keyed by the repo's full name and the pushed ref
+    windows: HashMap<(String, String), Window>,
+}
+
+impl Debouncer {
+    pub fn new(config: &GitHubConfig) -> Self {
+        Self {
+            window: Some(Duration::from_secs(config.push_debounce_seconds))
+                .filter(|window| !window.is_zero()),
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Buffers the pushes to a branch, the other events are returned to be handled right away.
+    pub fn push(&mut self, delivery: Delivery) -> Option<Delivery> {
+        let window = match self.window {
+            Some(window) => window,
+            None => return Some(delivery),
+        };
+        let key = match &delivery.event {
+            // tags are announced by `create` events, their pushes don't need to wait
+            Event::GitHub(GitHubEvent::Push(event)) if event.branch().is_some() => {
+                (event.repository.full_name.clone(), event.r#ref.clone())
+            }
+            _ => return Some(delivery),
+        };
+
+        self.windows
+            .entry(key)
+            .or_insert_with(|| Window {
+                closes: Instant::now() + window,
+                deliveries: Vec::new(),
+            })
+            .deliveries
+            .push(delivery);
+
+        None
+    }
+
+    /// Waits until the next window closes, forever if there is none.
+    pub async fn next_close(&self) {
+        match self.windows.values().map(|window| window.closes).min() {
+            Some(closes) => tokio::time::sleep_until(closes).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Returns the combined pushes of the windows closed at `now`, all of them if `now` is `None`.
+    pub fn flush(&mut self, now: Option<Instant>) -> Vec<Delivery> {
+        let closed: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|(_, window)| now.map_or(true, |now| window.closes <= now))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        closed
+            .into_iter()
+            .filter_map(|key| self.windows.remove(&key))
+            .map(|window| combine(window.deliveries))
+            .collect()
+    }
+}
+
+/// Combines the deliveries of the pushes to a branch into the last one, which is done once all of
+/// them are
+fn combine(mut deliveries: Vec<Delivery>) -> Delivery {
+    let count = deliveries.len();
+    let mut last = deliveries.pop().expect("windows have at least one push");
+    if count == 1 {
+        return last;
+    }
+
+    let mut dones: Vec<_> = deliveries
+        .iter_mut()
+        .filter_map(|delivery| delivery.done.take())
+        .collect();
+    if !dones.is_empty() {
+        dones.extend(last.done.take());
+        let (done, combined_done) = oneshot::channel();
+        last.done = Some(done);
+        tokio::spawn(async move {
+            // none is acknowledged if the combined push isn't handled
+            if combined_done.await.is_ok() {
+                for done in dones {
+                    let _ = done.send(());
+                }
+            }
+        });
+    }
+
+    let events: Vec<PushEvent> = deliveries
+        .into_iter()
+        .filter_map(|delivery| match delivery.event {
+            Event::GitHub(GitHubEvent::Push(event)) => Some(event),
+            _ => None,
+        })
+        .collect();
+    if let Event::GitHub(GitHubEvent::Push(push)) = &mut last.event {
+        let first = events.first().expect("several pushes were combined");
+        push.before = first.before.clone();
+        push.created = first.created;
+        // the commits pushed before the last force push may no longer be on the branch
+        let last_forced = if push.forced {
+            Some(events.len())
+        } else {
+            events.iter().rposition(|event| event.forced)
+        };
+        push.forced = last_forced.is_some();
+        if let Ok(compare) = Url::parse(&format!(
+            "{}/compare/{}...{}",
+            push.repository.html_url, push.before, push.after
+        )) {
+            push.compare = compare;
+        }
+
+        let mut commits: Vec<_> = events
+            .into_iter()
+            .skip(last_forced.unwrap_or(0))
+            .flat_map(|event| event.commits)
+            .collect();
+        commits.append(&mut push.commits);
+        push.commits = commits;
+        push.pushes = Some(count);
+    }
+
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::webhooks::GitHubEventType;
+
+    fn push(before: &str, after: &str, commits: usize, forced: bool) -> Delivery {
+        let commits: Vec<_> = (0..commits)
+            .map(|i| {
+                json!({
+                    "id": format!("{}{}", after, i),
+                    "url": format!("https://github.com/prologin/site/commit/{}{}", after, i),
+                    "distinct": true,
+                    "message": format!("Commit {}", i),
+                })
+            })
+            .collect();
+        let payload = json!({
+            "repository": {
+                "name": "site",
+                "full_name": "prologin/site",
+                "html_url": "https://github.com/prologin/site",
+            },
+            "sender": {
+                "login": "alice",
+                "id": 1,
+                "html_url": "https://github.com/alice",
+                "avatar_url": "https://avatars.githubusercontent.com/u/1",
+                "type": "User",
+            },
+            "head_commit": commits.last(),
+            "commits": commits,
+            "forced": forced,
+            "created": false,
+            "ref": "refs/heads/main",
+            "before": before,
+            "after": after,
+            "compare": format!("https://github.com/prologin/site/compare/{}...{}", before, after),
+        });
+        let event = GitHubEventType::Push
+            .parse_payload(payload.to_string())
+            .unwrap();
+        Event::GitHub(event).into()
+    }
+
+    #[tokio::test]
+    async fn test_debouncer() {
+        let config = GitHubConfig {
+            push_debounce_seconds: 60,
+            ..Default::default()
+        };
+        let mut debouncer = Debouncer::new(&config);
+
+        let mut acks = Vec::new();
+        for (before, after, commits, forced) in [
+            ("aaa", "bbb", 2, false),
+            ("bbb", "ccc", 3, true),
+            ("ccc", "ddd", 1, false),
+        ] {
+            let mut delivery = push(before, after, commits, forced);
+            let (done, ack) = oneshot::channel();
+            delivery.done = Some(done);
+            acks.push(ack);
+            assert!(debouncer.push(delivery).is_none());
+        }
+        assert!(debouncer.flush(Some(Instant::now())).is_empty());
+
+        let mut combined = debouncer.flush(None);
+        assert_eq!(combined.len(), 1);
+        let mut combined = combined.pop().unwrap();
+        match &combined.event {
+            Event::GitHub(GitHubEvent::Push(event)) => {
+                assert_eq!(event.pushes, Some(3));
+                // the commits of the first push were overwritten by the force push
+                assert_eq!(event.commits.len(), 4);
+                assert_eq!(event.before, "aaa");
+                assert_eq!(event.after, "ddd");
+                assert!(event.forced);
+                assert_eq!(
+                    event.compare.as_str(),
+                    "https://github.com/prologin/site/compare/aaa...ddd"
+                );
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        // the pushes are acknowledged once the combined one is handled
+        for ack in &mut acks {
+            assert!(ack.try_recv().is_err());
+        }
+        combined.done.take().unwrap().send(()).unwrap();
+        for ack in acks {
+            assert!(ack.await.is_ok());
+        }
+
+        let mut debouncer = Debouncer::new(&GitHubConfig::default());
+        assert!(debouncer.push(push("aaa", "bbb", 1, false)).is_some());
+    }
+}
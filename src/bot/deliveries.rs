@@ -0,0 +1,171 @@
+//! Checks of the deliveries of the bot's own webhook, as reported by GitHub. When the endpoints
+//! can't be reached (e.g. a misconfigured reverse proxy or an expired certificate), the bot doesn't
+//! receive anything, so it has to ask GitHub how its deliveries went.
+//!
+//! The ops room is alerted when deliveries start failing, and once they succeed again.
+
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use matrix_sdk::{ruma::events::AnyMessageEventContent, Client};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder},
+    config::{GitHubConfig, ProloloConfig},
+    github_api::{GitHubApi, HookDelivery},
+};
+
+/// What the bot knows of the previous checks
+#[derive(Default)]
+struct Checks {
+    /// The ID of the latest delivery seen, `None` before the first check
+    latest: Option<u64>,
+    failing: bool,
+}
+
+impl Checks {
+    /// Records the deliveries made since the previous check, returns the alert to send if the
+    /// deliveries started failing or recovered.
+    fn record(&mut self, deliveries: &[HookDelivery]) -> Option<MessageBuilder> {
+        let new: Vec<_> = deliveries
+            .iter()
+            .filter(|delivery| self.latest.map_or(true, |latest| delivery.id > latest))
+            .collect();
+        if let Some(latest) = new.iter().map(|delivery| delivery.id).max() {
+            self.latest = Some(latest);
+        }
+        // the state doesn't change while GitHub doesn't try to deliver anything
+        if new.is_empty() {
+            return None;
+        }
+
+        let failed: Vec<_> = new.iter().filter(|delivery| delivery.failed()).collect();
+        let failing = !failed.is_empty();
+        if failing == self.failing {
+            return None;
+        }
+        self.failing = failing;
+
+        let mut message = MessageBuilder::new();
+        match failed.iter().max_by_key(|delivery| delivery.id) {
+            Some(last) => {
+                message.tag("webhook", Some(emoji::POLICE_CAR_LIGHT));
+                write!(
+                    message,
+                    " {} of the last {} GitHub deliveries failed, latest error on a {} event: {}",
+                    failed.len(),
+                    new.len(),
+                    last.event,
+                    last.status
+                )
+                .unwrap();
+            }
+            None => {
+                message.tag("webhook", Some(emoji::CHECK_MARK));
+                write!(message, " GitHub deliveries are succeeding again").unwrap();
+            }
+        }
+
+        Some(message)
+    }
+}
+
+async fn check_deliveries(
+    client: &Client,
+    config: &ProloloConfig,
+    api: &GitHubApi,
+    checks: &mut Checks,
+) -> anyhow::Result<()> {
+    let deliveries_config = config.deliveries.as_ref().expect("checks are enabled");
+
+    let deliveries = api
+        .hook_deliveries(&deliveries_config.org, deliveries_config.hook_id)
+        .await?;
+    let message = match checks.record(&deliveries) {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+
+    let room_id = config.room_by_name(&deliveries_config.room)?;
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+    room.send(AnyMessageEventContent::RoomMessage(message.into()), None)
+        .await?;
+
+    Ok(())
+}
+
+/// Regularly checks the deliveries of the bot's webhook, and alerts the ops room of failures.
+pub async fn monitor_deliveries(client: Client, config: ProloloConfig) {
+    let deliveries_config = match &config.deliveries {
+        Some(deliveries_config) => deliveries_config,
+        None => return,
+    };
+    let github = GitHubConfig {
+        api_token: deliveries_config
+            .token
+            .clone()
+            .or_else(|| config.github.api_token.clone()),
+        ..config.github.clone()
+    };
+    let api = match GitHubApi::new(&github, config.timeouts.http()) {
+        Ok(api) => api,
+        Err(e) => {
+            warn!("couldn't build deliveries client: {:#}", e);
+            return;
+        }
+    };
+
+    let mut checks = Checks::default();
+    loop {
+        debug!("checking webhook deliveries");
+        if let Err(e) = check_deliveries(&client, &config, &api, &mut checks).await {
+            warn!("couldn't check webhook deliveries: {:#}", e);
+        }
+        tokio::time::sleep(deliveries_config.interval()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivery(id: u64, status_code: u16, status: &str) -> HookDelivery {
+        HookDelivery {
+            id,
+            status: status.to_string(),
+            status_code,
+            event: "push".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_checks() {
+        let mut checks = Checks::default();
+        assert!(checks.record(&[delivery(1, 200, "OK")]).is_none());
+        // nothing new
+        assert!(checks.record(&[delivery(1, 200, "OK")]).is_none());
+
+        let deliveries = [
+            delivery(4, 0, "Timed out"),
+            delivery(3, 502, "Invalid HTTP Response: 502"),
+            delivery(2, 200, "OK"),
+            delivery(1, 200, "OK"),
+        ];
+        let alert = checks.record(&deliveries).unwrap();
+        assert_eq!(
+            alert.plain,
+            "[🚨 webhook] 2 of the last 3 GitHub deliveries failed, latest error on a push event: Timed out"
+        );
+        // still failing, already alerted
+        assert!(checks.record(&[delivery(5, 0, "Timed out")]).is_none());
+
+        let recovered = checks.record(&[delivery(6, 200, "OK")]).unwrap();
+        assert_eq!(
+            recovered.plain,
+            "[✅ webhook] GitHub deliveries are succeeding again"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Tracking of what is deployed in each environment, from the `deployment_status` events of the
+//! repos.
+//!
+//! The status of all the environments is available through the `!deployments` command, and kept
+//! up to date in a message pinned in the configured room.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use matrix_sdk::{
+    ruma::{
+        events::{
+            room::{
+                message::{MessageEventContent, Relation, Replacement},
+                pinned_events::PinnedEventsEventContent,
+            },
+            AnyMessageEventContent, EventType, SyncStateEvent,
+        },
+        EventId,
+    },
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder, state::SharedState},
+    config::ProloloConfig,
+    webhooks::{
+        github::{DeploymentState, DeploymentStatusAction},
+        GitHubEvent,
+    },
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeployedRef {
+    pub r#ref: String,
+    pub sha: String,
+    /// The GitHub login of who deployed it
+    pub deployer: String,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Deployments {
+    /// Keys are the repos' full names, then environments
+    repos: BTreeMap<String, BTreeMap<String, DeployedRef>>,
+    /// The pinned message showing the status of the environments, once it was sent
+    status_message: Option<EventId>,
+}
+
+impl Deployments {
+    /// Records what is deployed in an environment, returns whether it changed. Deploying the same
+    /// commit again, e.g. when an event is redelivered, keeps the date of the first deployment.
+    pub fn record(&mut self, repo: &str, environment: &str, deployed: DeployedRef) -> bool {
+        let environments = self.repos.entry(repo.to_string()).or_default();
+        if let Some(current) = environments.get(environment) {
+            if current.r#ref == deployed.r#ref && current.sha == deployed.sha {
+                return false;
+            }
+        }
+        environments.insert(environment.to_string(), deployed);
+        true
+    }
+
+    pub fn status_message(&self) -> MessageBuilder {
+        let mut message = MessageBuilder::new();
+
+        message.tag("deployments", Some(emoji::ROCKET));
+        if self.repos.is_empty() {
+            write!(message, " nothing was deployed yet").unwrap();
+            return message;
+        }
+
+        for (repo, environments) in &self.repos {
+            let repo_name = repo.rsplit('/').next().unwrap_or(repo);
+            for (environment, deployed) in environments {
+                message.line_break();
+                message.bold();
+                write!(message, "{}", repo_name).unwrap();
+                message.close_last();
+                write!(
+                    message,
+                    ": {} is at {} since {} ({})",
+                    environment,
+                    deployed.r#ref,
+                    deployed.since.format("%a %d %b %H:%M"),
+                    deployed.deployer
+                )
+                .unwrap();
+            }
+        }
+
+        message
+    }
+}
+
+/// Updates the deployed refs if this event is a successful deployment, returns whether they
+/// changed.
+pub async fn record_event(event: &GitHubEvent, state: &SharedState) -> anyhow::Result<bool> {
+    let event = match event {
+        GitHubEvent::DeploymentStatus(event)
+            if event.action == DeploymentStatusAction::Created
+                && event.deployment_status.state == DeploymentState::Success =>
+        {
+            event
+        }
+        _ => return Ok(false),
+    };
+    let deployment = &event.deployment;
+
+    debug!(
+        "recording deployment of {} to {} in {}",
+        deployment.r#ref, deployment.environment, event.repository.full_name
+    );
+
+    let mut store = state.lock().await;
+    let changed = store.state.deployments.record(
+        &event.repository.full_name,
+        &deployment.environment,
+        DeployedRef {
+            r#ref: deployment.r#ref.clone(),
+            sha: deployment.sha.clone(),
+            deployer: event.sender.login.clone(),
+            since: Utc::now(),
+        },
+    );
+    if changed {
+        store.save()?;
+    }
+
+    Ok(changed)
+}
+
+/// Edits the pinned status message of the configured room, or sends and pins it the first time.
+pub async fn update_status_message(
+    client: &Client,
+    config: &ProloloConfig,
+    state: &SharedState,
+) -> anyhow::Result<()> {
+    let room_id = match &config.deployments {
+        Some(deployments) => config.room_by_name(&deployments.room)?,
+        None => return Ok(()),
+    };
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+
+    // the state isn't locked during the requests to the homeserver
+    let (message, status_message) = {
+        let store = state.lock().await;
+        let deployments = &store.state.deployments;
+        (
+            deployments.status_message(),
+            deployments.status_message.clone(),
+        )
+    };
+    let (plain, html) = message.into_bodies();
+    let content = MessageEventContent::text_html(plain.clone(), html);
+
+    if let Some(event_id) = status_message {
+        // clients not supporting edits show the fallback as a new message
+        let mut edit = MessageEventContent::text_plain(format!("* {}", plain));
+        edit.relates_to = Some(Relation::Replacement(Replacement::new(
+            event_id,
+            Box::new(content),
+        )));
+        room.send(AnyMessageEventContent::RoomMessage(edit), None)
+            .await?;
+        return Ok(());
+    }
+
+    let event_id = room
+        .send(AnyMessageEventContent::RoomMessage(content), None)
+        .await?
+        .event_id;
+
+    // keep the messages that were already pinned
+    let mut pinned = match room
+        .get_state_event(EventType::RoomPinnedEvents, "")
+        .await?
+    {
+        Some(raw) => {
+            raw.deserialize_as::<SyncStateEvent<PinnedEventsEventContent>>()?
+                .content
+                .pinned
+        }
+        None => Vec::new(),
+    };
+    pinned.push(event_id.clone());
+    room.send_state_event(PinnedEventsEventContent::new(pinned), "")
+        .await?;
+
+    let mut store = state.lock().await;
+    store.state.deployments.status_message = Some(event_id);
+    store.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_message() {
+        let mut deployments = Deployments::default();
+        assert_eq!(
+            deployments.status_message().plain,
+            "[🚀 deployments] nothing was deployed yet"
+        );
+
+        let deployed = |r#ref: &str, since: &str| DeployedRef {
+            r#ref: r#ref.to_string(),
+            sha: "deadbeef".to_string(),
+            deployer: "alice".to_string(),
+            since: DateTime::parse_from_rfc3339(since)
+                .unwrap()
+                .with_timezone(&Utc),
+        };
+        deployments.record(
+            "prologin/site",
+            "prod",
+            deployed("v1.2.2", "2021-12-07T10:00:00Z"),
+        );
+        deployments.record(
+            "prologin/site",
+            "staging",
+            deployed("main", "2021-12-13T18:30:00Z"),
+        );
+        assert!(deployments.record(
+            "prologin/site",
+            "prod",
+            deployed("v1.2.3", "2021-12-14T09:15:00Z"),
+        ));
+        // redelivered deployment
+        assert!(!deployments.record(
+            "prologin/site",
+            "prod",
+            deployed("v1.2.3", "2021-12-14T09:20:00Z"),
+        ));
+
+        assert_eq!(
+            deployments.status_message().plain,
+            "[🚀 deployments]\nsite: prod is at v1.2.3 since Tue 14 Dec 09:15 (alice)\nsite: staging is at main since Mon 13 Dec 18:30 (alice)"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Detection of identical messages sent to the same room in a row, usually because an event is
+//! delivered by several webhooks (e.g. one on the organization and one on the repo). The copies
+//! are dropped, and the misconfiguration is reported once per room.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use matrix_sdk::ruma::RoomId;
+
+use crate::config::ProloloConfig;
+
+pub struct Duplicates {
+    /// Disabled if zero
+    window: Duration,
+    /// The last message sent to each room, with when it was sent
+    last: Mutex<HashMap<RoomId, (String, Instant)>>,
+    /// The rooms whose duplicates were already reported
+    reported: Mutex<HashSet<RoomId>>,
+}
+
+impl Duplicates {
+    pub fn from_config(config: &ProloloConfig) -> Self {
+        Self {
+            window: Duration::from_secs(config.duplicate_window_seconds),
+            last: Default::default(),
+            reported: Default::default(),
+        }
+    }
+
+    /// Whether the message is the same as the last one sent to the room, during the window. The
+    /// window starts with the first copy, later copies don't extend it
+    pub fn is_duplicate(&self, room_id: &RoomId, plain: &str, now: Instant) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+
+        let mut last = self.last.lock().unwrap();
+        match last.get(room_id) {
+            Some((previous, sent))
+                if previous == plain && now.duration_since(*sent) < self.window =>
+            {
+                true
+            }
+            _ => {
+                last.insert(room_id.clone(), (plain.to_string(), now));
+                false
+            }
+        }
+    }
+
+    /// Whether the duplicates of a room should be reported, only the first time
+    pub fn should_report(&self, room_id: &RoomId) -> bool {
+        self.reported.lock().unwrap().insert(room_id.clone())
+    }
+
+    /// The window, to explain the reports
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    #[test]
+    fn test_duplicates() {
+        let dev = RoomId::try_from("!dev:prologin.org").unwrap();
+        let infra = RoomId::try_from("!infra:prologin.org").unwrap();
+        let duplicates = Duplicates {
+            window: Duration::from_secs(60),
+            last: Default::default(),
+            reported: Default::default(),
+        };
+
+        let start = Instant::now();
+        let pushed = "[site] alice pushed deadbee on ⊶main: Fix login";
+        assert!(!duplicates.is_duplicate(&dev, pushed, start));
+        assert!(duplicates.is_duplicate(&dev, pushed, start + Duration::from_secs(1)));
+        assert!(duplicates.is_duplicate(&dev, pushed, start + Duration::from_secs(2)));
+        // the same message in another room, or after the window, is sent
+        assert!(!duplicates.is_duplicate(&infra, pushed, start + Duration::from_secs(2)));
+        assert!(!duplicates.is_duplicate(&dev, pushed, start + Duration::from_secs(61)));
+        // only consecutive messages are compared
+        assert!(!duplicates.is_duplicate(&dev, "[site] bob starred site", start));
+        assert!(!duplicates.is_duplicate(&dev, pushed, start + Duration::from_secs(62)));
+
+        assert!(duplicates.should_report(&dev));
+        assert!(!duplicates.should_report(&dev));
+        assert!(duplicates.should_report(&infra));
+    }
+}
@@ -1,9 +1,26 @@
+pub(crate) const ALARM_CLOCK: char = '\u{23F0}';
+pub(crate) const BOOK: char = '\u{1F4D6}';
+pub(crate) const CHECK_MARK: char = '\u{2705}';
+pub(crate) const CLIPBOARD: char = '\u{1F4CB}';
+pub(crate) const CROSS_MARK: char = '\u{274C}';
+pub(crate) const E_MAIL: char = '\u{1F4E7}';
 pub(crate) const FIRE: char = '\u{1F525}';
 pub(crate) const GRADUATION_CAP: char = '\u{1F393}';
+pub(crate) const HOURGLASS: char = '\u{231B}';
+pub(crate) const LABEL: char = '\u{1F3F7}';
+pub(crate) const LOCKED_WITH_PEN: char = '\u{1F50F}';
+pub(crate) const MAGNIFYING_GLASS: char = '\u{1F50D}';
 pub(crate) const OUTBOX_TRAY: char = '\u{1F4E4}';
 pub(crate) const PACKAGE: char = '\u{1F4E6}';
 pub(crate) const PEOPLE: char = '\u{1F9D1}';
 pub(crate) const PING_PONG: char = '\u{1F3D3}';
 pub(crate) const POLICE_CAR_LIGHT: char = '\u{1F6A8}';
+pub(crate) const ROCKET: char = '\u{1F680}';
+pub(crate) const SCROLL: char = '\u{1F4DC}';
+pub(crate) const SHIELD: char = '\u{1F6E1}';
 pub(crate) const SPEECH_BALLOON: char = '\u{1F4AC}';
+pub(crate) const STAR: char = '\u{2B50}';
+pub(crate) const TROPHY: char = '\u{1F3C6}';
+pub(crate) const WARNING: char = '\u{26A0}';
+pub(crate) const WAVING_HAND: char = '\u{1F44B}';
 pub(crate) const WRENCH: char = '\u{1F527}';
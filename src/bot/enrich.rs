@@ -0,0 +1,199 @@
+//! Completion of events with information that isn't in their payload, fetched from the GitHub API
+//! before they are formatted.
+
+use tracing::warn;
+
+use crate::{
+    config::GitHubConfig,
+    github_api::{GitHubApi, Release},
+    webhooks::{
+        github::{
+            CreateEvent, PullRequestAction, PullRequestEvent, PushEvent, RefType, ReleaseAction,
+            ReleaseEvent,
+        },
+        Event, GitHubEvent,
+    },
+};
+
+pub async fn enrich_event(event: &mut Event, api: &GitHubApi, config: &GitHubConfig) {
+    match event {
+        Event::GitHub(GitHubEvent::Create(event)) => enrich_create(event, api).await,
+        Event::GitHub(GitHubEvent::PullRequest(event)) => {
+            enrich_pull_request(event, api, config).await
+        }
+        Event::GitHub(GitHubEvent::Push(event)) => enrich_push(event, api, config).await,
+        Event::GitHub(GitHubEvent::Release(event)) => enrich_release(event, api).await,
+        _ => {}
+    }
+}
+
+async fn enrich_create(event: &mut CreateEvent, api: &GitHubApi) {
+    if let RefType::Branch = event.ref_type {
+        return;
+    }
+
+    match api
+        .release_by_tag(&event.repository.full_name, &event.r#ref)
+        .await
+    {
+        Ok(release) => event.release_notes = release.and_then(|release| release.body),
+        Err(e) => warn!(
+            "couldn't get release for tag {} in {}: {:#}",
+            event.r#ref, event.repository.full_name, e
+        ),
+    }
+}
+
+async fn enrich_release(event: &mut ReleaseEvent, api: &GitHubApi) {
+    if event.action != ReleaseAction::Published {
+        return;
+    }
+
+    match api.releases(&event.repository.full_name).await {
+        Ok(releases) => {
+            event.previous_tag =
+                previous_tag(&releases, &event.release.tag_name).map(str::to_string)
+        }
+        Err(e) => warn!(
+            "couldn't list releases of {}: {:#}",
+            event.repository.full_name, e
+        ),
+    }
+}
+
+/// The tag of the release published before the one of `tag`, `releases` being newest first
+fn previous_tag<'a>(releases: &'a [Release], tag: &str) -> Option<&'a str> {
+    releases
+        .iter()
+        .skip_while(|release| release.tag_name != tag)
+        .skip(1)
+        .find(|release| !release.draft)
+        .map(|release| release.tag_name.as_str())
+}
+
+async fn enrich_pull_request(event: &mut PullRequestEvent, api: &GitHubApi, config: &GitHubConfig) {
+    let repo = &event.repository.full_name;
+    let announced = matches!(
+        event.action,
+        PullRequestAction::Opened | PullRequestAction::Reopened
+    );
+    if !announced || !config.watches_repo(repo) {
+        return;
+    }
+
+    match api
+        .pull_request_files(repo, event.pull_request.number)
+        .await
+    {
+        Ok(files) => {
+            event.changed_files = files.into_iter().map(|file| file.filename).collect();
+        }
+        Err(e) => warn!(
+            "couldn't list files of PR #{} in {}: {:#}",
+            event.pull_request.number, repo, e
+        ),
+    }
+}
+
+async fn enrich_push(event: &mut PushEvent, api: &GitHubApi, config: &GitHubConfig) {
+    if event.is_new_ref() {
+        return;
+    }
+
+    if event.forced {
+        // the commits only reachable from the old head were discarded
+        match api
+            .compare(&event.repository.full_name, &event.after, &event.before)
+            .await
+        {
+            Ok(comparison) => event.discarded_commits = Some(comparison.ahead_by),
+            Err(e) => warn!(
+                "couldn't compare {}...{} in {}: {:#}",
+                event.after, event.before, event.repository.full_name, e
+            ),
+        }
+    }
+
+    let protected = event.branch().map_or(false, |branch| {
+        config
+            .protected_branches
+            .iter()
+            .any(|re| re.is_match(branch))
+    });
+    let truncated = event.may_be_truncated();
+    // the payloads don't tell whether commits are signed, this matters on protected branches
+    if protected || truncated {
+        match api
+            .compare(&event.repository.full_name, &event.before, &event.after)
+            .await
+        {
+            Ok(comparison) => {
+                if truncated {
+                    event.total_commits = Some(comparison.ahead_by);
+                }
+                if protected {
+                    event.flagged_files = comparison
+                        .files
+                        .into_iter()
+                        .map(|file| file.filename)
+                        .filter(|filename| needs_attention(filename))
+                        .collect()
+                }
+                if let Some(head) = event
+                    .head_commit
+                    .as_mut()
+                    .filter(|head| head.verification.is_none())
+                {
+                    head.verification = comparison
+                        .commits
+                        .into_iter()
+                        .find(|commit| commit.sha == *head.id)
+                        .and_then(|commit| commit.commit.verification);
+                }
+            }
+            Err(e) => warn!(
+                "couldn't compare {}...{} in {}: {:#}",
+                event.before, event.after, event.repository.full_name, e
+            ),
+        }
+    }
+}
+
+/// Whether changing a file can break builds in non-obvious ways: `.gitmodules` for submodules,
+/// `.gitattributes` for LFS tracking
+fn needs_attention(filename: &str) -> bool {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    name == ".gitmodules" || name == ".gitattributes"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_attention() {
+        assert!(needs_attention(".gitmodules"));
+        assert!(needs_attention("assets/.gitattributes"));
+        assert!(!needs_attention("src/main.rs"));
+        assert!(!needs_attention("docs/gitmodules.md"));
+    }
+
+    #[test]
+    fn test_previous_tag() {
+        let release = |tag: &str, draft| Release {
+            tag_name: tag.to_string(),
+            body: None,
+            draft,
+        };
+        let releases = [
+            release("v1.3", true),
+            release("v1.2", false),
+            release("v1.2-rc1", true),
+            release("v1.1", false),
+        ];
+
+        assert_eq!(previous_tag(&releases, "v1.2"), Some("v1.1"));
+        assert_eq!(previous_tag(&releases, "v1.1"), None);
+        assert_eq!(previous_tag(&releases, "v0.1"), None);
+    }
+}
@@ -0,0 +1,112 @@
+//! Per-room content filters, masking unwanted words and room-wide pings that could be relayed from
+//! user-generated text (commit messages, comments, ...).
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use matrix_sdk::ruma::RoomId;
+use regex::{Captures, Regex};
+
+use crate::{
+    bot::utils::map_html_text,
+    config::{ContentFilterConfig, ProloloConfig},
+};
+
+struct ContentFilter {
+    words: Option<Regex>,
+    pings: Option<Regex>,
+}
+
+impl ContentFilter {
+    fn new(config: &ContentFilterConfig) -> anyhow::Result<Self> {
+        let words = if config.words.is_empty() {
+            None
+        } else {
+            let words = config
+                .words
+                .iter()
+                .map(|word| regex::escape(word))
+                .collect::<Vec<_>>();
+            Some(Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|")))?)
+        };
+
+        let pings = if config.strip_pings {
+            Some(Regex::new(r"@(room|everyone|here|channel)\b").expect("valid regex"))
+        } else {
+            None
+        };
+
+        Ok(Self { words, pings })
+    }
+
+    fn filter_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+
+        if let Some(words) = &self.words {
+            text = words
+                .replace_all(&text, |caps: &Captures| "*".repeat(caps[0].chars().count()))
+                .into_owned();
+        }
+
+        if let Some(pings) = &self.pings {
+            text = pings.replace_all(&text, "$1").into_owned();
+        }
+
+        text
+    }
+
+    fn filter_html(&self, html: &str) -> String {
+        map_html_text(html, |text, _| self.filter_text(text))
+    }
+}
+
+pub struct Filters {
+    filters: HashMap<RoomId, ContentFilter>,
+}
+
+impl Filters {
+    pub fn from_config(config: &ProloloConfig) -> anyhow::Result<Self> {
+        let mut filters = HashMap::new();
+
+        for (name, room) in &config.matrix_rooms {
+            if let Some(filter) = &room.filter {
+                let filter = ContentFilter::new(filter)
+                    .with_context(|| format!("invalid content filter for room {}", name))?;
+                filters.insert(room.id.clone(), filter);
+            }
+        }
+
+        Ok(Self { filters })
+    }
+
+    /// Applies the content filter of a room to both bodies of a message.
+    pub fn apply(&self, room_id: &RoomId, plain: String, html: String) -> (String, String) {
+        match self.filters.get(room_id) {
+            Some(filter) => (filter.filter_text(&plain), filter.filter_html(&html)),
+            None => (plain, html),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_filter() {
+        let filter = ContentFilter::new(&ContentFilterConfig {
+            words: vec!["darn".to_string(), "b.d".to_string()],
+            strip_pings: true,
+        })
+        .unwrap();
+
+        assert_eq!(
+            filter.filter_text("Darn it @room, bad b.d darned"),
+            "**** it room, bad *** darned"
+        );
+        assert_eq!(
+            filter.filter_html(r#"<b>[site]</b> <a href="https://darn.org">darn</a> @everyone"#),
+            r#"<b>[site]</b> <a href="https://darn.org">****</a> everyone"#
+        );
+    }
+}
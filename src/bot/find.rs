@@ -0,0 +1,189 @@
+//! Search of the archived announcements, answering questions like "when was X deployed?".
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, Context};
+use chrono::Utc;
+use matrix_sdk::ruma::RoomId;
+use regex::Regex;
+use url::Url;
+
+use crate::{
+    bot::{
+        archive::{Archive, ArchivedKind, ArchivedMessage},
+        args::Args,
+        emoji,
+        message_builder::MessageBuilder,
+        utils::{parse_duration, shorten_content},
+    },
+    config::ProloloConfig,
+};
+
+/// Maximum number of matches listed in the answer
+const MAX_RESULTS: usize = 10;
+
+fn event_link(message: &ArchivedMessage) -> Url {
+    Url::parse(&format!(
+        "https://matrix.to/#/{}/{}",
+        message.room, message.event_id
+    ))
+    .expect("room and event IDs are valid URL fragments")
+}
+
+/// Returns the announcements matching `pattern`, most recent first.
+fn search<'a>(
+    messages: &'a [ArchivedMessage],
+    pattern: &Regex,
+    room: Option<&RoomId>,
+) -> Vec<&'a ArchivedMessage> {
+    messages
+        .iter()
+        .rev()
+        .filter(|message| matches!(message.kind, ArchivedKind::Announcement { .. }))
+        .filter(|message| room.map_or(true, |room| &message.room == room))
+        .filter(|message| pattern.is_match(&message.body))
+        .collect()
+}
+
+fn results_message(pattern: &str, results: &[&ArchivedMessage]) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+    message.tag("find", Some(emoji::MAGNIFYING_GLASS));
+
+    match results.len() {
+        0 => {
+            write!(message, " nothing matches `{}`", pattern).unwrap();
+            return message;
+        }
+        count if count > MAX_RESULTS => write!(
+            message,
+            " {} messages match `{}`, the {} most recent ones:",
+            count, pattern, MAX_RESULTS
+        )
+        .unwrap(),
+        count => write!(message, " {} messages match `{}`:", count, pattern).unwrap(),
+    }
+
+    for result in results.iter().take(MAX_RESULTS) {
+        message.line_break();
+        // the other lines are usually the link to the event
+        let summary = result.body.lines().next().unwrap_or_default();
+        message.link(result.time.format("%Y-%m-%d %H:%M"), &event_link(result));
+        write!(message, " {}", shorten_content(summary)).unwrap();
+    }
+
+    message
+}
+
+/// `!find <regex> [room] [since]`: searches the archived announcements, optionally only those sent
+/// in a room (by its name in the config) or during the last `since` (e.g. `2h` or `30d`). The
+/// filters can also be given as `room=<room>` and `since=<duration>` flags.
+pub fn find(
+    mut args: Args,
+    config: &ProloloConfig,
+    archive: &Archive,
+) -> anyhow::Result<MessageBuilder> {
+    let mut room_name = args.flag("room");
+    let mut since = args.duration_flag("since")?;
+    let positional = args.expect_positional(1, 3)?.to_vec();
+    args.finish()?;
+
+    let pattern = &positional[0];
+    let regex = Regex::new(pattern).with_context(|| format!("invalid regex `{}`", pattern))?;
+
+    for filter in &positional[1..] {
+        // durations can't be mistaken for room names, they start with a digit
+        if filter.starts_with(|c: char| c.is_ascii_digit()) {
+            since = Some(parse_duration(filter)?);
+        } else {
+            room_name = Some(filter.clone());
+        }
+    }
+    let room = match &room_name {
+        Some(name) => Some(
+            config
+                .matrix_rooms
+                .get(name)
+                .map(|room| &room.id)
+                .ok_or_else(|| anyhow!("unknown room `{}`", name))?,
+        ),
+        None => None,
+    };
+    let since = match since {
+        Some(since) => Some(
+            Utc::now()
+                .checked_sub_signed(since)
+                .ok_or_else(|| anyhow!("the period is too long"))?,
+        ),
+        None => None,
+    };
+
+    let messages = match since {
+        Some(since) => archive.since(since)?,
+        None => archive.all()?,
+    };
+
+    Ok(results_message(pattern, &search(&messages, &regex, room)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use chrono::DateTime;
+    use matrix_sdk::ruma::EventId;
+
+    use crate::bot::Severity;
+
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let site = RoomId::try_from("!site:prologin.org").unwrap();
+        let infra = RoomId::try_from("!infra:prologin.org").unwrap();
+        let message = |time: &str, room: &RoomId, body: &str| ArchivedMessage {
+            time: DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc),
+            room: room.clone(),
+            event_id: EventId::try_from(format!("${}:prologin.org", time.len() + body.len()))
+                .unwrap(),
+            kind: ArchivedKind::Announcement {
+                repo: None,
+                author: None,
+                severity: Severity::Normal,
+            },
+            body: body.to_string(),
+        };
+        let messages = vec![
+            message(
+                "2021-12-13T18:30:00Z",
+                &site,
+                "[🚀 site] alice deployed v1.2.2 (deadbee) to prod\nhttps://github.com/prologin/site",
+            ),
+            message(
+                "2021-12-14T09:15:00Z",
+                &infra,
+                "[🚀 infra] bob deployed main (cafe123) to prod",
+            ),
+            message(
+                "2021-12-14T10:00:00Z",
+                &site,
+                "[🚀 site] alice deployed v1.2.3 (f00ba42) to prod",
+            ),
+        ];
+        let regex = Regex::new("deployed .* to prod").unwrap();
+
+        let results = search(&messages, &regex, Some(&site));
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results_message("deployed .* to prod", &results).plain,
+            "[🔍 find] 2 messages match `deployed .* to prod`:\n2021-12-14 10:00 [🚀 site] alice deployed v1.2.3 (f00ba42) to prod\n2021-12-13 18:30 [🚀 site] alice deployed v1.2.2 (deadbee) to prod"
+        );
+
+        assert_eq!(search(&messages, &regex, None).len(), 3);
+        assert_eq!(
+            results_message("nope", &[]).plain,
+            "[🔍 find] nothing matches `nope`"
+        );
+    }
+}
@@ -1,48 +1,226 @@
-use std::fmt::Write;
+use std::fmt::{Display, Write};
 
+use anyhow::Context;
 use tracing::{error, info};
 use url::Url;
 
 use crate::{
-    bot::{emoji, message_builder::MessageBuilder, utils::shorten_content, Response},
+    bot::{
+        emoji,
+        message_builder::MessageBuilder,
+        utils::{first_image, shorten_content, SHORTENED_LENGTH},
+        Response, Severity,
+    },
+    config::GitHubConfig,
     webhooks::{
         github::{
-            CreateEvent, IssueCommentEvent, IssuesEvent, OrganizationEvent, PingEvent,
-            PullRequestEvent, PullRequestReviewCommentEvent, PullRequestReviewEvent, PushEvent,
-            RefType, RepositoryEvent,
+            CheckRunAction, CheckRunEvent, CheckSuiteAction, CheckSuiteEvent, CommitUser,
+            CreateEvent, DeployKeyAction, DeployKeyEvent, DeploymentAction, DeploymentEvent,
+            DeploymentStatusAction, DeploymentStatusEvent, GenericEvent, GitHubUser, GollumEvent,
+            IssueCommentAction, IssueCommentEvent, IssueStateReason, IssuesAction, IssuesEvent,
+            Label, LabelAction, LabelEvent, MembershipAction, MergeGroupAction, MergeGroupEvent,
+            MetaAction, MetaEvent, OrganizationAction, OrganizationEvent, PingEvent,
+            ProjectsV2Action, ProjectsV2Event, ProjectsV2ItemAction, ProjectsV2ItemEvent,
+            PublicEvent, PullRequestAction, PullRequestEvent, PullRequestReviewAction,
+            PullRequestReviewCommentAction, PullRequestReviewCommentEvent, PullRequestReviewEvent,
+            PushEvent, RefType, ReleaseAction, ReleaseEvent, Repository, RepositoryAction,
+            RepositoryEvent, RepositoryRulesetAction, RepositoryRulesetEvent,
+            RepositoryVulnerabilityAlertAction, RepositoryVulnerabilityAlertEvent,
+            SecurityAdvisoryAction, SecurityAdvisoryEvent, StarAction, StarEvent,
+            WorkflowJobAction, WorkflowJobEvent, WorkflowRunAction, WorkflowRunEvent,
         },
         GitHubEvent,
     },
 };
 
-const BRANCH: &str = "⊶";
-const SHORT_HASH_LENGTH: usize = 7;
+/// Number of lines of release notes shown when a tag is created or a release is published
+const RELEASE_NOTES_LINES: usize = 3;
+
+/// Shortens a commit hash, leaving it untouched if it is already shorter than `length`
+fn short_hash(hash: &str, length: usize) -> &str {
+    hash.char_indices()
+        .nth(length)
+        .map_or(hash, |(end, _)| &hash[..end])
+}
 
-pub fn handle_github_event(event: GitHubEvent) -> anyhow::Result<Option<Response>> {
+pub fn handle_github_event(
+    event: GitHubEvent,
+    config: &GitHubConfig,
+) -> anyhow::Result<Option<Response>> {
     let response = match event {
-        GitHubEvent::CommitComment(event) => handle_commit_comment(event),
-        GitHubEvent::Create(event) => handle_create(event),
-        GitHubEvent::Fork(event) => handle_fork(event),
-        GitHubEvent::IssueComment(event) => handle_issue_comment(event),
-        GitHubEvent::Issues(event) => handle_issues(event),
-        GitHubEvent::Membership(event) => handle_membership(event),
-        GitHubEvent::Organization(event) => handle_organization(event),
-        GitHubEvent::Ping(event) => handle_ping(event),
-        GitHubEvent::PullRequest(event) => handle_pull_request(event),
-        GitHubEvent::PullRequestReview(event) => handle_pull_request_review(event),
-        GitHubEvent::PullRequestReviewComment(event) => handle_pull_request_review_comment(event),
-        GitHubEvent::Push(event) => handle_push(event),
-        GitHubEvent::Repository(event) => handle_repository(event),
+        GitHubEvent::CheckRun(event) => handle_check_run(event, config),
+        GitHubEvent::CheckSuite(event) => handle_check_suite(event, config),
+        GitHubEvent::CommitComment(event) => handle_commit_comment(event, config),
+        GitHubEvent::Create(event) => handle_create(event, config),
+        GitHubEvent::DeployKey(event) => handle_deploy_key(event, config),
+        GitHubEvent::Deployment(event) => handle_deployment(event, config),
+        GitHubEvent::DeploymentStatus(event) => handle_deployment_status(event, config),
+        GitHubEvent::Fork(event) => handle_fork(event, config),
+        GitHubEvent::Generic(event) => handle_generic(event, config),
+        GitHubEvent::Gollum(event) => handle_gollum(event, config),
+        GitHubEvent::IssueComment(event) => handle_issue_comment(event, config),
+        GitHubEvent::Issues(event) => handle_issues(event, config)?,
+        GitHubEvent::Label(event) => handle_label(event, config),
+        GitHubEvent::Membership(event) => handle_membership(event, config),
+        GitHubEvent::MergeGroup(event) => handle_merge_group(event, config),
+        GitHubEvent::Meta(event) => handle_meta(event, config),
+        GitHubEvent::Organization(event) => handle_organization(event, config)?,
+        GitHubEvent::Ping(event) => handle_ping(event, config),
+        GitHubEvent::ProjectsV2(event) => handle_projects_v2(event, config),
+        GitHubEvent::ProjectsV2Item(event) => handle_projects_v2_item(event, config),
+        GitHubEvent::Public(event) => handle_public(event, config),
+        GitHubEvent::PullRequest(event) => handle_pull_request(event, config)?,
+        GitHubEvent::PullRequestReview(event) => handle_pull_request_review(event, config),
+        GitHubEvent::PullRequestReviewComment(event) => {
+            handle_pull_request_review_comment(event, config)
+        }
+        GitHubEvent::Push(event) => handle_push(event, config)?,
+        GitHubEvent::Release(event) => handle_release(event, config),
+        GitHubEvent::Repository(event) => handle_repository(event, config)?,
+        GitHubEvent::RepositoryRuleset(event) => handle_repository_ruleset(event, config),
+        GitHubEvent::RepositoryVulnerabilityAlert(event) => {
+            handle_repository_vulnerability_alert(event, config)
+        }
+        GitHubEvent::SecurityAdvisory(event) => handle_security_advisory(event, config),
+        GitHubEvent::Star(event) => handle_star(event, config),
+        GitHubEvent::WorkflowJob(event) => handle_workflow_job(event, config),
+        GitHubEvent::WorkflowRun(event) => handle_workflow_run(event, config),
     };
 
     Ok(response)
 }
 
-fn handle_commit_comment(event: crate::webhooks::github::CommitCommentEvent) -> Option<Response> {
+/// Lists the watched files changed by an event, whose message is then sent to the security room
+/// with a high severity. Returns the room and severity of the message
+fn escalate(
+    message: &mut MessageBuilder,
+    watched_files: &[String],
+    config: &GitHubConfig,
+) -> (Option<String>, Severity) {
+    if watched_files.is_empty() {
+        return (None, Severity::Normal);
+    }
+
+    write!(
+        message,
+        " ({} touches {})",
+        emoji::SHIELD,
+        watched_files.join(", ")
+    )
+    .unwrap();
+
+    (config.security_room.clone(), Severity::High)
+}
+
+fn handle_check_run(event: CheckRunEvent, config: &GitHubConfig) -> Option<Response> {
+    let check = event.check_run;
+
+    // GitHub Actions checks are already covered by `workflow_job` events
+    if event.action != CheckRunAction::Completed
+        || !check.failed()
+        || check.app.slug == "github-actions"
+    {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::CROSS_MARK));
+    write!(&mut message, " check ").unwrap();
+    message.main_link(
+        &check.name,
+        check.details_url.as_ref().unwrap_or(&check.html_url),
+    );
+    write!(
+        &mut message,
+        " ({}) {}",
+        check.app.name,
+        conclusion_verb(check.conclusion.as_deref())
+    )
+    .unwrap();
+    if let Some(branch) = &check.check_suite.head_branch {
+        write!(&mut message, " on ").unwrap();
+        branch_link(&mut message, &event.repository, branch, config);
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_check_suite(event: CheckSuiteEvent, config: &GitHubConfig) -> Option<Response> {
+    let suite = event.check_suite;
+
+    if event.action != CheckSuiteAction::Completed || suite.app.slug == "github-actions" {
+        return None;
+    }
+    let failed = suite.failed();
+    // neutral, skipped, cancelled... suites don't give any verdict
+    if !failed && suite.conclusion.as_deref() != Some("success") {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    let marker = if failed {
+        emoji::CROSS_MARK
+    } else {
+        emoji::CHECK_MARK
+    };
+    message.tag(&event.repository.name, Some(marker));
+    write!(
+        &mut message,
+        " {} {} for ",
+        suite.app.name,
+        conclusion_verb(suite.conclusion.as_deref())
+    )
+    .unwrap();
+
+    let hash = short_hash(&suite.head_sha, config.short_hash_length);
+    match event.repository.checks_url(&suite.head_sha) {
+        Ok(url) => message.main_link(hash, &url),
+        Err(e) => {
+            error!(
+                "couldn't build checks url for commit {} in repo {}: {}",
+                suite.head_sha, event.repository.full_name, e
+            );
+            write!(&mut message, "{}", hash).unwrap();
+        }
+    }
+
+    if let Some(branch) = &suite.head_branch {
+        write!(&mut message, " on ").unwrap();
+        branch_link(&mut message, &event.repository, branch, config);
+    }
+    if let Some(commit) = &suite.head_commit {
+        write!(&mut message, ": {}", shorten_content(commit.title())).unwrap();
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_commit_comment(
+    event: crate::webhooks::github::CommitCommentEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
     let comment = event.comment;
-    let commit_id = comment
-        .commit_id
-        .expect("commit comment without a commit id");
+    let commit_id = match &comment.commit_id {
+        Some(commit_id) => commit_id,
+        None => {
+            error!("commit comment without a commit id: {}", comment.html_url);
+            return None;
+        }
+    };
 
     let mut commit_html_url = comment.html_url.clone();
     commit_html_url.set_fragment(None);
@@ -51,21 +229,33 @@ fn handle_commit_comment(event: crate::webhooks::github::CommitCommentEvent) ->
 
     message.tag(&event.repository.name, Some(emoji::SPEECH_BALLOON));
 
-    write!(&mut message, " {} ", event.sender.login).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " ").unwrap();
 
     message.main_link("commented", &comment.html_url);
     write!(message, " on ").unwrap();
-    message.link(&commit_id[..SHORT_HASH_LENGTH], &commit_html_url);
+    message.link(
+        short_hash(commit_id, config.short_hash_length),
+        &commit_html_url,
+    );
+    if let Some(location) = comment.location() {
+        write!(message, " {}", location).unwrap();
+    }
 
-    write!(message, ": {}", shorten_content(&comment.body)).unwrap();
+    write!(message, ": ").unwrap();
+    message.markdown_shortened(&comment.body, SHORTENED_LENGTH);
 
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: first_image(&comment.body),
     })
 }
 
-fn handle_create(event: CreateEvent) -> Option<Response> {
+fn handle_create(event: CreateEvent, config: &GitHubConfig) -> Option<Response> {
     let mut message = MessageBuilder::new();
 
     match event.ref_type {
@@ -73,7 +263,9 @@ fn handle_create(event: CreateEvent) -> Option<Response> {
         RefType::Tag => {
             message.tag(&event.repository.name, None);
 
-            write!(&mut message, " {} created tag ", event.sender.login,).unwrap();
+            write!(&mut message, " ").unwrap();
+            user_link(&mut message, &event.sender, config);
+            write!(&mut message, " created tag ").unwrap();
 
             let ref_url = match event.repository.ref_url(&event.r#ref) {
                 Ok(url) => url,
@@ -85,32 +277,420 @@ fn handle_create(event: CreateEvent) -> Option<Response> {
                     event.repository.html_url
                 }
             };
-            message.main_link(&event.r#ref, &ref_url)
+            message.main_link(&event.r#ref, &ref_url);
+
+            if let Some(notes) = event
+                .release_notes
+                .as_deref()
+                .and_then(release_notes_excerpt)
+            {
+                write!(&mut message, ": ").unwrap();
+                message.markdown_shortened(&notes, SHORTENED_LENGTH);
+            }
+        }
+    };
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+/// Joins the first lines of release notes, without their Markdown heading markers
+fn release_notes_excerpt(notes: &str) -> Option<String> {
+    let lines = notes
+        .lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .filter(|line| !line.is_empty())
+        .take(RELEASE_NOTES_LINES)
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join(" / "))
+    }
+}
+
+fn handle_release(event: ReleaseEvent, config: &GitHubConfig) -> Option<Response> {
+    // drafts are published when they are ready, the other actions are too verbose
+    if event.action != ReleaseAction::Published {
+        return None;
+    }
+    let release = event.release;
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::PACKAGE));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    if release.prerelease {
+        write!(&mut message, " published pre-release ").unwrap();
+    } else {
+        write!(&mut message, " published release ").unwrap();
+    }
+    // the name defaults to the tag in GitHub's interface
+    let name = release
+        .name
+        .as_deref()
+        .filter(|name| !name.is_empty() && *name != release.tag_name);
+    match name {
+        Some(name) => message.main_link(
+            format_args!("{} ({})", release.tag_name, name),
+            &release.html_url,
+        ),
+        None => message.main_link(&release.tag_name, &release.html_url),
+    }
+
+    if let Some(notes) = release.body.as_deref().and_then(release_notes_excerpt) {
+        write!(&mut message, ": ").unwrap();
+        message.markdown_shortened(&notes, SHORTENED_LENGTH);
+    }
+    if let Some(previous_tag) = &event.previous_tag {
+        let compare = Url::parse(&format!(
+            "{}/compare/{}...{}",
+            event.repository.html_url, previous_tag, release.tag_name
+        ));
+        if let Ok(compare) = compare {
+            write!(&mut message, " (").unwrap();
+            message.link("full changelog", &compare);
+            write!(&mut message, ")").unwrap();
+        }
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_deployment(event: DeploymentEvent, config: &GitHubConfig) -> Option<Response> {
+    if event.action != DeploymentAction::Created {
+        return None;
+    }
+    let deployment = event.deployment;
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::ROCKET));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " deployed ").unwrap();
+
+    let ref_url = match event.repository.ref_url(&deployment.r#ref) {
+        Ok(url) => url,
+        Err(e) => {
+            error!(
+                "couldn't build ref url for {} in repo {}: {}",
+                deployment.r#ref, event.repository.full_name, e
+            );
+            event.repository.html_url
         }
     };
+    message.main_link(&deployment.r#ref, &ref_url);
+    if deployment.r#ref != deployment.sha {
+        write!(
+            &mut message,
+            " ({})",
+            short_hash(&deployment.sha, config.short_hash_length)
+        )
+        .unwrap();
+    }
+    write!(&mut message, " to {}", deployment.environment).unwrap();
 
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_fork(event: crate::webhooks::github::ForkEvent) -> Option<Response> {
+fn handle_fork(
+    event: crate::webhooks::github::ForkEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
     let mut message = MessageBuilder::new();
 
     message.tag(&event.repository.name, Some(emoji::PACKAGE));
     write!(&mut message, " ").unwrap();
-    message.link(&event.sender.login, &event.sender.html_url);
+    user_link(&mut message, &event.sender, config);
     write!(&mut message, " forked into ").unwrap();
     message.main_link(&event.forkee.full_name, &event.forkee.html_url);
 
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_deploy_key(event: DeployKeyEvent, config: &GitHubConfig) -> Option<Response> {
+    let key = event.key;
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::SHIELD));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    let access = if key.read_only {
+        "read-only"
+    } else {
+        "read-write"
+    };
+    let severity = match event.action {
+        DeployKeyAction::Created => {
+            write!(&mut message, " added {} deploy key ", access).unwrap();
+            Severity::High
+        }
+        DeployKeyAction::Deleted => {
+            write!(&mut message, " deleted {} deploy key ", access).unwrap();
+            Severity::Normal
+        }
+        DeployKeyAction::Unknown(_) => {
+            error!("invalid or unsupported deploy key action: {}", event.action);
+            return unknown_action(
+                &event.repository.name,
+                &event.sender,
+                event.action.as_str(),
+                format_args!("deploy key {}", key.title),
+                Some(&event.repository.html_url),
+                Some(&event.repository),
+                config,
+            );
+        }
+    };
+    message.code();
+    write!(&mut message, "{}", key.title).unwrap();
+    message.close_last();
+    write!(&mut message, " to ").unwrap();
+    message.main_link(&event.repository.full_name, &event.repository.html_url);
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: config.security_room.clone(),
+        severity,
+        image: None,
+    })
+}
+
+/// A deleted webhook can silently stop all announcements, if it was one of the bot's
+fn handle_meta(event: MetaEvent, config: &GitHubConfig) -> Option<Response> {
+    if event.action != MetaAction::Deleted {
+        error!("invalid or unsupported meta action: {}", event.action);
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    let owner = match (&event.repository, &event.organization) {
+        (Some(repository), _) => &repository.name,
+        (None, Some(organization)) => &organization.login,
+        (None, None) => "webhooks",
+    };
+    message.tag(owner, Some(emoji::SHIELD));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " deleted webhook {}", event.hook_id).unwrap();
+    // the rest of the URL may hold credentials
+    if let Some(host) = event.hook.config.url.as_ref().and_then(Url::host_str) {
+        write!(&mut message, " to {}", host).unwrap();
+    }
+    match (&event.repository, &event.organization) {
+        (Some(repository), _) => {
+            write!(&mut message, " of ").unwrap();
+            message.main_link(&repository.full_name, &repository.html_url);
+        }
+        (None, Some(organization)) => {
+            write!(&mut message, " of organization {}", organization.login).unwrap()
+        }
+        (None, None) => {}
+    }
+    write!(
+        &mut message,
+        ", its events ({}) won't be sent anymore",
+        event.hook.events.join(", ")
+    )
+    .unwrap();
+
+    Some(Response {
+        message,
+        repo: event.repository.map(|repository| repository.full_name),
+        room: config.security_room.clone(),
+        severity: Severity::High,
+        image: None,
+    })
+}
+
+/// Only failed deployments are announced, the successful ones were announced when created
+fn handle_deployment_status(
+    event: DeploymentStatusEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
+    if event.action != DeploymentStatusAction::Created || !event.deployment_status.failed() {
+        return None;
+    }
+    let deployment = event.deployment;
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::CROSS_MARK));
+    write!(&mut message, " deploying ").unwrap();
+    let ref_url = match event.repository.ref_url(&deployment.r#ref) {
+        Ok(url) => url,
+        Err(e) => {
+            error!(
+                "couldn't build ref url for {} in repo {}: {}",
+                deployment.r#ref, event.repository.full_name, e
+            );
+            event.repository.html_url
+        }
+    };
+    message.main_link(&deployment.r#ref, &ref_url);
+    if deployment.r#ref != deployment.sha {
+        write!(
+            &mut message,
+            " ({})",
+            short_hash(&deployment.sha, config.short_hash_length)
+        )
+        .unwrap();
+    }
+    write!(
+        &mut message,
+        " to {} ended with {}",
+        deployment.environment, event.deployment_status.state
+    )
+    .unwrap();
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+/// A minimal message for the events that aren't handled yet, to see their activity anyway
+fn handle_generic(event: GenericEvent, config: &GitHubConfig) -> Option<Response> {
+    let mut message = MessageBuilder::new();
+
+    let tag = match (&event.repository, &event.organization) {
+        (Some(repository), _) => &repository.name,
+        (None, Some(organization)) => &organization.login,
+        (None, None) => &event.name,
+    };
+    message.tag(tag, None);
+    write!(&mut message, " ").unwrap();
+    match &event.sender {
+        Some(sender) => {
+            user_link(&mut message, sender, config);
+            write!(&mut message, " performed {}", event.name).unwrap();
+        }
+        None => write!(&mut message, "received {}", event.name).unwrap(),
+    }
+    if let Some(action) = &event.action {
+        write!(&mut message, " ({})", action).unwrap();
+    }
+    match (&event.repository, &event.organization) {
+        (Some(repository), _) => {
+            write!(&mut message, " on ").unwrap();
+            message.main_link(&repository.full_name, &repository.html_url);
+        }
+        (None, Some(organization)) => write!(&mut message, " on {}", organization.login).unwrap(),
+        (None, None) => {}
+    }
+
+    Some(Response {
+        message,
+        repo: event.repository.map(|repository| repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+/// A generic message for the actions a handler doesn't support yet, if `announce_unknown_actions`
+/// is set, so that new GitHub actions don't silently disappear
+fn unknown_action(
+    tag: &str,
+    sender: &GitHubUser,
+    action: &str,
+    object: impl Display,
+    url: Option<&Url>,
+    repo: Option<&Repository>,
+    config: &GitHubConfig,
+) -> Option<Response> {
+    if !config.announce_unknown_actions {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(tag, None);
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, sender, config);
+    write!(&mut message, " performed {} on ", action).unwrap();
+    match url {
+        Some(url) => message.main_link(object, url),
+        None => write!(&mut message, "{}", object).unwrap(),
+    }
+
+    Some(Response {
+        message,
+        repo: repo.map(|repository| repository.full_name.clone()),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_gollum(event: GollumEvent, config: &GitHubConfig) -> Option<Response> {
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::BOOK));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " ").unwrap();
+
+    match event.pages.as_slice() {
+        [] => return None,
+        [page] => {
+            write!(&mut message, "{} wiki page ", page.action).unwrap();
+            message.main_link(&page.title, &page.html_url);
+        }
+        pages => {
+            write!(&mut message, "updated {} wiki pages: ", pages.len()).unwrap();
+            for (i, page) in pages.iter().enumerate() {
+                if i > 0 {
+                    write!(&mut message, ", ").unwrap();
+                }
+                message.link(&page.title, &page.html_url);
+                write!(&mut message, " ({})", page.action).unwrap();
+            }
+        }
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_issue_comment(event: IssueCommentEvent) -> Option<Response> {
+fn handle_issue_comment(event: IssueCommentEvent, config: &GitHubConfig) -> Option<Response> {
     let action = event.action;
     let comment = event.comment;
     let issue = event.issue;
@@ -125,34 +705,48 @@ fn handle_issue_comment(event: IssueCommentEvent) -> Option<Response> {
 
     message.tag(&event.repository.name, Some(emoji::WRENCH));
 
-    write!(&mut message, " {} ", event.sender.login).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " ").unwrap();
 
-    match action.as_str() {
-        "created" => {
+    match action {
+        IssueCommentAction::Created => {
             message.main_link("commented", &comment.html_url);
             write!(message, " on {} ", issue_or_pr,).unwrap();
 
-            message.link(&format!("{}", issue), &issue.html_url);
+            message.link(&issue, &issue.html_url);
 
-            write!(message, ": {}", shorten_content(&comment.body),).unwrap();
+            write!(message, ": ").unwrap();
+            message.markdown_shortened(&comment.body, SHORTENED_LENGTH);
         }
 
         // too verbose, don't log that
-        "edited" | "deleted" => return None,
+        IssueCommentAction::Edited | IssueCommentAction::Deleted => return None,
 
-        _ => {
+        IssueCommentAction::Unknown(_) => {
             error!("invalid or unsupported issue comment action: {}", action);
-            return None;
+            return unknown_action(
+                &event.repository.name,
+                &event.sender,
+                action.as_str(),
+                format_args!("a comment on {}", issue),
+                Some(&comment.html_url),
+                Some(&event.repository),
+                config,
+            );
         }
     }
 
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: first_image(&comment.body),
     })
 }
 
-fn handle_issues(event: IssuesEvent) -> Option<Response> {
+fn handle_issues(event: IssuesEvent, config: &GitHubConfig) -> anyhow::Result<Option<Response>> {
     let action = event.action;
     let issue = event.issue;
 
@@ -160,32 +754,39 @@ fn handle_issues(event: IssuesEvent) -> Option<Response> {
 
     message.tag(&event.repository.name, Some(emoji::WRENCH));
 
-    write!(&mut message, " {}", event.sender.login).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
 
-    match action.as_str() {
-        "assigned" | "unassigned" => {
+    match action {
+        IssuesAction::Assigned | IssuesAction::Unassigned => {
             let assignee = event
                 .assignee
-                .expect("assigned action should always have an assignee");
+                .with_context(|| format!("{} action without an assignee", action))?;
             let sender = event.sender;
             if assignee.id == sender.id {
                 write!(message, " self-{}", action).unwrap();
             } else {
-                write!(message, " {} {}", action, assignee.login).unwrap();
+                write!(message, " {} ", action).unwrap();
+                user_link(&mut message, &assignee, config);
             }
             write!(message, " to ").unwrap();
         }
 
         // too verbose, don't log that
-        "labeled" | "unlabeled" => return None,
+        IssuesAction::Labeled | IssuesAction::Unlabeled => return Ok(None),
 
-        "opened" | "deleted" | "pinned" | "unpinned" | "reopened" | "closed" | "locked"
-        | "unlocked" | "transferred" => write!(message, " {} issue ", action).unwrap(),
+        IssuesAction::Opened
+        | IssuesAction::Deleted
+        | IssuesAction::Pinned
+        | IssuesAction::Unpinned
+        | IssuesAction::Reopened
+        | IssuesAction::Closed
+        | IssuesAction::Locked
+        | IssuesAction::Unlocked
+        | IssuesAction::Transferred => write!(message, " {} issue ", action).unwrap(),
 
-        "edited" => {
-            let changes = event
-                .changes
-                .expect("edited issue without changes shouldn't happen");
+        IssuesAction::Edited => {
+            let changes = event.changes.context("edited issue without changes")?;
 
             write!(message, " edited").unwrap();
             let changed = match (changes.title, changes.body) {
@@ -194,221 +795,599 @@ fn handle_issues(event: IssuesEvent) -> Option<Response> {
                 (None, Some(_)) => "body",
                 (None, None) => {
                     error!("issue was edited but received an empty change!");
-                    return None;
+                    return Ok(None);
                 }
             };
             write!(message, " {} of issue ", changed).unwrap();
         }
 
-        "milestoned" => {
+        IssuesAction::Milestoned => {
             let milestone = issue
                 .milestone
                 .as_ref()
-                .expect("milestoned issue should have a milestone");
+                .context("milestoned issue without a milestone")?;
             write!(message, " added milestone {} to ", milestone.title).unwrap();
         }
 
         // https://github.com/isaacs/github/issues/880
-        "demilestoned" => write!(message, " removed the milestone from ").unwrap(),
+        IssuesAction::Demilestoned => write!(message, " removed the milestone from ").unwrap(),
 
-        _ => {
+        IssuesAction::Unknown(_) => {
             error!("invalid or unsupported issues action: {}", action);
-            return None;
+            return Ok(unknown_action(
+                &event.repository.name,
+                &event.sender,
+                action.as_str(),
+                format_args!("issue {}", issue),
+                Some(&issue.html_url),
+                Some(&event.repository),
+                config,
+            ));
         }
     }
 
-    message.main_link(&format!("{}", issue), &issue.html_url);
+    message.main_link(&issue, &issue.html_url);
+    match action {
+        IssuesAction::Opened => write_labels(&mut message, &issue.labels),
+        IssuesAction::Closed => {
+            // completed and not planned issues are triaged differently
+            match &issue.state_reason {
+                Some(IssueStateReason::Completed) => write!(message, " as completed").unwrap(),
+                Some(IssueStateReason::NotPlanned) => write!(message, " as not planned").unwrap(),
+                Some(IssueStateReason::Duplicate) => write!(message, " as a duplicate").unwrap(),
+                Some(IssueStateReason::Reopened) | None => {}
+                Some(IssueStateReason::Unknown(reason)) => {
+                    write!(message, " as {}", reason.replace('_', " ")).unwrap()
+                }
+            }
+            // a running sense of the progress, e.g. of the preparation of a contest
+            let progress = issue
+                .milestone
+                .as_ref()
+                .and_then(|milestone| Some((milestone, milestone.progress()?)));
+            if let Some((milestone, (closed, total))) = progress {
+                write!(
+                    message,
+                    " (milestone {}: {}/{} done)",
+                    milestone.title, closed, total
+                )
+                .unwrap();
+            }
+        }
+        _ => {}
+    }
 
-    Some(Response {
+    Ok(Some(Response {
         message,
         repo: Some(event.repository.full_name),
-    })
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    }))
 }
 
-fn handle_membership(event: crate::webhooks::github::MembershipEvent) -> Option<Response> {
-    let action = event.action;
+fn handle_label(event: LabelEvent, config: &GitHubConfig) -> Option<Response> {
+    let label = event.label;
+    let deleted = event.action == LabelAction::Deleted;
 
     let mut message = MessageBuilder::new();
 
-    message.tag(&event.team.name, Some(emoji::PEOPLE));
+    // deleted labels can break the automations relying on them
+    let marker = if deleted {
+        emoji::WARNING
+    } else {
+        emoji::LABEL
+    };
+    message.tag(&event.repository.name, Some(marker));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " {} label ", event.action).unwrap();
 
-    // Do not leak secret teams
-    if event.team.privacy == "secret" {
-        info!("Team {} is secret, not sending anything", event.team.name);
+    message.color(&format!("#{}", label.color));
+    match event.repository.labels_url() {
+        Ok(url) if !deleted => message.main_link(&label.name, &url),
+        _ => write!(&mut message, "{}", label.name).unwrap(),
+    }
+    message.close_last();
+    write!(&mut message, " (#{})", label.color).unwrap();
+
+    if let Some(changes) = event.changes {
+        if let Some(name) = changes.name {
+            write!(&mut message, ", renamed from {}", name.from).unwrap();
+        }
+        if let Some(color) = changes.color {
+            write!(&mut message, ", previously #{}", color.from).unwrap();
+        }
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_membership(
+    event: crate::webhooks::github::MembershipEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
+    let action = event.action;
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.team.name, Some(emoji::PEOPLE));
+
+    // Do not leak secret teams
+    if event.team.privacy == "secret" {
+        info!("Team {} is secret, not sending anything", event.team.name);
         return None;
     }
 
-    let preposition = match action.as_str() {
-        "added" => "to",
-        "removed" => "from",
-        _ => {
+    let preposition = match action {
+        MembershipAction::Added => "to",
+        MembershipAction::Removed => "from",
+        MembershipAction::Unknown(_) => {
             error!("invalid or unsupported membership action: {}", action);
-            return None;
+            return unknown_action(
+                &event.team.name,
+                &event.sender,
+                action.as_str(),
+                format_args!("team {}", event.team.name),
+                Some(&event.team.html_url),
+                None,
+                config,
+            );
         }
     };
 
-    write!(&mut message, " {} {} ", event.sender.login, action).unwrap();
-    message.link(&event.member.login, &event.member.html_url);
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " {} ", action).unwrap();
+    user_link(&mut message, &event.member, config);
     write!(&mut message, " {} the team", preposition).unwrap();
 
     Some(Response {
         message,
         repo: None,
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_merge_group(event: MergeGroupEvent, config: &GitHubConfig) -> Option<Response> {
+    let group = event.merge_group;
+
+    // the checks completed on the group are announced by the `check_suite` events
+    let (marker, verb) = match (event.action, event.reason.as_deref()) {
+        (MergeGroupAction::ChecksRequested, _) => (emoji::HOURGLASS, "checks requested for"),
+        (MergeGroupAction::Destroyed, Some("merged")) => (emoji::CHECK_MARK, "merged"),
+        (MergeGroupAction::Destroyed, Some("invalidated")) => (emoji::CROSS_MARK, "invalidated"),
+        (MergeGroupAction::Destroyed, Some("dequeued")) => (emoji::CROSS_MARK, "dequeued"),
+        (action, reason) => {
+            error!(
+                "invalid or unsupported merge group action: {} ({:?})",
+                action, reason
+            );
+            return None;
+        }
+    };
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(marker));
+    write!(&mut message, " {} merge group ", verb).unwrap();
+
+    let hash = short_hash(&group.head_sha, config.short_hash_length);
+    match event.repository.checks_url(&group.head_sha) {
+        Ok(url) => message.main_link(hash, &url),
+        Err(e) => {
+            error!(
+                "couldn't build checks url for commit {} in repo {}: {}",
+                group.head_sha, event.repository.full_name, e
+            );
+            write!(&mut message, "{}", hash).unwrap();
+        }
+    }
+
+    write!(&mut message, " on ").unwrap();
+    branch_link(&mut message, &event.repository, group.head_branch(), config);
+    write!(&mut message, " into ").unwrap();
+    branch_link(&mut message, &event.repository, group.base_branch(), config);
+    if let Some(commit) = &group.head_commit {
+        write!(&mut message, ": {}", shorten_content(commit.title())).unwrap();
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_organization(event: OrganizationEvent) -> Option<Response> {
+fn handle_organization(
+    event: OrganizationEvent,
+    config: &GitHubConfig,
+) -> anyhow::Result<Option<Response>> {
     let action = event.action;
 
     let mut message = MessageBuilder::new();
 
-    let (action, user, preposition, role) = match action.as_str() {
-        "member_invited" => {
+    let (action, user, preposition, role) = match action {
+        OrganizationAction::MemberInvited => {
             let invitation = event
                 .invitation
-                .expect("member was invited but no invitation is set");
-            let user = event.user.expect("member was invited but no user is set");
+                .context("member was invited but no invitation is set")?;
+            let user = event
+                .user
+                .context("member was invited but no user is set")?;
 
-            ("invited", user, "to", invitation.role)
+            ("invited", user, "to", format!(" as {}", invitation.role))
         }
-        "member_added" => {
+        OrganizationAction::MemberAdded => {
             let membership = event
                 .membership
-                .expect("member was added but no membership is set");
+                .context("member was added but no membership is set")?;
             let user = membership.user;
 
-            ("added", user, "to", membership.role)
+            ("added", user, "to", format!(" as {}", membership.role))
         }
-        "member_removed" => {
+        OrganizationAction::MemberRemoved => {
             let membership = event
                 .membership
-                .expect("member was removed but no membership is set");
+                .context("member was removed but no membership is set")?;
             let user = membership.user;
 
-            ("removed", user, "from", membership.role)
+            (
+                "removed",
+                user,
+                "from",
+                format!(" (was {})", membership.role),
+            )
         }
 
         // TODO maybe handle `renamed` and `deleted` actions even tho it should not happen in our case
-        _ => {
+        OrganizationAction::Renamed
+        | OrganizationAction::Deleted
+        | OrganizationAction::Unknown(_) => {
             error!("invalid or unsupported organization action: {}", action);
-            return None;
+            return Ok(unknown_action(
+                "organization",
+                &event.sender,
+                action.as_str(),
+                "the organization",
+                None,
+                None,
+                config,
+            ));
         }
     };
 
-    write!(&mut message, "{} {} ", event.sender.login, action).unwrap();
-    message.link(&user.login, &user.html_url);
-    write!(&mut message, " {} organization", preposition).unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " {} ", action).unwrap();
+    user_link(&mut message, &user, config);
+    write!(&mut message, " {} organization{}", preposition, role).unwrap();
 
-    match action {
-        "invited" | "added" => write!(&mut message, " as {}", role).unwrap(),
-        "removed" => write!(&mut message, " (was {})", role).unwrap(),
-        _ => unreachable!(),
+    Ok(Some(Response {
+        message,
+        repo: None,
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    }))
+}
+
+fn handle_ping(event: PingEvent, config: &GitHubConfig) -> Option<Response> {
+    let mut message = MessageBuilder::new();
+
+    if let Some(repo) = &event.repository {
+        message.tag(&repo.name, Some(emoji::PING_PONG));
+        write!(&mut message, " ").unwrap();
+    }
+
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " completed webhook setup! {}", event.zen).unwrap();
+
+    Some(Response {
+        message,
+        repo: event.repository.map(|r| r.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_projects_v2(event: ProjectsV2Event, config: &GitHubConfig) -> Option<Response> {
+    let project = event.projects_v2;
+
+    let action = match event.action {
+        ProjectsV2Action::Created
+        | ProjectsV2Action::Closed
+        | ProjectsV2Action::Reopened
+        | ProjectsV2Action::Deleted => event.action,
+        // titles and descriptions are edited all the time
+        ProjectsV2Action::Edited => return None,
+        ProjectsV2Action::Unknown(_) => {
+            let url = event
+                .organization
+                .projects_url(&config.web_url, Some(project.number))
+                .ok();
+            return unknown_action(
+                &event.organization.login,
+                &event.sender,
+                event.action.as_str(),
+                format_args!("project #{}: {}", project.number, project.title),
+                url.as_ref(),
+                None,
+                config,
+            );
+        }
     };
 
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.organization.login, Some(emoji::CLIPBOARD));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " {} project ", action).unwrap();
+    let title = format!("#{}: {}", project.number, project.title);
+    match event
+        .organization
+        .projects_url(&config.web_url, Some(project.number))
+    {
+        Ok(url) if action != ProjectsV2Action::Deleted => message.main_link(&title, &url),
+        _ => write!(&mut message, "{}", title).unwrap(),
+    }
+
     Some(Response {
         message,
         repo: None,
+        room: config.planning_room.clone(),
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_ping(event: PingEvent) -> Option<Response> {
+fn handle_projects_v2_item(event: ProjectsV2ItemEvent, config: &GitHubConfig) -> Option<Response> {
+    let item = match event.projects_v2_item.content_type.as_str() {
+        "Issue" => "an issue",
+        "PullRequest" => "a pull request",
+        "DraftIssue" => "a draft issue",
+        _ => "an item",
+    };
+
     let mut message = MessageBuilder::new();
 
-    match &(event.repository) {
-        Some(repo) => {
-            message.tag(&repo.name, Some(emoji::PING_PONG));
-            write!(&mut message, " ").unwrap();
+    message.tag(&event.organization.login, Some(emoji::CLIPBOARD));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " ").unwrap();
+
+    let mut project_number = None;
+    match event.action {
+        ProjectsV2ItemAction::Created => write!(&mut message, "added {} to ", item).unwrap(),
+        ProjectsV2ItemAction::Archived
+        | ProjectsV2ItemAction::Restored
+        | ProjectsV2ItemAction::Deleted => {
+            write!(&mut message, "{} {} of ", event.action, item).unwrap()
+        }
+        ProjectsV2ItemAction::Edited => {
+            // only field changes are interesting, not the edition of draft issues
+            let change = event.changes.and_then(|changes| changes.field_value)?;
+            let field = change.field_name.as_deref().unwrap_or("a field");
+            match (change.old_value(), change.new_value()) {
+                (Some(from), Some(to)) if field.eq_ignore_ascii_case("status") => {
+                    write!(&mut message, "moved {} from {} to {} on ", item, from, to).unwrap()
+                }
+                (_, Some(to)) => {
+                    write!(&mut message, "set {} of {} to {} on ", field, item, to).unwrap()
+                }
+                (Some(_), None) => {
+                    write!(&mut message, "cleared {} of {} on ", field, item).unwrap()
+                }
+                // older payloads don't have the values
+                (None, None) => return None,
+            }
+            project_number = change.project_number;
+        }
+        // reordering items and converting drafts are too noisy
+        ProjectsV2ItemAction::Converted | ProjectsV2ItemAction::Reordered => return None,
+        ProjectsV2ItemAction::Unknown(_) => {
+            let url = event.organization.projects_url(&config.web_url, None).ok();
+            return unknown_action(
+                &event.organization.login,
+                &event.sender,
+                event.action.as_str(),
+                item,
+                url.as_ref(),
+                None,
+                config,
+            );
         }
-        None => {}
     }
 
-    write!(
-        &mut message,
-        "{} completed webhook setup! {}",
-        event.sender.login, event.zen
-    )
-    .unwrap();
+    let project = match project_number {
+        Some(number) => format!("project #{}", number),
+        None => "a project".to_string(),
+    };
+    match event
+        .organization
+        .projects_url(&config.web_url, project_number)
+    {
+        Ok(url) => message.main_link(&project, &url),
+        Err(_) => write!(&mut message, "{}", project).unwrap(),
+    }
 
     Some(Response {
         message,
-        repo: event.repository.map(|r| r.full_name),
+        repo: None,
+        room: config.planning_room.clone(),
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_pull_request(event: PullRequestEvent) -> Option<Response> {
+fn handle_pull_request(
+    event: PullRequestEvent,
+    config: &GitHubConfig,
+) -> anyhow::Result<Option<Response>> {
     let action = event.action;
     let pr = event.pull_request;
+    let watched_files = config.watched_files(
+        &event.repository.full_name,
+        event.changed_files.iter().map(String::as_str),
+    );
 
     let mut message = MessageBuilder::new();
 
-    message.tag(&event.repository.name, Some(emoji::OUTBOX_TRAY));
+    let marker = if watched_files.is_empty() {
+        emoji::OUTBOX_TRAY
+    } else {
+        emoji::SHIELD
+    };
+    message.tag(&event.repository.name, Some(marker));
 
-    write!(&mut message, " {}", event.sender.login).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
 
-    match action.as_str() {
-        "assigned" | "unassigned" => {
+    match action {
+        PullRequestAction::Assigned | PullRequestAction::Unassigned => {
             let assignee = event
                 .assignee
-                .expect("assigned action should always have an assignee");
+                .with_context(|| format!("{} action without an assignee", action))?;
             let sender = event.sender;
             if assignee.id == sender.id {
                 write!(message, " self-{}", action).unwrap();
             } else {
-                write!(message, " {} {}", action, assignee.login).unwrap();
+                write!(message, " {} ", action).unwrap();
+                user_link(&mut message, &assignee, config);
             }
             write!(message, " to ").unwrap();
-            message.main_link(&format!("{}", pr), &pr.html_url);
+            message.main_link(&pr, &pr.html_url);
         }
 
-        "review_requested" => {
-            let reviewers = pr
-                .requested_reviewers
-                .iter()
-                .map(|user| user.login.as_str())
-                .collect::<Vec<&str>>()
-                .join(", ");
-
-            write!(message, " requested {} to review ", reviewers).unwrap();
-            message.main_link(&format!("{}", pr), &pr.html_url);
+        PullRequestAction::ReviewRequested => {
+            write!(message, " requested ").unwrap();
+            for (i, reviewer) in pr.requested_reviewers.iter().enumerate() {
+                if i > 0 {
+                    write!(message, ", ").unwrap();
+                }
+                user_link(&mut message, reviewer, config);
+            }
+            write!(message, " to review ").unwrap();
+            message.main_link(&pr, &pr.html_url);
         }
 
         // too verbose, don't log that
-        "labeled" | "unlabeled" | "review_requested_removed" => return None,
+        PullRequestAction::Labeled
+        | PullRequestAction::Unlabeled
+        | PullRequestAction::ReviewRequestRemoved => return Ok(None),
 
-        "opened" | "edited" | "reopened" => {
+        PullRequestAction::Opened | PullRequestAction::Edited | PullRequestAction::Reopened => {
             let base = &pr.base.r#ref;
             let head = &pr.head.r#ref;
             write!(message, " {} ", action).unwrap();
-            message.main_link(&format!("{}", pr), &pr.html_url);
+            if pr.draft {
+                write!(message, "draft ").unwrap();
+            }
+            message.main_link(&pr, &pr.html_url);
             write!(message, " ({}...{})", base, head).unwrap();
+            if action == PullRequestAction::Opened {
+                write_labels(&mut message, &pr.labels);
+                if let (Some(stats), Some(lines)) = (pr.diff_stats(), pr.changed_lines()) {
+                    let size = config.pr_sizes.classify(lines);
+                    write!(message, " ({}, size {})", stats, size).unwrap();
+                }
+            }
         }
 
-        "closed" => {
-            let decision = if pr
-                .merged
-                .expect("PR should always have a merged field in this case")
-            {
+        PullRequestAction::ReadyForReview => {
+            write!(message, " marked ").unwrap();
+            message.main_link(&pr, &pr.html_url);
+            write!(message, " as ready for review").unwrap();
+        }
+
+        // reviewers should hold off until it is ready again
+        PullRequestAction::ConvertedToDraft => {
+            write!(message, " converted ").unwrap();
+            message.main_link(&pr, &pr.html_url);
+            write!(message, " back to a draft").unwrap();
+        }
+
+        PullRequestAction::Closed => {
+            let decision = if pr.merged.context("closed PR without a merged field")? {
                 "merged"
             } else {
                 "closed"
             };
             write!(message, " {} ", decision).unwrap();
-            message.main_link(&format!("{}", pr), &pr.html_url);
+            message.main_link(&pr, &pr.html_url);
+            if decision == "merged" {
+                if let Some(merger) = pr.merged_by.as_ref().filter(|m| m.id != pr.user.id) {
+                    write!(message, ", merged by ").unwrap();
+                    user_link(&mut message, merger, config);
+                }
+                if let Some(stats) = pr.diff_stats() {
+                    write!(message, " ({})", stats).unwrap();
+                }
+            }
         }
 
-        _ => {
+        PullRequestAction::AutoMergeEnabled => {
+            write!(message, " enabled auto-merge").unwrap();
+            if let Some(auto_merge) = &pr.auto_merge {
+                write!(message, " ({})", auto_merge.merge_method).unwrap();
+            }
+            write!(message, " for ").unwrap();
+            message.main_link(&pr, &pr.html_url);
+        }
+
+        PullRequestAction::AutoMergeDisabled => {
+            write!(message, " disabled auto-merge for ").unwrap();
+            message.main_link(&pr, &pr.html_url);
+            if let Some(reason) = &event.reason {
+                write!(message, " ({})", reason).unwrap();
+            }
+        }
+
+        PullRequestAction::Synchronize
+        | PullRequestAction::Locked
+        | PullRequestAction::Unlocked
+        | PullRequestAction::Enqueued
+        | PullRequestAction::Dequeued
+        | PullRequestAction::Unknown(_) => {
             error!("invalid or unsupported pull request action: {}", action);
-            return None;
+            return Ok(unknown_action(
+                &event.repository.name,
+                &event.sender,
+                action.as_str(),
+                &pr,
+                Some(&pr.html_url),
+                Some(&event.repository),
+                config,
+            ));
         }
     }
 
-    Some(Response {
+    let (room, severity) = escalate(&mut message, &watched_files, config);
+
+    Ok(Some(Response {
         message,
         repo: Some(event.repository.full_name),
-    })
+        room,
+        severity,
+        image: None,
+    }))
 }
 
-fn handle_pull_request_review(event: PullRequestReviewEvent) -> Option<Response> {
+fn handle_pull_request_review(
+    event: PullRequestReviewEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
     let action = event.action;
     let review = event.review;
     let reviewer = review.user.login;
@@ -432,18 +1411,19 @@ fn handle_pull_request_review(event: PullRequestReviewEvent) -> Option<Response>
     let mut message = MessageBuilder::new();
 
     message.tag(&event.repository.name, Some(emoji::OUTBOX_TRAY));
-    write!(&mut message, " {}", event.sender.login).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
 
-    match action.as_str() {
-        "submitted" => {
+    match action {
+        PullRequestReviewAction::Submitted => {
             write!(message, " {} ", decision).unwrap();
-            message.main_link(&format!("{}", pr), &pr.html_url);
+            message.main_link(&pr, &pr.html_url);
         }
 
         // ignored, too verbose
-        "edited" => return None,
+        PullRequestReviewAction::Edited => return None,
 
-        "dismissed" => {
+        PullRequestReviewAction::Dismissed => {
             write!(message, " dismissed ").unwrap();
 
             let mut whose = String::new();
@@ -453,29 +1433,43 @@ fn handle_pull_request_review(event: PullRequestReviewEvent) -> Option<Response>
                 write!(whose, "{}'s", reviewer).unwrap();
             };
 
-            message.main_link(&format!("{} review", whose), &review.html_url);
+            message.main_link(format_args!("{} review", whose), &review.html_url);
 
             write!(message, " for ").unwrap();
-            message.link(&format!("{}", pr), &pr.html_url);
+            message.link(&pr, &pr.html_url);
             write!(message, " (they {} the PR)", decision).unwrap();
         }
 
-        _ => {
+        PullRequestReviewAction::Unknown(_) => {
             error!(
                 "invalid or unsupported pull request review action: {}",
                 action
             );
-            return None;
+            return unknown_action(
+                &event.repository.name,
+                &event.sender,
+                action.as_str(),
+                format_args!("a review of {}", pr),
+                Some(&review.html_url),
+                Some(&event.repository),
+                config,
+            );
         }
     }
 
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_pull_request_review_comment(event: PullRequestReviewCommentEvent) -> Option<Response> {
+fn handle_pull_request_review_comment(
+    event: PullRequestReviewCommentEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
     let action = event.action;
     let comment = event.comment;
     let pr = event.pull_request;
@@ -492,13 +1486,15 @@ fn handle_pull_request_review_comment(event: PullRequestReviewCommentEvent) -> O
 
     message.tag(&event.repository.name, Some(emoji::SPEECH_BALLOON));
 
-    write!(&mut message, " {} ", event.sender.login).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " ").unwrap();
 
-    match action.as_str() {
-        "created" => {
+    match action {
+        PullRequestReviewCommentAction::Created => {
             message.main_link("commented", &comment.html_url);
             write!(message, " on ").unwrap();
-            message.link(&format!("{}", pr), &pr.html_url);
+            message.link(&pr, &pr.html_url);
 
             // comment can be on a specific line of a file
             if let Some(location) = comment.location() {
@@ -507,68 +1503,151 @@ fn handle_pull_request_review_comment(event: PullRequestReviewCommentEvent) -> O
         }
 
         // ignored, too verbose
-        "edited" | "deleted" => return None,
+        PullRequestReviewCommentAction::Edited | PullRequestReviewCommentAction::Deleted => {
+            return None
+        }
 
-        _ => {
+        PullRequestReviewCommentAction::Unknown(_) => {
             error!(
                 "invalid or unsupported pull request review comment action: {}",
                 action
             );
-            return None;
+            return unknown_action(
+                &event.repository.name,
+                &event.sender,
+                action.as_str(),
+                format_args!("a comment on {}", pr),
+                Some(&comment.html_url),
+                Some(&event.repository),
+                config,
+            );
         }
     }
 
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_push(event: PushEvent) -> Option<Response> {
+fn handle_push(event: PushEvent, config: &GitHubConfig) -> anyhow::Result<Option<Response>> {
+    let rewrote_history = event.forced && !event.is_new_ref() && !event.is_deleted_ref();
+    let watched_files = config.watched_files(
+        &event.repository.full_name,
+        event
+            .commits
+            .iter()
+            .flat_map(|commit| commit.changed_files()),
+    );
     let commits = event.commits;
 
     if commits.is_empty() {
-        // no commits => a tag was pushed, handled by `create` events
-        return None;
+        // no commits => a tag was pushed, handled by `create` events, a branch was deleted, or a
+        // branch was force-pushed back to one of its older commits
+        let branch = match event.r#ref.strip_prefix("refs/heads/") {
+            Some(branch) if rewrote_history => branch,
+            _ => return Ok(None),
+        };
+
+        let mut message = MessageBuilder::new();
+        message.tag(&event.repository.name, Some(emoji::WARNING));
+        write!(&mut message, " ").unwrap();
+        user_link(&mut message, &event.sender, config);
+        write!(&mut message, " force-pushed ").unwrap();
+        branch_link(&mut message, &event.repository, branch, config);
+        write!(&mut message, " back to ").unwrap();
+        message.main_link(
+            short_hash(&event.after, config.short_hash_length),
+            &event.compare,
+        );
+        write_rewritten_range(
+            &mut message,
+            &event.before,
+            &event.after,
+            event.discarded_commits,
+            config,
+        );
+        if let Some(head) = &event.head_commit {
+            write!(message, ": {}", shorten_content(head.title())).unwrap();
+        }
+
+        return Ok(Some(Response {
+            message,
+            repo: Some(event.repository.full_name),
+            room: None,
+            severity: Severity::Normal,
+            image: None,
+        }));
     }
 
-    let pusher = event.sender.login;
-    let head = event.head_commit.expect("should have at least one commit");
-    // it should be okay to use slicing on a string here because commit hashes should only contain
-    // single byte ascii characters
-    let hash = &head.id[..SHORT_HASH_LENGTH];
+    let head = event
+        .head_commit
+        .context("push with commits but without a head commit")?;
+    let hash = short_hash(&head.id, config.short_hash_length);
     let force = if event.forced { "force-" } else { "" };
 
     let mut message = MessageBuilder::new();
 
-    message.tag(&event.repository.name, None);
+    // force pushes can silently rewrite shared history, make them stand out
+    let marker = if !watched_files.is_empty() {
+        Some(emoji::SHIELD)
+    } else if event.forced {
+        Some(emoji::WARNING)
+    } else {
+        None
+    };
+    message.tag(&event.repository.name, marker);
 
-    write!(&mut message, " {} {}pushed ", pusher, force).unwrap();
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " {}pushed ", force).unwrap();
 
-    let url: &Url;
     let mut text = String::new();
 
-    if commits.len() == 1 {
+    // the payload only lists the last commits of big pushes
+    let total = event
+        .total_commits
+        .map_or(commits.len(), |total| total as usize);
+
+    let url = if let Some(pushes) = event.pushes {
+        write!(text, "{} times, {} commits total", pushes, total).unwrap();
+        &event.compare
+    } else if total == 1 {
         write!(text, "{}", hash).unwrap();
-        url = &head.url;
+        &head.url
     } else {
-        write!(text, "{} commits", commits.len()).unwrap();
+        write!(text, "{} commits", total).unwrap();
 
         let distinct_count = commits.iter().filter(|c| c.distinct).count();
-        if distinct_count != commits.len() {
+        if event.total_commits.is_none() && distinct_count != commits.len() {
             write!(text, " ({} distinct)", distinct_count).unwrap();
         }
 
         write!(text, " including {}", hash).unwrap();
 
-        url = &event.compare;
-    }
+        &event.compare
+    };
     message.main_link(&text, url);
+    if head.is_signed() {
+        write!(message, " {}", emoji::LOCKED_WITH_PEN).unwrap();
+    }
+
+    // maintainers often push the work of contributors, who deserve the credit
+    let author = head
+        .written_by()
+        .filter(|author| !author.is(&event.sender.login));
+    if let (Some(author), None) = (author, event.pushes) {
+        write!(message, " by ").unwrap();
+        commit_user_link(&mut message, author, config);
+    }
 
     let branch = event
         .r#ref
         .strip_prefix("refs/heads/")
-        .expect("couldn't find branch name");
+        .with_context(|| format!("couldn't find branch name in {}", event.r#ref))?;
 
     write!(message, " on ").unwrap();
     if event.created {
@@ -586,111 +1665,1241 @@ fn handle_push(event: PushEvent) -> Option<Response> {
         }
     };
 
-    message.link(&format!("{}{}", BRANCH, branch), &ref_url);
+    message.link(format_args!("{}{}", config.branch_symbol, branch), &ref_url);
+
+    if rewrote_history {
+        write_rewritten_range(
+            &mut message,
+            &event.before,
+            &event.after,
+            event.discarded_commits,
+            config,
+        );
+    } else if event.total_commits.is_some() {
+        write!(
+            message,
+            " ({}..{})",
+            short_hash(&event.before, config.short_hash_length),
+            short_hash(&event.after, config.short_hash_length)
+        )
+        .unwrap();
+    }
+
     write!(message, ": {}", shorten_content(head.title())).unwrap();
 
+    if !event.flagged_files.is_empty() {
+        write!(
+            message,
+            " ({} changes {})",
+            emoji::WARNING,
+            event.flagged_files.join(", ")
+        )
+        .unwrap();
+    }
+
+    let (room, severity) = escalate(&mut message, &watched_files, config);
+
+    // the list comes last, so that the escalation notes stay on the summary line
+    if commits.len() > 1 && config.push_commits > 0 {
+        message.list();
+        for commit in commits.iter().take(config.push_commits) {
+            message.list_item();
+            message.link(
+                short_hash(&commit.id, config.short_hash_length),
+                &commit.url,
+            );
+            write!(message, " {}", shorten_content(commit.title())).unwrap();
+            message.close_last();
+        }
+        if commits.len() > config.push_commits {
+            message.list_item();
+            write!(message, "and {} more", commits.len() - config.push_commits).unwrap();
+            message.close_last();
+        }
+        message.close_last();
+    }
+
+    Ok(Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room,
+        severity,
+        image: None,
+    }))
+}
+
+/// Writes the range of commits replaced by a force push, and how many commits it discarded
+fn write_rewritten_range(
+    message: &mut MessageBuilder,
+    before: &str,
+    after: &str,
+    discarded_commits: Option<u64>,
+    config: &GitHubConfig,
+) {
+    write!(
+        message,
+        " (rewrote {}..{}",
+        short_hash(before, config.short_hash_length),
+        short_hash(after, config.short_hash_length)
+    )
+    .unwrap();
+    match discarded_commits {
+        Some(1) => write!(message, ", 1 commit discarded").unwrap(),
+        Some(count) => write!(message, ", {} commits discarded", count).unwrap(),
+        None => {}
+    }
+    write!(message, ")").unwrap();
+}
+
+/// The full name of the repo made public by an event. GitHub sends both a `public` event and a
+/// `repository` event when a repo goes public, only the first one should be announced
+pub(crate) fn publicized_repo(event: &GitHubEvent) -> Option<&str> {
+    match event {
+        GitHubEvent::Public(event) => Some(&event.repository.full_name),
+        GitHubEvent::Repository(event) if event.action == RepositoryAction::Publicized => {
+            Some(&event.repository.full_name)
+        }
+        _ => None,
+    }
+}
+
+fn handle_public(event: PublicEvent, config: &GitHubConfig) -> Option<Response> {
+    let mut message = MessageBuilder::new();
+    message.tag(&event.repository.name, Some(emoji::PACKAGE));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " publicized repository").unwrap();
+
     Some(Response {
         message,
         repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-fn handle_repository(event: RepositoryEvent) -> Option<Response> {
+fn handle_repository(
+    event: RepositoryEvent,
+    config: &GitHubConfig,
+) -> anyhow::Result<Option<Response>> {
     let mut message = MessageBuilder::new();
 
-    match event.action.as_str() {
-        "created" | "deleted" | "archived" | "unarchived" | "transferred" | "publicized"
-        | "privatized" => {
+    match event.action {
+        RepositoryAction::Created
+        | RepositoryAction::Deleted
+        | RepositoryAction::Archived
+        | RepositoryAction::Unarchived
+        | RepositoryAction::Transferred
+        | RepositoryAction::Publicized
+        | RepositoryAction::Privatized => {
             message.tag(&event.repository.name, Some(emoji::PACKAGE));
 
-            write!(
-                &mut message,
-                " {} {} repository",
-                event.sender.login, event.action
-            )
-            .unwrap();
+            write!(&mut message, " ").unwrap();
+            user_link(&mut message, &event.sender, config);
+            write!(&mut message, " {} repository", event.action).unwrap();
         }
 
-        "renamed" => {
+        RepositoryAction::Renamed => {
             let old_repo_name = event
                 .changes
-                .expect("no changes reported even if repository was renamed")
+                .context("no changes reported even if repository was renamed")?
                 .repository
                 .name
                 .from;
 
             message.tag(&old_repo_name, Some(emoji::PACKAGE));
 
+            write!(&mut message, " ").unwrap();
+            user_link(&mut message, &event.sender, config);
             write!(
                 &mut message,
-                " {} renamed repository to {}",
-                event.sender.login, event.repository.name
+                " renamed repository to {}",
+                event.repository.name
             )
             .unwrap();
         }
 
-        "edited" => return None, // ignore, too verbose
+        RepositoryAction::Edited => return Ok(None), // ignore, too verbose
 
-        _ => {
+        RepositoryAction::Unknown(_) => {
             error!("invalid or unsupported repository action: {}", event.action);
+            return Ok(unknown_action(
+                &event.repository.name,
+                &event.sender,
+                event.action.as_str(),
+                &event.repository.full_name,
+                Some(&event.repository.html_url),
+                Some(&event.repository),
+                config,
+            ));
+        }
+    }
+
+    Ok(Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    }))
+}
+
+fn handle_repository_ruleset(
+    event: RepositoryRulesetEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
+    let ruleset = event.repository_ruleset;
+
+    let mut message = MessageBuilder::new();
+
+    let tag = match (&event.repository, &event.organization) {
+        (Some(repository), _) => &repository.name,
+        (None, Some(organization)) => &organization.login,
+        (None, None) => {
+            error!("ruleset {} without repository or organization", ruleset.id);
             return None;
         }
+    };
+    message.tag(tag, Some(emoji::SHIELD));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " {} ", event.action).unwrap();
+    if let Some(target) = &ruleset.target {
+        write!(&mut message, "{} ", target).unwrap();
+    }
+    write!(&mut message, "ruleset ").unwrap();
+
+    let url = ruleset.html_url(
+        event.repository.as_ref(),
+        event.organization.as_ref(),
+        &config.web_url,
+    );
+    match url {
+        Some(url) if event.action != RepositoryRulesetAction::Deleted => {
+            message.main_link(&ruleset.name, &url)
+        }
+        _ => write!(&mut message, "{}", ruleset.name).unwrap(),
+    }
+
+    match event.action {
+        RepositoryRulesetAction::Created => {
+            write!(&mut message, " ({})", ruleset.enforcement).unwrap()
+        }
+        RepositoryRulesetAction::Edited => {
+            let changes = event.changes;
+            let (name, enforcement) = match changes {
+                Some(changes) => (changes.name, changes.enforcement),
+                None => (None, None),
+            };
+            if let Some(name) = name {
+                write!(&mut message, ", renamed from {}", name.from).unwrap();
+            }
+            match enforcement {
+                Some(enforcement) => write!(
+                    &mut message,
+                    ", enforcement {} → {}",
+                    enforcement.from, ruleset.enforcement
+                )
+                .unwrap(),
+                None => write!(&mut message, " ({})", ruleset.enforcement).unwrap(),
+            }
+        }
+        RepositoryRulesetAction::Deleted => {}
+        RepositoryRulesetAction::Unknown(_) => {
+            error!(
+                "invalid or unsupported repository ruleset action: {}",
+                event.action
+            );
+            let url = ruleset.html_url(
+                event.repository.as_ref(),
+                event.organization.as_ref(),
+                &config.web_url,
+            );
+            return unknown_action(
+                tag,
+                &event.sender,
+                event.action.as_str(),
+                format_args!("ruleset {}", ruleset.name),
+                url.as_ref(),
+                event.repository.as_ref(),
+                config,
+            );
+        }
     }
 
     Some(Response {
         message,
-        repo: Some(event.repository.full_name),
+        repo: event.repository.map(|repository| repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::webhooks::github::{
-        Comment, Commit, CommitCommentEvent, ForkEvent, GitHubUser, Issue, MembershipEvent,
-        OrganizationMembership, PrRef, PullRequest, Repository, Review, Team,
-    };
+/// Severity of the message announcing a vulnerability of the given GitHub severity
+fn vulnerability_severity(severity: &str) -> Severity {
+    match severity {
+        "high" | "critical" => Severity::High,
+        _ => Severity::Normal,
+    }
+}
 
-    use super::*;
+fn handle_repository_vulnerability_alert(
+    event: RepositoryVulnerabilityAlertEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
+    let alert = event.alert;
 
-    #[test]
-    fn test_handle_commit_comment() {
-        let event = CommitCommentEvent {
-            sender: GitHubUser {
-                login: "test-user".to_string(),
-                id: 42,
-                html_url: Url::parse("https://github.com/test-user").unwrap(),
-            },
-            repository: Repository {
-                name: "test-repo".to_string(),
-                full_name: "test-user/test-repo".to_string(),
-                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
-            },
-            comment: Comment {
-                html_url: Url::parse("https://github.com/test-user/test-repo/issues/42#issue-42424242").unwrap(),
-                body: "This content is very long, longer than our character limit, so it will definitely be truncated".to_string(),
-                commit_id: Some("4242424242424242424242424242424242424242".to_string()),
-                pull_request_review_id: None,
-                path: None,
-                position: None,
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::SHIELD));
+    let severity = match event.action {
+        RepositoryVulnerabilityAlertAction::Create => {
+            write!(
+                &mut message,
+                " {} severity vulnerability in {} {}: ",
+                alert.severity, alert.affected_package_name, alert.affected_range
+            )
+            .unwrap();
+            message.main_link(&alert.external_identifier, &alert.external_reference);
+            match &alert.fixed_in {
+                Some(version) => write!(&mut message, ", fixed in {}", version).unwrap(),
+                None => write!(&mut message, ", no fix yet").unwrap(),
+            }
+            vulnerability_severity(&alert.severity)
+        }
+        RepositoryVulnerabilityAlertAction::Dismiss
+        | RepositoryVulnerabilityAlertAction::Resolve => {
+            let action = if event.action == RepositoryVulnerabilityAlertAction::Dismiss {
+                "dismissed"
+            } else {
+                "resolved"
+            };
+            write!(&mut message, " ").unwrap();
+            user_link(&mut message, &event.sender, config);
+            write!(
+                &mut message,
+                " {} the vulnerability alert for {} (",
+                action, alert.affected_package_name
+            )
+            .unwrap();
+            message.main_link(&alert.external_identifier, &alert.external_reference);
+            write!(&mut message, ")").unwrap();
+            Severity::Normal
+        }
+        RepositoryVulnerabilityAlertAction::Reopen => {
+            error!(
+                "invalid or unsupported repository vulnerability alert action: {}",
+                event.action
+            );
+            return None;
+        }
+        RepositoryVulnerabilityAlertAction::Unknown(_) => {
+            error!(
+                "invalid or unsupported repository vulnerability alert action: {}",
+                event.action
+            );
+            let response = unknown_action(
+                &event.repository.name,
+                &event.sender,
+                event.action.as_str(),
+                format_args!(
+                    "the vulnerability alert for {}",
+                    alert.affected_package_name
+                ),
+                Some(&alert.external_reference),
+                Some(&event.repository),
+                config,
+            )?;
+            return Some(Response {
+                room: config.security_room.clone(),
+                ..response
+            });
+        }
+    };
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: config.security_room.clone(),
+        severity,
+        image: None,
+    })
+}
+
+fn handle_security_advisory(
+    event: SecurityAdvisoryEvent,
+    config: &GitHubConfig,
+) -> Option<Response> {
+    // updates and withdrawals of advisories aren't worth a message
+    if event.action != SecurityAdvisoryAction::Published {
+        return None;
+    }
+    let advisory = event.security_advisory;
+
+    // GitHub sends every advisory of its database, most are about packages nobody here uses
+    let relevant: Vec<_> = advisory
+        .vulnerabilities
+        .iter()
+        .filter(|vulnerability| {
+            let package = &vulnerability.package;
+            config
+                .advisory_ecosystems
+                .iter()
+                .any(|ecosystem| ecosystem.eq_ignore_ascii_case(&package.ecosystem))
+                || config
+                    .advisory_packages
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&package.name))
+        })
+        .collect();
+    if relevant.is_empty() {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    message.tag("advisory", Some(emoji::SHIELD));
+    write!(&mut message, " {} severity: ", advisory.severity).unwrap();
+    match advisory.html_url(&config.web_url) {
+        Ok(url) => message.main_link(&advisory.ghsa_id, &url),
+        Err(_) => write!(&mut message, "{}", advisory.ghsa_id).unwrap(),
+    }
+    write!(&mut message, " {}", shorten_content(&advisory.summary)).unwrap();
+
+    for vulnerability in relevant {
+        message.line_break();
+        write!(
+            &mut message,
+            "- {} {} {}",
+            vulnerability.package.ecosystem,
+            vulnerability.package.name,
+            vulnerability.vulnerable_version_range
+        )
+        .unwrap();
+        if let Some(patched) = &vulnerability.first_patched_version {
+            write!(&mut message, ", fixed in {}", patched.identifier).unwrap();
+        }
+    }
+
+    Some(Response {
+        message,
+        repo: None,
+        room: config.security_room.clone(),
+        severity: vulnerability_severity(&advisory.severity),
+        image: None,
+    })
+}
+
+fn handle_star(event: StarEvent, config: &GitHubConfig) -> Option<Response> {
+    let stars = event.repository.stargazers_count;
+
+    // unstarring isn't worth a message
+    if event.action != StarAction::Created
+        || config.star_threshold == 0
+        || stars % config.star_threshold != 0
+    {
+        return None;
+    }
+
+    let repository = event.repository.repository;
+    let mut message = MessageBuilder::new();
+
+    message.tag(&repository.name, Some(emoji::STAR));
+    write!(&mut message, " ").unwrap();
+    user_link(&mut message, &event.sender, config);
+    write!(&mut message, " starred ").unwrap();
+    message.main_link(&repository.full_name, &repository.html_url);
+    write!(&mut message, " ({} {})", emoji::STAR, stars).unwrap();
+
+    Some(Response {
+        message,
+        repo: Some(repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn conclusion_verb(conclusion: Option<&str>) -> &'static str {
+    match conclusion {
+        Some("success") => "succeeded",
+        Some("timed_out") => "timed out",
+        Some("startup_failure") => "failed to start",
+        _ => "failed",
+    }
+}
+
+/// Writes labels in their color, e.g. ` [bug] [good first issue]`
+fn write_labels(message: &mut MessageBuilder, labels: &[Label]) {
+    for label in labels {
+        write!(message, " ").unwrap();
+        // the color ends up in an attribute, it must be a hex code
+        let colored = label.color.chars().all(|c| c.is_ascii_hexdigit());
+        if colored {
+            message.color(&format!("#{}", label.color));
+        }
+        write!(message, "[{}]", label.name).unwrap();
+        if colored {
+            message.close_last();
+        }
+    }
+}
+
+/// Writes the login of a user, linking to their profile, or mentions them if they have a Matrix
+/// user in [`GitHubConfig::user_mapping`]
+fn user_link(message: &mut MessageBuilder, user: &GitHubUser, config: &GitHubConfig) {
+    match config.user_mapping.get(&user.login) {
+        Some(user_id) => message.mention(user_id),
+        None => message.link(&user.login, &user.html_url),
+    }
+}
+
+/// Links to the profile of a commit's author, or only writes their name if they don't have one
+fn commit_user_link(message: &mut MessageBuilder, user: &CommitUser, config: &GitHubConfig) {
+    let profile = user
+        .username
+        .as_ref()
+        .and_then(|username| Some((username, config.web_url.join(username).ok()?)));
+    match profile {
+        Some((username, url)) => message.link(username, &url),
+        None => write!(message, "{}", user.name).unwrap(),
+    }
+}
+
+fn branch_link(
+    message: &mut MessageBuilder,
+    repository: &Repository,
+    branch: &str,
+    config: &GitHubConfig,
+) {
+    let ref_url = match repository.ref_url(branch) {
+        Ok(url) => url,
+        Err(e) => {
+            error!(
+                "couldn't build ref url for branch {} in repo {}: {}",
+                branch, repository.full_name, e
+            );
+            repository.html_url.clone()
+        }
+    };
+    message.link(format_args!("{}{}", config.branch_symbol, branch), &ref_url);
+}
+
+fn handle_workflow_job(event: WorkflowJobEvent, config: &GitHubConfig) -> Option<Response> {
+    let job = event.workflow_job;
+
+    // failing jobs are announced right away, successes are left to `workflow_run`
+    if event.action != WorkflowJobAction::Completed || !job.failed() {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    message.tag(&event.repository.name, Some(emoji::CROSS_MARK));
+    write!(&mut message, " job ").unwrap();
+    message.main_link(&job.name, &job.html_url);
+    if let Some(workflow) = &job.workflow_name {
+        write!(&mut message, " ({})", workflow).unwrap();
+    }
+    write!(
+        &mut message,
+        " {}",
+        conclusion_verb(job.conclusion.as_deref())
+    )
+    .unwrap();
+    if let Some(branch) = &job.head_branch {
+        write!(&mut message, " on ").unwrap();
+        branch_link(&mut message, &event.repository, branch, config);
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+fn handle_workflow_run(event: WorkflowRunEvent, config: &GitHubConfig) -> Option<Response> {
+    let run = event.workflow_run;
+
+    if event.action != WorkflowRunAction::Completed {
+        return None;
+    }
+    let failed = run.failed();
+    let succeeded = run.conclusion.as_deref() == Some("success");
+    if !(failed || (succeeded && !config.workflow_failures_only)) {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    let marker = if failed {
+        emoji::CROSS_MARK
+    } else {
+        emoji::CHECK_MARK
+    };
+    message.tag(&event.repository.name, Some(marker));
+    write!(&mut message, " workflow ").unwrap();
+    message.main_link(
+        format_args!("{} #{}", run.name, run.run_number),
+        &run.html_url,
+    );
+
+    write!(
+        &mut message,
+        " {}",
+        conclusion_verb(run.conclusion.as_deref())
+    )
+    .unwrap();
+    if let Some(branch) = &run.head_branch {
+        write!(&mut message, " on ").unwrap();
+        branch_link(&mut message, &event.repository, branch, config);
+    }
+
+    Some(Response {
+        message,
+        repo: Some(event.repository.full_name),
+        room: None,
+        severity: Severity::Normal,
+        image: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::UserId;
+    use regex::Regex;
+
+    use crate::config::{PrSizeThresholds, WatchedPaths};
+    use crate::webhooks::github::{
+        App, AutoMerge, CheckRun, CheckSuite, CheckSuiteCommit, CheckSuiteRef, Comment, Commit,
+        CommitCommentEvent, DeployKey, Deployment, DeploymentState, DeploymentStatus,
+        FieldValueChange, ForkEvent, GitHubUser, Hook, HookConfig, Issue, Label, LabelChanges,
+        MembershipEvent, MergeGroup, Milestone, Organization, OrganizationMembership, Package,
+        PatchedVersion, PrRef, PreviousValue, ProjectV2, ProjectV2Item, ProjectV2ItemChanges,
+        PullRequest, Release, Repository, Review, Ruleset, RulesetChanges, SecurityAdvisory,
+        StarredRepository, Team, UserKind, Verification, Vulnerability, VulnerabilityAlert,
+        WikiPage, WikiPageAction, WorkflowJob, WorkflowRun,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_short_hash() {
+        let hash = "4242424242424242424242424242424242424242";
+
+        assert_eq!(short_hash(hash, 7), "4242424");
+        assert_eq!(short_hash("42424", 7), "42424");
+        assert_eq!(short_hash("4242424", 7), "4242424");
+        assert_eq!(short_hash(hash, 0), "");
+        assert_eq!(short_hash(hash, 100), hash);
+    }
+
+    #[test]
+    fn test_handle_check_run() {
+        let event = |app: &str| CheckRunEvent {
+            action: CheckRunAction::Completed,
+            check_run: CheckRun {
+                name: "lint".to_string(),
+                conclusion: Some("failure".to_string()),
+                html_url: Url::parse("https://github.com/test-user/test-repo/runs/42").unwrap(),
+                details_url: Some(Url::parse("https://ci.prologin.org/builds/42").unwrap()),
+                app: App {
+                    slug: app.to_string(),
+                    name: "Prologin CI".to_string(),
+                },
+                check_suite: CheckSuiteRef {
+                    head_branch: Some("main".to_string()),
+                },
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+        };
+        let config = GitHubConfig::default();
+
+        assert!(handle_check_run(event("github-actions"), &config).is_none());
+
+        let response =
+            handle_check_run(event("prologin-ci"), &config).expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(
+            message.plain,
+            "[❌ test-repo] check lint (Prologin CI) failed on ⊶main"
+        );
+        assert_eq!(
+            message.url,
+            Some(Url::parse("https://ci.prologin.org/builds/42").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_handle_check_suite() {
+        let event = |conclusion: &str| CheckSuiteEvent {
+            action: CheckSuiteAction::Completed,
+            check_suite: CheckSuite {
+                head_branch: Some("main".to_string()),
+                head_sha: "deadbeef42".to_string(),
+                conclusion: Some(conclusion.to_string()),
+                app: App {
+                    slug: "prologin-ci".to_string(),
+                    name: "Prologin CI".to_string(),
+                },
+                head_commit: Some(CheckSuiteCommit {
+                    message: "Fix the build\n\nFor real this time".to_string(),
+                }),
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+        };
+        let config = GitHubConfig::default();
+
+        assert!(handle_check_suite(event("neutral"), &config).is_none());
+
+        let response =
+            handle_check_suite(event("success"), &config).expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(
+            message.plain,
+            "[✅ test-repo] Prologin CI succeeded for deadbee on ⊶main: Fix the build"
+        );
+        assert_eq!(
+            message.url,
+            Some(
+                Url::parse("https://github.com/test-user/test-repo/commit/deadbeef42/checks")
+                    .unwrap()
+            )
+        );
+
+        let response =
+            handle_check_suite(event("failure"), &config).expect("should have a response");
+        assert!(response
+            .message
+            .plain
+            .starts_with("[❌ test-repo] Prologin CI failed"));
+    }
+
+    #[test]
+    fn test_handle_merge_group() {
+        let event = |action, reason: Option<&str>| MergeGroupEvent {
+            action,
+            merge_group: MergeGroup {
+                head_sha: "deadbeef42".to_string(),
+                head_ref: "refs/heads/gh-readonly-queue/main/pr-42-c0ffee".to_string(),
+                base_ref: "refs/heads/main".to_string(),
+                head_commit: Some(CheckSuiteCommit {
+                    message: "Merge pull request #42\n\nRewrite the forum".to_string(),
+                }),
+            },
+            reason: reason.map(str::to_string),
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig::default();
+
+        let response = handle_merge_group(event(MergeGroupAction::ChecksRequested, None), &config)
+            .expect("should have a response");
+        let message = response.message;
+        assert_eq!(
+            message.plain,
+            "[⌛ test-repo] checks requested for merge group deadbee on ⊶gh-readonly-queue/main/pr-42-c0ffee into ⊶main: Merge pull request #42"
+        );
+        assert_eq!(
+            message.url,
+            Some(
+                Url::parse("https://github.com/test-user/test-repo/commit/deadbeef42/checks")
+                    .unwrap()
+            )
+        );
+
+        let response =
+            handle_merge_group(event(MergeGroupAction::Destroyed, Some("merged")), &config)
+                .expect("should have a response");
+        assert!(response
+            .message
+            .plain
+            .starts_with("[✅ test-repo] merged merge group deadbee"));
+
+        assert!(handle_merge_group(event(MergeGroupAction::Destroyed, None), &config).is_none());
+    }
+
+    #[test]
+    fn test_handle_deployment() {
+        let event = DeploymentEvent {
+            action: DeploymentAction::Created,
+            deployment: Deployment {
+                r#ref: "v1.2.3".to_string(),
+                sha: "deadbeef42".to_string(),
+                environment: "production".to_string(),
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+
+        let response =
+            handle_deployment(event, &GitHubConfig::default()).expect("should have a response");
+
+        assert_eq!(
+            response.message.html,
+            r#"<b>[🚀 test-repo]</b> <a href="https://github.com/test-user">test-user</a> deployed <a href="https://github.com/test-user/test-repo/tree/v1.2.3">v1.2.3</a> (deadbee) to production"#
+        );
+    }
+
+    #[test]
+    fn test_handle_deployment_status() {
+        let event = |state| DeploymentStatusEvent {
+            action: DeploymentStatusAction::Created,
+            deployment_status: DeploymentStatus { state },
+            deployment: Deployment {
+                r#ref: "v1.2.3".to_string(),
+                sha: "deadbeef42".to_string(),
+                environment: "production".to_string(),
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig::default();
+
+        assert!(handle_deployment_status(event(DeploymentState::Success), &config).is_none());
+        assert!(handle_deployment_status(event(DeploymentState::InProgress), &config).is_none());
+
+        let response = handle_deployment_status(event(DeploymentState::Failure), &config)
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[❌ test-repo] deploying v1.2.3 (deadbee) to production ended with failure"
+        );
+    }
+
+    #[test]
+    fn test_handle_label() {
+        let event = |action, changes| LabelEvent {
+            action,
+            label: Label {
+                name: "triage".to_string(),
+                color: "d73a4a".to_string(),
+            },
+            changes,
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+
+        let response = handle_label(
+            event(
+                LabelAction::Edited,
+                Some(LabelChanges {
+                    name: Some(PreviousValue {
+                        from: "needs-triage".to_string(),
+                    }),
+                    color: None,
+                }),
+            ),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert_eq!(
+            response.message.html,
+            r#"<b>[🏷 test-repo]</b> <a href="https://github.com/test-user">test-user</a> edited label <span style="color: #d73a4a"><a href="https://github.com/test-user/test-repo/labels">triage</a></span> (#d73a4a), renamed from needs-triage"#
+        );
+
+        let response = handle_label(event(LabelAction::Deleted, None), &GitHubConfig::default())
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[⚠ test-repo] test-user deleted label triage (#d73a4a)"
+        );
+        assert_eq!(response.message.url, None);
+    }
+
+    #[test]
+    fn test_handle_projects_v2() {
+        let event = |action| ProjectsV2Event {
+            action,
+            projects_v2: ProjectV2 {
+                number: 3,
+                title: "Finale 2022".to_string(),
+            },
+            organization: Organization {
+                login: "prologin".to_string(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+
+        let response =
+            handle_projects_v2(event(ProjectsV2Action::Closed), &GitHubConfig::default())
+                .expect("should have a response");
+        assert_eq!(
+            response.message.html,
+            r#"<b>[📋 prologin]</b> <a href="https://github.com/test-user">test-user</a> closed project <a href="https://github.com/orgs/prologin/projects/3">#3: Finale 2022</a>"#
+        );
+        assert_eq!(response.room, None);
+
+        assert!(
+            handle_projects_v2(event(ProjectsV2Action::Edited), &GitHubConfig::default()).is_none()
+        );
+    }
+
+    #[test]
+    fn test_handle_projects_v2_item() {
+        let event = |action, from: serde_json::Value, to: serde_json::Value| ProjectsV2ItemEvent {
+            action,
+            projects_v2_item: ProjectV2Item {
+                content_type: "Issue".to_string(),
+            },
+            changes: Some(ProjectV2ItemChanges {
+                field_value: Some(FieldValueChange {
+                    field_name: Some("Status".to_string()),
+                    project_number: Some(3),
+                    from,
+                    to,
+                }),
+            }),
+            organization: Organization {
+                login: "prologin".to_string(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig {
+            planning_room: Some("planning".to_string()),
+            ..GitHubConfig::default()
+        };
+
+        let response = handle_projects_v2_item(
+            event(
+                ProjectsV2ItemAction::Edited,
+                serde_json::json!({ "id": "a", "name": "Todo" }),
+                serde_json::json!({ "id": "b", "name": "In Progress" }),
+            ),
+            &config,
+        )
+        .expect("should have a response");
+        assert_eq!(
+            response.message.html,
+            r#"<b>[📋 prologin]</b> <a href="https://github.com/test-user">test-user</a> moved an issue from Todo to In Progress on <a href="https://github.com/orgs/prologin/projects/3">project #3</a>"#
+        );
+        assert_eq!(response.room.as_deref(), Some("planning"));
+
+        // older payloads only have the ID of the field
+        assert!(handle_projects_v2_item(
+            event(
+                ProjectsV2ItemAction::Edited,
+                serde_json::Value::Null,
+                serde_json::Value::Null
+            ),
+            &config
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_handle_repository_vulnerability_alert() {
+        let event = RepositoryVulnerabilityAlertEvent {
+            action: RepositoryVulnerabilityAlertAction::Create,
+            alert: VulnerabilityAlert {
+                affected_package_name: "lodash".to_string(),
+                affected_range: "<4.17.21".to_string(),
+                external_identifier: "CVE-2021-23337".to_string(),
+                external_reference: Url::parse("https://nvd.nist.gov/vuln/detail/CVE-2021-23337")
+                    .unwrap(),
+                fixed_in: Some("4.17.21".to_string()),
+                severity: "high".to_string(),
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig {
+            security_room: Some("security".to_string()),
+            ..GitHubConfig::default()
+        };
+
+        let response =
+            handle_repository_vulnerability_alert(event, &config).expect("should have a response");
+
+        assert_eq!(
+            response.message.plain,
+            "[🛡 test-repo] high severity vulnerability in lodash <4.17.21: CVE-2021-23337, fixed in 4.17.21"
+        );
+        assert_eq!(response.room.as_deref(), Some("security"));
+        assert_eq!(response.severity, Severity::High);
+    }
+
+    #[test]
+    fn test_handle_deploy_key() {
+        let event = DeployKeyEvent {
+            action: DeployKeyAction::Created,
+            key: DeployKey {
+                id: 1,
+                title: "ci@prologin".to_string(),
+                read_only: false,
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig {
+            security_room: Some("security".to_string()),
+            ..GitHubConfig::default()
+        };
+
+        let response = handle_deploy_key(event, &config).expect("should have a response");
+
+        assert_eq!(
+            response.message.plain,
+            "[🛡 test-repo] test-user added read-write deploy key ci@prologin to test-user/test-repo"
+        );
+        assert_eq!(response.room.as_deref(), Some("security"));
+        assert_eq!(response.severity, Severity::High);
+    }
+
+    #[test]
+    fn test_handle_meta() {
+        let event = MetaEvent {
+            action: MetaAction::Deleted,
+            hook_id: 1234,
+            hook: Hook {
+                events: vec!["push".to_string(), "pull_request".to_string()],
+                config: HookConfig {
+                    url: Some(
+                        Url::parse("https://prololo.prologin.org/api/webhooks/github?token=s3cr3t")
+                            .unwrap(),
+                    ),
+                },
+            },
+            repository: None,
+            organization: Some(Organization {
+                login: "prologin".to_string(),
+            }),
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig {
+            security_room: Some("security".to_string()),
+            ..GitHubConfig::default()
+        };
+
+        let response = handle_meta(event, &config).expect("should have a response");
+
+        assert_eq!(
+            response.message.plain,
+            "[🛡 prologin] test-user deleted webhook 1234 to prololo.prologin.org of organization prologin, its events (push, pull_request) won't be sent anymore"
+        );
+        assert_eq!(response.room.as_deref(), Some("security"));
+        assert_eq!(response.severity, Severity::High);
+    }
+
+    #[test]
+    fn test_handle_security_advisory() {
+        let event = |action| SecurityAdvisoryEvent {
+            action,
+            security_advisory: SecurityAdvisory {
+                ghsa_id: "GHSA-35jh-r3h4-6jhm".to_string(),
+                summary: "Command Injection in lodash".to_string(),
+                severity: "moderate".to_string(),
+                vulnerabilities: vec![Vulnerability {
+                    package: Package {
+                        ecosystem: "npm".to_string(),
+                        name: "lodash".to_string(),
+                    },
+                    vulnerable_version_range: "< 4.17.21".to_string(),
+                    first_patched_version: Some(PatchedVersion {
+                        identifier: "4.17.21".to_string(),
+                    }),
+                }],
+            },
+        };
+
+        let config = GitHubConfig {
+            advisory_ecosystems: vec!["pip".to_string()],
+            advisory_packages: vec!["lodash".to_string()],
+            ..GitHubConfig::default()
+        };
+        let response = handle_security_advisory(event(SecurityAdvisoryAction::Published), &config)
+            .expect("should have a response");
+
+        assert_eq!(
+            response.message.html,
+            r#"<b>[🛡 advisory]</b> moderate severity: <a href="https://github.com/advisories/GHSA-35jh-r3h4-6jhm">GHSA-35jh-r3h4-6jhm</a> Command Injection in lodash<br>- npm lodash &lt; 4.17.21, fixed in 4.17.21"#
+        );
+        assert_eq!(response.severity, Severity::Normal);
+
+        assert!(
+            handle_security_advisory(event(SecurityAdvisoryAction::Withdrawn), &config).is_none()
+        );
+        // nobody uses lodash
+        assert!(handle_security_advisory(
+            event(SecurityAdvisoryAction::Published),
+            &GitHubConfig::default()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_handle_commit_comment() {
+        let event = CommitCommentEvent {
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            comment: Comment {
+                html_url: Url::parse("https://github.com/test-user/test-repo/issues/42#issue-42424242").unwrap(),
+                body: "This content is very long, longer than our character limit, so it will definitely be truncated".to_string(),
+                commit_id: Some("4242424242424242424242424242424242424242".to_string()),
+                pull_request_review_id: None,
+                path: None,
+                position: None,
+                line: None,
+                start_line: None,
+                side: None,
+            },
+        };
+
+        let response =
+            handle_commit_comment(event, &GitHubConfig::default()).expect("should have a response");
+
+        let message = response.message;
+
+        assert!(message.url.is_some());
+
+        assert_eq!(message.plain, "[💬 test-repo] test-user commented on 4242424: This content is very long, longer than our character limit, so it will d…",);
+
+        assert_eq!(
+            message.html,
+            r#"<b>[💬 test-repo]</b> <a href="https://github.com/test-user">test-user</a> <a href="https://github.com/test-user/test-repo/issues/42#issue-42424242">commented</a> on <a href="https://github.com/test-user/test-repo/issues/42">4242424</a>: This content is very long, longer than our character limit, so it will d…"#,
+        );
+    }
+
+    #[test]
+    fn test_handle_create() {
+        let event = CreateEvent {
+            ref_type: RefType::Tag,
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
+            r#ref: "test-tag".to_string(),
+            release_notes: None,
         };
 
-        let response = handle_commit_comment(event).expect("should have a response");
+        let response =
+            handle_create(event, &GitHubConfig::default()).expect("should have a response");
 
         let message = response.message;
 
         assert!(message.url.is_some());
 
-        assert_eq!(message.plain, "[💬 test-repo] test-user commented on 4242424: This content is very long, longer than our character limit, so it will d…",);
+        assert_eq!(message.plain, "[test-repo] test-user created tag test-tag",);
 
         assert_eq!(
             message.html,
-            r#"<b>[💬 test-repo]</b> test-user <a href="https://github.com/test-user/test-repo/issues/42#issue-42424242">commented</a> on <a href="https://github.com/test-user/test-repo/issues/42">4242424</a>: This content is very long, longer than our character limit, so it will d…"#,
+            r#"<b>[test-repo]</b> <a href="https://github.com/test-user">test-user</a> created tag <a href="https://github.com/test-user/test-repo/tree/test-tag">test-tag</a>"#,
         );
     }
 
     #[test]
-    fn test_handle_create() {
+    fn test_handle_create_with_release_notes() {
         let event = CreateEvent {
             ref_type: RefType::Tag,
             repository: Repository {
@@ -702,21 +2911,75 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
-            r#ref: "test-tag".to_string(),
+            r#ref: "v1.2".to_string(),
+            release_notes: Some(
+                "## What's new\n\n* `!status` command\n* faster\n* more".to_string(),
+            ),
         };
 
-        let response = handle_create(event).expect("should have a response");
-
-        let message = response.message;
+        let response =
+            handle_create(event, &GitHubConfig::default()).expect("should have a response");
 
-        assert!(message.url.is_some());
+        assert_eq!(
+            response.message.plain,
+            "[test-repo] test-user created tag v1.2: What's new / * !status command / * faster",
+        );
+    }
 
-        assert_eq!(message.plain, "[test-repo] test-user created tag test-tag",);
+    #[test]
+    fn test_handle_release() {
+        let event = |action, previous_tag: Option<&str>| ReleaseEvent {
+            action,
+            release: Release {
+                tag_name: "v1.2".to_string(),
+                name: Some("Spring cleaning".to_string()),
+                body: Some("## What's new\n\n* **faster** `!status`\n* fixes".to_string()),
+                html_url: Url::parse("https://github.com/test-user/test-repo/releases/tag/v1.2")
+                    .unwrap(),
+                prerelease: false,
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+            previous_tag: previous_tag.map(str::to_string),
+        };
 
+        let response = handle_release(
+            event(ReleaseAction::Published, Some("v1.1")),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        let message = response.message;
+        assert_eq!(
+            message.plain,
+            "[📦 test-repo] test-user published release v1.2 (Spring cleaning): What's new / * faster !status / * fixes (full changelog)"
+        );
         assert_eq!(
             message.html,
-            r#"<b>[test-repo]</b> test-user created tag <a href="https://github.com/test-user/test-repo/tree/test-tag">test-tag</a>"#,
+            r#"<b>[📦 test-repo]</b> <a href="https://github.com/test-user">test-user</a> published release <a href="https://github.com/test-user/test-repo/releases/tag/v1.2">v1.2 (Spring cleaning)</a>: What&#39;s new / * <b>faster</b> <code>!status</code> / * fixes (<a href="https://github.com/test-user/test-repo/compare/v1.1...v1.2">full changelog</a>)"#
+        );
+
+        let response = handle_release(
+            event(ReleaseAction::Published, None),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert!(!response.message.plain.contains("full changelog"));
+
+        assert!(
+            handle_release(event(ReleaseAction::Edited, None), &GitHubConfig::default()).is_none()
         );
     }
 
@@ -737,10 +3000,13 @@ mod tests {
                 login: "test-user2".to_string(),
                 id: 420,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
         };
 
-        let response = handle_fork(event).expect("should have a response");
+        let response =
+            handle_fork(event, &GitHubConfig::default()).expect("should have a response");
 
         let message = response.message;
 
@@ -757,6 +3023,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_handle_generic() {
+        let event = GenericEvent {
+            name: "watch".to_string(),
+            action: Some("started".to_string()),
+            repository: Some(Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            }),
+            organization: None,
+            sender: Some(GitHubUser {
+                login: "test-user2".to_string(),
+                id: 420,
+                html_url: Url::parse("https://github.com/test-user2").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            }),
+        };
+
+        let response =
+            handle_generic(event, &GitHubConfig::default()).expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(response.repo.as_deref(), Some("test-user/test-repo"));
+        assert_eq!(
+            message.plain,
+            "[test-repo] test-user2 performed watch (started) on test-user/test-repo",
+        );
+        assert_eq!(
+            message.html,
+            r#"<b>[test-repo]</b> <a href="https://github.com/test-user2">test-user2</a> performed watch (started) on <a href="https://github.com/test-user/test-repo">test-user/test-repo</a>"#,
+        );
+    }
+
+    #[test]
+    fn test_handle_star() {
+        let event = |stars| StarEvent {
+            action: StarAction::Created,
+            repository: StarredRepository {
+                repository: Repository {
+                    name: "test-repo".to_string(),
+                    full_name: "test-user/test-repo".to_string(),
+                    html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+                },
+                stargazers_count: stars,
+            },
+            sender: GitHubUser {
+                login: "test-user2".to_string(),
+                id: 420,
+                html_url: Url::parse("https://github.com/test-user2").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+        let config = GitHubConfig {
+            star_threshold: 50,
+            ..Default::default()
+        };
+
+        assert!(handle_star(event(42), &config).is_none());
+
+        let response = handle_star(event(100), &config).expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(
+            message.plain,
+            "[⭐ test-repo] test-user2 starred test-user/test-repo (⭐ 100)",
+        );
+        assert_eq!(
+            message.html,
+            r#"<b>[⭐ test-repo]</b> <a href="https://github.com/test-user2">test-user2</a> starred <a href="https://github.com/test-user/test-repo">test-user/test-repo</a> (⭐ 100)"#,
+        );
+    }
+
+    #[test]
+    fn test_handle_gollum() {
+        let page = |title: &str, action| WikiPage {
+            title: title.to_string(),
+            action,
+            html_url: Url::parse(&format!(
+                "https://github.com/test-user/test-repo/wiki/{}",
+                title
+            ))
+            .unwrap(),
+        };
+        let event = |pages| GollumEvent {
+            pages,
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+
+        let response = handle_gollum(
+            event(vec![page("Logistics", WikiPageAction::Edited)]),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📖 test-repo] test-user edited wiki page Logistics"
+        );
+        assert!(response.message.url.is_some());
+
+        let response = handle_gollum(
+            event(vec![
+                page("Logistics", WikiPageAction::Edited),
+                page("Rooms", WikiPageAction::Created),
+            ]),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert_eq!(
+            response.message.html,
+            r#"<b>[📖 test-repo]</b> <a href="https://github.com/test-user">test-user</a> updated 2 wiki pages: <a href="https://github.com/test-user/test-repo/wiki/Logistics">Logistics</a> (edited), <a href="https://github.com/test-user/test-repo/wiki/Rooms">Rooms</a> (created)"#
+        );
+    }
+
     #[test]
     fn test_handle_issue_comment() {
         let event = IssueCommentEvent {
@@ -764,6 +3157,8 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             repository: Repository {
                 name: "test-repo".to_string(),
@@ -775,9 +3170,11 @@ mod tests {
                 html_url: Url::parse("https://github.com/test-user/test-repo/issues/42").unwrap(),
                 title: "Test Issue Title".to_string(),
                 milestone: None,
+                labels: vec![],
                 pull_request: None,
+                state_reason: None,
             },
-            action: "created".to_string(),
+            action: IssueCommentAction::Created,
             comment: Comment {
                 html_url: Url::parse("https://github.com/test-user/test-repo/issues/42#issue-42424242").unwrap(),
                 body: "This content is very long, longer than our character limit, so it will definitely be truncated".to_string(),
@@ -785,10 +3182,14 @@ mod tests {
                 pull_request_review_id: None,
                 path: None,
                 position: None,
+                line: None,
+                start_line: None,
+                side: None,
             },
         };
 
-        let response = handle_issue_comment(event).expect("should have a response");
+        let response =
+            handle_issue_comment(event, &GitHubConfig::default()).expect("should have a response");
 
         let message = response.message;
 
@@ -801,7 +3202,7 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"<b>[🔧 test-repo]</b> test-user <a href="https://github.com/test-user/test-repo/issues/42#issue-42424242">commented</a> on issue <a href="https://github.com/test-user/test-repo/issues/42">#42 (Test Issue Title)</a>: This content is very long, longer than our character limit, so it will d…"#,
+            r#"<b>[🔧 test-repo]</b> <a href="https://github.com/test-user">test-user</a> <a href="https://github.com/test-user/test-repo/issues/42#issue-42424242">commented</a> on issue <a href="https://github.com/test-user/test-repo/issues/42">#42 (Test Issue Title)</a>: This content is very long, longer than our character limit, so it will d…"#,
         );
     }
 
@@ -817,20 +3218,35 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             issue: Issue {
                 number: 42,
                 html_url: Url::parse("https://github.com/test-user/test-repo/issues/42").unwrap(),
                 title: "Test Issue Title".to_string(),
                 milestone: None,
+                labels: vec![
+                    Label {
+                        name: "bug".to_string(),
+                        color: "d73a4a".to_string(),
+                    },
+                    Label {
+                        name: "triage".to_string(),
+                        color: "not a color\"".to_string(),
+                    },
+                ],
                 pull_request: None,
+                state_reason: None,
             },
             changes: None,
             assignee: None,
-            action: "opened".to_string(),
+            action: IssuesAction::Opened,
         };
 
-        let response = handle_issues(event).expect("should have a response");
+        let response = handle_issues(event.clone(), &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -838,23 +3254,76 @@ mod tests {
 
         assert_eq!(
             message.plain,
-            "[🔧 test-repo] test-user opened issue #42 (Test Issue Title)",
+            "[🔧 test-repo] test-user opened issue #42 (Test Issue Title) [bug] [triage]",
         );
 
         assert_eq!(
             message.html,
-            r#"<b>[🔧 test-repo]</b> test-user opened issue <a href="https://github.com/test-user/test-repo/issues/42">#42 (Test Issue Title)</a>"#,
+            r#"<b>[🔧 test-repo]</b> <a href="https://github.com/test-user">test-user</a> opened issue <a href="https://github.com/test-user/test-repo/issues/42">#42 (Test Issue Title)</a> <span style="color: #d73a4a">[bug]</span> [triage]"#,
+        );
+
+        let mut typed = event.clone();
+        typed.action = IssuesAction::Unknown("typed".to_string());
+        assert!(handle_issues(typed.clone(), &GitHubConfig::default())
+            .unwrap()
+            .is_none());
+        let config = GitHubConfig {
+            announce_unknown_actions: true,
+            ..Default::default()
+        };
+        let response = handle_issues(typed, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[test-repo] test-user performed typed on issue #42 (Test Issue Title)",
+        );
+
+        let mut assigned = event.clone();
+        assigned.action = IssuesAction::Assigned;
+        let error = handle_issues(assigned, &GitHubConfig::default())
+            .err()
+            .expect("should fail without an assignee");
+        assert_eq!(error.to_string(), "assigned action without an assignee");
+
+        let mut closed = event.clone();
+        closed.action = IssuesAction::Closed;
+        closed.issue.state_reason = Some(IssueStateReason::NotPlanned);
+        let response = handle_issues(closed, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[🔧 test-repo] test-user closed issue #42 (Test Issue Title) as not planned",
+        );
+
+        let mut completed = event.clone();
+        completed.action = IssuesAction::Closed;
+        completed.issue.state_reason = Some(IssueStateReason::Completed);
+        completed.issue.milestone = Some(Milestone {
+            title: "Finale 2022".to_string(),
+            open_issues: Some(3),
+            closed_issues: Some(7),
+        });
+        let response = handle_issues(completed, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[🔧 test-repo] test-user closed issue #42 (Test Issue Title) as completed (milestone Finale 2022: 7/10 done)",
         );
     }
 
     #[test]
     fn test_handle_membership() {
         let event = MembershipEvent {
-            action: "added".to_string(),
+            action: MembershipAction::Added,
             member: GitHubUser {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             team: Team {
                 name: "test-team".to_string(),
@@ -868,10 +3337,13 @@ mod tests {
                 login: "test-admin".to_string(),
                 id: 4242,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
         };
 
-        let response = handle_membership(event).expect("should have a response");
+        let response =
+            handle_membership(event, &GitHubConfig::default()).expect("should have a response");
 
         let message = response.message;
 
@@ -884,18 +3356,20 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"<b>[🧑 test-team]</b> test-admin added <a href="https://github.com/test-user">test-user</a> to the team"#,
+            r#"<b>[🧑 test-team]</b> <a href="https://github.com/test-user">test-admin</a> added <a href="https://github.com/test-user">test-user</a> to the team"#,
         );
     }
 
     #[test]
     fn test_handle_organization() {
         let event = OrganizationEvent {
-            action: "member_added".to_string(),
+            action: OrganizationAction::MemberAdded,
             sender: GitHubUser {
                 login: "test-admin".to_string(),
                 id: 4242,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             invitation: None,
             user: None,
@@ -905,11 +3379,15 @@ mod tests {
                     login: "test-user".to_string(),
                     id: 42,
                     html_url: Url::parse("https://github.com/test-user").unwrap(),
+                    avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                    kind: UserKind::User,
                 },
             }),
         };
 
-        let response = handle_organization(event).expect("should have a response");
+        let response = handle_organization(event, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -922,7 +3400,38 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"test-admin added <a href="https://github.com/test-user">test-user</a> to organization as member"#,
+            r#"<a href="https://github.com/test-user">test-admin</a> added <a href="https://github.com/test-user">test-user</a> to organization as member"#,
+        );
+    }
+
+    #[test]
+    fn test_user_link() {
+        let user = GitHubUser {
+            login: "alice".to_string(),
+            id: 1,
+            html_url: Url::parse("https://github.com/alice").unwrap(),
+            avatar_url: Url::parse("https://avatars.githubusercontent.com/u/1").unwrap(),
+            kind: UserKind::User,
+        };
+        let mut config = GitHubConfig::default();
+
+        let mut message = MessageBuilder::new();
+        user_link(&mut message, &user, &config);
+        assert_eq!(
+            message.html,
+            r#"<a href="https://github.com/alice">alice</a>"#
+        );
+
+        config.user_mapping.insert(
+            "alice".to_string(),
+            UserId::try_from("@alice:prologin.org").unwrap(),
+        );
+        let mut message = MessageBuilder::new();
+        user_link(&mut message, &user, &config);
+        assert_eq!(message.plain, "@alice:prologin.org");
+        assert_eq!(
+            message.html,
+            r#"<a href="https://matrix.to/#/@alice:prologin.org">@alice:prologin.org</a>"#
         );
     }
 
@@ -939,10 +3448,13 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
         };
 
-        let response = handle_ping(event).expect("should have a response");
+        let response =
+            handle_ping(event, &GitHubConfig::default()).expect("should have a response");
 
         let message = response.message;
 
@@ -955,17 +3467,19 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"<b>[🏓 test-repo]</b> test-user completed webhook setup! Follow the rules, you must!"#,
+            r#"<b>[🏓 test-repo]</b> <a href="https://github.com/test-user">test-user</a> completed webhook setup! Follow the rules, you must!"#,
         );
     }
 
     #[test]
     fn test_handle_pull_request() {
-        let event = PullRequestEvent {
+        let event = |changed_files: Vec<&str>| PullRequestEvent {
             sender: GitHubUser {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             repository: Repository {
                 name: "test-repo".to_string(),
@@ -980,6 +3494,8 @@ mod tests {
                     login: "test-user".to_string(),
                     id: 42,
                     html_url: Url::parse("https://github.com/test-user").unwrap(),
+                    avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                    kind: UserKind::User,
                 },
                 requested_reviewers: vec![],
                 base: PrRef {
@@ -989,12 +3505,28 @@ mod tests {
                     r#ref: "test".to_string(),
                 },
                 merged: None,
+                merged_by: None,
+                draft: false,
+                labels: vec![],
+                additions: Some(120),
+                deletions: Some(30),
+                changed_files: Some(7),
+                auto_merge: None,
             },
-            action: "opened".to_string(),
+            action: PullRequestAction::Opened,
             assignee: None,
+            reason: None,
+            changed_files: changed_files.into_iter().map(String::from).collect(),
+        };
+
+        let mut config = GitHubConfig {
+            security_room: Some("security".to_string()),
+            ..Default::default()
         };
 
-        let response = handle_pull_request(event).expect("should have a response");
+        let response = handle_pull_request(event(vec![]), &config)
+            .unwrap()
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -1002,13 +3534,154 @@ mod tests {
 
         assert_eq!(
             message.plain,
-            "[📤 test-repo] test-user opened PR #42: Test PR Title by test-user (main...test)",
+            "[📤 test-repo] test-user opened PR #42: Test PR Title by test-user (main...test) (+120 −30, 7 files, size M)",
         );
 
         assert_eq!(
             message.html,
-            r#"<b>[📤 test-repo]</b> test-user opened <a href="https://github.com/test-user/test-repo/pull/42">PR #42: Test PR Title by test-user</a> (main...test)"#,
+            r#"<b>[📤 test-repo]</b> <a href="https://github.com/test-user">test-user</a> opened <a href="https://github.com/test-user/test-repo/pull/42">PR #42: Test PR Title by test-user</a> (main...test) (+120 −30, 7 files, size M)"#,
+        );
+        assert_eq!(response.room, None);
+
+        let mut merged = event(vec![]);
+        merged.action = PullRequestAction::Closed;
+        merged.pull_request.merged = Some(true);
+        merged.pull_request.changed_files = Some(1);
+        let response = handle_pull_request(merged, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user merged PR #42: Test PR Title by test-user (+120 −30, 1 file)",
+        );
+
+        let mut merged = event(vec![]);
+        merged.action = PullRequestAction::Closed;
+        merged.pull_request.merged = Some(true);
+        merged.pull_request.merged_by = Some(GitHubUser {
+            login: "merger".to_string(),
+            id: 43,
+            html_url: Url::parse("https://github.com/merger").unwrap(),
+            avatar_url: Url::parse("https://avatars.githubusercontent.com/u/43").unwrap(),
+            kind: UserKind::User,
+        });
+        let response = handle_pull_request(merged, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user merged PR #42: Test PR Title by test-user, merged by merger (+120 −30, 7 files)",
         );
+
+        let mut draft = event(vec![]);
+        draft.pull_request.draft = true;
+        let response = handle_pull_request(draft, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user opened draft PR #42: Test PR Title by test-user (main...test) (+120 −30, 7 files, size M)",
+        );
+
+        let small_config = GitHubConfig {
+            pr_sizes: PrSizeThresholds {
+                xs: 50,
+                s: 200,
+                m: 400,
+                l: 800,
+            },
+            ..config.clone()
+        };
+        let response = handle_pull_request(event(vec![]), &small_config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user opened PR #42: Test PR Title by test-user (main...test) (+120 −30, 7 files, size S)",
+        );
+
+        let mut ready = event(vec![]);
+        ready.action = PullRequestAction::ReadyForReview;
+        let response = handle_pull_request(ready, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user marked PR #42: Test PR Title by test-user as ready for review",
+        );
+
+        let mut draft = event(vec![]);
+        draft.action = PullRequestAction::ConvertedToDraft;
+        draft.pull_request.draft = true;
+        let response = handle_pull_request(draft, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user converted PR #42: Test PR Title by test-user back to a draft",
+        );
+
+        let mut auto_merge = event(vec![]);
+        auto_merge.action = PullRequestAction::AutoMergeEnabled;
+        auto_merge.pull_request.auto_merge = Some(AutoMerge {
+            enabled_by: auto_merge.sender.clone(),
+            merge_method: "squash".to_string(),
+        });
+        let response = handle_pull_request(auto_merge.clone(), &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user enabled auto-merge (squash) for PR #42: Test PR Title by test-user",
+        );
+
+        auto_merge.action = PullRequestAction::AutoMergeDisabled;
+        auto_merge.pull_request.auto_merge = None;
+        auto_merge.reason = Some("Base branch was modified".to_string());
+        let response = handle_pull_request(auto_merge, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[📤 test-repo] test-user disabled auto-merge for PR #42: Test PR Title by test-user (Base branch was modified)",
+        );
+
+        let mut synchronized = event(vec![]);
+        synchronized.action = PullRequestAction::Synchronize;
+        assert!(handle_pull_request(synchronized.clone(), &config)
+            .unwrap()
+            .is_none());
+        config.announce_unknown_actions = true;
+        let response = handle_pull_request(synchronized, &config)
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[test-repo] test-user performed synchronize on PR #42: Test PR Title by test-user",
+        );
+        config.announce_unknown_actions = false;
+
+        config.watched_paths.push(WatchedPaths {
+            repo: Regex::new("^test-user/").unwrap(),
+            paths: vec![".github/workflows/".to_string(), "Dockerfile".to_string()],
+        });
+        let response = handle_pull_request(
+            event(vec![
+                "Dockerfile.dev",
+                ".github/workflows/ci.yml",
+                "src/main.rs",
+            ]),
+            &config,
+        )
+        .unwrap()
+        .expect("should have a response");
+
+        assert_eq!(
+            response.message.plain,
+            "[🛡 test-repo] test-user opened PR #42: Test PR Title by test-user (main...test) (+120 −30, 7 files, size M) (🛡 touches .github/workflows/ci.yml)",
+        );
+        assert_eq!(response.room, Some("security".to_string()));
+        assert_eq!(response.severity, Severity::High);
     }
 
     #[test]
@@ -1018,6 +3691,8 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             repository: Repository {
                 name: "test-repo".to_string(),
@@ -1032,6 +3707,8 @@ mod tests {
                     login: "test-user".to_string(),
                     id: 42,
                     html_url: Url::parse("https://github.com/test-user").unwrap(),
+                    avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                    kind: UserKind::User,
                 },
                 requested_reviewers: vec![],
                 base: PrRef {
@@ -1041,20 +3718,30 @@ mod tests {
                     r#ref: "test".to_string(),
                 },
                 merged: None,
+                merged_by: None,
+                draft: false,
+                labels: vec![],
+                additions: None,
+                deletions: None,
+                changed_files: None,
+                auto_merge: None,
             },
-            action: "dismissed".to_string(),
+            action: PullRequestReviewAction::Dismissed,
             review: Review {
                 state: "approved".to_string(),
                 user: GitHubUser {
                     login: "test-user".to_string(),
                     id: 42,
                     html_url: Url::parse("https://github.com/test-user").unwrap(),
+                    avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                    kind: UserKind::User,
                 },
                 html_url: Url::parse("https://github.com/test-user/test-repo/whatever").unwrap(),
             },
         };
 
-        let response = handle_pull_request_review(event).expect("should have a response");
+        let response = handle_pull_request_review(event, &GitHubConfig::default())
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -1067,7 +3754,7 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"<b>[📤 test-repo]</b> test-user dismissed <a href="https://github.com/test-user/test-repo/whatever">their review</a> for <a href="https://github.com/test-user/test-repo/pull/42">PR #42: Test PR Title by test-user</a> (they approved the PR)"#,
+            r#"<b>[📤 test-repo]</b> <a href="https://github.com/test-user">test-user</a> dismissed <a href="https://github.com/test-user/test-repo/whatever">their review</a> for <a href="https://github.com/test-user/test-repo/pull/42">PR #42: Test PR Title by test-user</a> (they approved the PR)"#,
         );
     }
 
@@ -1078,6 +3765,8 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             repository: Repository {
                 name: "test-repo".to_string(),
@@ -1092,6 +3781,8 @@ mod tests {
                     login: "test-user".to_string(),
                     id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
                 },
                 requested_reviewers: vec![],
                 base: PrRef {
@@ -1101,8 +3792,15 @@ mod tests {
                     r#ref: "test".to_string(),
                 },
                 merged: None,
+                merged_by: None,
+                draft: false,
+                labels: vec![],
+                additions: None,
+                deletions: None,
+                changed_files: None,
+                auto_merge: None,
             },
-            action: "created".to_string(),
+            action: PullRequestReviewCommentAction::Created,
             comment: Comment {
                 html_url: Url::parse("https://github.com/test-user/test-repo/whatever").unwrap(),
                 body: "This content is very long, longer than our character limit, so it will definitely be truncated".to_string(),
@@ -1110,10 +3808,14 @@ mod tests {
                 pull_request_review_id: None,
                 path: None,
                 position: None,
+                line: None,
+                start_line: None,
+                side: None,
             },
         };
 
-        let response = handle_pull_request_review_comment(event).expect("should have a response");
+        let response = handle_pull_request_review_comment(event, &GitHubConfig::default())
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -1126,7 +3828,7 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"<b>[💬 test-repo]</b> test-user <a href="https://github.com/test-user/test-repo/whatever">commented</a> on <a href="https://github.com/test-user/test-repo/pull/42">PR #42: Test PR Title by test-user</a>"#,
+            r#"<b>[💬 test-repo]</b> <a href="https://github.com/test-user">test-user</a> <a href="https://github.com/test-user/test-repo/whatever">commented</a> on <a href="https://github.com/test-user/test-repo/pull/42">PR #42: Test PR Title by test-user</a>"#,
         );
     }
 
@@ -1142,39 +3844,71 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             commits: vec![
                 Commit {
-                    id: "deadbeef".to_string(),
+                    id: "deadbeef".into(),
                     url: Url::parse("https://github.com/test-user/test-repo/commit/deadbeef").unwrap(),
                     distinct: true,
-                    message: "This content is very long, longer than our character limit, so it will definitely be truncated".to_string(),
+                    message: "This content is very long, longer than our character limit, so it will definitely be truncated".into(),
+                    added: vec![],
+                    removed: vec![],
+                    modified: vec![],
+                    author: None,
+                    committer: None,
+                    verification: None,
                 },
 
                 Commit {
-                    id: "beefdead".to_string(),
+                    id: "beefdead".into(),
                     url: Url::parse("https://github.com/test-user/test-repo/commit/beefdead").unwrap(),
                     distinct: true,
-                    message: "Another message".to_string(),
+                    message: "Another message".into(),
+                    added: vec![],
+                    removed: vec![],
+                    modified: vec![],
+                    author: None,
+                    committer: None,
+                    verification: None,
                 }
 
             ],
             head_commit: Some(Commit {
-                id: "deadbeef".to_string(),
+                id: "deadbeef".into(),
                 url: Url::parse("https://github.com/test-user/test-repo/commit/deadbeef").unwrap(),
                 distinct: true,
-                message: "This content is very long, longer than our character limit, so it will definitely be truncated".to_string(),
+                message: "This content is very long, longer than our character limit, so it will definitely be truncated".into(),
+                added: vec![],
+                removed: vec![],
+                modified: vec![],
+                author: None,
+                committer: None,
+                verification: None,
             }),
             forced: true,
             created: true,
             compare: Url::parse(
-                "https://github.com/test-user/test-repo/compare/deadbeef...beefdead",
+                "https://github.com/test-user/test-repo/compare/c0ffee00...deadbeef",
             )
                 .unwrap(),
             r#ref: "refs/heads/new-test-branch".to_string(),
+            before: "c0ffee0042".to_string(),
+            after: "deadbeef".to_string(),
+            discarded_commits: Some(3),
+            flagged_files: vec![".gitmodules".to_string()],
+            pushes: None,
+            total_commits: None,
         };
+        let mut truncated = event.clone();
+        let mut contributed = event.clone();
+        let mut combined = event.clone();
+        let mut rewound = event.clone();
 
-        let response = handle_push(event).expect("should have a response");
+        let response = handle_push(event, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -1182,19 +3916,203 @@ mod tests {
 
         assert_eq!(
             message.plain,
-            "[test-repo] test-user force-pushed 2 commits including deadbee on new ⊶new-test-branch: This content is very long, longer than our character limit, so it will d…",
+            "[⚠ test-repo] test-user force-pushed 2 commits including deadbee on new ⊶new-test-branch (rewrote c0ffee0..deadbee, 3 commits discarded): This content is very long, longer than our character limit, so it will d… (⚠ changes .gitmodules)\n- deadbee This content is very long, longer than our character limit, so it will d…\n- beefdea Another message",
+        );
+
+        assert_eq!(
+            message.html,
+            r#"<b>[⚠ test-repo]</b> <a href="https://github.com/test-user">test-user</a> force-pushed <a href="https://github.com/test-user/test-repo/compare/c0ffee00...deadbeef">2 commits including deadbee</a> on new <a href="https://github.com/test-user/test-repo/tree/new-test-branch">⊶new-test-branch</a> (rewrote c0ffee0..deadbee, 3 commits discarded): This content is very long, longer than our character limit, so it will d… (⚠ changes .gitmodules)<ul><li><a href="https://github.com/test-user/test-repo/commit/deadbeef">deadbee</a> This content is very long, longer than our character limit, so it will d…</li><li><a href="https://github.com/test-user/test-repo/commit/beefdead">beefdea</a> Another message</li></ul>"#,
+        );
+
+        combined.forced = false;
+        combined.created = false;
+        combined.flagged_files.clear();
+        combined.pushes = Some(3);
+        let response = handle_push(combined, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[test-repo] test-user pushed 3 times, 2 commits total on ⊶new-test-branch: This content is very long, longer than our character limit, so it will d…\n- deadbee This content is very long, longer than our character limit, so it will d…\n- beefdea Another message",
+        );
+
+        truncated.forced = false;
+        truncated.created = false;
+        truncated.flagged_files.clear();
+        truncated.commits[1].distinct = false;
+        truncated.total_commits = Some(57);
+        let response = handle_push(truncated, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[test-repo] test-user pushed 57 commits including deadbee on ⊶new-test-branch (c0ffee0..deadbee): This content is very long, longer than our character limit, so it will d…\n- deadbee This content is very long, longer than our character limit, so it will d…\n- beefdea Another message",
+        );
+
+        contributed.forced = false;
+        contributed.created = false;
+        contributed.flagged_files.clear();
+        contributed.commits.truncate(1);
+        let head = contributed.head_commit.as_mut().unwrap();
+        head.author = Some(CommitUser {
+            name: "Bob".into(),
+            username: Some("bob".into()),
+        });
+        head.committer = Some(CommitUser {
+            name: "Test User".into(),
+            username: Some("test-user".into()),
+        });
+        let response = handle_push(contributed.clone(), &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.html,
+            r#"<b>[test-repo]</b> <a href="https://github.com/test-user">test-user</a> pushed <a href="https://github.com/test-user/test-repo/commit/deadbeef">deadbee</a> by <a href="https://github.com/bob">bob</a> on <a href="https://github.com/test-user/test-repo/tree/new-test-branch">⊶new-test-branch</a>: This content is very long, longer than our character limit, so it will d…"#,
+        );
+
+        // the pusher's own commits don't need an author
+        let head = contributed.head_commit.as_mut().unwrap();
+        head.author = Some(CommitUser {
+            name: "test-user".into(),
+            username: None,
+        });
+        head.verification = Some(Verification {
+            verified: true,
+            reason: "valid".to_string(),
+        });
+        let response = handle_push(contributed, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[test-repo] test-user pushed deadbee 🔏 on ⊶new-test-branch: This content is very long, longer than our character limit, so it will d…",
+        );
+
+        // `git reset --hard HEAD~3 && git push -f`
+        rewound.commits.clear();
+        rewound.created = false;
+        rewound.flagged_files.clear();
+        let response = handle_push(rewound.clone(), &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[⚠ test-repo] test-user force-pushed ⊶new-test-branch back to deadbee (rewrote c0ffee0..deadbee, 3 commits discarded): This content is very long, longer than our character limit, so it will d…",
+        );
+        assert_eq!(
+            response.message.html,
+            r#"<b>[⚠ test-repo]</b> <a href="https://github.com/test-user">test-user</a> force-pushed <a href="https://github.com/test-user/test-repo/tree/new-test-branch">⊶new-test-branch</a> back to <a href="https://github.com/test-user/test-repo/compare/c0ffee00...deadbeef">deadbee</a> (rewrote c0ffee0..deadbee, 3 commits discarded): This content is very long, longer than our character limit, so it will d…"#,
+        );
+
+        let mut tag = rewound.clone();
+        tag.r#ref = "refs/tags/v1.0".to_string();
+        assert!(handle_push(tag, &GitHubConfig::default())
+            .unwrap()
+            .is_none());
+        rewound.forced = false;
+        assert!(handle_push(rewound, &GitHubConfig::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_handle_workflow_job() {
+        let event = |conclusion: &str| WorkflowJobEvent {
+            action: WorkflowJobAction::Completed,
+            workflow_job: WorkflowJob {
+                name: "build".to_string(),
+                workflow_name: Some("CI".to_string()),
+                head_branch: Some("main".to_string()),
+                conclusion: Some(conclusion.to_string()),
+                html_url: Url::parse(
+                    "https://github.com/test-user/test-repo/actions/runs/1234/job/5678",
+                )
+                .unwrap(),
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+        };
+        let config = GitHubConfig::default();
+
+        assert!(handle_workflow_job(event("success"), &config).is_none());
+
+        let response =
+            handle_workflow_job(event("timed_out"), &config).expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(
+            message.plain,
+            "[❌ test-repo] job build (CI) timed out on ⊶main"
+        );
+        assert_eq!(
+            message.html,
+            r#"<b>[❌ test-repo]</b> job <a href="https://github.com/test-user/test-repo/actions/runs/1234/job/5678">build</a> (CI) timed out on <a href="https://github.com/test-user/test-repo/tree/main">⊶main</a>"#
         );
+    }
+
+    #[test]
+    fn test_handle_workflow_run() {
+        let event = |conclusion: &str| WorkflowRunEvent {
+            action: WorkflowRunAction::Completed,
+            workflow_run: WorkflowRun {
+                name: "CI".to_string(),
+                run_number: 42,
+                head_branch: Some("main".to_string()),
+                conclusion: Some(conclusion.to_string()),
+                html_url: Url::parse("https://github.com/test-user/test-repo/actions/runs/1234")
+                    .unwrap(),
+            },
+            repository: Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            },
+        };
+        let config = GitHubConfig::default();
+
+        assert!(handle_workflow_run(event("success"), &config).is_none());
+        assert!(handle_workflow_run(event("cancelled"), &config).is_none());
 
+        let response =
+            handle_workflow_run(event("failure"), &config).expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(
+            message.plain,
+            "[❌ test-repo] workflow CI #42 failed on ⊶main"
+        );
         assert_eq!(
             message.html,
-            r#"<b>[test-repo]</b> test-user force-pushed <a href="https://github.com/test-user/test-repo/compare/deadbeef...beefdead">2 commits including deadbee</a> on new <a href="https://github.com/test-user/test-repo/tree/new-test-branch">⊶new-test-branch</a>: This content is very long, longer than our character limit, so it will d…"#,
+            r#"<b>[❌ test-repo]</b> workflow <a href="https://github.com/test-user/test-repo/actions/runs/1234">CI #42</a> failed on <a href="https://github.com/test-user/test-repo/tree/main">⊶main</a>"#
+        );
+
+        let config = GitHubConfig {
+            workflow_failures_only: false,
+            ..Default::default()
+        };
+        let response =
+            handle_workflow_run(event("success"), &config).expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[✅ test-repo] workflow CI #42 succeeded on ⊶main"
+        );
+
+        let mut detached = event("failure");
+        detached.workflow_run.head_branch = None;
+        let response = handle_workflow_run(detached, &config).expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[❌ test-repo] workflow CI #42 failed"
         );
     }
 
     #[test]
     fn test_handle_repository() {
         let event = RepositoryEvent {
-            action: "created".to_string(),
+            action: RepositoryAction::Created,
             repository: Repository {
                 name: "test-repo".to_string(),
                 full_name: "test-user/test-repo".to_string(),
@@ -1204,11 +4122,15 @@ mod tests {
                 login: "test-user".to_string(),
                 id: 42,
                 html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
             },
             changes: None,
         };
 
-        let response = handle_repository(event).expect("should have a response");
+        let response = handle_repository(event, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a response");
 
         let message = response.message;
 
@@ -1218,7 +4140,110 @@ mod tests {
 
         assert_eq!(
             message.html,
-            r#"<b>[📦 test-repo]</b> test-user created repository"#,
+            r#"<b>[📦 test-repo]</b> <a href="https://github.com/test-user">test-user</a> created repository"#,
+        );
+    }
+
+    #[test]
+    fn test_handle_repository_ruleset() {
+        let event = |action, changes| RepositoryRulesetEvent {
+            action,
+            repository_ruleset: Ruleset {
+                id: 42,
+                name: "main".to_string(),
+                target: Some("branch".to_string()),
+                enforcement: "active".to_string(),
+            },
+            changes,
+            repository: Some(Repository {
+                name: "test-repo".to_string(),
+                full_name: "test-user/test-repo".to_string(),
+                html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+            }),
+            organization: None,
+            sender: GitHubUser {
+                login: "test-user".to_string(),
+                id: 42,
+                html_url: Url::parse("https://github.com/test-user").unwrap(),
+                avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+                kind: UserKind::User,
+            },
+        };
+
+        let response = handle_repository_ruleset(
+            event(RepositoryRulesetAction::Created, None),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[🛡 test-repo] test-user created branch ruleset main (active)"
+        );
+        assert_eq!(
+            response.message.url,
+            Some(Url::parse("https://github.com/test-user/test-repo/rules/42").unwrap())
+        );
+
+        let changes = RulesetChanges {
+            name: None,
+            enforcement: Some(PreviousValue {
+                from: "evaluate".to_string(),
+            }),
+        };
+        let response = handle_repository_ruleset(
+            event(RepositoryRulesetAction::Edited, Some(changes)),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert_eq!(
+            response.message.plain,
+            "[🛡 test-repo] test-user edited branch ruleset main, enforcement evaluate → active"
         );
+
+        let response = handle_repository_ruleset(
+            event(RepositoryRulesetAction::Deleted, None),
+            &GitHubConfig::default(),
+        )
+        .expect("should have a response");
+        assert!(response.message.url.is_none());
+    }
+
+    #[test]
+    fn test_handle_public() {
+        let repository = || Repository {
+            name: "test-repo".to_string(),
+            full_name: "test-user/test-repo".to_string(),
+            html_url: Url::parse("https://github.com/test-user/test-repo").unwrap(),
+        };
+        let sender = || GitHubUser {
+            login: "test-user".to_string(),
+            id: 42,
+            html_url: Url::parse("https://github.com/test-user").unwrap(),
+            avatar_url: Url::parse("https://avatars.githubusercontent.com/u/42").unwrap(),
+            kind: UserKind::User,
+        };
+
+        let public = GitHubEvent::Public(PublicEvent {
+            repository: repository(),
+            sender: sender(),
+        });
+        let publicized = GitHubEvent::Repository(RepositoryEvent {
+            action: RepositoryAction::Publicized,
+            repository: repository(),
+            sender: sender(),
+            changes: None,
+        });
+        assert_eq!(publicized_repo(&public), Some("test-user/test-repo"));
+        assert_eq!(publicized_repo(&publicized), Some("test-user/test-repo"));
+
+        for event in [public, publicized] {
+            let response = handle_github_event(event, &GitHubConfig::default())
+                .unwrap()
+                .expect("should have a response");
+            assert_eq!(
+                response.message.plain,
+                "[📦 test-repo] test-user publicized repository"
+            );
+        }
     }
 }
@@ -1,2 +1,11 @@
+pub mod acks;
+pub use acks::archive_acks;
+
 pub mod autojoin;
 pub use autojoin::autojoin_authorized_rooms;
+
+pub mod commands;
+pub use commands::handle_commands;
+
+pub mod welcome;
+pub use welcome::welcome_new_members;
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use matrix_sdk::{
+    room::Room,
+    ruma::events::{
+        room::message::{MessageEventContent, MessageType, Relation, TextMessageEventContent},
+        SyncMessageEvent,
+    },
+    Client,
+};
+use tracing::warn;
+
+use crate::bot::{
+    archive::{Archive, ArchivedKind, ArchivedMessage},
+    state::SharedState,
+};
+
+/// Archives the chat messages acknowledging an announcement, i.e. replies and messages sent in the
+/// room of the current incident, so that they show up in timelines.
+pub async fn archive_acks(
+    event: SyncMessageEvent<MessageEventContent>,
+    client: Client,
+    room: Room,
+    state: SharedState,
+    archive: Arc<Archive>,
+) {
+    if client.user_id().await.as_ref() == Some(&event.sender) {
+        return;
+    }
+
+    let is_reply = matches!(event.content.relates_to, Some(Relation::Reply { .. }));
+    let in_incident_room = match &state.lock().await.state.incident {
+        Some(incident) => &incident.room == room.room_id(),
+        None => false,
+    };
+    if !is_reply && !in_incident_room {
+        return;
+    }
+
+    let body = match event.content.msgtype {
+        MessageType::Text(TextMessageEventContent { body, .. }) => body,
+        _ => return,
+    };
+
+    let message = ArchivedMessage {
+        time: Utc::now(),
+        room: room.room_id().clone(),
+        event_id: event.event_id,
+        kind: ArchivedKind::Chat {
+            sender: event.sender,
+        },
+        body,
+    };
+    if let Err(e) = archive.append(&message) {
+        warn!("couldn't archive chat message: {:#}", e);
+    }
+}
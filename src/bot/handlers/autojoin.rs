@@ -4,18 +4,32 @@ use matrix_sdk::{
     room::Room,
     ruma::{
         events::{room::member::MemberEventContent, StrippedStateEvent},
-        RoomId,
+        RoomId, UserId,
     },
     Client,
 };
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+/// Whether the bot accepts an invitation to a room
+fn should_join(
+    room_id: &RoomId,
+    invite: &StrippedStateEvent<MemberEventContent>,
+    authorized_rooms: &[RoomId],
+    dm_users: &[UserId],
+) -> bool {
+    // people in `user_mapping` can talk to the bot in direct messages, e.g. to set their
+    // preferences
+    let direct = invite.content.is_direct == Some(true) && dm_users.contains(&invite.sender);
+    authorized_rooms.contains(room_id) || direct
+}
+
 pub async fn autojoin_authorized_rooms(
     room_member: StrippedStateEvent<MemberEventContent>,
     client: Client,
     room: Room,
     authorized_rooms: Vec<RoomId>,
+    dm_users: Vec<UserId>,
 ) {
     if room_member.state_key != client.user_id().await.unwrap() {
         return;
@@ -32,7 +46,7 @@ pub async fn autojoin_authorized_rooms(
             room_id, room_name
         );
 
-        if !authorized_rooms.contains(room_id) {
+        if !should_join(room_id, &room_member, &authorized_rooms, &dm_users) {
             warn!(
                 "Bot isn't authorized to join room `{}`, declining invitation",
                 room_id
@@ -66,3 +80,41 @@ pub async fn autojoin_authorized_rooms(
         info!("Successfully joined room {}", room.room_id());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use matrix_sdk::ruma::events::room::member::MembershipState;
+
+    use super::*;
+
+    fn invite(sender: &str, is_direct: Option<bool>) -> StrippedStateEvent<MemberEventContent> {
+        let mut content = MemberEventContent::new(MembershipState::Invite);
+        content.is_direct = is_direct;
+        StrippedStateEvent {
+            content,
+            sender: UserId::try_from(sender).unwrap(),
+            state_key: "@prololo:prologin.org".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_should_join() {
+        let dev = RoomId::try_from("!dev:prologin.org").unwrap();
+        let other = RoomId::try_from("!other:prologin.org").unwrap();
+        let authorized_rooms = vec![dev.clone()];
+        let dm_users = vec![UserId::try_from("@alice:prologin.org").unwrap()];
+
+        let alice = invite("@alice:prologin.org", None);
+        assert!(should_join(&dev, &alice, &authorized_rooms, &dm_users));
+        assert!(!should_join(&other, &alice, &authorized_rooms, &dm_users));
+
+        let alice = invite("@alice:prologin.org", Some(true));
+        assert!(should_join(&other, &alice, &authorized_rooms, &dm_users));
+
+        let mallory = invite("@mallory:example.org", Some(true));
+        assert!(should_join(&dev, &mallory, &authorized_rooms, &dm_users));
+        assert!(!should_join(&other, &mallory, &authorized_rooms, &dm_users));
+    }
+}
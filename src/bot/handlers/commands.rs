@@ -0,0 +1,291 @@
+use std::{fmt::Write, sync::Arc};
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use matrix_sdk::{
+    room::{Joined, Room},
+    ruma::{
+        events::{
+            room::message::{MessageEventContent, MessageType, TextMessageEventContent},
+            AnyMessageEventContent, SyncMessageEvent,
+        },
+        RoomId, UserId,
+    },
+    Client,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{
+        activity::Activity,
+        archive::Archive,
+        args::Args,
+        breaker::Breakers,
+        emoji,
+        find::find,
+        incidents::{close_incident, is_allowed, open_incident},
+        leaderboard::{is_month_key, leaderboard_message, month_key},
+        message_builder::MessageBuilder,
+        preferences::prefs_command,
+        search::search_command,
+        state::SharedState,
+        timeline::send_timeline,
+        utils::parse_duration,
+    },
+    config::ProloloConfig,
+    github_api::GitHubApi,
+};
+
+/// Number of days covered by `!history` without a period
+const DEFAULT_HISTORY_DAYS: i64 = 30;
+
+/// Answers chat commands (messages starting with `!`) sent in joined rooms.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_commands(
+    event: SyncMessageEvent<MessageEventContent>,
+    client: Client,
+    room: Room,
+    config: ProloloConfig,
+    state: SharedState,
+    archive: Arc<Archive>,
+    breakers: Arc<Breakers>,
+    activity: Arc<Activity>,
+    github: Arc<GitHubApi>,
+) {
+    let room = match room {
+        Room::Joined(room) => room,
+        _ => return,
+    };
+
+    // don't answer our own messages
+    if client.user_id().await.as_ref() == Some(&event.sender) {
+        return;
+    }
+
+    let body = match event.content.msgtype {
+        MessageType::Text(TextMessageEventContent { body, .. }) => body,
+        _ => return,
+    };
+
+    let trimmed = body.trim();
+    let (command, rest) = trimmed
+        .split_once(char::is_whitespace)
+        .unwrap_or((trimmed, ""));
+    let usage = match command {
+        "!deployments" => "!deployments",
+        "!find" => "!find <regex> [room] [since], or with flags: !find <regex> room=<room> since=<duration>",
+        "!history" => "!history [period]",
+        "!incident" => "!incident <title> | !incident close",
+        "!leaderboard" => "!leaderboard [YYYY-MM]",
+        "!prefs" => "!prefs [dm=on|off] [timezone=+02:00] [locale=en|fr] [delivery=realtime|digest]",
+        "!search" => "!search <repo> <query>",
+        "!status" => "!status",
+        "!timeline" => "!timeline [duration]",
+        "!uptime" => "!uptime",
+        _ => return,
+    };
+
+    let result = match Args::parse(rest, usage) {
+        Ok(args) => {
+            let context = CommandContext {
+                sender: &event.sender,
+                client: &client,
+                room: &room,
+                config: &config,
+                state: &state,
+                archive: &archive,
+                breakers: &breakers,
+                activity: &activity,
+                github: &github,
+            };
+            run_command(command, args, context).await
+        }
+        Err(e) => Err(e),
+    };
+    let message = match result {
+        Ok(Some(message)) => message,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("couldn't handle command `{}`: {:#}", body, e);
+            let mut message = MessageBuilder::new();
+            write!(message, "{:#}", e).unwrap();
+            message
+        }
+    };
+
+    debug!("answering command `{}` in room {}", body, room.room_id());
+
+    let message = AnyMessageEventContent::RoomMessage(message.into());
+    if let Err(e) = room.send(message, None).await {
+        warn!("couldn't answer command `{}`: {}", body, e);
+    }
+}
+
+/// What the commands can use to answer
+struct CommandContext<'a> {
+    sender: &'a UserId,
+    client: &'a Client,
+    room: &'a Joined,
+    config: &'a ProloloConfig,
+    state: &'a SharedState,
+    archive: &'a Archive,
+    breakers: &'a Breakers,
+    activity: &'a Activity,
+    github: &'a GitHubApi,
+}
+
+/// Answers a known command, returns `None` if the command is disabled.
+async fn run_command(
+    command: &str,
+    args: Args,
+    context: CommandContext<'_>,
+) -> anyhow::Result<Option<MessageBuilder>> {
+    let message = match command {
+        "!deployments" => {
+            args.expect_positional(0, 0)?;
+            args.finish()?;
+            deployments_command(context.state).await
+        }
+        "!find" => {
+            find(args, context.config, context.archive).context("couldn't search messages")?
+        }
+        "!history" => {
+            let period = args.expect_positional(0, 1)?.first().cloned();
+            args.finish()?;
+            let period = match period {
+                Some(period) => parse_duration(&period)?,
+                None => Duration::days(DEFAULT_HISTORY_DAYS),
+            };
+            context
+                .activity
+                .metrics()
+                .history_message(period, Utc::now())
+        }
+        "!incident" => {
+            let title = args.expect_positional(1, usize::MAX)?.to_vec();
+            args.finish()?;
+            match incident_command(
+                &title,
+                context.sender,
+                context.room.room_id(),
+                context.client,
+                context.config,
+                context.state,
+            )
+            .await
+            {
+                Some(message) => message,
+                None => return Ok(None),
+            }
+        }
+        "!leaderboard" => {
+            let month = args.expect_positional(0, 1)?.first().cloned();
+            if let Some(month) = month.as_deref().filter(|month| !is_month_key(month)) {
+                return Err(args.error(format_args!("invalid month `{}`", month)));
+            }
+            args.finish()?;
+            leaderboard_command(month.as_deref(), context.config, context.state).await
+        }
+        "!prefs" => {
+            prefs_command(
+                args,
+                context.sender,
+                context.room,
+                context.config,
+                context.state,
+            )
+            .await?
+        }
+        "!search" => {
+            search_command(args, context.room.room_id(), context.config, context.github).await?
+        }
+        "!status" => {
+            args.expect_positional(0, 0)?;
+            args.finish()?;
+            let mut message = context.activity.status_message(Utc::now());
+            context.breakers.write_status(&mut message, context.config);
+            message
+        }
+        "!timeline" => {
+            let duration = args.expect_positional(0, 1)?.first().cloned();
+            args.finish()?;
+            send_timeline(
+                context.room,
+                duration.as_deref(),
+                context.config,
+                context.state,
+                context.archive,
+            )
+            .await
+            .context("couldn't send timeline")?
+        }
+        "!uptime" => {
+            args.expect_positional(0, 0)?;
+            args.finish()?;
+            context.activity.metrics().uptime_message(Utc::now())
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(message))
+}
+
+/// `!deployments`: what is deployed in each environment.
+async fn deployments_command(state: &SharedState) -> MessageBuilder {
+    let store = state.lock().await;
+    store.state.deployments.status_message()
+}
+
+/// `!incident <title>`: opens an incident in a new room, `!incident close` closes it. Returns
+/// `None` if incidents are disabled.
+async fn incident_command(
+    args: &[String],
+    sender: &UserId,
+    room: &RoomId,
+    client: &Client,
+    config: &ProloloConfig,
+    state: &SharedState,
+) -> Option<MessageBuilder> {
+    let incidents = config.incidents.as_ref()?;
+    if !is_allowed(config, incidents, sender, room) {
+        let mut message = MessageBuilder::new();
+        message.tag("incident", Some(emoji::POLICE_CAR_LIGHT));
+        write!(message, " you aren't allowed to handle incidents").unwrap();
+        return Some(message);
+    }
+
+    let result = match args {
+        [close] if close == "close" => close_incident(client, state).await,
+        title => {
+            let assets_dir = config.assets_dir.as_deref();
+            let title = title.join(" ");
+            open_incident(client, incidents, assets_dir, state, sender, &title).await
+        }
+    };
+
+    match result {
+        Ok(message) => Some(message),
+        Err(e) => {
+            warn!("couldn't handle incident command: {:#}", e);
+            let mut message = MessageBuilder::new();
+            write!(message, "couldn't handle incident command: {}", e).unwrap();
+            Some(message)
+        }
+    }
+}
+
+/// `!leaderboard [YYYY-MM]`: merged PRs per author for the given month, or the current one.
+async fn leaderboard_command(
+    month: Option<&str>,
+    config: &ProloloConfig,
+    state: &SharedState,
+) -> MessageBuilder {
+    let month = month.map_or_else(|| month_key(Utc::now()), str::to_string);
+
+    let store = state.lock().await;
+    leaderboard_message(
+        &month,
+        &store.state.leaderboard.ranking(&month),
+        &config.user_mapping,
+    )
+}
@@ -0,0 +1,227 @@
+//! Greeting of the people joining the rooms with a `welcome` setting, explaining what the bot posts
+//! there and how to get notified personally. Each person is greeted once per room.
+
+use std::fmt::Write;
+
+use matrix_sdk::{
+    room::Room,
+    ruma::{
+        api::client::r0::room::{create_room, Visibility},
+        events::{
+            room::member::{MemberEventContent, MembershipState},
+            AnyMessageEventContent, SyncStateEvent,
+        },
+        UserId,
+    },
+    Client,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, incidents::send_to, message_builder::MessageBuilder, state::SharedState},
+    config::{ProloloConfig, WelcomeTarget},
+};
+
+/// Describes what the bot posts in the room named `name`
+fn room_summary(name: &str, config: &ProloloConfig) -> Vec<String> {
+    let mut summary = Vec::new();
+
+    let repos: Vec<String> = config
+        .destinations
+        .iter()
+        .filter(|destination| destination.room == name)
+        .map(|destination| format!("`{}`", destination.regex))
+        .collect();
+    if !repos.is_empty() {
+        summary.push(format!(
+            "the activity of the repos matching {}",
+            repos.join(", ")
+        ));
+    }
+    let id = config.room_by_name(name).ok();
+    if id.is_some() && id == config.default_room().ok() {
+        summary.push("the events that aren't sent to another room".to_string());
+    }
+    if config.github.security_room.as_deref() == Some(name) {
+        summary.push("security alerts".to_string());
+    }
+    if config.github.planning_room.as_deref() == Some(name) {
+        summary.push("the changes of the project boards".to_string());
+    }
+
+    summary
+}
+
+fn welcome_message(
+    name: &str,
+    config: &ProloloConfig,
+    user_id: &UserId,
+    text: Option<&str>,
+) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    message.tag("welcome", Some(emoji::WAVING_HAND));
+    write!(message, " hi ").unwrap();
+    message.mention(user_id);
+    write!(message, ", welcome to {}!", name).unwrap();
+
+    let summary = room_summary(name, config);
+    if !summary.is_empty() {
+        write!(message, " I announce here:").unwrap();
+        message.list();
+        for item in summary {
+            message.list_item();
+            message.markdown(&item);
+            message.close_last();
+        }
+        message.close_last();
+    }
+    if let Some(text) = text {
+        message.line_break();
+        message.markdown(text);
+    }
+    // only the people with a GitHub login in the config get personal notifications
+    if config.user_mapping.values().any(|mapped| mapped == user_id) {
+        message.line_break();
+        write!(
+            message,
+            "To get review reminders directly, invite me to a direct message and send "
+        )
+        .unwrap();
+        message.code();
+        write!(message, "!prefs dm=on").unwrap();
+        message.close_last();
+        write!(message, ".").unwrap();
+    }
+
+    message
+}
+
+/// Opens a direct chat with `user_id` to send them `message`
+async fn send_direct(
+    client: &Client,
+    user_id: &UserId,
+    message: MessageBuilder,
+) -> anyhow::Result<()> {
+    let invites = [user_id.clone()];
+    let mut request = create_room::Request::new();
+    request.invite = &invites;
+    request.is_direct = true;
+    request.visibility = Visibility::Private;
+    request.preset = Some(create_room::RoomPreset::TrustedPrivateChat);
+    let room = client.create_room(request).await?.room_id;
+
+    send_to(client, &room, message.build()).await
+}
+
+/// Greets the people joining a room configured with a `welcome` setting.
+pub async fn welcome_new_members(
+    event: SyncStateEvent<MemberEventContent>,
+    client: Client,
+    room: Room,
+    config: ProloloConfig,
+    state: SharedState,
+) {
+    let room = match room {
+        Room::Joined(room) => room,
+        _ => return,
+    };
+
+    // only actual joins, not profile changes
+    let joined = event.content.membership == MembershipState::Join
+        && event
+            .prev_content
+            .as_ref()
+            .map_or(true, |prev| prev.membership != MembershipState::Join);
+    if !joined || client.user_id().await.as_ref() == Some(&event.sender) {
+        return;
+    }
+    let (name, welcome) = match config
+        .matrix_rooms
+        .iter()
+        .find(|(_, room_config)| &room_config.id == room.room_id())
+        .and_then(|(name, room_config)| Some((name, room_config.welcome.as_ref()?)))
+    {
+        Some(found) => found,
+        None => return,
+    };
+
+    let user_id = &event.sender;
+    {
+        let mut store = state.lock().await;
+        let welcomed = store
+            .state
+            .welcomed
+            .entry(room.room_id().clone())
+            .or_default();
+        if !welcomed.insert(user_id.clone()) {
+            return;
+        }
+        if let Err(e) = store.save() {
+            warn!("couldn't save bot state: {:#}", e);
+        }
+    }
+
+    debug!("welcoming {} to room {}", user_id, name);
+    let message = welcome_message(name, &config, user_id, welcome.text.as_deref());
+    let result = match welcome.to {
+        WelcomeTarget::Room => room
+            .send(AnyMessageEventContent::RoomMessage(message.build()), None)
+            .await
+            .map(|_| ())
+            .map_err(anyhow::Error::from),
+        WelcomeTarget::Direct => send_direct(&client, user_id, message).await,
+    };
+    if let Err(e) = result {
+        warn!("couldn't welcome {} to room {}: {:#}", user_id, name, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    #[test]
+    fn test_welcome_message() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            r#"
+matrix_username: prololo
+matrix_password: secret
+matrix_homeserver: https://matrix.prologin.org
+matrix_state_dir: state
+github_secret: secret
+prolosite_secret: secret
+destinations:
+  - room: site
+    regex: "^prologin/site"
+matrix_rooms:
+  dev:
+    id: "!dev:prologin.org"
+    default: true
+  site:
+    id: "!site:prologin.org"
+    welcome:
+      to: direct
+user_mapping:
+  alice: "@alice:prologin.org"
+"#,
+        )
+        .unwrap();
+        let alice = UserId::try_from("@alice:prologin.org").unwrap();
+        let bob = UserId::try_from("@bob:prologin.org").unwrap();
+
+        let message = welcome_message("site", &config, &alice, Some("Be **nice**"));
+        assert_eq!(
+            message.plain,
+            "[👋 welcome] hi @alice:prologin.org, welcome to site! I announce here:\n- the activity of the repos matching ^prologin/site\nBe nice\nTo get review reminders directly, invite me to a direct message and send !prefs dm=on."
+        );
+
+        let message = welcome_message("dev", &config, &bob, None);
+        assert_eq!(
+            message.html,
+            r#"<b>[👋 welcome]</b> hi <a href="https://matrix.to/#/@bob:prologin.org">@bob:prologin.org</a>, welcome to dev! I announce here:<ul><li>the events that aren&#39;t sent to another room</li></ul>"#
+        );
+    }
+}
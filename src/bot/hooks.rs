@@ -0,0 +1,219 @@
+//! Per-room Lua scripts post-processing the messages announcing events.
+//!
+//! A hook is a Lua chunk run before each message is sent to its room, with a global `message` table
+//! holding the `plain` and `html` bodies, the `room` name and the `severity` of the message. The
+//! script can rewrite the bodies in place, or return `false` to veto the message. Each run uses a
+//! fresh Lua state, so nothing is kept between messages.
+//!
+//! Scripts only get the `string`, `table`, `math` and `utf8` libraries besides the base functions
+//! that don't touch the filesystem, and each run is limited in memory, instructions and time.
+
+use std::{cell::Cell, collections::HashMap, fs, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context};
+use matrix_sdk::ruma::RoomId;
+use mlua::{HookTriggers, Lua, LuaOptions, StdLib, Table, Value};
+use tokio::time::timeout;
+
+use crate::{bot::Severity, config::ProloloConfig};
+
+/// Memory available to a run of a hook, in bytes
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+/// The VM instructions a run of a hook can execute, checked every [`INSTRUCTION_STEP`]
+const MAX_INSTRUCTIONS: u32 = 10_000_000;
+const INSTRUCTION_STEP: u32 = 10_000;
+/// How long a message waits for the hook of its room
+const HOOK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A Lua state with the safe libraries only, and the memory and instruction limits of a hook
+fn sandbox() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8,
+        LuaOptions::default(),
+    )?;
+    for unsafe_global in ["dofile", "loadfile", "load", "collectgarbage"] {
+        lua.globals().set(unsafe_global, Value::Nil)?;
+    }
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+
+    let steps_left = Cell::new(MAX_INSTRUCTIONS / INSTRUCTION_STEP);
+    lua.set_hook(
+        HookTriggers {
+            every_nth_instruction: Some(INSTRUCTION_STEP),
+            ..Default::default()
+        },
+        move |_, _| match steps_left.get() {
+            0 => Err(mlua::Error::RuntimeError(
+                "too many instructions".to_string(),
+            )),
+            left => {
+                steps_left.set(left - 1);
+                Ok(())
+            }
+        },
+    )?;
+
+    Ok(lua)
+}
+
+struct LuaHook {
+    /// The room name, as used in [`ProloloConfig::matrix_rooms`]
+    room: String,
+    source: String,
+}
+
+impl LuaHook {
+    fn run(
+        &self,
+        severity: Severity,
+        plain: String,
+        html: String,
+    ) -> mlua::Result<Option<(String, String)>> {
+        let lua = sandbox()?;
+
+        let message = lua.create_table()?;
+        message.set("plain", plain)?;
+        message.set("html", html)?;
+        message.set("room", self.room.as_str())?;
+        message.set("severity", severity.to_string())?;
+        lua.globals().set("message", message)?;
+
+        let result: Value = lua.load(&self.source).set_name(&self.room)?.eval()?;
+        if let Value::Boolean(false) = result {
+            return Ok(None);
+        }
+
+        let message: Table = lua.globals().get("message")?;
+        Ok(Some((message.get("plain")?, message.get("html")?)))
+    }
+}
+
+pub struct Hooks {
+    hooks: HashMap<RoomId, Arc<LuaHook>>,
+}
+
+impl Hooks {
+    /// Loads the scripts of all rooms configured with a hook, and checks that they compile.
+    pub fn from_config(config: &ProloloConfig) -> anyhow::Result<Self> {
+        let mut hooks = HashMap::new();
+
+        for (name, room) in &config.matrix_rooms {
+            let path = match &room.hook {
+                Some(path) => path,
+                None => continue,
+            };
+
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("couldn't read hook {}", path.display()))?;
+            sandbox()
+                .and_then(|lua| lua.load(&source).into_function().map(drop))
+                .map_err(|e| anyhow!("invalid hook {}: {}", path.display(), e))?;
+
+            hooks.insert(
+                room.id.clone(),
+                Arc::new(LuaHook {
+                    room: name.clone(),
+                    source,
+                }),
+            );
+        }
+
+        Ok(Self { hooks })
+    }
+
+    /// Runs the hook of a room on a message, returns `None` if the hook vetoed it.
+    pub async fn run(
+        &self,
+        room_id: &RoomId,
+        severity: Severity,
+        plain: String,
+        html: String,
+    ) -> anyhow::Result<Option<(String, String)>> {
+        let hook = match self.hooks.get(room_id) {
+            Some(hook) => hook.clone(),
+            None => return Ok(Some((plain, html))),
+        };
+
+        let room = hook.room.clone();
+        let run = tokio::task::spawn_blocking(move || hook.run(severity, plain, html));
+        timeout(HOOK_TIMEOUT, run)
+            .await
+            .map_err(|_| anyhow!("hook for room {} timed out", room))?
+            .context("hook panicked")?
+            .map_err(|e| anyhow!("hook for room {} failed: {}", room, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(source: &str) -> LuaHook {
+        LuaHook {
+            room: "ops".to_string(),
+            source: source.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_hook_rewrites_message() {
+        let hook = hook(
+            r#"
+            message.plain = message.plain:gsub("prologin/", "")
+            message.html = message.html .. " (" .. message.severity .. ")"
+            "#,
+        );
+
+        assert_eq!(
+            hook.run(
+                Severity::High,
+                "[prologin/site] hi".to_string(),
+                "<b>[prologin/site]</b> hi".to_string()
+            )
+            .unwrap(),
+            Some((
+                "[site] hi".to_string(),
+                "<b>[prologin/site]</b> hi (high)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_hook_vetoes_message() {
+        let hook = hook(r#"return not message.plain:find("dependabot")"#);
+
+        assert_eq!(
+            hook.run(
+                Severity::Normal,
+                "[site] dependabot opened a PR".to_string(),
+                String::new()
+            )
+            .unwrap(),
+            None
+        );
+        assert!(hook
+            .run(
+                Severity::Normal,
+                "[site] alice opened a PR".to_string(),
+                String::new()
+            )
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_hook_sandbox() {
+        let run = |source: &str| hook(source).run(Severity::Normal, String::new(), String::new());
+
+        assert!(
+            run("return io == nil and os == nil and dofile == nil and require == nil")
+                .unwrap()
+                .is_some()
+        );
+        assert!(run("return io.open('/etc/passwd')").is_err());
+        assert!(run("while true do end").is_err());
+        assert!(
+            run("local s = string.rep('x', 1024 * 1024) while true do s = s .. s end").is_err()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Attachment of the images found in relayed content, e.g. screenshots in bug reports.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use matrix_sdk::room::Joined;
+use mime::Mime;
+use tracing::debug;
+use url::Url;
+
+use crate::config::ImageConfig;
+
+/// GitHub redirects the attachments to signed URLs, once
+const MAX_REDIRECTS: usize = 5;
+
+/// Downloads an image, failing if it is bigger than `max_size` bytes or takes more than `timeout`.
+/// Only the allowed URLs are fetched, redirections included.
+async fn download(
+    url: &Url,
+    config: &ImageConfig,
+    timeout: Duration,
+) -> anyhow::Result<(Mime, Vec<u8>)> {
+    let max_size = config.max_size;
+    if !config.allows(url) {
+        bail!("images aren't downloaded from {}", url);
+    }
+    let redirects = {
+        let config = config.clone();
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirections")
+            } else if config.allows(attempt.url()) {
+                attempt.follow()
+            } else {
+                let url = attempt.url().to_string();
+                attempt.error(format!("redirected to {}", url))
+            }
+        })
+    };
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(redirects)
+        .build()?;
+    let mut response = client.get(url.clone()).send().await?.error_for_status()?;
+
+    let content_type: Mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .context("missing or invalid content type")?;
+    if content_type.type_() != mime::IMAGE {
+        bail!("{} isn't an image", content_type);
+    }
+
+    if response.content_length().unwrap_or(0) > max_size as u64 {
+        bail!("image is bigger than {} bytes", max_size);
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if data.len() + chunk.len() > max_size {
+            bail!("image is bigger than {} bytes", max_size);
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok((content_type, data))
+}
+
+/// Downloads an image and sends it to a room.
+pub async fn attach_image(
+    room: &Joined,
+    url: &Url,
+    config: &ImageConfig,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let (content_type, data) = download(url, config, timeout)
+        .await
+        .with_context(|| format!("couldn't download image {}", url))?;
+
+    let name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("image");
+
+    debug!("attaching image {} ({} bytes)", url, data.len());
+    room.send_attachment(name, &content_type, &mut data.as_slice(), None)
+        .await?;
+
+    Ok(())
+}
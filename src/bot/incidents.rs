@@ -0,0 +1,261 @@
+//! A lightweight incident workflow: `!incident <title>` creates a room for the incident and invites
+//! the on-call people, then high-severity messages are mirrored there until `!incident close`.
+
+use std::{convert::TryFrom, fmt::Write, path::Path};
+
+use chrono::{DateTime, Utc};
+use matrix_sdk::{
+    ruma::{
+        api::client::r0::{
+            message::send_message_event,
+            room::{create_room, Visibility},
+        },
+        events::{room::message::MessageEventContent, AnyMessageEventContent},
+        identifiers::RoomNameBox,
+        RoomId, UserId,
+    },
+    uuid::Uuid,
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    assets,
+    bot::{emoji, message_builder::MessageBuilder, state::SharedState},
+    config::{IncidentsConfig, ProloloConfig},
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Incident {
+    pub title: String,
+    pub room: RoomId,
+    pub opened_at: DateTime<Utc>,
+}
+
+fn room_link(room: &RoomId) -> Url {
+    Url::parse(&format!("https://matrix.to/#/{}", room)).expect("room IDs are valid URL fragments")
+}
+
+/// Sends a message to a room that may not be known by the client yet, e.g. right after creating it
+pub(crate) async fn send_to(
+    client: &Client,
+    room: &RoomId,
+    message: MessageEventContent,
+) -> anyhow::Result<()> {
+    let content = AnyMessageEventContent::RoomMessage(message);
+    let txn_id = Uuid::new_v4().to_string();
+    client
+        .send(
+            send_message_event::Request::new(room, &txn_id, &content),
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Whether `sender` may open or close incidents from `room`: the on-call people, the allowed
+/// users, and anyone in the configured rooms.
+pub fn is_allowed(
+    config: &ProloloConfig,
+    incidents: &IncidentsConfig,
+    sender: &UserId,
+    room: &RoomId,
+) -> bool {
+    incidents.on_call.contains(sender)
+        || incidents.allowed_users.contains(sender)
+        || incidents
+            .rooms
+            .iter()
+            .filter_map(|name| config.matrix_rooms.get(name))
+            .any(|room_config| &room_config.id == room)
+}
+
+/// Creates the room of a new incident, returns the answer to the command.
+pub async fn open_incident(
+    client: &Client,
+    config: &IncidentsConfig,
+    assets_dir: Option<&Path>,
+    state: &SharedState,
+    sender: &UserId,
+    title: &str,
+) -> anyhow::Result<MessageBuilder> {
+    let mut message = MessageBuilder::new();
+    message.tag("incident", Some(emoji::POLICE_CAR_LIGHT));
+
+    // The slot stays locked until the room is recorded, so that concurrent commands don't open
+    // several rooms
+    let mut store = state.lock().await;
+    if let Some(incident) = &store.state.incident {
+        write!(message, " ").unwrap();
+        message.link(&incident.title, &room_link(&incident.room));
+        write!(
+            message,
+            " is still open, close it first with !incident close"
+        )
+        .unwrap();
+        return Ok(message);
+    }
+
+    // `{title}` and `{sender}` are replaced once the room exists
+    let template = match &config.template {
+        Some(template) => template.clone(),
+        None => assets::load(assets_dir, "templates/incident.txt")?,
+    };
+
+    let mut invites = config.on_call.clone();
+    if !invites.contains(sender) {
+        invites.push(sender.clone());
+    }
+    let name = RoomNameBox::try_from(format!("Incident: {}", title))?;
+
+    let mut request = create_room::Request::new();
+    request.name = Some(&name);
+    request.topic = Some(title);
+    request.invite = &invites;
+    request.visibility = Visibility::Private;
+    request.preset = Some(create_room::RoomPreset::PrivateChat);
+    let room = client.create_room(request).await?.room_id;
+
+    debug!("opened incident `{}` in room {}", title, room);
+
+    store.state.incident = Some(Incident {
+        title: title.to_string(),
+        room: room.clone(),
+        opened_at: Utc::now(),
+    });
+    store.save()?;
+    drop(store);
+
+    let template = template
+        .trim_end()
+        .replace("{title}", title)
+        .replace("{sender}", sender.as_str());
+    send_to(client, &room, MessageEventContent::text_plain(template)).await?;
+
+    write!(message, " opened ").unwrap();
+    message.main_link(title, &room_link(&room));
+
+    Ok(message)
+}
+
+fn closed_message(incident: &Incident, now: DateTime<Utc>) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+    message.tag("incident", Some(emoji::POLICE_CAR_LIGHT));
+
+    let duration = now - incident.opened_at;
+    write!(
+        message,
+        " closed {} after {}h{:02}",
+        incident.title,
+        duration.num_hours(),
+        duration.num_minutes() % 60
+    )
+    .unwrap();
+
+    message
+}
+
+/// Stops mirroring alerts to the room of the current incident, returns the answer to the command.
+pub async fn close_incident(
+    client: &Client,
+    state: &SharedState,
+) -> anyhow::Result<MessageBuilder> {
+    let mut store = state.lock().await;
+    let incident = match store.state.incident.take() {
+        Some(incident) => incident,
+        None => {
+            let mut message = MessageBuilder::new();
+            message.tag("incident", Some(emoji::POLICE_CAR_LIGHT));
+            write!(message, " no incident is open").unwrap();
+            return Ok(message);
+        }
+    };
+    store.save()?;
+
+    let now = Utc::now();
+    if let Err(e) = send_to(
+        client,
+        &incident.room,
+        closed_message(&incident, now).into(),
+    )
+    .await
+    {
+        warn!(
+            "couldn't announce the end of the incident in its room: {:#}",
+            e
+        );
+    }
+
+    Ok(closed_message(&incident, now))
+}
+
+/// Mirrors a high-severity message to the room of the current incident, if any.
+pub async fn mirror_alert(
+    client: &Client,
+    state: &SharedState,
+    from: &RoomId,
+    plain: &str,
+    html: &str,
+) -> anyhow::Result<()> {
+    let room = match &state.lock().await.state.incident {
+        Some(incident) if &incident.room != from => incident.room.clone(),
+        _ => return Ok(()),
+    };
+
+    let content = MessageEventContent::text_html(plain.to_string(), html.to_string());
+    send_to(client, &room, content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_allowed() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            "{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_state_dir: state, \
+             matrix_rooms: {ops: {id: '!ops:prologin.org'}}, destinations: [], \
+             github_secret: secret, prolosite_secret: secret, \
+             incidents: {on_call: ['@alice:prologin.org'], allowed_users: ['@bob:prologin.org'], \
+             rooms: [ops]}}",
+        )
+        .unwrap();
+        let incidents = config.incidents.as_ref().unwrap();
+        let allowed = |sender: &str, room: &str| {
+            is_allowed(
+                &config,
+                incidents,
+                &UserId::try_from(sender).unwrap(),
+                &RoomId::try_from(room).unwrap(),
+            )
+        };
+
+        assert!(allowed("@alice:prologin.org", "!dev:prologin.org"));
+        assert!(allowed("@bob:prologin.org", "!dev:prologin.org"));
+        assert!(allowed("@eve:prologin.org", "!ops:prologin.org"));
+        assert!(!allowed("@eve:prologin.org", "!dev:prologin.org"));
+    }
+
+    #[test]
+    fn test_closed_message() {
+        let date = |date: &str| {
+            DateTime::parse_from_rfc3339(date)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let incident = Incident {
+            title: "site is down".to_string(),
+            room: RoomId::try_from("!abc:prologin.org").unwrap(),
+            opened_at: date("2021-12-14T09:15:00Z"),
+        };
+
+        assert_eq!(
+            closed_message(&incident, date("2021-12-14T11:20:00Z")).plain,
+            "[🚨 incident] closed site is down after 2h05"
+        );
+    }
+}
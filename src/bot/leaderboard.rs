@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use matrix_sdk::{
+    ruma::{events::AnyMessageEventContent, UserId},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder, state::SharedState},
+    config::ProloloConfig,
+    webhooks::{
+        github::{PullRequestAction, PullRequestEvent},
+        GitHubEvent,
+    },
+};
+
+/// How many authors are listed in a leaderboard message
+const LEADERBOARD_SIZE: usize = 10;
+
+/// Merged pull request counts per author, grouped by month.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Leaderboard {
+    /// Keys are months formatted as `YYYY-MM`, then GitHub logins
+    months: BTreeMap<String, BTreeMap<String, u64>>,
+    /// The counted PRs as `owner/repo#number`, so that a redelivered merge isn't counted twice
+    #[serde(default)]
+    merged: BTreeSet<String>,
+}
+
+impl Leaderboard {
+    /// Counts the merge of a PR, returns whether it wasn't counted yet
+    pub fn record_merge(&mut self, month: &str, pr: &str, author: &str) -> bool {
+        if !self.merged.insert(pr.to_string()) {
+            return false;
+        }
+        *self
+            .months
+            .entry(month.to_string())
+            .or_default()
+            .entry(author.to_string())
+            .or_default() += 1;
+        true
+    }
+
+    /// Returns the authors who merged PRs during `month`, most prolific first.
+    pub fn ranking(&self, month: &str) -> Vec<(&str, u64)> {
+        let mut ranking: Vec<(&str, u64)> = self
+            .months
+            .get(month)
+            .map(|authors| {
+                authors
+                    .iter()
+                    .map(|(login, count)| (login.as_str(), *count))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // ties are broken alphabetically, the map is already sorted by login
+        ranking.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        ranking
+    }
+}
+
+pub fn month_key(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m").to_string()
+}
+
+/// Whether `month` is formatted like the keys of [`Leaderboard`], i.e. `YYYY-MM`
+pub fn is_month_key(month: &str) -> bool {
+    // the parser is lenient, e.g. with single digit months
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_or(false, |date| date.format("%Y-%m").to_string() == month)
+}
+
+fn first_day_of_month(year: i32, month: u32) -> DateTime<Utc> {
+    let date = NaiveDate::from_ymd_opt(year, month, 1).expect("first day of month is valid");
+    DateTime::from_utc(date.and_hms(0, 0, 0), Utc)
+}
+
+fn next_month_start(date: DateTime<Utc>) -> DateTime<Utc> {
+    if date.month() == 12 {
+        first_day_of_month(date.year() + 1, 1)
+    } else {
+        first_day_of_month(date.year(), date.month() + 1)
+    }
+}
+
+/// Returns the PR as `owner/repo#number` and its author if this event is a PR getting merged.
+fn merged_pr_author(event: &PullRequestEvent) -> Option<(String, &str)> {
+    let merged = event.pull_request.merged.unwrap_or(false);
+    if event.action == PullRequestAction::Closed && merged {
+        let pr = format!(
+            "{}#{}",
+            event.repository.full_name, event.pull_request.number
+        );
+        Some((pr, &event.pull_request.user.login))
+    } else {
+        None
+    }
+}
+
+/// Updates the leaderboard if this event is a PR getting merged.
+pub async fn record_event(event: &GitHubEvent, state: &SharedState) -> anyhow::Result<()> {
+    let author = match event {
+        GitHubEvent::PullRequest(event) => merged_pr_author(event),
+        _ => None,
+    };
+
+    if let Some((pr, author)) = author {
+        debug!("recording merged PR {} by {} in leaderboard", pr, author);
+
+        let mut store = state.lock().await;
+        let recorded = store
+            .state
+            .leaderboard
+            .record_merge(&month_key(Utc::now()), &pr, author);
+        if recorded {
+            store.save()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the top authors, mentioning the ones with a Matrix user in `user_mapping`
+pub fn leaderboard_message(
+    month: &str,
+    ranking: &[(&str, u64)],
+    user_mapping: &HashMap<String, UserId>,
+) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    message.tag("leaderboard", Some(emoji::TROPHY));
+
+    if ranking.is_empty() {
+        write!(message, " no merged PRs for {}", month).unwrap();
+        return message;
+    }
+
+    write!(message, " merged PRs for {}: ", month).unwrap();
+    for (i, (login, count)) in ranking.iter().take(LEADERBOARD_SIZE).enumerate() {
+        if i > 0 {
+            write!(message, ", ").unwrap();
+        }
+        write!(message, "{}. ", i + 1).unwrap();
+        message.bold();
+        match user_mapping.get(*login) {
+            Some(user_id) => message.mention(user_id),
+            None => write!(message, "{}", login).unwrap(),
+        }
+        message.close_last();
+        write!(message, " ({})", count).unwrap();
+    }
+
+    message
+}
+
+/// Posts the previous month's leaderboard every time a new month starts.
+pub async fn post_monthly_leaderboard(client: Client, config: ProloloConfig, state: SharedState) {
+    loop {
+        let now = Utc::now();
+        let next_month = next_month_start(now);
+        let delay = (next_month - now)
+            .to_std()
+            .expect("next month is always in the future");
+
+        debug!("next leaderboard post in {:?}", delay);
+        tokio::time::sleep(delay).await;
+
+        let month = month_key(now);
+        let message = {
+            let store = state.lock().await;
+            leaderboard_message(
+                &month,
+                &store.state.leaderboard.ranking(&month),
+                &config.user_mapping,
+            )
+        };
+
+        if let Err(e) = send_leaderboard(&client, &config, message).await {
+            warn!("couldn't post monthly leaderboard: {}", e);
+        }
+    }
+}
+
+async fn send_leaderboard(
+    client: &Client,
+    config: &ProloloConfig,
+    message: MessageBuilder,
+) -> anyhow::Result<()> {
+    let room_id = match &config.leaderboard.room {
+        Some(room) => config.room_by_name(room)?,
+        None => config.default_room()?,
+    };
+
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow::anyhow!("room with id {} isn't joined yet", room_id))?;
+
+    let message = AnyMessageEventContent::RoomMessage(message.into());
+    room.send(message, None).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranking() {
+        let mut leaderboard = Leaderboard::default();
+
+        assert!(leaderboard.record_merge("2021-12", "prologin/site#1", "bob"));
+        assert!(leaderboard.record_merge("2021-12", "prologin/site#2", "alice"));
+        assert!(leaderboard.record_merge("2021-12", "prologin/site#3", "carol"));
+        assert!(leaderboard.record_merge("2021-12", "prologin/stechec2#3", "carol"));
+        assert!(leaderboard.record_merge("2021-11", "prologin/site#4", "bob"));
+        // redelivered merge
+        assert!(!leaderboard.record_merge("2021-12", "prologin/site#3", "carol"));
+
+        assert_eq!(
+            leaderboard.ranking("2021-12"),
+            vec![("carol", 2), ("alice", 1), ("bob", 1)]
+        );
+        assert!(leaderboard.ranking("2022-01").is_empty());
+    }
+
+    #[test]
+    fn test_next_month_start() {
+        let date = DateTime::parse_from_rfc3339("2021-12-15T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(month_key(date), "2021-12");
+        assert_eq!(month_key(next_month_start(date)), "2022-01");
+    }
+
+    #[test]
+    fn test_leaderboard_message() {
+        let mut user_mapping = HashMap::new();
+        user_mapping.insert(
+            "alice".to_string(),
+            UserId::try_from("@alice:prologin.org").unwrap(),
+        );
+        let message = leaderboard_message("2021-12", &[("carol", 2), ("alice", 1)], &user_mapping);
+
+        assert_eq!(
+            message.plain,
+            "[🏆 leaderboard] merged PRs for 2021-12: 1. carol (2), 2. @alice:prologin.org (1)"
+        );
+        assert_eq!(
+            message.html,
+            "<b>[🏆 leaderboard]</b> merged PRs for 2021-12: 1. <b>carol</b> (2), 2. <b><a href=\"https://matrix.to/#/@alice:prologin.org\">@alice:prologin.org</a></b> (1)"
+        );
+    }
+
+    #[test]
+    fn test_is_month_key() {
+        assert!(is_month_key("2021-12"));
+        assert!(is_month_key("2022-01"));
+        assert!(!is_month_key("2021-13"));
+        assert!(!is_month_key("2021-1"));
+        assert!(!is_month_key("2021-12-01"));
+        assert!(!is_month_key("12-2021"));
+        assert!(!is_month_key("december"));
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt::Write;
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder, utils::shorten_content, Response},
+    mail::MailEvent,
+};
+
+pub(crate) fn handle_mail_event(event: MailEvent) -> anyhow::Result<Option<Response>> {
+    let mut message = MessageBuilder::new();
+
+    message.tag("mail", Some(emoji::E_MAIL));
+
+    write!(message, " {} ", event.sender).unwrap();
+    message.bold();
+    write!(message, "{}", event.subject).unwrap();
+    message.close_last();
+
+    if let Some(first_line) = event.body.lines().find(|line| !line.trim().is_empty()) {
+        write!(message, ": {}", shorten_content(first_line.trim())).unwrap();
+    }
+
+    Ok(Some(Response {
+        message,
+        repo: None,
+        room: Some(event.room),
+        severity: event.severity,
+        image: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::Severity;
+
+    use super::*;
+
+    #[test]
+    fn test_handle_mail_event() {
+        let event = MailEvent {
+            sender: "root@server".to_string(),
+            subject: "Cron <root@server> backup".to_string(),
+            body: "\nrsync: connection refused\nmore details".to_string(),
+            room: "ops".to_string(),
+            severity: Severity::High,
+        };
+
+        let response = handle_mail_event(event)
+            .unwrap()
+            .expect("should have a response");
+        let message = response.message;
+
+        assert_eq!(response.room.as_deref(), Some("ops"));
+        assert_eq!(response.severity, Severity::High);
+        assert_eq!(
+            message.plain,
+            "[📧 mail] root@server Cron <root@server> backup: rsync: connection refused"
+        );
+        assert_eq!(
+            message.html,
+            "<b>[📧 mail]</b> root@server <b>Cron &lt;root@server&gt; backup</b>: rsync: connection refused"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Processing of the GitHub `@user` mentions found in relayed content, so they don't end up
+//! highlighting people on Matrix.
+
+use regex::{Captures, Regex};
+use url::Url;
+
+use crate::{bot::utils::map_html_text, config::MentionMode};
+
+fn mention_regex() -> Regex {
+    // NOTE: the mention can't follow a word character, to leave e-mail addresses alone
+    Regex::new(r"(^|[^\w@/.])@([A-Za-z0-9][A-Za-z0-9-]{0,38})\b").expect("valid regex")
+}
+
+/// Applies the configured [`MentionMode`] to both bodies of a message.
+pub fn process_mentions(
+    mode: MentionMode,
+    web_url: &Url,
+    plain: String,
+    html: String,
+) -> (String, String) {
+    if let MentionMode::Keep = mode {
+        return (plain, html);
+    }
+
+    let re = mention_regex();
+    let plain = re.replace_all(&plain, "$1$2").into_owned();
+    let html = map_html_text(&html, |text, in_link| match mode {
+        // links can't be nested
+        MentionMode::Link if !in_link => re
+            .replace_all(text, |caps: &Captures| {
+                format!(
+                    r#"{}<a href="{}{}">{}</a>"#,
+                    &caps[1], web_url, &caps[2], &caps[2]
+                )
+            })
+            .into_owned(),
+        _ => re.replace_all(text, "$1$2").into_owned(),
+    });
+
+    (plain, html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_mentions() {
+        let (plain, html) = process_mentions(
+            MentionMode::Strip,
+            &Url::parse("https://github.com/").unwrap(),
+            "thanks @alice, cc root@server".to_string(),
+            "<b>thanks</b> @alice, cc root@server".to_string(),
+        );
+
+        assert_eq!(plain, "thanks alice, cc root@server");
+        assert_eq!(html, "<b>thanks</b> alice, cc root@server");
+    }
+
+    #[test]
+    fn test_link_mentions() {
+        let (plain, html) = process_mentions(
+            MentionMode::Link,
+            &Url::parse("https://github.com/").unwrap(),
+            "fix for @bob (@carol-d)".to_string(),
+            r#"<a href="https://github.com/prologin/site/pull/1">fix for @bob</a> (@carol-d)"#
+                .to_string(),
+        );
+
+        assert_eq!(plain, "fix for bob (carol-d)");
+        assert_eq!(
+            html,
+            r#"<a href="https://github.com/prologin/site/pull/1">fix for bob</a> (<a href="https://github.com/carol-d">carol-d</a>)"#
+        );
+    }
+}
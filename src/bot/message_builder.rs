@@ -1,15 +1,26 @@
-use std::fmt::Write;
+use std::fmt::{Display, Write};
 
-use matrix_sdk::ruma::events::room::message::MessageEventContent;
+use matrix_sdk::ruma::{events::room::message::MessageEventContent, UserId};
 use url::Url;
 
 const SEPARATOR: &str = "⋅";
 
+/// The characters that can start a Markdown span rendered by [`MessageBuilder::markdown`]
+const MARKDOWN_SYNTAX: &[char] = &['`', '*', '_', '['];
+
+/// Bytes reserved for the plain text body of a new message, enough for most announcements. The
+/// HTML body gets twice as much
+const INITIAL_CAPACITY: usize = 256;
+
+#[derive(Clone)]
 enum Style {
     Bold,
     Code,
     Span,
     Italic,
+    List,
+    ListItem,
+    Preformatted,
 }
 
 impl Style {
@@ -19,30 +30,104 @@ impl Style {
             Self::Code => "</code>",
             Self::Span => "</span>",
             Self::Italic => "</i>",
+            Self::List => "</ul>",
+            Self::ListItem => "</li>",
+            Self::Preformatted => "</pre>",
         }
     }
 }
 
-#[derive(Default)]
+/// The plain text left to write in [`MessageBuilder::markdown_shortened`]
+#[derive(Clone)]
+struct Budget {
+    remaining: usize,
+    exceeded: bool,
+}
+
+/// Returns the length of the start of `s` that can be written without exceeding the budget, and
+/// takes it out of the budget
+fn spend(budget: &mut Option<Budget>, s: &str) -> usize {
+    let budget = match budget {
+        Some(budget) if !budget.exceeded => budget,
+        Some(_) => return 0,
+        None => return s.len(),
+    };
+
+    match s.char_indices().nth(budget.remaining) {
+        Some((end, _)) => {
+            budget.remaining = 0;
+            budget.exceeded = true;
+            end
+        }
+        None => {
+            budget.remaining -= s.chars().count();
+            s.len()
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct MessageBuilder {
     pub(crate) html: String,
     pub(crate) plain: String,
     style_stack: Vec<Style>,
     pub(crate) url: Option<Url>,
+    budget: Option<Budget>,
 }
 
 impl MessageBuilder {
     pub fn new() -> Self {
-        Default::default()
+        Self::with_capacity(INITIAL_CAPACITY)
     }
 
-    pub fn build(mut self) -> MessageEventContent {
+    /// Creates a builder whose plain text body can hold `capacity` bytes without reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            html: String::with_capacity(2 * capacity),
+            plain: String::with_capacity(capacity),
+            style_stack: Vec::new(),
+            url: None,
+            budget: None,
+        }
+    }
+
+    /// Empties the builder to format another message, keeping its buffers
+    pub fn clear(&mut self) {
+        self.html.clear();
+        self.plain.clear();
+        self.style_stack.clear();
+        self.url = None;
+        self.budget = None;
+    }
+
+    pub fn build(self) -> MessageEventContent {
+        let (plain, html) = self.into_bodies();
+        MessageEventContent::text_html(plain, html)
+    }
+
+    /// Returns the final plain text and HTML bodies of the message
+    pub fn into_bodies(mut self) -> (String, String) {
         // Append main URL to plain text message, if we have one
         if let Some(url) = self.url {
             write!(self.plain, " {} {}", SEPARATOR, url).unwrap();
         }
 
-        MessageEventContent::text_html(self.plain, self.html)
+        (self.plain, self.html)
+    }
+
+    /// Same as [`MessageBuilder::build`], but copies the bodies to reuse the builder
+    pub fn build_copy(&mut self) -> MessageEventContent {
+        let (plain, html) = self.bodies();
+        MessageEventContent::text_html(plain, html)
+    }
+
+    /// Same as [`MessageBuilder::into_bodies`], but keeps the buffers to reuse the builder
+    pub fn bodies(&mut self) -> (&str, &str) {
+        if let Some(url) = self.url.take() {
+            write!(self.plain, " {} {}", SEPARATOR, url).unwrap();
+        }
+
+        (&self.plain, &self.html)
     }
 
     pub fn bold(&mut self) {
@@ -65,6 +150,26 @@ impl MessageBuilder {
         self.style_stack.push(Style::Italic);
     }
 
+    /// Starts a block keeping its whitespace in a monospace font, e.g. for tables. Its lines are
+    /// separated with `\n`, not [`MessageBuilder::line_break`]
+    pub fn preformatted(&mut self) {
+        self.html.push_str("<pre>");
+        self.style_stack.push(Style::Preformatted);
+    }
+
+    /// Starts a bulleted list, its items are added with [`MessageBuilder::list_item`]
+    pub fn list(&mut self) {
+        self.html.push_str("<ul>");
+        self.style_stack.push(Style::List);
+    }
+
+    /// Starts an item of the current list, on its own line in plain text
+    pub fn list_item(&mut self) {
+        self.plain.push_str("\n- ");
+        self.html.push_str("<li>");
+        self.style_stack.push(Style::ListItem);
+    }
+
     pub fn tag(&mut self, tag: &str, emoji: Option<char>) {
         self.bold();
         write!(self, "[").unwrap();
@@ -75,21 +180,128 @@ impl MessageBuilder {
         self.close_last();
     }
 
-    pub fn link(&mut self, text: &str, href: &Url) {
+    pub fn link(&mut self, text: impl Display, href: &Url) {
         // NOTE: we consider that the URL is bonus information, not needed in plain text mode to
         // understand the message
-        self.plain.push_str(text);
+        let start = self.plain.len();
+        write!(self.plain, "{}", text).unwrap();
+        let end = start + spend(&mut self.budget, &self.plain[start..]);
+        self.plain.truncate(end);
+        if end == start {
+            return;
+        }
+
+        // serialized URLs are percent-encoded, they can't close the attribute
+        self.html.push_str(r#"<a href=""#);
+        self.html.push_str(href.as_str());
+        self.html.push_str(r#"">"#);
+        escape_html(&mut self.html, &self.plain[start..]);
+        self.html.push_str("</a>");
+    }
 
-        write!(self.html, r#"<a href="{}">{}</a>"#, href, text).unwrap();
+    /// Writes the ID of a Matrix user, linked so that clients display a mention pill and notify them
+    pub fn mention(&mut self, user_id: &UserId) {
+        let url = Url::parse(&format!("https://matrix.to/#/{}", user_id))
+            .expect("user IDs are valid URL fragments");
+        self.link(user_id.as_str(), &url);
     }
 
     /// Format the provided text as an anchor tag, and set the URL to be appended at the end of the
     /// plain text message
-    pub fn main_link(&mut self, text: &str, href: &Url) {
+    pub fn main_link(&mut self, text: impl Display, href: &Url) {
         self.link(text, href);
         self.url = Some(href.clone());
     }
 
+    /// Renders a safe subset of Markdown (bold, italic, inline code and links), as found in GitHub
+    /// comments. The plain text body gets the text without the Markdown syntax. Unterminated spans
+    /// are written as is.
+    pub fn markdown(&mut self, text: &str) {
+        let mut rest = text;
+        let mut previous = None;
+
+        while let Some(c) = rest.chars().next() {
+            if let Some(budget) = &mut self.budget {
+                if budget.remaining == 0 {
+                    budget.exceeded = true;
+                }
+                if budget.exceeded {
+                    break;
+                }
+            }
+
+            // text without any Markdown syntax is written at once, not character by character
+            if !MARKDOWN_SYNTAX.contains(&c) {
+                let end = rest.find(MARKDOWN_SYNTAX).unwrap_or(rest.len());
+                let (text, remaining) = rest.split_at(end);
+                self.write_str(text).unwrap();
+                previous = text.chars().last();
+                rest = remaining;
+                continue;
+            }
+
+            if let Some((code, remaining)) = delimited(rest, "`", previous) {
+                self.code();
+                write!(self, "{}", code).unwrap();
+                self.close_last();
+                previous = Some('`');
+                rest = remaining;
+                continue;
+            }
+
+            let emphasis = [("**", true), ("__", true), ("*", false), ("_", false)]
+                .iter()
+                .find_map(|&(marker, bold)| {
+                    delimited(rest, marker, previous)
+                        .map(|(inner, remaining)| (inner, remaining, bold))
+                });
+            if let Some((inner, remaining, bold)) = emphasis {
+                if bold {
+                    self.bold();
+                } else {
+                    self.italic();
+                }
+                self.markdown(inner);
+                self.close_last();
+                previous = rest[..rest.len() - remaining.len()].chars().last();
+                rest = remaining;
+                continue;
+            }
+
+            if let Some((link_text, href, remaining)) = markdown_link(rest) {
+                self.link(link_text, &href);
+                previous = Some(')');
+                rest = remaining;
+                continue;
+            }
+
+            let (text, remaining) = rest.split_at(c.len_utf8());
+            self.write_str(text).unwrap();
+            previous = Some(c);
+            rest = remaining;
+        }
+    }
+
+    /// Same as [`MessageBuilder::markdown`], but only the first `max_length` characters of the
+    /// rendered text are kept, followed by an ellipsis. Spans cut in the middle are closed, unlike
+    /// when the Markdown source is shortened.
+    pub fn markdown_shortened(&mut self, text: &str, max_length: usize) {
+        self.budget = Some(Budget {
+            remaining: max_length,
+            exceeded: false,
+        });
+        self.markdown(text);
+        if self.budget.take().map_or(false, |budget| budget.exceeded) {
+            self.write_str("…").unwrap();
+        }
+    }
+
+    /// Starts a new line, e.g. between the items of a list
+    pub fn line_break(&mut self) {
+        self.plain.push('\n');
+        self.html.push_str("<br>");
+    }
+
     /// Panics if called with no style in the stack
     pub fn close_last(&mut self) {
         let style = self.style_stack.pop().expect("cannot be empty");
@@ -103,31 +315,110 @@ impl MessageBuilder {
     }
 }
 
-impl std::fmt::Write for MessageBuilder {
-    fn write_str(&mut self, s: &str) -> std::fmt::Result {
-        self.plain.push_str(s);
+impl Default for MessageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let mut last = 0;
-        for (i, c) in s.char_indices() {
-            // NOTE: escape characters that have a special meaning in HTML. Shamelessly adapted from
-            // rustdoc/html/escape.rs
-            let escaped = match c {
-                '>' => "&gt;",
-                '<' => "&lt;",
-                '&' => "&amp;",
-                '\'' => "&#39;",
-                '"' => "&quot;",
-                _ => continue,
-            };
-
-            self.html.push_str(&s[last..i]);
-            self.html.push_str(escaped);
-            last = i + 1;
-        }
+/// Builders kept between messages, so that the modes formatting many messages at once reuse their
+/// buffers instead of allocating new ones for every event. The messages must be read with
+/// [`MessageBuilder::bodies`] or [`MessageBuilder::build_copy`], [`MessageBuilder::into_bodies`]
+/// gives the buffers away.
+#[derive(Default)]
+pub struct MessageBuilderPool {
+    builders: Vec<MessageBuilder>,
+}
 
-        if last < s.len() {
-            self.html.push_str(&s[last..]);
+impl MessageBuilderPool {
+    pub fn take(&mut self) -> MessageBuilder {
+        self.builders.pop().unwrap_or_default()
+    }
+
+    pub fn give_back(&mut self, mut builder: MessageBuilder) {
+        builder.clear();
+        self.builders.push(builder);
+    }
+}
+
+/// Splits `text` into the content of the span opened by `marker` at its start and the text after the
+/// span, if it is terminated. Like on GitHub, `_` doesn't open or close spans inside words.
+fn delimited<'a>(
+    text: &'a str,
+    marker: &str,
+    previous: Option<char>,
+) -> Option<(&'a str, &'a str)> {
+    let inner = text.strip_prefix(marker)?;
+    let intraword = marker.starts_with('_');
+    if intraword && previous.map_or(false, char::is_alphanumeric) {
+        return None;
+    }
+    if inner.starts_with(char::is_whitespace) || inner.starts_with(marker) {
+        return None;
+    }
+
+    let mut search = 0;
+    while let Some(end) = inner[search..].find(marker) {
+        let end = search + end;
+        let after = &inner[end + marker.len()..];
+        let cant_close = inner[..end].ends_with(char::is_whitespace)
+            || after.starts_with(marker)
+            || (intraword && after.starts_with(char::is_alphanumeric));
+        if end > 0 && !cant_close {
+            return Some((&inner[..end], after));
         }
+        search = end + marker.len();
+    }
+
+    None
+}
+
+/// Parses a `[text](url)` link at the start of `text`, only HTTP(S) links are accepted
+fn markdown_link(text: &str) -> Option<(&str, Url, &str)> {
+    let inner = text.strip_prefix('[')?;
+    let (link_text, after) = inner.split_once("](")?;
+    let (href, remaining) = after.split_once(')')?;
+
+    let href = Url::parse(href).ok()?;
+    if link_text.is_empty() || !matches!(href.scheme(), "http" | "https") {
+        return None;
+    }
+
+    Some((link_text, href, remaining))
+}
+
+/// Appends `s` to `html`, escaping the characters that have a special meaning in HTML
+fn escape_html(html: &mut String, s: &str) {
+    html.reserve(s.len());
+    let mut last = 0;
+    // the escaped characters are ASCII, they can't be part of a multi-byte character
+    for (i, b) in s.bytes().enumerate() {
+        // NOTE: escape characters that have a special meaning in HTML. Shamelessly adapted from
+        // rustdoc/html/escape.rs
+        let escaped = match b {
+            b'>' => "&gt;",
+            b'<' => "&lt;",
+            b'&' => "&amp;",
+            b'\'' => "&#39;",
+            b'"' => "&quot;",
+            _ => continue,
+        };
+
+        html.push_str(&s[last..i]);
+        html.push_str(escaped);
+        last = i + 1;
+    }
+
+    if last < s.len() {
+        html.push_str(&s[last..]);
+    }
+}
+
+impl std::fmt::Write for MessageBuilder {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        let s = &s[..spend(&mut self.budget, s)];
+        self.plain.push_str(s);
+        escape_html(&mut self.html, s);
 
         Ok(())
     }
@@ -160,6 +451,104 @@ mod tests {
         assert_eq!(msgbld.plain, "These should be escaped: < > & \" '");
     }
 
+    #[test]
+    fn test_list() {
+        let mut msgbld = MessageBuilder::new();
+
+        write!(&mut msgbld, "Pushed:").unwrap();
+        msgbld.list();
+        for item in ["first", "<second>"] {
+            msgbld.list_item();
+            write!(&mut msgbld, "{}", item).unwrap();
+            msgbld.close_last();
+        }
+        msgbld.close_last();
+
+        assert_eq!(msgbld.plain, "Pushed:\n- first\n- <second>");
+        assert_eq!(
+            msgbld.html,
+            "Pushed:<ul><li>first</li><li>&lt;second&gt;</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        let mut msgbld = MessageBuilder::new();
+
+        msgbld.markdown(
+            "**Fixed** the `<main>` loop in some_file_name, see [docs](https://prologin.org) *or* _not_ **",
+        );
+
+        assert_eq!(
+            msgbld.plain,
+            "Fixed the <main> loop in some_file_name, see docs or not **"
+        );
+        assert_eq!(
+            msgbld.html,
+            r#"<b>Fixed</b> the <code>&lt;main&gt;</code> loop in some_file_name, see <a href="https://prologin.org/">docs</a> <i>or</i> <i>not</i> **"#
+        );
+    }
+
+    #[test]
+    fn test_markdown_shortened() {
+        let mut msgbld = MessageBuilder::new();
+        msgbld.markdown_shortened("see **the [long docs](https://prologin.org)** here", 12);
+        assert_eq!(msgbld.plain, "see the long…");
+        assert_eq!(
+            msgbld.html,
+            r#"see <b>the <a href="https://prologin.org/">long</a></b>…"#
+        );
+
+        let mut msgbld = MessageBuilder::new();
+        msgbld.markdown_shortened("`fits` exactly", 11);
+        write!(msgbld, " after").unwrap();
+        assert_eq!(msgbld.plain, "fits exactl… after");
+
+        let mut msgbld = MessageBuilder::new();
+        msgbld.markdown_shortened("*short*", 5);
+        assert_eq!(msgbld.html, "<i>short</i>");
+    }
+
+    #[test]
+    fn test_markdown_unsafe_link() {
+        let mut msgbld = MessageBuilder::new();
+
+        msgbld.markdown("[click](javascript:alert(1))");
+
+        assert_eq!(msgbld.plain, "[click](javascript:alert(1))");
+        assert!(!msgbld.html.contains("<a"));
+    }
+
+    #[test]
+    fn test_link_escaped() {
+        let mut msgbld = MessageBuilder::new();
+
+        let url = Url::parse("https://github.com/prologin/site/pull/1?q=\"a\"").unwrap();
+        msgbld.link(format_args!("PR #{}: <script>", 1), &url);
+
+        assert_eq!(msgbld.plain, "PR #1: <script>");
+        assert_eq!(
+            msgbld.html,
+            r#"<a href="https://github.com/prologin/site/pull/1?q=%22a%22">PR #1: &lt;script&gt;</a>"#
+        );
+    }
+
+    #[test]
+    fn test_pool() {
+        let mut pool = MessageBuilderPool::default();
+
+        let mut message = pool.take();
+        message.bold();
+        write!(message, "{}", "a".repeat(1000)).unwrap();
+        let capacity = message.plain.capacity();
+        pool.give_back(message);
+
+        let mut message = pool.take();
+        assert_eq!(message.bodies(), ("", ""));
+        assert!(message.style_stack.is_empty());
+        assert_eq!(message.plain.capacity(), capacity);
+    }
+
     #[test]
     fn test_append_main_url() {
         let mut msgbld = MessageBuilder::new();
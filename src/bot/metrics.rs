@@ -0,0 +1,257 @@
+//! Daily event counters and the past runs of the bot, kept in the state so that `!history` and
+//! `!uptime` go further back than the current run, e.g. over a whole contest season.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::bot::{
+    activity::{ago, Activity, SOURCES},
+    message_builder::MessageBuilder,
+    state::SharedState,
+};
+
+/// How long the daily counters are kept, a bit more than a year to compare contest seasons
+const RETENTION_DAYS: i64 = 400;
+
+/// Number of runs remembered for `!uptime`
+const MAX_RUNS: usize = 20;
+
+/// Number of past runs listed by `!uptime`
+const LISTED_RUNS: usize = 5;
+
+/// How often the metrics are written to the state, it's also the precision of the downtimes
+const PERSIST_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Event counts of a day, in UTC
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DailyMetrics {
+    /// Keys are the event sources, e.g. `github`
+    #[serde(default)]
+    pub events: BTreeMap<String, u64>,
+    /// Events that couldn't be handled
+    #[serde(default)]
+    pub errors: u64,
+}
+
+/// A period during which the bot was running
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Run {
+    started: DateTime<Utc>,
+    /// The last time the bot was known to be running, updated when the metrics are persisted
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Metrics {
+    /// Keys are days formatted as `YYYY-MM-DD`
+    #[serde(default)]
+    days: BTreeMap<String, DailyMetrics>,
+    /// The oldest run first, the last one is the current run
+    #[serde(default)]
+    runs: VecDeque<Run>,
+}
+
+fn day_key(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+impl Metrics {
+    /// Records the start of a new run
+    pub fn start(&mut self, now: DateTime<Utc>) {
+        self.runs.push_back(Run {
+            started: now,
+            last_seen: now,
+        });
+        while self.runs.len() > MAX_RUNS {
+            self.runs.pop_front();
+        }
+    }
+
+    /// Records that the current run is still going, and forgets the days past the retention
+    pub fn seen(&mut self, now: DateTime<Utc>) {
+        if let Some(run) = self.runs.back_mut() {
+            run.last_seen = now;
+        }
+        let oldest = day_key((now - Duration::days(RETENTION_DAYS)).date().naive_utc());
+        self.days = self.days.split_off(&oldest);
+    }
+
+    fn day(&mut self, now: DateTime<Utc>) -> &mut DailyMetrics {
+        self.days
+            .entry(day_key(now.date().naive_utc()))
+            .or_default()
+    }
+
+    pub fn record_event(&mut self, source: &str, now: DateTime<Utc>) {
+        *self.day(now).events.entry(source.to_string()).or_default() += 1;
+    }
+
+    pub fn record_error(&mut self, now: DateTime<Utc>) {
+        self.day(now).errors += 1;
+    }
+
+    /// The counters of every day with events, the oldest first
+    pub fn days(&self) -> impl Iterator<Item = (&str, &DailyMetrics)> {
+        self.days
+            .iter()
+            .map(|(day, metrics)| (day.as_str(), metrics))
+    }
+
+    /// `!uptime`: how long the bot has been running, and its last restarts
+    pub fn uptime_message(&self, now: DateTime<Utc>) -> MessageBuilder {
+        let mut message = MessageBuilder::new();
+        let current = match self.runs.back() {
+            Some(run) => run,
+            None => {
+                write!(message, "no run recorded yet").unwrap();
+                return message;
+            }
+        };
+        write!(
+            message,
+            "up for {}, since {}",
+            ago(now - current.started),
+            current.started.format("%Y-%m-%d %H:%M")
+        )
+        .unwrap();
+
+        if self.runs.len() > 1 {
+            message.list();
+            // each run is followed by the downtime until the next one, the newest first
+            let runs: Vec<&Run> = self.runs.iter().collect();
+            for pair in runs.windows(2).rev().take(LISTED_RUNS) {
+                let (run, next) = (pair[0], pair[1]);
+                message.list_item();
+                write!(
+                    message,
+                    "ran for {} from {}, then down for {}",
+                    ago(run.last_seen - run.started),
+                    run.started.format("%Y-%m-%d %H:%M"),
+                    ago(next.started - run.last_seen)
+                )
+                .unwrap();
+                message.close_last();
+            }
+            message.close_last();
+        }
+
+        message
+    }
+
+    /// `!history [period]`: the events received and the errors of each day of the period
+    pub fn history_message(&self, period: Duration, now: DateTime<Utc>) -> MessageBuilder {
+        let today = now.date().naive_utc();
+        let days = period.num_days().clamp(1, RETENTION_DAYS);
+
+        let mut header = vec!["day".to_string()];
+        header.extend(SOURCES.iter().map(|source| source.to_string()));
+        header.push("errors".to_string());
+        let mut rows = vec![header];
+        let mut totals = vec![0; SOURCES.len() + 1];
+        for offset in (0..days).rev() {
+            let day = day_key(today - Duration::days(offset));
+            let metrics = self.days.get(&day);
+            let mut counts: Vec<u64> = SOURCES
+                .iter()
+                .map(|source| {
+                    metrics
+                        .and_then(|metrics| metrics.events.get(*source))
+                        .copied()
+                        .unwrap_or(0)
+                })
+                .collect();
+            counts.push(metrics.map_or(0, |metrics| metrics.errors));
+
+            for (total, count) in totals.iter_mut().zip(&counts) {
+                *total += count;
+            }
+            let mut row = vec![day];
+            row.extend(counts.iter().map(u64::to_string));
+            rows.push(row);
+        }
+        let mut total_row = vec!["total".to_string()];
+        total_row.extend(totals.iter().map(u64::to_string));
+        rows.push(total_row);
+
+        let widths: Vec<usize> = (0..rows[0].len())
+            .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+            .collect();
+        let mut message = MessageBuilder::new();
+        message.preformatted();
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(message).unwrap();
+            }
+            // the day is left aligned, the counts right aligned
+            write!(message, "{:width$}", row[0], width = widths[0]).unwrap();
+            for (value, width) in row.iter().zip(&widths).skip(1) {
+                write!(message, "  {:>width$}", value, width = width).unwrap();
+            }
+        }
+        message.close_last();
+
+        message
+    }
+}
+
+/// Writes the metrics to the state regularly, the counters since the last write are lost on a
+/// crash
+pub async fn persist_metrics(activity: Arc<Activity>, state: SharedState) {
+    let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let metrics = activity.checkpoint(Utc::now());
+        let mut store = state.lock().await;
+        store.state.metrics = metrics;
+        if let Err(e) = store.save() {
+            warn!("couldn't save metrics: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let start = Utc.ymd(2021, 10, 1).and_hms(9, 0, 0);
+        let mut metrics = Metrics::default();
+        metrics.start(start);
+        metrics.record_event("github", start);
+        metrics.seen(start + Duration::days(2));
+        metrics.start(start + Duration::days(2) + Duration::minutes(5));
+        metrics.record_event("github", start + Duration::days(2));
+        metrics.record_event("mail", start + Duration::days(2));
+        metrics.record_error(start + Duration::days(2));
+
+        // the metrics survive a restart
+        let metrics: Metrics =
+            serde_yaml::from_str(&serde_yaml::to_string(&metrics).unwrap()).unwrap();
+
+        let now = start + Duration::days(2) + Duration::hours(3);
+        assert_eq!(
+            metrics.uptime_message(now).plain,
+            "up for 2h, since 2021-10-03 09:05\n\
+             - ran for 2d from 2021-10-01 09:00, then down for 5m"
+        );
+        assert_eq!(
+            metrics.history_message(Duration::days(3), now).plain,
+            "day         github  prolosite  mail  errors\n\
+             2021-10-01       1          0     0       0\n\
+             2021-10-02       0          0     0       0\n\
+             2021-10-03       1          0     1       1\n\
+             total            2          0     1       1"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use matrix_sdk::ruma::RoomId;
+
+use crate::{bot::Severity, config::ProloloConfig, push::PushNotifier, xmpp::XmppGateway};
+
+/// Secondary destinations receiving copies of the messages sent to Matrix
+pub struct Mirrors {
+    xmpp: Option<XmppGateway>,
+    push: Option<PushNotifier>,
+    /// The names and IDs of the Matrix rooms copying each room
+    rooms: HashMap<RoomId, Vec<(String, RoomId)>>,
+}
+
+impl Mirrors {
+    pub fn from_config(config: &ProloloConfig) -> anyhow::Result<Self> {
+        let xmpp = match config.xmpp.clone() {
+            Some(xmpp) => {
+                Some(XmppGateway::start(config, xmpp).context("couldn't start XMPP gateway")?)
+            }
+            None => None,
+        };
+        let push = match config.push.clone() {
+            Some(push) => Some(
+                PushNotifier::new(push, config.timeouts.http())
+                    .context("couldn't build push notifier")?,
+            ),
+            None => None,
+        };
+
+        let mut rooms = HashMap::new();
+        for (source, targets) in &config.mirrors {
+            let source = config
+                .room_by_name(source)
+                .context("invalid mirrored room")?;
+            let targets = targets
+                .iter()
+                .map(|name| {
+                    let room_id = config.room_by_name(name).context("invalid mirror room")?;
+                    Ok((name.clone(), room_id.clone()))
+                })
+                .collect::<anyhow::Result<_>>()?;
+            rooms.insert(source.clone(), targets);
+        }
+
+        Ok(Self { xmpp, push, rooms })
+    }
+
+    /// The Matrix rooms receiving a copy of the messages sent to `room_id`, with their names
+    pub fn rooms(&self, room_id: &RoomId) -> &[(String, RoomId)] {
+        self.rooms.get(room_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Forwards a message that was just sent to a Matrix room.
+    pub fn mirror(&self, room_id: &RoomId, severity: Severity, plain: &str) {
+        if let Some(xmpp) = &self.xmpp {
+            xmpp.mirror(room_id, plain);
+        }
+
+        if let Some(push) = &self.push {
+            if severity >= push.min_severity() {
+                push.notify(plain);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_mirrors() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            r#"
+matrix_username: prololo
+matrix_password: secret
+matrix_homeserver: https://matrix.prologin.org
+matrix_state_dir: state
+github_secret: secret
+prolosite_secret: secret
+destinations: []
+matrix_rooms:
+  dev:
+    id: "!dev:prologin.org"
+  alumni:
+    id: "!alumni:prologin.org"
+mirrors:
+  dev: [alumni]
+"#,
+        )
+        .unwrap();
+        let mirrors = Mirrors::from_config(&config).unwrap();
+
+        let dev = config.room_by_name("dev").unwrap();
+        let alumni = config.room_by_name("alumni").unwrap();
+        assert_eq!(
+            mirrors.rooms(dev),
+            &[("alumni".to_string(), alumni.clone())]
+        );
+        assert!(mirrors.rooms(alumni).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Pings added in front of the messages according to their severity, e.g. `@room` for security
+//! alerts. `@room` is used at most once per cooldown in each room, so that a burst of alerts
+//! doesn't notify everyone again and again.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use matrix_sdk::ruma::RoomId;
+use tracing::debug;
+
+use crate::{
+    bot::{message_builder::MessageBuilder, Severity},
+    config::{PingMode, PingTarget, ProloloConfig},
+};
+
+pub struct Pings {
+    defaults: BTreeMap<Severity, PingTarget>,
+    rooms: HashMap<RoomId, BTreeMap<Severity, PingTarget>>,
+    cooldown: Duration,
+    /// When `@room` was last used in each room
+    last_room_pings: Mutex<HashMap<RoomId, Instant>>,
+}
+
+impl Pings {
+    pub fn from_config(config: &ProloloConfig) -> Self {
+        let rooms = config
+            .matrix_rooms
+            .values()
+            .filter(|room| !room.pings.is_empty())
+            .map(|room| (room.id.clone(), room.pings.clone()))
+            .collect();
+
+        Self {
+            defaults: config.pings.severities.clone(),
+            rooms,
+            cooldown: Duration::from_secs(config.pings.room_cooldown_minutes * 60),
+            last_room_pings: Default::default(),
+        }
+    }
+
+    fn target(&self, room_id: &RoomId, severity: Severity) -> Option<&PingTarget> {
+        self.rooms
+            .get(room_id)
+            .and_then(|pings| pings.get(&severity))
+            .or_else(|| self.defaults.get(&severity))
+    }
+
+    /// Adds the ping of a message sent to `room_id` in front of both of its bodies
+    pub fn apply(
+        &self,
+        room_id: &RoomId,
+        severity: Severity,
+        plain: String,
+        html: String,
+        now: Instant,
+    ) -> (String, String) {
+        let mut ping = MessageBuilder::new();
+        match self.target(room_id, severity) {
+            None | Some(PingTarget::Mode(PingMode::None)) => return (plain, html),
+            Some(PingTarget::Mode(PingMode::Room)) => {
+                let mut last_room_pings = self.last_room_pings.lock().unwrap();
+                match last_room_pings.get(room_id) {
+                    Some(last) if now.duration_since(*last) < self.cooldown => {
+                        debug!("not pinging room {} again so soon", room_id);
+                        return (plain, html);
+                    }
+                    _ => last_room_pings.insert(room_id.clone(), now),
+                };
+                write!(ping, "@room").unwrap();
+            }
+            Some(PingTarget::Users(users)) => {
+                for (i, user) in users.iter().enumerate() {
+                    if i > 0 {
+                        write!(ping, ", ").unwrap();
+                    }
+                    ping.mention(user);
+                }
+            }
+        }
+        write!(ping, ": ").unwrap();
+
+        let (ping_plain, ping_html) = ping.into_bodies();
+        (ping_plain + &plain, ping_html + &html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    #[test]
+    fn test_pings() {
+        let alerts = RoomId::try_from("!alerts:prologin.org").unwrap();
+        let dev = RoomId::try_from("!dev:prologin.org").unwrap();
+        let mut dev_pings = BTreeMap::new();
+        dev_pings.insert(
+            Severity::High,
+            serde_yaml::from_str("['@alice:prologin.org']").unwrap(),
+        );
+        let mut defaults = BTreeMap::new();
+        defaults.insert(Severity::High, serde_yaml::from_str("room").unwrap());
+        let pings = Pings {
+            defaults,
+            rooms: vec![(dev.clone(), dev_pings)].into_iter().collect(),
+            cooldown: Duration::from_secs(30 * 60),
+            last_room_pings: Default::default(),
+        };
+        let apply = |room_id: &RoomId, severity: Severity, now: Instant| {
+            pings.apply(
+                room_id,
+                severity,
+                "[site] crashed".to_string(),
+                "<b>[site]</b> crashed".to_string(),
+                now,
+            )
+        };
+
+        let start = Instant::now();
+        assert_eq!(apply(&alerts, Severity::Normal, start).0, "[site] crashed");
+        assert_eq!(
+            apply(&alerts, Severity::High, start).0,
+            "@room: [site] crashed"
+        );
+        // @room isn't used again during the cooldown
+        assert_eq!(
+            apply(&alerts, Severity::High, start + Duration::from_secs(60)).0,
+            "[site] crashed"
+        );
+        assert_eq!(
+            apply(
+                &alerts,
+                Severity::High,
+                start + Duration::from_secs(31 * 60)
+            )
+            .0,
+            "@room: [site] crashed"
+        );
+
+        assert_eq!(
+            apply(&dev, Severity::High, start),
+            (
+                "@alice:prologin.org: [site] crashed".to_string(),
+                r#"<a href="https://matrix.to/#/@alice:prologin.org">@alice:prologin.org</a>: <b>[site]</b> crashed"#.to_string()
+            )
+        );
+    }
+}
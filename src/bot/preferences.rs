@@ -0,0 +1,502 @@
+//! Preferences of the people notified by the bot, set with `!prefs` in a direct message with the
+//! bot. They decide whether review reminders are also sent to them directly, right away or in a
+//! daily digest sent in their timezone and language.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+use matrix_sdk::{
+    room::Joined,
+    ruma::{
+        events::{room::message::MessageEventContent, AnyMessageEventContent},
+        RoomId, UserId,
+    },
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    assets,
+    bot::{
+        args::Args,
+        emoji,
+        message_builder::{MessageBuilder, MessageBuilderPool},
+        state::SharedState,
+    },
+    config::ProloloConfig,
+};
+
+/// Hour of the day, in the timezone of each user, of the digests
+const DIGEST_HOUR: u32 = 9;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    /// Notifications are sent as soon as possible
+    Realtime,
+    /// Notifications are gathered in a daily digest, and users aren't mentioned in the rooms
+    Digest,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Self::Realtime
+    }
+}
+
+/// The languages of the digests
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    En,
+    Fr,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::En
+    }
+}
+
+impl Locale {
+    fn code(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Fr => "fr",
+        }
+    }
+}
+
+/// The texts of the digests in a language, from `locales/<code>.yaml` in the assets
+#[derive(Debug, Deserialize)]
+struct DigestTexts {
+    digest_tag: String,
+    digest_one: String,
+    /// `{count}` is replaced
+    digest_many: String,
+}
+
+impl DigestTexts {
+    fn load(assets_dir: Option<&Path>, locale: Locale) -> anyhow::Result<Self> {
+        let path = format!("locales/{}.yaml", locale.code());
+        serde_yaml::from_str(&assets::load(assets_dir, &path)?)
+            .with_context(|| format!("couldn't parse {}", path))
+    }
+}
+
+/// Something to tell a user in their next digest
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DigestItem {
+    pub text: String,
+    pub url: Url,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Preferences {
+    /// The direct message room with the user, where they last set their preferences
+    pub room: Option<RoomId>,
+    /// Whether notifications are also sent in the direct message room
+    #[serde(default)]
+    pub direct_messages: bool,
+    /// Offset of the user's timezone from UTC, in minutes
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    #[serde(default)]
+    pub locale: Locale,
+    #[serde(default)]
+    pub delivery: Delivery,
+    /// Notifications waiting for the next digest
+    #[serde(default)]
+    pub pending: Vec<DigestItem>,
+    /// The day of the latest digest, in the user's timezone
+    pub last_digest: Option<NaiveDate>,
+}
+
+impl Preferences {
+    /// Sets a preference from the `!prefs` flag of the same name
+    fn set(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        match name {
+            "dm" => {
+                self.direct_messages = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => bail!("invalid `dm` `{}`, expected on or off", value),
+                }
+            }
+            "timezone" => self.utc_offset_minutes = parse_utc_offset(value)?,
+            "locale" => {
+                self.locale = match value {
+                    "en" => Locale::En,
+                    "fr" => Locale::Fr,
+                    _ => bail!("invalid `locale` `{}`, expected en or fr", value),
+                }
+            }
+            "delivery" => {
+                self.delivery = match value {
+                    "realtime" => Delivery::Realtime,
+                    "digest" => Delivery::Digest,
+                    _ => bail!(
+                        "invalid `delivery` `{}`, expected realtime or digest",
+                        value
+                    ),
+                }
+            }
+            _ => unreachable!("only known flags are set"),
+        }
+
+        Ok(())
+    }
+
+    /// Whether notifications go to the digest, which is only sent with direct messages on
+    fn digest(&self) -> bool {
+        self.direct_messages && self.delivery == Delivery::Digest
+    }
+
+    fn local_time(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + Duration::minutes(self.utc_offset_minutes as i64)
+    }
+
+    /// Whether the digest is due at `now`: it's past [`DIGEST_HOUR`] for the user, and they
+    /// didn't get one today yet
+    fn digest_due(&self, now: DateTime<Utc>) -> bool {
+        let local = self.local_time(now);
+        self.digest()
+            && !self.pending.is_empty()
+            && local.hour() >= DIGEST_HOUR
+            && self.last_digest != Some(local.date().naive_utc())
+    }
+}
+
+/// The preferences of the users who set some
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UserPreferences {
+    users: BTreeMap<UserId, Preferences>,
+}
+
+impl UserPreferences {
+    pub fn get(&self, user_id: &UserId) -> Option<&Preferences> {
+        self.users.get(user_id)
+    }
+
+    /// Whether the user can be mentioned in the rooms, which notifies them right away
+    pub fn realtime(&self, user_id: &UserId) -> bool {
+        self.get(user_id).map_or(true, |prefs| !prefs.digest())
+    }
+
+    /// Keeps a notification for the next digest of the user
+    pub fn queue(&mut self, user_id: &UserId, item: DigestItem) {
+        if let Some(prefs) = self.users.get_mut(user_id) {
+            prefs.pending.push(item);
+        }
+    }
+}
+
+/// Parses an offset from UTC such as `+02:00` or `-05:30`, `UTC` being no offset. Returns the
+/// offset in minutes.
+fn parse_utc_offset(offset: &str) -> anyhow::Result<i32> {
+    let invalid = || {
+        anyhow!(
+            "invalid `timezone` `{}`, expected an offset like +02:00 or UTC",
+            offset
+        )
+    };
+    if offset == "UTC" {
+        return Ok(0);
+    }
+
+    let (sign, rest) = match offset.chars().next() {
+        Some('+') => (1, &offset[1..]),
+        Some('-') => (-1, &offset[1..]),
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes) = rest.split_once(':').ok_or_else(invalid)?;
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(invalid());
+    }
+
+    Ok(sign * (hours * 60 + minutes))
+}
+
+fn format_utc_offset(minutes: i32) -> String {
+    if minutes == 0 {
+        return "UTC".to_string();
+    }
+    let sign = if minutes < 0 { '-' } else { '+' };
+    format!(
+        "{}{:02}:{:02}",
+        sign,
+        minutes.abs() / 60,
+        minutes.abs() % 60
+    )
+}
+
+fn preferences_message(prefs: &Preferences) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    message.tag("prefs", Some(emoji::WRENCH));
+    write!(message, " your preferences:").unwrap();
+    message.list();
+    message.list_item();
+    let dm = if prefs.direct_messages { "on" } else { "off" };
+    write!(message, "dm: {}", dm).unwrap();
+    message.close_last();
+    message.list_item();
+    write!(
+        message,
+        "timezone: {}",
+        format_utc_offset(prefs.utc_offset_minutes)
+    )
+    .unwrap();
+    message.close_last();
+    message.list_item();
+    write!(message, "locale: {}", prefs.locale.code()).unwrap();
+    message.close_last();
+    message.list_item();
+    let delivery = match prefs.delivery {
+        Delivery::Realtime => "realtime",
+        Delivery::Digest => "digest",
+    };
+    write!(message, "delivery: {}", delivery).unwrap();
+    message.close_styles();
+
+    message
+}
+
+/// `!prefs [dm=on|off] [timezone=+02:00] [locale=en|fr] [delivery=realtime|digest]`: shows the
+/// preferences of the sender, after changing the given ones. Only answered in direct messages, the
+/// room becomes the one where the bot sends the notifications of the sender.
+pub async fn prefs_command(
+    mut args: Args,
+    sender: &UserId,
+    room: &Joined,
+    config: &ProloloConfig,
+    state: &SharedState,
+) -> anyhow::Result<MessageBuilder> {
+    let room_id = room.room_id();
+    let members: Vec<UserId> = room
+        .active_members()
+        .await?
+        .iter()
+        .map(|member| member.user_id().clone())
+        .collect();
+    if config.matrix_rooms.values().any(|room| &room.id == room_id)
+        || !is_direct_message(&members, room.own_user_id(), sender)
+    {
+        bail!("preferences can only be set in a direct message with the bot");
+    }
+
+    let mut changes = Vec::new();
+    for name in ["dm", "timezone", "locale", "delivery"] {
+        if let Some(value) = args.flag(name) {
+            changes.push((name, value));
+        }
+    }
+    args.expect_positional(0, 0)?;
+    args.finish()?;
+
+    let mut store = state.lock().await;
+    let mut prefs = store
+        .state
+        .preferences
+        .users
+        .get(sender)
+        .cloned()
+        .unwrap_or_default();
+    for (name, value) in changes {
+        prefs.set(name, &value)?;
+    }
+    prefs.room = Some(room_id.clone());
+
+    let message = preferences_message(&prefs);
+    store.state.preferences.users.insert(sender.clone(), prefs);
+    store.save()?;
+
+    Ok(message)
+}
+
+/// Whether the only members of a room (joined or invited) are the bot and the sender, so that
+/// nobody else gets the notifications of the sender
+fn is_direct_message(members: &[UserId], own_user_id: &UserId, sender: &UserId) -> bool {
+    members.len() == 2 && members.contains(own_user_id) && members.contains(sender)
+}
+
+fn digest_message(message: &mut MessageBuilder, prefs: &Preferences, texts: &DigestTexts) {
+    let count = prefs.pending.len();
+    message.tag(&texts.digest_tag, Some(emoji::ALARM_CLOCK));
+    if count == 1 {
+        write!(message, " {}", texts.digest_one).unwrap();
+    } else {
+        let text = texts.digest_many.replace("{count}", &count.to_string());
+        write!(message, " {}", text).unwrap();
+    }
+    message.list();
+    for item in &prefs.pending {
+        message.list_item();
+        message.link(&item.text, &item.url);
+        message.close_last();
+    }
+    message.close_styles();
+}
+
+/// Sends the digests due at `now`, checked by the review reminders.
+pub async fn send_digests(
+    client: &Client,
+    state: &SharedState,
+    assets_dir: Option<&Path>,
+    now: DateTime<Utc>,
+) {
+    let due: Vec<(UserId, Preferences)> = state
+        .lock()
+        .await
+        .state
+        .preferences
+        .users
+        .iter()
+        .filter(|(_, prefs)| prefs.direct_messages && prefs.digest_due(now))
+        .map(|(user_id, prefs)| (user_id.clone(), prefs.clone()))
+        .collect();
+
+    let mut pool = MessageBuilderPool::default();
+    for (user_id, prefs) in due {
+        debug!(
+            "sending digest of {} items to {}",
+            prefs.pending.len(),
+            user_id
+        );
+        let texts = match DigestTexts::load(assets_dir, prefs.locale) {
+            Ok(texts) => texts,
+            Err(e) => {
+                warn!("couldn't load the texts of the digests: {:#}", e);
+                continue;
+            }
+        };
+        let mut message = pool.take();
+        digest_message(&mut message, &prefs, &texts);
+        let result = send_direct_message(client, &prefs, message.build_copy()).await;
+        pool.give_back(message);
+        if let Err(e) = result {
+            warn!("couldn't send digest to {}: {:#}", user_id, e);
+            continue;
+        }
+
+        let mut store = state.lock().await;
+        if let Some(stored) = store.state.preferences.users.get_mut(&user_id) {
+            // notifications queued while the digest was sent wait for the next one
+            stored.pending.drain(..prefs.pending.len());
+            stored.last_digest = Some(prefs.local_time(now).date().naive_utc());
+        }
+        if let Err(e) = store.save() {
+            warn!("couldn't save bot state: {:#}", e);
+        }
+    }
+}
+
+/// Sends a message in the direct message room of a user
+pub async fn send_direct_message(
+    client: &Client,
+    prefs: &Preferences,
+    message: MessageEventContent,
+) -> anyhow::Result<()> {
+    let room_id = prefs
+        .room
+        .as_ref()
+        .ok_or_else(|| anyhow!("no direct message room"))?;
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+    room.send(AnyMessageEventContent::RoomMessage(message), None)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_offset() {
+        assert_eq!(parse_utc_offset("+02:00").unwrap(), 120);
+        assert_eq!(parse_utc_offset("-05:30").unwrap(), -330);
+        assert_eq!(parse_utc_offset("UTC").unwrap(), 0);
+        assert!(parse_utc_offset("02:00").is_err());
+        assert!(parse_utc_offset("+2").is_err());
+
+        assert_eq!(format_utc_offset(-330), "-05:30");
+        assert_eq!(format_utc_offset(0), "UTC");
+    }
+
+    #[test]
+    fn test_is_direct_message() {
+        let user = |id: &str| UserId::try_from(id).unwrap();
+        let bot = user("@prololo:prologin.org");
+        let alice = user("@alice:prologin.org");
+        let bob = user("@bob:prologin.org");
+
+        assert!(is_direct_message(
+            &[bot.clone(), alice.clone()],
+            &bot,
+            &alice
+        ));
+        assert!(!is_direct_message(
+            &[bot.clone(), alice.clone(), bob.clone()],
+            &bot,
+            &alice
+        ));
+        assert!(!is_direct_message(
+            &[bot.clone(), bob.clone()],
+            &bot,
+            &alice
+        ));
+        assert!(!is_direct_message(&[alice.clone(), bob], &bot, &alice));
+    }
+
+    #[test]
+    fn test_digest() {
+        let mut prefs = Preferences::default();
+        prefs.set("delivery", "digest").unwrap();
+        assert!(!prefs.digest());
+        prefs.set("dm", "on").unwrap();
+        assert!(prefs.digest());
+        prefs.set("timezone", "+02:00").unwrap();
+        prefs.set("locale", "fr").unwrap();
+        assert!(prefs.set("locale", "de").is_err());
+
+        let time = |time: &str| {
+            DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        // nothing to tell
+        assert!(!prefs.digest_due(time("2021-12-02T08:00:00Z")));
+
+        prefs.pending.push(DigestItem {
+            text: "PR #42: Fix login by alice".to_string(),
+            url: Url::parse("https://github.com/prologin/site/pull/42").unwrap(),
+        });
+        // 8:00 in the user's timezone
+        assert!(!prefs.digest_due(time("2021-12-02T06:00:00Z")));
+        assert!(prefs.digest_due(time("2021-12-02T07:00:00Z")));
+        prefs.last_digest = Some(NaiveDate::from_ymd(2021, 12, 2));
+        assert!(!prefs.digest_due(time("2021-12-02T21:00:00Z")));
+        // the next day for the user, but too early
+        assert!(!prefs.digest_due(time("2021-12-02T22:00:00Z")));
+        assert!(prefs.digest_due(time("2021-12-03T07:00:00Z")));
+
+        let mut message = MessageBuilder::new();
+        digest_message(
+            &mut message,
+            &prefs,
+            &DigestTexts::load(None, prefs.locale).unwrap(),
+        );
+        assert_eq!(
+            message.plain,
+            "[⏰ résumé] 1 PR attend ta relecture :\n- PR #42: Fix login by alice"
+        );
+    }
+}
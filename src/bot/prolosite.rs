@@ -3,7 +3,9 @@ use std::fmt::Write;
 use tracing::trace;
 
 use crate::{
-    bot::{emoji, message_builder::MessageBuilder, utils::shorten_content_length, Response},
+    bot::{
+        emoji, message_builder::MessageBuilder, utils::shorten_content_length, Response, Severity,
+    },
     webhooks::{
         prolosite::{DjangoErrorPayload, ForumPayload, ImpersonatePayload, NewSchoolPayload},
         ProloSiteEvent,
@@ -50,6 +52,9 @@ fn handle_prolosite_error(event: DjangoErrorPayload) -> Option<Response> {
     Some(Response {
         message,
         repo: None,
+        room: None,
+        severity: Severity::High,
+        image: None,
     })
 }
 
@@ -73,6 +78,9 @@ fn handle_prolosite_forum(event: ForumPayload) -> Option<Response> {
     Some(Response {
         message,
         repo: None,
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
@@ -88,6 +96,9 @@ fn handle_prolosite_new_school(event: NewSchoolPayload) -> Option<Response> {
     Some(Response {
         message,
         repo: None,
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
@@ -104,6 +115,9 @@ fn handle_prolosite_impersonate(event: ImpersonatePayload) -> Option<Response> {
     Some(Response {
         message,
         repo: None,
+        room: None,
+        severity: Severity::Normal,
+        image: None,
     })
 }
 
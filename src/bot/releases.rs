@@ -0,0 +1,213 @@
+//! Reminders to cut a release of the repos following a release train, listing what changed since
+//! their latest tag, whether it was published as a GitHub release or not.
+
+use std::{fmt::Write, sync::Arc};
+
+use anyhow::anyhow;
+use chrono::{Date, DateTime, Datelike, Duration, Utc};
+use matrix_sdk::{ruma::events::AnyMessageEventContent, Client};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder, utils::shorten_content},
+    config::{ProloloConfig, ReleaseTrain},
+    github_api::{Comparison, GitHubApi},
+};
+
+/// Hour of the day (UTC) of the reminders
+const REMINDER_HOUR: u32 = 8;
+
+/// Whether a release is due on `date`
+fn is_release_day(train: &ReleaseTrain, date: Date<Utc>) -> bool {
+    let week = (date.day() - 1) / 7 + 1;
+    date.weekday() == train.weekday && train.week.map_or(true, |w| w == week)
+}
+
+/// Returns the next time reminders are checked, strictly after `now`.
+fn next_check(now: DateTime<Utc>) -> DateTime<Utc> {
+    let check = now.date().and_hms(REMINDER_HOUR, 0, 0);
+    if check > now {
+        check
+    } else {
+        check + Duration::days(1)
+    }
+}
+
+/// Returns the title of the PR merged by a commit, for both merge and squash commits.
+fn merged_pr_title(message: &str) -> Option<&str> {
+    let mut lines = message.lines();
+    let title = lines.next()?;
+
+    if title.starts_with("Merge pull request #") {
+        // the PR's title is the commit's body
+        return lines.find(|line| !line.trim().is_empty()).map(str::trim);
+    }
+
+    // squash merges end with the PR's number, e.g. `Fix login (#42)`
+    let (_, number) = title.trim_end().strip_suffix(')')?.rsplit_once("(#")?;
+    if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+        Some(title)
+    } else {
+        None
+    }
+}
+
+fn reminder_message(repo: &str, tag: &str, comparison: &Comparison) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    let repo_name = repo.rsplit('/').next().unwrap_or(repo);
+    message.tag(repo_name, Some(emoji::PACKAGE));
+    write!(message, " time for a release: ").unwrap();
+
+    let commits = if comparison.ahead_by == 1 {
+        "1 commit".to_string()
+    } else {
+        format!("{} commits", comparison.ahead_by)
+    };
+    message.main_link(
+        format_args!("{} since {}", commits, tag),
+        &comparison.html_url,
+    );
+
+    let prs: Vec<&str> = comparison
+        .commits
+        .iter()
+        .filter_map(|commit| merged_pr_title(&commit.commit.message))
+        .collect();
+    if !prs.is_empty() {
+        write!(message, ", merged PRs:").unwrap();
+        for title in prs {
+            message.line_break();
+            write!(message, "- {}", shorten_content(title)).unwrap();
+        }
+    }
+
+    message
+}
+
+async fn remind_release(
+    client: &Client,
+    config: &ProloloConfig,
+    api: &GitHubApi,
+    train: &ReleaseTrain,
+) -> anyhow::Result<()> {
+    let tag = match api.latest_tag(&train.repo).await? {
+        Some(tag) => tag,
+        None => {
+            debug!("{} wasn't released yet, no release reminder", train.repo);
+            return Ok(());
+        }
+    };
+    let branch = api.repository(&train.repo).await?.default_branch;
+    let comparison = api.compare(&train.repo, &tag.name, &branch).await?;
+    if comparison.ahead_by == 0 {
+        debug!("nothing to release in {}", train.repo);
+        return Ok(());
+    }
+
+    let room_id = config.find_room_for(train.repo.clone())?;
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+
+    let message = reminder_message(&train.repo, &tag.name, &comparison);
+    let message = AnyMessageEventContent::RoomMessage(message.into());
+    room.send(message, None).await?;
+
+    Ok(())
+}
+
+/// Posts the reminders of the release trains, on their release days.
+pub async fn remind_release_trains(client: Client, config: ProloloConfig, api: Arc<GitHubApi>) {
+    loop {
+        let now = Utc::now();
+        let delay = (next_check(now) - now)
+            .to_std()
+            .expect("next check is always in the future");
+
+        debug!("next release trains check in {:?}", delay);
+        tokio::time::sleep(delay).await;
+
+        let today = Utc::now().date();
+        for train in &config.release_trains {
+            if !is_release_day(train, today) {
+                continue;
+            }
+
+            if let Err(e) = remind_release(&client, &config, &api, train).await {
+                warn!("couldn't post release reminder for {}: {:#}", train.repo, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Weekday};
+    use url::Url;
+
+    use crate::github_api::{CommitDetails, ComparedCommit};
+
+    use super::*;
+
+    #[test]
+    fn test_is_release_day() {
+        let train = ReleaseTrain {
+            repo: "prologin/site".to_string(),
+            weekday: Weekday::Mon,
+            week: Some(1),
+        };
+
+        assert!(is_release_day(&train, Utc.ymd(2021, 12, 6)));
+        assert!(!is_release_day(&train, Utc.ymd(2021, 12, 13)));
+        assert!(!is_release_day(&train, Utc.ymd(2021, 12, 7)));
+
+        let weekly = ReleaseTrain {
+            week: None,
+            ..train
+        };
+        assert!(is_release_day(&weekly, Utc.ymd(2021, 12, 13)));
+    }
+
+    #[test]
+    fn test_merged_pr_title() {
+        assert_eq!(
+            merged_pr_title("Merge pull request #12 from alice/login\n\nFix login"),
+            Some("Fix login")
+        );
+        assert_eq!(
+            merged_pr_title("Fix login (#12)\n\n* wip"),
+            Some("Fix login (#12)")
+        );
+        assert_eq!(merged_pr_title("Fix login (see #12)"), None);
+        assert_eq!(merged_pr_title("Bump version"), None);
+    }
+
+    #[test]
+    fn test_reminder_message() {
+        let commit = |message: &str| ComparedCommit {
+            sha: "0123456789abcdef".to_string(),
+            commit: CommitDetails {
+                message: message.to_string(),
+                verification: None,
+            },
+        };
+        let comparison = Comparison {
+            html_url: Url::parse("https://github.com/prologin/site/compare/v1.2.3...main").unwrap(),
+            ahead_by: 3,
+            commits: vec![
+                commit("Fix login (#12)"),
+                commit("Bump version"),
+                commit("Merge pull request #13 from bob/docs\n\nDocument the API"),
+            ],
+            files: vec![],
+        };
+
+        let message = reminder_message("prologin/site", "v1.2.3", &comparison);
+
+        assert_eq!(
+            message.plain,
+            "[📦 site] time for a release: 3 commits since v1.2.3, merged PRs:\n- Fix login (#12)\n- Document the API"
+        );
+    }
+}
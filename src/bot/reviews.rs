@@ -0,0 +1,444 @@
+//! Reminders about PRs waiting too long for their first review, mentioning the Matrix users of the
+//! code owners of the files they change.
+//!
+//! PRs are tracked in the bot state from the moment they're announced, until they get reviewed or
+//! closed. A background task regularly checks for PRs that waited more than the configured delay,
+//! and replies to their announcement with a single reminder.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use matrix_sdk::{
+    ruma::{
+        events::{
+            room::message::{InReplyTo, MessageEventContent, Relation},
+            AnyMessageEventContent,
+        },
+        EventId, RoomId, UserId,
+    },
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    bot::{
+        codeowners::{CodeOwners, CODEOWNERS_PATHS},
+        emoji,
+        message_builder::MessageBuilder,
+        preferences::{send_digests, send_direct_message, Delivery, DigestItem},
+        state::SharedState,
+    },
+    config::ProloloConfig,
+    github_api::GitHubApi,
+    webhooks::{
+        github::{PullRequestAction, PullRequestReviewAction},
+        GitHubEvent,
+    },
+};
+
+/// How often PRs waiting for a review are checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// A PR waiting for its first review
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReviewRequest {
+    /// The repository's full name
+    pub repo: String,
+    pub number: u64,
+    pub title: String,
+    pub author: String,
+    pub html_url: Url,
+    pub opened_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingReview {
+    #[serde(flatten)]
+    pub pr: ReviewRequest,
+    /// The room where the PR was announced
+    pub room: RoomId,
+    /// The message announcing the PR, the reminder replies to it
+    pub event_id: EventId,
+}
+
+/// PRs waiting for their first review, keyed by `owner/repo#number`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PendingReviews {
+    prs: BTreeMap<String, PendingReview>,
+}
+
+fn pr_key(repo: &str, number: u64) -> String {
+    format!("{}#{}", repo, number)
+}
+
+impl PendingReviews {
+    /// Starts tracking a PR, a PR already tracked (e.g. when its event is redelivered) keeps
+    /// waiting since it was first opened
+    pub fn track(&mut self, review: PendingReview) {
+        self.prs
+            .entry(pr_key(&review.pr.repo, review.pr.number))
+            .or_insert(review);
+    }
+
+    pub fn untrack(&mut self, repo: &str, number: u64) -> bool {
+        self.prs.remove(&pr_key(repo, number)).is_some()
+    }
+
+    /// Returns the PRs that have been waiting for longer than `delay`.
+    pub fn due(&self, now: DateTime<Utc>, delay: chrono::Duration) -> Vec<PendingReview> {
+        self.prs
+            .values()
+            .filter(|review| now - review.pr.opened_at >= delay)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Stops tracking PRs that were reviewed or closed, returns the PR to track if this event opened
+/// one. It should only be tracked once its announcement is sent.
+pub async fn record_event(
+    event: &GitHubEvent,
+    state: &SharedState,
+) -> anyhow::Result<Option<ReviewRequest>> {
+    let (repo, pr) = match event {
+        GitHubEvent::PullRequest(event) => {
+            if matches!(
+                event.action,
+                PullRequestAction::Opened | PullRequestAction::Reopened
+            ) {
+                let pr = &event.pull_request;
+                return Ok(Some(ReviewRequest {
+                    repo: event.repository.full_name.clone(),
+                    number: pr.number,
+                    title: pr.title.clone(),
+                    author: pr.user.login.clone(),
+                    html_url: pr.html_url.clone(),
+                    opened_at: Utc::now(),
+                }));
+            }
+            if event.action != PullRequestAction::Closed {
+                return Ok(None);
+            }
+            (&event.repository, &event.pull_request)
+        }
+        GitHubEvent::PullRequestReview(event)
+            if event.action == PullRequestReviewAction::Submitted =>
+        {
+            (&event.repository, &event.pull_request)
+        }
+        _ => return Ok(None),
+    };
+
+    let mut store = state.lock().await;
+    if store
+        .state
+        .pending_reviews
+        .untrack(&repo.full_name, pr.number)
+    {
+        debug!(
+            "{}#{} isn't waiting for a review anymore",
+            repo.full_name, pr.number
+        );
+        store.save()?;
+    }
+
+    Ok(None)
+}
+
+/// Starts tracking a PR once `event_id` announced it in `room`.
+pub async fn track(
+    state: &SharedState,
+    pr: ReviewRequest,
+    room: RoomId,
+    event_id: EventId,
+) -> anyhow::Result<()> {
+    let mut store = state.lock().await;
+    store
+        .state
+        .pending_reviews
+        .track(PendingReview { pr, room, event_id });
+    store.save()
+}
+
+/// Lists the owners of the changed files, without duplicates nor the PR's author.
+fn owners_of_files<'a>(
+    codeowners: &'a CodeOwners,
+    files: impl IntoIterator<Item = &'a str>,
+    author: &str,
+) -> Vec<&'a str> {
+    let mut owners: Vec<&str> = Vec::new();
+
+    for file in files {
+        for owner in codeowners.owners_of(file) {
+            let is_author = owner.strip_prefix('@') == Some(author);
+            if !is_author && !owners.contains(&owner.as_str()) {
+                owners.push(owner);
+            }
+        }
+    }
+
+    owners
+}
+
+fn reminder_message(
+    pr: &ReviewRequest,
+    owners: &[&str],
+    user_mapping: &HashMap<String, UserId>,
+    now: DateTime<Utc>,
+) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    let repo_name = pr.repo.rsplit('/').next().unwrap_or(&pr.repo);
+    message.tag(repo_name, Some(emoji::ALARM_CLOCK));
+    write!(message, " ").unwrap();
+    message.main_link(
+        format_args!("PR #{}: {} by {}", pr.number, pr.title, pr.author),
+        &pr.html_url,
+    );
+    write!(
+        message,
+        " has been waiting for a review for {} hours, code owners: ",
+        (now - pr.opened_at).num_hours()
+    )
+    .unwrap();
+
+    for (i, owner) in owners.iter().enumerate() {
+        if i > 0 {
+            write!(message, ", ").unwrap();
+        }
+
+        let login = owner.trim_start_matches('@');
+        match user_mapping.get(login) {
+            Some(user_id) => message.mention(user_id),
+            None => write!(message, "{}", login).unwrap(),
+        }
+    }
+
+    message
+}
+
+async fn code_owners(api: &GitHubApi, pr: &ReviewRequest) -> anyhow::Result<Option<CodeOwners>> {
+    for path in CODEOWNERS_PATHS {
+        if let Some(content) = api.file_content(&pr.repo, path).await? {
+            return Ok(Some(CodeOwners::parse(&content)));
+        }
+    }
+
+    Ok(None)
+}
+
+async fn remind_reviewers(
+    client: &Client,
+    config: &ProloloConfig,
+    state: &SharedState,
+    api: &GitHubApi,
+    review: &PendingReview,
+) -> anyhow::Result<()> {
+    let pr = &review.pr;
+
+    if api.has_reviews(&pr.repo, pr.number).await? {
+        debug!("{}#{} was already reviewed", pr.repo, pr.number);
+        return Ok(());
+    }
+
+    let codeowners = match code_owners(api, pr).await? {
+        Some(codeowners) => codeowners,
+        None => return Ok(()),
+    };
+    let files = api.pull_request_files(&pr.repo, pr.number).await?;
+    let owners = owners_of_files(
+        &codeowners,
+        files.iter().map(|file| file.filename.as_str()),
+        &pr.author,
+    );
+    if owners.is_empty() {
+        debug!("{}#{} doesn't touch any owned path", pr.repo, pr.number);
+        return Ok(());
+    }
+
+    let room = client
+        .get_joined_room(&review.room)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", review.room))?;
+
+    // the owners reading digests aren't mentioned, that would notify them right away
+    let now = Utc::now();
+    let preferences = state.lock().await.state.preferences.clone();
+    let mentioned: HashMap<String, UserId> = config
+        .user_mapping
+        .iter()
+        .filter(|(_, user_id)| preferences.realtime(user_id))
+        .map(|(login, user_id)| (login.clone(), user_id.clone()))
+        .collect();
+    let mut message: MessageEventContent = reminder_message(pr, &owners, &mentioned, now).into();
+    message.relates_to = Some(Relation::Reply {
+        in_reply_to: InReplyTo::new(review.event_id.clone()),
+    });
+    room.send(AnyMessageEventContent::RoomMessage(message), None)
+        .await?;
+
+    let mut digested = Vec::new();
+    for owner in &owners {
+        let user_id = match config.user_mapping.get(owner.trim_start_matches('@')) {
+            Some(user_id) => user_id,
+            None => continue,
+        };
+        let prefs = match preferences.get(user_id) {
+            Some(prefs) if prefs.direct_messages => prefs,
+            _ => continue,
+        };
+        match prefs.delivery {
+            Delivery::Realtime => {
+                let message = reminder_message(pr, &owners, &mentioned, now);
+                if let Err(e) = send_direct_message(client, prefs, message.into()).await {
+                    warn!("couldn't send reminder to {}: {:#}", user_id, e);
+                }
+            }
+            Delivery::Digest => digested.push(user_id),
+        }
+    }
+    if !digested.is_empty() {
+        let item = DigestItem {
+            text: format!(
+                "PR #{}: {} by {} ({})",
+                pr.number, pr.title, pr.author, pr.repo
+            ),
+            url: pr.html_url.clone(),
+        };
+        let mut store = state.lock().await;
+        for user_id in digested {
+            store.state.preferences.queue(user_id, item.clone());
+        }
+        store.save()?;
+    }
+
+    Ok(())
+}
+
+/// Reminds code owners of the PRs waiting for their review, checking them every
+/// [`CHECK_INTERVAL`]. The digests of the owners who prefer them are sent by the same checks.
+pub async fn remind_pending_reviews(
+    client: Client,
+    config: ProloloConfig,
+    state: SharedState,
+    api: Arc<GitHubApi>,
+) {
+    let delay = match &config.reviews {
+        Some(reviews) => chrono::Duration::hours(reviews.delay_hours as i64),
+        None => return,
+    };
+
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let due = state
+            .lock()
+            .await
+            .state
+            .pending_reviews
+            .due(Utc::now(), delay);
+
+        for review in due {
+            let pr = &review.pr;
+
+            // failed reminders are retried at the next check
+            if let Err(e) = remind_reviewers(&client, &config, &state, &api, &review).await {
+                warn!(
+                    "couldn't remind reviewers of {}#{}: {:#}",
+                    pr.repo, pr.number, e
+                );
+                continue;
+            }
+
+            let mut store = state.lock().await;
+            store.state.pending_reviews.untrack(&pr.repo, pr.number);
+            if let Err(e) = store.save() {
+                warn!("couldn't save bot state: {:#}", e);
+            }
+        }
+
+        send_digests(&client, &state, config.assets_dir.as_deref(), Utc::now()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    fn review_request(opened_at: &str) -> ReviewRequest {
+        ReviewRequest {
+            repo: "prologin/site".to_string(),
+            number: 42,
+            title: "Fix login".to_string(),
+            author: "alice".to_string(),
+            html_url: Url::parse("https://github.com/prologin/site/pull/42").unwrap(),
+            opened_at: DateTime::parse_from_rfc3339(opened_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn test_due_reviews() {
+        let mut reviews = PendingReviews::default();
+        let pending = |opened_at| PendingReview {
+            pr: review_request(opened_at),
+            room: RoomId::try_from("!abc:prologin.org").unwrap(),
+            event_id: EventId::try_from("$def:prologin.org").unwrap(),
+        };
+        reviews.track(pending("2021-12-01T10:00:00Z"));
+        // redelivered event
+        reviews.track(pending("2021-12-02T10:00:00Z"));
+
+        let delay = chrono::Duration::hours(24);
+        let now = DateTime::parse_from_rfc3339("2021-12-02T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(reviews.due(now, delay).is_empty());
+        assert_eq!(reviews.due(now + delay, delay).len(), 1);
+
+        assert!(reviews.untrack("prologin/site", 42));
+        assert!(reviews.due(now + delay, delay).is_empty());
+    }
+
+    #[test]
+    fn test_reminder_message() {
+        let codeowners = CodeOwners::parse("* @alice @prologin/web\n*.py @bob");
+        let owners = owners_of_files(&codeowners, vec!["README.md", "main.py"], "alice");
+        assert_eq!(owners, ["@prologin/web", "@bob"]);
+
+        let mut user_mapping = HashMap::new();
+        user_mapping.insert(
+            "bob".to_string(),
+            UserId::try_from("@bob:prologin.org").unwrap(),
+        );
+
+        let now = DateTime::parse_from_rfc3339("2021-12-02T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let message = reminder_message(
+            &review_request("2021-12-01T10:00:00Z"),
+            &owners,
+            &user_mapping,
+            now,
+        );
+
+        assert_eq!(
+            message.plain,
+            "[⏰ site] PR #42: Fix login by alice has been waiting for a review for 26 hours, code owners: prologin/web, @bob:prologin.org"
+        );
+        assert_eq!(
+            message.html,
+            r#"<b>[⏰ site]</b> <a href="https://github.com/prologin/site/pull/42">PR #42: Fix login by alice</a> has been waiting for a review for 26 hours, code owners: prologin/web, <a href="https://matrix.to/#/@bob:prologin.org">@bob:prologin.org</a>"#
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! `!search`, to check whether there is already an issue about something before opening one.
+
+use std::fmt::Write;
+
+use anyhow::{bail, Context};
+use matrix_sdk::ruma::RoomId;
+
+use crate::{
+    bot::{args::Args, emoji, message_builder::MessageBuilder, utils::shorten_content},
+    config::ProloloConfig,
+    github_api::{GitHubApi, IssueSearch},
+};
+
+/// Maximum number of matches listed in the answer
+const MAX_RESULTS: usize = 5;
+
+/// Qualifiers widening the search beyond the repo, they could reveal private repos
+const SCOPE_QUALIFIERS: &[&str] = &["repo:", "org:", "user:", "owner:"];
+
+/// Whether the announcements of `repo` go to the room: the search uses the bot's token, the room
+/// must not learn about private repos that aren't announced there
+fn routed_to(config: &ProloloConfig, repo: &str, room_id: &RoomId) -> bool {
+    config
+        .destinations
+        .iter()
+        .find(|dest| dest.regex.is_match(repo))
+        .and_then(|dest| config.matrix_rooms.get(&dest.room))
+        .map_or(false, |room| &room.id == room_id)
+}
+
+/// The first word of the query that would widen its scope, e.g. `org:prologin`
+fn scope_qualifier(query: &str) -> Option<&str> {
+    query.split_whitespace().find(|word| {
+        let word = word
+            .trim_start_matches(|c| c == '-' || c == '(')
+            .to_lowercase();
+        SCOPE_QUALIFIERS
+            .iter()
+            .any(|qualifier| word.starts_with(qualifier))
+    })
+}
+
+fn results_message(repo: &str, query: &str, search: &IssueSearch) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+    message.tag("search", Some(emoji::MAGNIFYING_GLASS));
+
+    match search.total_count {
+        0 => {
+            write!(message, " nothing in {} matches `{}`", repo, query).unwrap();
+            return message;
+        }
+        count if count > search.items.len() as u64 => write!(
+            message,
+            " {} issues and PRs of {} match `{}`, the {} best ones:",
+            count,
+            repo,
+            query,
+            search.items.len()
+        )
+        .unwrap(),
+        count => write!(
+            message,
+            " {} issues and PRs of {} match `{}`:",
+            count, repo, query
+        )
+        .unwrap(),
+    }
+
+    for issue in &search.items {
+        message.line_break();
+        let kind = if issue.pull_request.is_some() {
+            "PR"
+        } else {
+            "issue"
+        };
+        message.link(format_args!("{} #{}", kind, issue.number), &issue.html_url);
+        write!(
+            message,
+            " ({}) {}",
+            issue.state,
+            shorten_content(&issue.title)
+        )
+        .unwrap();
+    }
+
+    message
+}
+
+/// `!search <repo> <query>`: the issues and PRs of a repo (by its full name, e.g.
+/// `prologin/site`) matching a query, which can use GitHub's search syntax, e.g. `is:open crash`.
+/// Only the repos announced in the room can be searched.
+pub async fn search_command(
+    args: Args,
+    room_id: &RoomId,
+    config: &ProloloConfig,
+    api: &GitHubApi,
+) -> anyhow::Result<MessageBuilder> {
+    let positional = args.expect_positional(2, usize::MAX)?.to_vec();
+    let repo = &positional[0];
+    if repo.split('/').count() != 2 {
+        return Err(args.error(format_args!(
+            "`{}` isn't the full name of a repo, e.g. `prologin/site`",
+            repo
+        )));
+    }
+    args.finish()?;
+
+    if !routed_to(config, repo, room_id) {
+        bail!(
+            "{} isn't announced in this room, it can't be searched here",
+            repo
+        );
+    }
+    let query = positional[1..].join(" ");
+    if let Some(qualifier) = scope_qualifier(&query) {
+        bail!("`{}` can't be used, only {} is searched", qualifier, repo);
+    }
+    let search = api
+        .search_issues(repo, &query, MAX_RESULTS)
+        .await
+        .with_context(|| format!("couldn't search the issues of {}", repo))?;
+
+    Ok(results_message(repo, &query, &search))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_scope() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            r#"
+matrix_username: prololo
+matrix_password: secret
+matrix_homeserver: https://matrix.prologin.org
+matrix_state_dir: state
+github_secret: secret
+prolosite_secret: secret
+destinations:
+  - room: infra
+    regex: "^prologin/infra-.*$"
+  - room: dev
+    regex: "^prologin/.*$"
+matrix_rooms:
+  dev:
+    id: "!dev:prologin.org"
+    default: true
+  infra:
+    id: "!infra:prologin.org"
+"#,
+        )
+        .unwrap();
+        let dev = config.room_by_name("dev").unwrap();
+        let infra = config.room_by_name("infra").unwrap();
+
+        assert!(routed_to(&config, "prologin/site", dev));
+        assert!(!routed_to(&config, "prologin/site", infra));
+        assert!(routed_to(&config, "prologin/infra-secrets", infra));
+        assert!(!routed_to(&config, "prologin/infra-secrets", dev));
+        // the default room doesn't get every repo without a destination
+        assert!(!routed_to(&config, "someone/private", dev));
+
+        assert_eq!(scope_qualifier("is:open crash"), None);
+        assert_eq!(scope_qualifier("crash org:prologin"), Some("org:prologin"));
+        assert_eq!(scope_qualifier("crash -REPO:a/b"), Some("-REPO:a/b"));
+        assert_eq!(scope_qualifier("(user:alice OR x)"), Some("(user:alice"));
+    }
+
+    #[test]
+    fn test_results_message() {
+        let search: IssueSearch = serde_json::from_str(
+            r#"{
+                "total_count": 12,
+                "items": [
+                    {
+                        "number": 42,
+                        "title": "Crash when <submitting> twice",
+                        "html_url": "https://github.com/prologin/site/issues/42",
+                        "state": "open"
+                    },
+                    {
+                        "number": 40,
+                        "title": "Fix the submission crash",
+                        "html_url": "https://github.com/prologin/site/pull/40",
+                        "state": "closed",
+                        "pull_request": {}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let message = results_message("prologin/site", "crash", &search);
+        assert_eq!(
+            message.plain,
+            "[🔍 search] 12 issues and PRs of prologin/site match `crash`, the 2 best ones:\n\
+             issue #42 (open) Crash when <submitting> twice\n\
+             PR #40 (closed) Fix the submission crash"
+        );
+        assert!(message.html.contains(
+            r#"<a href="https://github.com/prologin/site/issues/42">issue #42</a> (open) Crash when &lt;submitting&gt; twice"#
+        ));
+
+        let empty = IssueSearch {
+            total_count: 0,
+            items: Vec::new(),
+        };
+        assert_eq!(
+            results_message("prologin/site", "crash", &empty).plain,
+            "[🔍 search] nothing in prologin/site matches `crash`"
+        );
+    }
+}
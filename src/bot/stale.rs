@@ -0,0 +1,214 @@
+//! Weekly lists of the PRs and issues that stayed open for too long, posted in the room of each
+//! checked repo.
+
+use std::{collections::BTreeMap, fmt::Write, sync::Arc};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Duration, Utc, Weekday};
+use matrix_sdk::{
+    ruma::{events::AnyMessageEventContent, RoomId},
+    Client,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder},
+    config::ProloloConfig,
+    github_api::{GitHubApi, OpenIssue},
+};
+
+/// Hour of the day (UTC) of the weekly post
+const POST_HOUR: u32 = 8;
+
+/// Returns the next time the list should be posted, strictly after `now`.
+pub(super) fn next_post(now: DateTime<Utc>, weekday: Weekday) -> DateTime<Utc> {
+    let days_ahead =
+        (7 + weekday.num_days_from_monday() - now.weekday().num_days_from_monday()) % 7;
+    let post = (now.date() + Duration::days(days_ahead.into())).and_hms(POST_HOUR, 0, 0);
+
+    if post > now {
+        post
+    } else {
+        post + Duration::weeks(1)
+    }
+}
+
+/// Builds the list of old PRs and issues of some repos, `None` if there are none.
+fn stale_message(
+    days: u64,
+    repos: &[(String, Vec<OpenIssue>)],
+    now: DateTime<Utc>,
+) -> Option<MessageBuilder> {
+    let count: usize = repos.iter().map(|(_, issues)| issues.len()).sum();
+    if count == 0 {
+        return None;
+    }
+
+    let mut message = MessageBuilder::new();
+
+    message.tag("needs attention", Some(emoji::HOURGLASS));
+    write!(
+        message,
+        " {} PRs and issues opened more than {} days ago:",
+        count, days
+    )
+    .unwrap();
+
+    for (repo, issues) in repos {
+        for issue in issues {
+            message.line_break();
+            let kind = if issue.pull_request.is_some() {
+                "PR"
+            } else {
+                "issue"
+            };
+            write!(message, "{}: ", repo).unwrap();
+            message.link(
+                format_args!("{} #{}: {}", kind, issue.number, issue.title),
+                &issue.html_url,
+            );
+            write!(message, " ({} days)", (now - issue.created_at).num_days()).unwrap();
+        }
+    }
+
+    Some(message)
+}
+
+async fn post_stale_lists(
+    client: &Client,
+    config: &ProloloConfig,
+    api: &GitHubApi,
+) -> anyhow::Result<()> {
+    let stale = config.stale.as_ref().expect("stale lists are enabled");
+    let now = Utc::now();
+    let max_age = Duration::days(stale.days as i64);
+
+    let mut rooms: BTreeMap<&RoomId, Vec<(String, Vec<OpenIssue>)>> = BTreeMap::new();
+    for repo in &stale.repos {
+        let issues = match api.oldest_open_issues(repo).await {
+            Ok(issues) => issues,
+            Err(e) => {
+                warn!("couldn't list open issues of {}: {:#}", repo, e);
+                continue;
+            }
+        };
+        let issues = issues
+            .into_iter()
+            .filter(|issue| now - issue.created_at > max_age)
+            .collect();
+
+        let room_id = match config.find_room_for(repo.clone()) {
+            Ok(room_id) => room_id,
+            Err(e) => {
+                warn!("couldn't find the room of {}: {:#}", repo, e);
+                continue;
+            }
+        };
+        let repo_name = repo.rsplit('/').next().unwrap_or(repo);
+        rooms
+            .entry(room_id)
+            .or_default()
+            .push((repo_name.to_string(), issues));
+    }
+
+    for (room_id, repos) in rooms {
+        let message = match stale_message(stale.days, &repos, now) {
+            Some(message) => message,
+            None => continue,
+        };
+
+        let room = client
+            .get_joined_room(room_id)
+            .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+
+        let message = AnyMessageEventContent::RoomMessage(message.into());
+        room.send(message, None).await?;
+    }
+
+    Ok(())
+}
+
+/// Posts the lists of old PRs and issues every week.
+pub async fn post_weekly_stale_lists(client: Client, config: ProloloConfig, api: Arc<GitHubApi>) {
+    let weekday = match &config.stale {
+        Some(stale) => stale.weekday,
+        None => return,
+    };
+
+    loop {
+        let now = Utc::now();
+        let delay = (next_post(now, weekday) - now)
+            .to_std()
+            .expect("next post is always in the future");
+
+        debug!("next stale PRs and issues post in {:?}", delay);
+        tokio::time::sleep(delay).await;
+
+        if let Err(e) = post_stale_lists(&client, &config, &api).await {
+            warn!("couldn't post stale PRs and issues: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::*;
+
+    fn date(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_post() {
+        // 2021-12-15 is a Wednesday
+        let now = date("2021-12-15T10:00:00Z");
+
+        assert_eq!(next_post(now, Weekday::Mon), date("2021-12-20T08:00:00Z"));
+        assert_eq!(next_post(now, Weekday::Thu), date("2021-12-16T08:00:00Z"));
+        assert_eq!(next_post(now, Weekday::Wed), date("2021-12-22T08:00:00Z"));
+        assert_eq!(
+            next_post(date("2021-12-15T07:00:00Z"), Weekday::Wed),
+            date("2021-12-15T08:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_stale_message() {
+        let now = date("2021-12-15T10:00:00Z");
+        let issue = |number: u64, pull_request: bool| OpenIssue {
+            number,
+            title: "Fix login".to_string(),
+            html_url: Url::parse(&format!(
+                "https://github.com/prologin/site/issues/{}",
+                number
+            ))
+            .unwrap(),
+            created_at: date("2021-11-01T10:00:00Z"),
+            pull_request: if pull_request {
+                Some(serde::de::IgnoredAny)
+            } else {
+                None
+            },
+        };
+
+        assert!(stale_message(30, &[("site".to_string(), vec![])], now).is_none());
+
+        let message = stale_message(
+            30,
+            &[("site".to_string(), vec![issue(12, true), issue(13, false)])],
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            message.plain,
+            "[⌛ needs attention] 2 PRs and issues opened more than 30 days ago:\nsite: PR #12: Fix login (44 days)\nsite: issue #13: Fix login (44 days)"
+        );
+        assert!(message
+            .html
+            .contains(r#"<br>site: <a href="https://github.com/prologin/site/issues/12">PR #12: Fix login</a> (44 days)"#));
+    }
+}
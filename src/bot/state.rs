@@ -0,0 +1,110 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use matrix_sdk::ruma::{RoomId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::bot::{
+    deployments::Deployments, incidents::Incident, leaderboard::Leaderboard, metrics::Metrics,
+    preferences::UserPreferences, reviews::PendingReviews,
+};
+
+/// Everything the bot needs to remember across restarts.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BotState {
+    #[serde(default)]
+    pub leaderboard: Leaderboard,
+    #[serde(default)]
+    pub pending_reviews: PendingReviews,
+    #[serde(default)]
+    pub deployments: Deployments,
+    /// The incident currently open, if any
+    pub incident: Option<Incident>,
+    /// Timestamp (in milliseconds) of the latest relayed audit log entry, unset until the audit
+    /// log is first polled
+    pub audit_log_cursor: Option<i64>,
+    #[serde(default)]
+    pub preferences: UserPreferences,
+    /// The people already greeted in each room with a welcome message
+    #[serde(default)]
+    pub welcomed: BTreeMap<RoomId, BTreeSet<UserId>>,
+    #[serde(default)]
+    pub metrics: Metrics,
+}
+
+/// Where a [`StateStore`] keeps the state, a YAML file by default. Programs embedding the bot can
+/// keep it elsewhere, e.g. in their own database.
+pub trait StateBackend: Send {
+    /// Reads the saved state, `None` if nothing was saved yet
+    fn load(&self) -> anyhow::Result<Option<BotState>>;
+
+    fn save(&self, state: &BotState) -> anyhow::Result<()>;
+}
+
+/// A YAML file on disk, `state.yaml` in the state directory of the bot
+pub struct YamlFile {
+    pub path: PathBuf,
+}
+
+impl StateBackend for YamlFile {
+    fn load(&self) -> anyhow::Result<Option<BotState>> {
+        if !self.path.is_file() {
+            return Ok(None);
+        }
+
+        let reader = BufReader::new(File::open(&self.path)?);
+        let state = serde_yaml::from_reader(reader)
+            .with_context(|| format!("couldn't parse state file {}", self.path.display()))?;
+        Ok(Some(state))
+    }
+
+    /// The state is first written to a temporary file which then replaces the previous one, so
+    /// that a crash can't leave a truncated state behind.
+    fn save(&self, state: &BotState) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+
+        let writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_yaml::to_writer(writer, state)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+/// A [`BotState`] with the backend it is saved to.
+pub struct StateStore {
+    backend: Box<dyn StateBackend>,
+    pub state: BotState,
+}
+
+pub type SharedState = Arc<Mutex<StateStore>>;
+
+impl StateStore {
+    /// Loads the state from the YAML file at `path`, or starts from an empty state if the file
+    /// doesn't exist yet.
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        Self::open(Box::new(YamlFile { path }))
+    }
+
+    /// Loads the state from a backend, or starts from an empty state if it has none yet.
+    pub fn open(backend: Box<dyn StateBackend>) -> anyhow::Result<Self> {
+        let state = backend.load()?.unwrap_or_default();
+
+        Ok(Self { backend, state })
+    }
+
+    /// Writes the state back to its backend.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.backend.save(&self.state)
+    }
+}
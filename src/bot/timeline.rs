@@ -0,0 +1,202 @@
+//! Markdown timelines of what happened during a period, uploaded as a file to help writing
+//! postmortems.
+
+use std::{collections::HashMap, fmt::Write};
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use matrix_sdk::{room::Joined, ruma::RoomId};
+use mime::Mime;
+use tracing::debug;
+
+use crate::{
+    bot::{
+        archive::{Archive, ArchivedKind, ArchivedMessage},
+        emoji,
+        message_builder::MessageBuilder,
+        state::SharedState,
+        utils::parse_duration,
+        Severity,
+    },
+    config::ProloloConfig,
+};
+
+fn timeline_markdown(
+    title: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    messages: &[ArchivedMessage],
+    room_names: &HashMap<&RoomId, &str>,
+) -> String {
+    const TIME_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+    let mut timeline = String::new();
+    writeln!(timeline, "# Timeline: {}", title).unwrap();
+    writeln!(timeline).unwrap();
+    writeln!(
+        timeline,
+        "From {} to {}.",
+        since.format(TIME_FORMAT),
+        until.format(TIME_FORMAT)
+    )
+    .unwrap();
+    writeln!(timeline).unwrap();
+
+    if messages.is_empty() {
+        writeln!(timeline, "Nothing happened.").unwrap();
+        return timeline;
+    }
+
+    // only show the dates if the timeline spans several days
+    let time_format = if since.date() == until.date() {
+        "%H:%M:%S"
+    } else {
+        "%Y-%m-%d %H:%M:%S"
+    };
+    for message in messages {
+        let room = room_names
+            .get(&message.room)
+            .map_or_else(|| message.room.to_string(), |name| name.to_string());
+        let who = match &message.kind {
+            ArchivedKind::Announcement {
+                severity: Severity::High,
+                ..
+            } => "**alert**".to_string(),
+            ArchivedKind::Announcement { .. } => "bot".to_string(),
+            ArchivedKind::Chat { sender } => sender.to_string(),
+        };
+        // keep multi-line messages in the same list item
+        let body = message.body.replace('\n', "\n  ");
+
+        writeln!(
+            timeline,
+            "- {} [{}] {}: {}",
+            message.time.format(time_format),
+            room,
+            who,
+            body
+        )
+        .unwrap();
+    }
+
+    timeline
+}
+
+/// Uploads the timeline of the given period, or of the current incident if no duration is given.
+/// Returns the answer to the command.
+pub async fn send_timeline(
+    room: &Joined,
+    duration: Option<&str>,
+    config: &ProloloConfig,
+    state: &SharedState,
+    archive: &Archive,
+) -> anyhow::Result<MessageBuilder> {
+    let until = Utc::now();
+    let (title, since) = match duration {
+        Some(duration) => (
+            format!("last {}", duration),
+            until
+                .checked_sub_signed(parse_duration(duration)?)
+                .ok_or_else(|| anyhow!("the duration is too long"))?,
+        ),
+        None => match &state.lock().await.state.incident {
+            Some(incident) => (incident.title.clone(), incident.opened_at),
+            None => bail!("no incident is open, give a duration, e.g. !timeline 2h"),
+        },
+    };
+
+    let messages = archive.since(since)?;
+    let room_names = config
+        .matrix_rooms
+        .iter()
+        .map(|(name, room)| (&room.id, name.as_str()))
+        .collect();
+    let timeline = timeline_markdown(&title, since, until, &messages, &room_names);
+
+    debug!(
+        "uploading timeline of {} messages to room {}",
+        messages.len(),
+        room.room_id()
+    );
+    let name = format!("timeline-{}.md", until.format("%Y%m%d-%H%M"));
+    let content_type: Mime = "text/markdown".parse().expect("valid media type");
+    room.send_attachment(&name, &content_type, &mut timeline.as_bytes(), None)
+        .await?;
+
+    let mut message = MessageBuilder::new();
+    message.tag("timeline", Some(emoji::SCROLL));
+    write!(
+        message,
+        " {} messages since {}",
+        messages.len(),
+        since.format("%a %d %b %H:%M")
+    )
+    .unwrap();
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use matrix_sdk::ruma::{EventId, UserId};
+
+    use super::*;
+
+    fn date(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_timeline_markdown() {
+        let room = RoomId::try_from("!abc:prologin.org").unwrap();
+        let message = |time: &str, kind: ArchivedKind, body: &str| ArchivedMessage {
+            time: date(time),
+            room: room.clone(),
+            event_id: EventId::try_from("$event:prologin.org").unwrap(),
+            kind,
+            body: body.to_string(),
+        };
+        let messages = vec![
+            message(
+                "2021-12-14T09:15:00Z",
+                ArchivedKind::Announcement {
+                    repo: Some("prologin/site".to_string()),
+                    author: None,
+                    severity: Severity::High,
+                },
+                "[site] check failed\nhttps://github.com/prologin/site",
+            ),
+            message(
+                "2021-12-14T09:17:30Z",
+                ArchivedKind::Chat {
+                    sender: UserId::try_from("@alice:prologin.org").unwrap(),
+                },
+                "looking into it",
+            ),
+        ];
+        let mut room_names = HashMap::new();
+        room_names.insert(&room, "dev");
+
+        assert_eq!(
+            timeline_markdown(
+                "site is down",
+                date("2021-12-14T09:00:00Z"),
+                date("2021-12-14T11:00:00Z"),
+                &messages,
+                &room_names
+            ),
+            "# Timeline: site is down
+
+From 2021-12-14 09:00 UTC to 2021-12-14 11:00 UTC.
+
+- 09:15:00 [dev] **alert**: [site] check failed
+  https://github.com/prologin/site
+- 09:17:30 [dev] @alice:prologin.org: looking into it
+"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Rooms trialling another formatting profile: they receive a copy of the messages of a source
+//! room, formatted with their own settings, so that volunteers can try new formatting before it is
+//! enabled everywhere.
+
+use anyhow::Context;
+use matrix_sdk::ruma::RoomId;
+
+use crate::config::{GitHubConfig, ProloloConfig};
+
+pub struct Trial {
+    /// The room name, as used in [`ProloloConfig::matrix_rooms`]
+    pub name: String,
+    pub room_id: RoomId,
+    pub github: GitHubConfig,
+    source: RoomId,
+}
+
+pub struct Trials {
+    trials: Vec<Trial>,
+}
+
+impl Trials {
+    pub fn from_config(config: &ProloloConfig) -> anyhow::Result<Self> {
+        let mut trials = Vec::new();
+
+        for (name, room) in &config.matrix_rooms {
+            if let Some(trial) = &room.trial {
+                let source = config
+                    .room_by_name(&trial.source)
+                    .with_context(|| format!("invalid trial source for room {}", name))?;
+                trials.push(Trial {
+                    name: name.clone(),
+                    room_id: room.id.clone(),
+                    github: trial.github.clone(),
+                    source: source.clone(),
+                });
+            }
+        }
+
+        Ok(Self { trials })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trials.is_empty()
+    }
+
+    /// The rooms receiving a copy of the messages sent to `room_id`
+    pub fn copying(&self, room_id: &RoomId) -> impl Iterator<Item = &Trial> {
+        let room_id = room_id.clone();
+        self.trials
+            .iter()
+            .filter(move |trial| trial.source == room_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trials() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            r#"
+matrix_username: prololo
+matrix_password: secret
+matrix_homeserver: https://matrix.prologin.org
+matrix_state_dir: state
+github_secret: secret
+prolosite_secret: secret
+destinations: []
+matrix_rooms:
+  dev:
+    id: "!dev:prologin.org"
+    default: true
+  dev-trial:
+    id: "!dev-trial:prologin.org"
+    trial:
+      source: dev
+      github:
+        push_commits: 0
+"#,
+        )
+        .unwrap();
+        let trials = Trials::from_config(&config).unwrap();
+
+        let dev = config.room_by_name("dev").unwrap();
+        let copies: Vec<_> = trials.copying(dev).collect();
+        assert_eq!(copies.len(), 1);
+        assert_eq!(copies[0].name, "dev-trial");
+        assert_eq!(copies[0].github.push_commits, 0);
+
+        let trial = config.room_by_name("dev-trial").unwrap();
+        assert_eq!(trials.copying(trial).count(), 0);
+    }
+}
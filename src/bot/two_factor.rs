@@ -0,0 +1,140 @@
+//! Weekly report of the organization members without two-factor authentication, posted in a
+//! private room.
+//!
+//! GitHub sends no webhook when the organization's two-factor requirement changes, those changes
+//! are relayed from the audit log instead.
+
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use matrix_sdk::{ruma::events::AnyMessageEventContent, Client};
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{emoji, message_builder::MessageBuilder, stale::next_post},
+    config::{GitHubConfig, ProloloConfig},
+    github_api::{GitHubApi, OrgMember},
+};
+
+fn report_message(org: &str, members: &[OrgMember]) -> MessageBuilder {
+    let mut message = MessageBuilder::new();
+
+    message.tag(org, Some(emoji::SHIELD));
+    if members.is_empty() {
+        write!(
+            message,
+            " every member has two-factor authentication enabled"
+        )
+        .unwrap();
+        return message;
+    }
+
+    write!(
+        message,
+        " {} members without two-factor authentication: ",
+        members.len()
+    )
+    .unwrap();
+    for (i, member) in members.iter().enumerate() {
+        if i > 0 {
+            write!(message, ", ").unwrap();
+        }
+        message.link(&member.login, &member.html_url);
+    }
+
+    message
+}
+
+async fn post_report(
+    client: &Client,
+    config: &ProloloConfig,
+    api: &GitHubApi,
+) -> anyhow::Result<()> {
+    let two_factor = config.two_factor.as_ref().expect("2FA reports are enabled");
+
+    let members = api.members_without_2fa(&two_factor.org).await?;
+    let room_id = config.room_by_name(&two_factor.room)?;
+    let room = client
+        .get_joined_room(room_id)
+        .ok_or_else(|| anyhow!("room with id {} isn't joined yet", room_id))?;
+
+    let message = report_message(&two_factor.org, &members);
+    room.send(AnyMessageEventContent::RoomMessage(message.into()), None)
+        .await?;
+
+    Ok(())
+}
+
+/// Posts the report of the members without two-factor authentication every week.
+pub async fn post_weekly_two_factor_reports(client: Client, config: ProloloConfig) {
+    let two_factor = match &config.two_factor {
+        Some(two_factor) => two_factor,
+        None => return,
+    };
+    let github = GitHubConfig {
+        api_token: two_factor
+            .token
+            .clone()
+            .or_else(|| config.github.api_token.clone()),
+        ..config.github.clone()
+    };
+    let api = match GitHubApi::new(&github, config.timeouts.http()) {
+        Ok(api) => api,
+        Err(e) => {
+            warn!("couldn't build 2FA report client: {:#}", e);
+            return;
+        }
+    };
+
+    loop {
+        let now = Utc::now();
+        let delay = (next_post(now, two_factor.weekday) - now)
+            .to_std()
+            .expect("next post is always in the future");
+
+        debug!("next 2FA report in {:?}", delay);
+        tokio::time::sleep(delay).await;
+
+        if let Err(e) = post_report(&client, &config, &api).await {
+            warn!("couldn't post 2FA report: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::*;
+
+    #[test]
+    fn test_report_message() {
+        let members = vec![
+            OrgMember {
+                login: "alice".to_string(),
+                html_url: Url::parse("https://github.com/alice").unwrap(),
+            },
+            OrgMember {
+                login: "bob".to_string(),
+                html_url: Url::parse("https://github.com/bob").unwrap(),
+            },
+        ];
+
+        let message = report_message("prologin", &members);
+        assert_eq!(
+            message.plain,
+            "[🛡 prologin] 2 members without two-factor authentication: alice, bob"
+        );
+        assert_eq!(
+            message.html,
+            r#"<b>[🛡 prologin]</b> 2 members without two-factor authentication: <a href="https://github.com/alice">alice</a>, <a href="https://github.com/bob">bob</a>"#
+        );
+
+        let message = report_message("prologin", &[]);
+        assert_eq!(
+            message.plain,
+            "[🛡 prologin] every member has two-factor authentication enabled"
+        );
+    }
+}
@@ -1,5 +1,18 @@
+use std::convert::TryInto;
+
+use anyhow::anyhow;
+use chrono::Duration;
+use matrix_sdk::uuid::Uuid;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// The number of characters kept by [`shorten_content`]
+pub(crate) const SHORTENED_LENGTH: usize = 72;
+
 pub(crate) fn shorten_content(content: &str) -> String {
-    let max_length = 72;
+    let max_length = SHORTENED_LENGTH;
     if content.len() <= max_length {
         content.to_owned()
     } else {
@@ -14,3 +27,148 @@ pub(crate) fn shorten_content_length(content: &str, max_length: usize) -> String
         content.chars().take(max_length).collect::<String>() + "…"
     }
 }
+
+/// Rewrites the text between the tags of an HTML body, leaving the markup untouched. The closure
+/// also gets whether the text is inside a link.
+pub(crate) fn map_html_text(html: &str, mut f: impl FnMut(&str, bool) -> String) -> String {
+    let mut mapped = String::with_capacity(html.len());
+    let mut in_link = false;
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |end| start + end + 1);
+        mapped.push_str(&f(&rest[..start], in_link));
+
+        let tag = &rest[start..end];
+        if tag.starts_with("<a ") || tag == "<a>" {
+            in_link = true;
+        } else if tag == "</a>" {
+            in_link = false;
+        }
+        mapped.push_str(tag);
+        rest = &rest[end..];
+    }
+    mapped.push_str(&f(rest, in_link));
+
+    mapped
+}
+
+/// Finds the first HTTP(S) image embedded in a Markdown body, either with the `![alt](url)` syntax
+/// or an `<img>` tag.
+pub(crate) fn first_image(body: &str) -> Option<Url> {
+    static IMAGE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"!\[[^\]]*\]\(\s*<?([^\s)>]+)>?[^)]*\)|<img\s[^>]*src=["']([^"']+)["']"#)
+            .expect("valid regex")
+    });
+
+    let image = IMAGE
+        .captures_iter(body)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .filter_map(|url| Url::parse(url.as_str()).ok())
+        .find(|url| matches!(url.scheme(), "http" | "https"));
+    image
+}
+
+/// The longest duration accepted by [`parse_duration`], in minutes (about ten years)
+const MAX_DURATION_MINUTES: i64 = 3650 * 24 * 60;
+
+/// Parses a duration such as `90m`, `2h`, `1d` or a sum of them like `2h30m`, up to about ten
+/// years.
+pub(crate) fn parse_duration(duration: &str) -> anyhow::Result<Duration> {
+    let invalid = || {
+        anyhow!(
+            "invalid duration `{}`, expected e.g. 30m, 2h or 1h30m",
+            duration
+        )
+    };
+
+    let mut total = Duration::zero();
+    let mut rest = duration;
+    loop {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let amount: i64 = rest[..end].parse().map_err(|_| invalid())?;
+        let mut units = rest[end..].chars();
+        let minutes_per_unit = match units.next() {
+            Some('m') => 1,
+            Some('h') => 60,
+            Some('d') => 24 * 60,
+            _ => return Err(invalid()),
+        };
+        let minutes = amount
+            .checked_mul(minutes_per_unit)
+            .filter(|&minutes| minutes <= MAX_DURATION_MINUTES)
+            .ok_or_else(invalid)?;
+        total = total
+            .checked_add(&Duration::minutes(minutes))
+            .filter(|&total| total <= Duration::minutes(MAX_DURATION_MINUTES))
+            .ok_or_else(invalid)?;
+
+        rest = units.as_str();
+        if rest.is_empty() {
+            return Ok(total);
+        }
+    }
+}
+
+/// The Matrix transaction ID of the announcement of a delivery, the same for all the attempts to
+/// send it so that the homeserver ignores the retries of a message it already received.
+pub(crate) fn transaction_id(delivery_id: &str) -> Uuid {
+    let hash = Sha256::digest(delivery_id.as_bytes());
+    Uuid::from_bytes(
+        hash[..16]
+            .try_into()
+            .expect("SHA-256 hashes are 32 bytes long"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_image() {
+        assert_eq!(
+            first_image(
+                "see ![logo](data:image/png;base64,AAAA) and ![crash](https://user-images.githubusercontent.com/1/crash.png \"title\")"
+            ),
+            Some(Url::parse("https://user-images.githubusercontent.com/1/crash.png").unwrap())
+        );
+        assert_eq!(
+            first_image(r#"<img width="300" src="https://prologin.org/shot.jpg">"#),
+            Some(Url::parse("https://prologin.org/shot.jpg").unwrap())
+        );
+        assert_eq!(first_image("[not an image](https://prologin.org)"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::hours(2));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::days(1));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("2w").is_err());
+        assert_eq!(
+            parse_duration("1d2h30m").unwrap(),
+            Duration::minutes(26 * 60 + 30)
+        );
+        assert!(parse_duration("2h30").is_err());
+        assert!(parse_duration("9223372036854775807d").is_err());
+        assert!(parse_duration("3650d").is_ok());
+        assert!(parse_duration("3650d1m").is_err());
+    }
+
+    #[test]
+    fn test_transaction_id() {
+        let id = "72d3162e-cc78-11e3-81ab-4c9367dc0958";
+        assert_eq!(transaction_id(id), transaction_id(id));
+        assert_ne!(
+            transaction_id(id),
+            transaction_id("72d3162e-cc78-11e3-81ab-4c9367dc0959")
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Assembly of the bots and of their webhook endpoints, used by the binary and by the programs
+//! embedding the bot (e.g. other Prologin services) to add their own sources of events, sinks or
+//! state stores without forking it.
+
+use anyhow::{anyhow, bail, Context};
+use clap::ArgEnum;
+use rocket::{routes, Build, Rocket};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    task::JoinHandle,
+};
+use tracing::error;
+
+use crate::{
+    bot::{
+        state::{StateBackend, StateStore},
+        Prololo,
+    },
+    config::{prepare_profiles, ProloloConfig},
+    mail::run_smtp_listener,
+    queue::{consume, Queue},
+    webhooks::{
+        activity::{activity_export, metrics_export},
+        cors::{prolosite_preflight, Cors},
+        github_webhook,
+        openapi::openapi,
+        parse_deliveries,
+        profile::{DeliverySink, EventSink, Profile, Profiles},
+        prolosite::{django, forum, impersonate, new_school},
+        render::render_preview,
+        status::status_page,
+        Delivery, ParseSettings,
+    },
+};
+
+/// Which parts of the bots run in this process
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The webhook endpoints and the Matrix bot
+    All,
+    /// Only the webhook endpoints, pushing the events to the queue
+    Ingest,
+    /// Only the Matrix bot, reading the events from the queue
+    Sender,
+}
+
+/// Feeds events to a bot besides its webhook endpoints, like the SMTP listener does for mails
+#[rocket::async_trait]
+pub trait EventSource: Send {
+    /// Sends events to the bot until the source stops
+    async fn run(self: Box<Self>, sender: UnboundedSender<Delivery>) -> anyhow::Result<()>;
+}
+
+/// A bot and its endpoints, set up like a profile of the configuration file
+pub struct ProfileBuilder {
+    config: ProloloConfig,
+    sources: Vec<Box<dyn EventSource>>,
+    sink: Option<Box<dyn DeliverySink>>,
+    state: Option<Box<dyn StateBackend>>,
+}
+
+impl ProfileBuilder {
+    pub fn new(config: ProloloConfig) -> Self {
+        Self {
+            config,
+            sources: Vec::new(),
+            sink: None,
+            state: None,
+        }
+    }
+
+    /// Adds a source of events, run along the bot
+    pub fn event_source(mut self, source: impl EventSource + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Sends the events received by the endpoints to `sink` instead of the bot or the queue
+    pub fn sink(mut self, sink: impl DeliverySink + 'static) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Keeps the state of the bot in `backend` instead of the state directory
+    pub fn state_backend(mut self, backend: impl StateBackend + 'static) -> Self {
+        self.state = Some(Box::new(backend));
+        self
+    }
+}
+
+/// Builds the bots of a process and the server of their webhook endpoints.
+///
+/// ```no_run
+/// # async fn run(config: prololo::config::ProloloConfig) -> anyhow::Result<()> {
+/// use prololo::builder::{ProfileBuilder, ProloloBuilder};
+///
+/// let instance = ProloloBuilder::new()
+///     .profile(ProfileBuilder::new(config))
+///     .build()
+///     .await?;
+/// instance.run().await
+/// # }
+/// ```
+pub struct ProloloBuilder {
+    role: Role,
+    profiles: Vec<ProfileBuilder>,
+}
+
+impl Default for ProloloBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProloloBuilder {
+    pub fn new() -> Self {
+        Self {
+            role: Role::All,
+            profiles: Vec::new(),
+        }
+    }
+
+    /// Runs only some parts of the bots, [`Role::All`] by default. The others need a queue or a
+    /// custom sink
+    pub fn role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Adds a bot, with its own endpoints under the `path_prefix` of its config
+    pub fn profile(mut self, profile: ProfileBuilder) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    /// Starts the bots, after checking their configurations with [`prepare_profiles`] and logging
+    /// them in to Matrix. The endpoints are only served once the instance runs.
+    pub async fn build(self) -> anyhow::Result<Instance> {
+        let role = self.role;
+        let (mut configs, extensions): (Vec<_>, Vec<_>) = self
+            .profiles
+            .into_iter()
+            .map(|profile| {
+                (
+                    profile.config,
+                    (profile.sources, profile.sink, profile.state),
+                )
+            })
+            .unzip();
+        prepare_profiles(&mut configs)?;
+
+        let mut rocket = rocket::build();
+        let mut profiles = Vec::new();
+        let mut bots = Vec::new();
+        for (config, (sources, sink, state)) in configs.into_iter().zip(extensions) {
+            if role != Role::All && config.queue.is_none() && sink.is_none() {
+                bail!(
+                    "the bot under {} needs a queue to run only some of its parts",
+                    config.path_prefix
+                );
+            }
+
+            let (sender, receiver) = unbounded_channel();
+            let mut archive = None;
+            let mut activity = None;
+            if role != Role::Ingest {
+                spawn_sources(sources, &sender);
+                if let Some(mail_config) = config.mail.clone() {
+                    let sender = sender.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = run_smtp_listener(mail_config, sender).await {
+                            error!("SMTP listener stopped: {:#}", e);
+                        }
+                    });
+                }
+                if let Some(queue_config) = config.queue.clone() {
+                    let sender = sender.clone();
+                    let settings = ParseSettings::from_config(&config);
+                    tokio::spawn(async move { consume(queue_config, settings, sender).await });
+                }
+
+                let prololo = create_bot(config.clone(), state)?;
+                prololo.init().await.context("failed to init prololo bot")?;
+                archive = Some(prololo.archive());
+                activity = Some(prololo.activity());
+                bots.push(tokio::spawn(async move { prololo.run(receiver).await }));
+            }
+
+            if role != Role::Sender {
+                let sink = match (sink, &config.queue) {
+                    (Some(sink), _) => EventSink::Custom(sink),
+                    (None, Some(queue_config)) => {
+                        EventSink::Queue(Queue::connect(queue_config).await?)
+                    }
+                    (None, None) => {
+                        let (raw_sender, raw_receiver) = unbounded_channel();
+                        let settings = ParseSettings::from_config(&config);
+                        tokio::spawn(parse_deliveries(raw_receiver, settings, sender));
+                        EventSink::Bot(raw_sender)
+                    }
+                };
+                profiles.push(Profile::new(&config, sink, archive, activity));
+
+                rocket = rocket.mount(
+                    config.path_prefix.as_str(),
+                    routes![
+                        github_webhook,
+                        django,
+                        forum,
+                        new_school,
+                        impersonate,
+                        prolosite_preflight,
+                        render_preview,
+                        activity_export,
+                        metrics_export,
+                        openapi,
+                        status_page
+                    ],
+                );
+            }
+        }
+
+        let rocket = match role {
+            Role::Sender => None,
+            _ => Some(rocket.attach(Cors).manage(Profiles(profiles))),
+        };
+        Ok(Instance { rocket, bots })
+    }
+}
+
+fn spawn_sources(sources: Vec<Box<dyn EventSource>>, sender: &UnboundedSender<Delivery>) {
+    for source in sources {
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            if let Err(e) = source.run(sender).await {
+                error!("event source stopped: {:#}", e);
+            }
+        });
+    }
+}
+
+/// Creates a bot, with its state in `state` or in its state directory
+fn create_bot(
+    config: ProloloConfig,
+    state: Option<Box<dyn StateBackend>>,
+) -> anyhow::Result<Prololo> {
+    match state {
+        Some(backend) => {
+            let state = StateStore::open(backend).context("couldn't load bot state")?;
+            Prololo::with_state(config, state)
+        }
+        None => Prololo::new(config),
+    }
+    .context("failed to create prololo bot")
+}
+
+/// The bots of a process, running in the background
+pub struct Instance {
+    /// The server of the webhook endpoints, `None` for [`Role::Sender`]. More routes can be mounted
+    /// on it before running the instance
+    pub rocket: Option<Rocket<Build>>,
+    bots: Vec<JoinHandle<()>>,
+}
+
+impl Instance {
+    /// Serves the endpoints, or waits for the bots if there are none
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self.rocket {
+            Some(rocket) => rocket.launch().await.map_err(|err| anyhow!(err)),
+            None => {
+                for bot in self.bots {
+                    bot.await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        bot::{state::BotState, Severity},
+        mail::MailEvent,
+        webhooks::{prolosite::ProloSiteEventType, Event, RawDelivery, RawEvent},
+    };
+    use rocket::{
+        http::{ContentType, Header, Status},
+        local::asynchronous::Client,
+    };
+
+    use super::*;
+
+    fn config(state_dir: &str) -> ProloloConfig {
+        serde_yaml::from_str(&format!(
+            "{{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_state_dir: '{}', matrix_rooms: {{}}, \
+             destinations: [], github_secret: secret, prolosite_secret: secret}}",
+            state_dir
+        ))
+        .unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn test_split_roles_need_queue() {
+        let error = ProloloBuilder::new()
+            .role(Role::Ingest)
+            .profile(ProfileBuilder::new(config("state")))
+            .build()
+            .await
+            .err()
+            .expect("should need a queue");
+        assert_eq!(
+            error.to_string(),
+            "the bot under / needs a queue to run only some of its parts"
+        );
+    }
+
+    #[rocket::async_test]
+    async fn test_profiles_are_checked() {
+        let error = ProloloBuilder::new()
+            .role(Role::Ingest)
+            .profile(ProfileBuilder::new(config("state")))
+            .profile(ProfileBuilder::new(config("alumni")))
+            .build()
+            .await
+            .err()
+            .expect("should reject the shared path prefix");
+        assert_eq!(error.to_string(), "several profiles use the path prefix /");
+    }
+
+    struct ChannelSink(UnboundedSender<RawDelivery>);
+
+    #[rocket::async_trait]
+    impl DeliverySink for ChannelSink {
+        async fn push(&self, delivery: RawDelivery) -> anyhow::Result<()> {
+            self.0
+                .send(delivery)
+                .map_err(|_| anyhow!("receiver dropped"))
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_custom_sink() {
+        let (sender, mut receiver) = unbounded_channel();
+        let instance = ProloloBuilder::new()
+            .role(Role::Ingest)
+            .profile(ProfileBuilder::new(config("state")).sink(ChannelSink(sender)))
+            .build()
+            .await
+            .unwrap();
+        let client = Client::tracked(instance.rocket.unwrap()).await.unwrap();
+
+        let response = client
+            .post("/api/webhooks/prolosite/forum")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", "secret"))
+            .body(
+                r#"{"username": "alice", "title": "Hello", "url": "https://prologin.org/forum/1"}"#,
+            )
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        match receiver.try_recv() {
+            Ok(RawDelivery {
+                event:
+                    RawEvent::ProloSite {
+                        event_type: ProloSiteEventType::Forum,
+                        payload,
+                    },
+                ..
+            }) => assert_eq!(payload["username"], "alice"),
+            _ => panic!("the sink should have received the event"),
+        }
+    }
+
+    struct OneMail;
+
+    #[rocket::async_trait]
+    impl EventSource for OneMail {
+        async fn run(self: Box<Self>, sender: UnboundedSender<Delivery>) -> anyhow::Result<()> {
+            let mail = MailEvent {
+                sender: "root@server".to_string(),
+                subject: "backup failed".to_string(),
+                body: String::new(),
+                room: "ops".to_string(),
+                severity: Severity::High,
+            };
+            sender
+                .send(Event::Mail(mail).into())
+                .map_err(|_| anyhow!("receiver dropped"))
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_event_source() {
+        let (sender, mut receiver) = unbounded_channel();
+        spawn_sources(vec![Box::new(OneMail)], &sender);
+
+        match receiver.recv().await {
+            Some(Delivery {
+                event: Event::Mail(mail),
+                ..
+            }) => assert_eq!(mail.subject, "backup failed"),
+            _ => panic!("the source should have sent a mail"),
+        }
+    }
+
+    /// Keeps the state as YAML in memory, shared with the test
+    #[derive(Clone, Default)]
+    struct MemoryBackend(Arc<Mutex<Option<String>>>);
+
+    impl StateBackend for MemoryBackend {
+        fn load(&self) -> anyhow::Result<Option<BotState>> {
+            match &*self.0.lock().unwrap() {
+                Some(yaml) => Ok(Some(serde_yaml::from_str(yaml)?)),
+                None => Ok(None),
+            }
+        }
+
+        fn save(&self, state: &BotState) -> anyhow::Result<()> {
+            *self.0.lock().unwrap() = Some(serde_yaml::to_string(state)?);
+            Ok(())
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_state_backend() {
+        let state_dir =
+            std::env::temp_dir().join(format!("prololo-builder-{}", std::process::id()));
+        let backend = MemoryBackend::default();
+        backend
+            .save(&BotState {
+                audit_log_cursor: Some(42),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let prololo = create_bot(
+            config(state_dir.to_str().unwrap()),
+            Some(Box::new(backend.clone())),
+        )
+        .unwrap();
+        {
+            let state = prololo.state();
+            let mut store = state.lock().await;
+            assert_eq!(store.state.audit_log_cursor, Some(42));
+            store.state.audit_log_cursor = Some(43);
+            store.save().unwrap();
+        }
+        assert_eq!(backend.load().unwrap().unwrap().audit_log_cursor, Some(43));
+
+        std::fs::remove_dir_all(state_dir).ok();
+    }
+}
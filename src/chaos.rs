@@ -0,0 +1,108 @@
+//! Faults injected on purpose, so that the resilience features (timeouts, circuit breakers, the
+//! queue) can be exercised in tests rather than discovered in production. Only built with the
+//! `chaos` feature, they are enabled by the undocumented `chaos` setting of a profile, e.g.:
+//!
+//! ```yaml
+//! chaos:
+//!   send_failure_rate: 0.2
+//!   parse_delay_ms: 500
+//!   sync_drop_rate: 0.1
+//! ```
+
+use std::time::Duration;
+
+use anyhow::bail;
+use matrix_sdk::{
+    ruma::{events::room::message::MessageEventContent, EventId, RoomId},
+    uuid::Uuid,
+};
+use tracing::warn;
+
+use crate::{bot::MessageSender, config::ChaosConfig};
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::random::<f64>() < rate
+}
+
+/// Waits before a payload is parsed, as if the process was overloaded
+pub async fn delay_parse(chaos: Option<&ChaosConfig>) {
+    if let Some(chaos) = chaos.filter(|chaos| chaos.parse_delay_ms > 0) {
+        tokio::time::sleep(Duration::from_millis(chaos.parse_delay_ms)).await;
+    }
+}
+
+/// Whether the sync loop should stop after a sync, as if the connection dropped
+pub fn drop_sync(chaos: Option<&ChaosConfig>) -> bool {
+    let dropped = chaos.map_or(false, |chaos| roll(chaos.sync_drop_rate));
+    if dropped {
+        warn!("chaos: dropping sync");
+    }
+    dropped
+}
+
+/// A [`MessageSender`] failing at random
+pub struct ChaosSender<'a, S> {
+    pub sender: &'a S,
+    pub chaos: Option<&'a ChaosConfig>,
+}
+
+#[rocket::async_trait]
+impl<S: MessageSender> MessageSender for ChaosSender<'_, S> {
+    async fn send_message(
+        &self,
+        room_id: &RoomId,
+        content: MessageEventContent,
+        txn_id: Option<Uuid>,
+    ) -> anyhow::Result<EventId> {
+        if self
+            .chaos
+            .map_or(false, |chaos| roll(chaos.send_failure_rate))
+        {
+            bail!("chaos: injected failure sending to {}", room_id);
+        }
+        self.sender.send_message(room_id, content, txn_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    struct Sender;
+
+    #[rocket::async_trait]
+    impl MessageSender for Sender {
+        async fn send_message(
+            &self,
+            _room_id: &RoomId,
+            _content: MessageEventContent,
+            _txn_id: Option<Uuid>,
+        ) -> anyhow::Result<EventId> {
+            Ok(EventId::try_from("$event:prologin.org")?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chaos_sender() {
+        let room_id = RoomId::try_from("!main:prologin.org").unwrap();
+        let room_id = &room_id;
+        let send = |chaos| async move {
+            let sender = ChaosSender {
+                sender: &Sender,
+                chaos,
+            };
+            let content = MessageEventContent::text_plain("hello");
+            sender.send_message(room_id, content, None).await
+        };
+
+        assert!(send(None).await.is_ok());
+        let chaos = ChaosConfig {
+            send_failure_rate: 1.0,
+            ..Default::default()
+        };
+        assert!(send(Some(&chaos)).await.is_err());
+        assert!(!drop_sync(Some(&chaos)));
+    }
+}
@@ -1,11 +1,21 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::BufReader,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::anyhow;
-use matrix_sdk::ruma::RoomId;
+use anyhow::{anyhow, bail, Context};
+use chrono::Weekday;
+use matrix_sdk::ruma::{RoomId, UserId};
 use regex::Regex;
 use serde::Deserialize;
 use url::Url;
 
+use crate::bot::Severity;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ProloloConfig {
     /// The URL for the homeserver we should connect to
@@ -16,14 +26,210 @@ pub struct ProloloConfig {
     pub matrix_password: String,
     /// Path to a directory where the bot will store Matrix state and current session information.
     pub matrix_state_dir: PathBuf,
+    /// Where the Matrix session is saved, `matrix-session.yaml` in the working directory by
+    /// default
+    #[serde(default = "default_matrix_session_file")]
+    pub matrix_session_file: PathBuf,
+    /// Prefix of the paths of the webhook endpoints, to tell apart the bots running in the same
+    /// process. `/` by default
+    #[serde(default = "default_path_prefix")]
+    pub path_prefix: String,
     /// Matrix rooms that the bot should join. The bot will only accept invitations to these rooms.
     pub matrix_rooms: HashMap<String, RoomConfig>,
     /// Mappings from all repos matching a certain regex, to a specific Matrix room
     pub destinations: Vec<Destination>,
+    /// Rooms receiving a copy of the messages announced in another room, e.g. a read-only room on
+    /// another server. Keys and values are room names, as used in `matrix_rooms`
+    #[serde(default)]
+    pub mirrors: HashMap<String, Vec<String>>,
     /// Secret used to verify HMAC signature of GitHub webhooks
     pub github_secret: String,
     /// Secret token used in Authorization header for Prologin site hooks
     pub prolosite_secret: String,
+    /// Settings for the formatting of GitHub events
+    #[serde(default)]
+    pub github: GitHubConfig,
+    /// Settings for the monthly merged PRs leaderboard
+    #[serde(default)]
+    pub leaderboard: LeaderboardConfig,
+    /// Settings for the SMTP listener relaying incoming mails, disabled if unset
+    pub mail: Option<MailConfig>,
+    /// Settings for the XMPP gateway mirroring announcements to MUCs, disabled if unset
+    pub xmpp: Option<XmppConfig>,
+    /// Settings for the push notifications sent for severe messages, disabled if unset
+    pub push: Option<PushConfig>,
+    /// Rules adjusting or dropping incoming payloads, only the first matching rule applies
+    #[serde(default)]
+    pub rules: Vec<TransformRule>,
+    /// What to do with GitHub `@user` mentions found in messages, `keep` by default
+    #[serde(default)]
+    pub mentions: MentionMode,
+    /// Who is pinged in the messages of each severity, nobody by default
+    #[serde(default)]
+    pub pings: PingsConfig,
+    /// Settings for attaching the images found in relayed comments, disabled if unset
+    pub images: Option<ImageConfig>,
+    /// Matrix users of people on GitHub, keys are GitHub logins. They are mentioned in the
+    /// announcements instead of their login, so that they get notified
+    #[serde(default)]
+    pub user_mapping: HashMap<String, UserId>,
+    /// Settings for the reminders about PRs waiting for a review, disabled if unset
+    pub reviews: Option<ReviewsConfig>,
+    /// Settings for the weekly list of old open PRs and issues, disabled if unset
+    pub stale: Option<StaleConfig>,
+    /// Settings for relaying the critical entries of the organization's audit log to
+    /// [`GitHubConfig::security_room`], which must be set, disabled if unset
+    pub audit_log: Option<AuditLogConfig>,
+    /// Settings for the weekly report of the organization members without two-factor
+    /// authentication, disabled if unset
+    pub two_factor: Option<TwoFactorConfig>,
+    /// Settings for the checks of the deliveries of the bot's own webhook, disabled if unset
+    pub deliveries: Option<DeliveriesConfig>,
+    /// Room where the events the bot couldn't handle are reported, they are only logged if unset
+    pub errors_room: Option<String>,
+    /// Seconds during which a message identical to the last one sent to the same room is dropped,
+    /// e.g. when an event is delivered by both an organization and a repo webhook. Disabled if
+    /// unset or 0, since legitimate messages can be identical too (e.g. a workflow failing twice
+    /// in a row). The first duplicate of each room is reported in `errors_room`
+    #[serde(default)]
+    pub duplicate_window_seconds: u64,
+    /// Directory whose files replace the templates and locales embedded in the binary, e.g.
+    /// `templates/incident.txt` or `locales/fr.yaml`
+    pub assets_dir: Option<PathBuf>,
+    /// Release cadences of some repos, reminders to cut a release are posted in their room
+    #[serde(default)]
+    pub release_trains: Vec<ReleaseTrain>,
+    /// Settings for the pinned status of the deployment environments, disabled if unset
+    pub deployments: Option<DeploymentsConfig>,
+    /// Settings for the `!incident` command, disabled if unset
+    pub incidents: Option<IncidentsConfig>,
+    /// Limits of the archive of sent messages, used by `!timeline` and `!find`, which is kept
+    /// forever if unset
+    pub retention: Option<RetentionConfig>,
+    /// Settings for the browsers calling the Prologin site endpoints, e.g. from a dashboard, they
+    /// can't read the responses if unset
+    pub browser: Option<BrowserConfig>,
+    /// Redis queue between the webhook endpoints and the Matrix sender, needed to run them as
+    /// separate processes. The endpoints hand the events directly to the bot if unset
+    pub queue: Option<QueueConfig>,
+    /// Limits on how long the requests to the homeserver and other services can take
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    /// Settings for holding back the messages of the rooms where sending keeps failing, disabled if
+    /// unset
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Settings for comparing the messages of the current rules with those of new rules on live
+    /// events, disabled if unset
+    pub canary: Option<CanaryConfig>,
+    // faults injected in tests, see `crate::chaos`
+    #[doc(hidden)]
+    #[cfg(any(test, feature = "chaos"))]
+    pub chaos: Option<ChaosConfig>,
+}
+
+fn default_matrix_session_file() -> PathBuf {
+    PathBuf::from("matrix-session.yaml")
+}
+
+fn default_path_prefix() -> String {
+    "/".to_string()
+}
+
+/// A configuration file, either for a single bot or for several independent ones (e.g. with
+/// different Matrix accounts) running in the same process
+#[derive(Debug, Deserialize)]
+struct ProfilesConfig {
+    profiles: Vec<ProloloConfig>,
+}
+
+/// Loads the configuration of the bots from a YAML file.
+pub fn load_profiles(path: &Path) -> anyhow::Result<Vec<ProloloConfig>> {
+    let file = File::open(path).with_context(|| format!("couldn't open {}:", path.display()))?;
+    let value: serde_yaml::Value =
+        serde_yaml::from_reader(BufReader::new(file)).context("couldn't parse config file")?;
+
+    parse_profiles(value)
+}
+
+fn parse_profiles(value: serde_yaml::Value) -> anyhow::Result<Vec<ProloloConfig>> {
+    let mut profiles = if value.get("profiles").is_some() {
+        serde_yaml::from_value::<ProfilesConfig>(value)
+            .context("couldn't parse config file")?
+            .profiles
+    } else {
+        vec![serde_yaml::from_value(value).context("couldn't parse config file")?]
+    };
+    prepare_profiles(&mut profiles)?;
+
+    Ok(profiles)
+}
+
+/// Checks that the bots of a process can run together, and fills the settings derived from
+/// others. Done by [`load_profiles`], and by [`crate::builder::ProloloBuilder`] for the
+/// configurations built by other means.
+pub fn prepare_profiles(profiles: &mut [ProloloConfig]) -> anyhow::Result<()> {
+    // the bots would overwrite each other's files, or receive each other's events
+    let mut prefixes = HashSet::new();
+    let mut paths = HashSet::new();
+    let mut queues = HashSet::new();
+    for profile in profiles.iter() {
+        for train in &profile.release_trains {
+            if let Some(week) = train.week.filter(|week| !(1..=5).contains(week)) {
+                bail!(
+                    "invalid week {} in the release train of {}, expected 1 to 5",
+                    week,
+                    train.repo
+                );
+            }
+        }
+        if let Some(push) = &profile.push {
+            if push.url.cannot_be_a_base() {
+                bail!("invalid push notification URL {}", push.url);
+            }
+        }
+        if profile.audit_log.is_some() && profile.github.security_room.is_none() {
+            bail!("the audit log is only relayed to the security room, which isn't set");
+        }
+        if let Some(mail) = &profile.mail {
+            // anyone who can reach the SMTP listener can send messages to the rooms
+            if !mail.listen.ip().is_loopback() {
+                bail!(
+                    "the SMTP listener must bind to a loopback address, not {}",
+                    mail.listen
+                );
+            }
+        }
+        if !prefixes.insert(profile.path_prefix.trim_end_matches('/')) {
+            bail!(
+                "several profiles use the path prefix {}",
+                profile.path_prefix
+            );
+        }
+        for path in [&profile.matrix_state_dir, &profile.matrix_session_file] {
+            if !paths.insert(path) {
+                bail!("several profiles use {}", path.display());
+            }
+        }
+        if let Some(queue) = &profile.queue {
+            if !queues.insert((&queue.redis_url, &queue.key)) {
+                bail!("several profiles use the queue {}", queue.key);
+            }
+        }
+    }
+
+    // the GitHub handlers only get the GitHub settings, including the ones of trial rooms
+    for profile in profiles.iter_mut() {
+        profile.github.user_mapping = profile.user_mapping.clone();
+        add_trailing_slashes(&mut profile.github);
+        for room in profile.matrix_rooms.values_mut() {
+            if let Some(trial) = &mut room.trial {
+                trial.github.user_mapping = profile.user_mapping.clone();
+                add_trailing_slashes(&mut trial.github);
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -33,6 +239,102 @@ pub struct RoomConfig {
     /// The default room will receive all messages that didn't match any destination
     #[serde(default)]
     default: bool,
+    /// Lua script rewriting or vetoing the messages announced in this room
+    pub hook: Option<PathBuf>,
+    /// Filter applied to the messages announced in this room, e.g. for public rooms
+    pub filter: Option<ContentFilterConfig>,
+    /// Formatting trialled in this room on a copy of another room's messages, disabled if unset
+    pub trial: Option<TrialConfig>,
+    /// Greeting of the people joining this room, disabled if unset
+    pub welcome: Option<WelcomeConfig>,
+    /// Pings of the messages of this room, replacing the ones of [`PingsConfig::severities`] for
+    /// the listed severities
+    #[serde(default)]
+    pub pings: BTreeMap<Severity, PingTarget>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PingsConfig {
+    /// The ping of the messages of each severity, e.g. `{high: room}`
+    #[serde(default)]
+    pub severities: BTreeMap<Severity, PingTarget>,
+    /// Minutes during which `@room` isn't used again in the same room, 30 by default. The people
+    /// listed by name are always pinged
+    #[serde(default = "default_room_ping_cooldown")]
+    pub room_cooldown_minutes: u64,
+}
+
+impl Default for PingsConfig {
+    fn default() -> Self {
+        Self {
+            severities: BTreeMap::new(),
+            room_cooldown_minutes: default_room_ping_cooldown(),
+        }
+    }
+}
+
+fn default_room_ping_cooldown() -> u64 {
+    30
+}
+
+/// Who is pinged: `none`, `room` for `@room`, or a list of Matrix IDs
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PingTarget {
+    Mode(PingMode),
+    Users(Vec<UserId>),
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PingMode {
+    None,
+    /// `@room`, the bot needs the power level to notify the whole room
+    Room,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WelcomeConfig {
+    /// Where newcomers are greeted, in the room by default
+    #[serde(default)]
+    pub to: WelcomeTarget,
+    /// Text added to the greeting, e.g. the rules of the room
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WelcomeTarget {
+    /// A message in the room, mentioning the newcomer
+    Room,
+    /// A direct message, in a new room with the newcomer
+    Direct,
+}
+
+impl Default for WelcomeTarget {
+    fn default() -> Self {
+        Self::Room
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TrialConfig {
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) whose messages are copied
+    pub source: String,
+    /// Settings for the formatting of the copied GitHub events, replacing
+    /// [`ProloloConfig::github`]
+    #[serde(default)]
+    pub github: GitHubConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ContentFilterConfig {
+    /// Words masked with asterisks, matched case-insensitively as whole words
+    #[serde(default)]
+    pub words: Vec<String>,
+    /// Neutralize room-wide pings such as `@room` by removing their `@`
+    #[serde(default)]
+    pub strip_pings: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -44,6 +346,750 @@ pub struct Destination {
     pub regex: Regex,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct GitHubConfig {
+    /// Only announce stars when the repo's star count is a multiple of this, so busy repos don't
+    /// spam the room. Every star is announced by default
+    #[serde(default = "default_star_threshold")]
+    pub star_threshold: u64,
+    /// Number of characters of commit hashes shown in messages, 7 by default
+    #[serde(default = "default_short_hash_length")]
+    pub short_hash_length: usize,
+    /// Symbol shown before branch names, `⊶` by default
+    #[serde(default = "default_branch_symbol")]
+    pub branch_symbol: String,
+    /// Number of commits listed in the announcements of pushes of several commits, 5 by default.
+    /// The list is left out if 0
+    #[serde(default = "default_push_commits")]
+    pub push_commits: usize,
+    /// Seconds during which the consecutive pushes to a branch are combined into a single message,
+    /// counted from the first one. Pushes are announced right away if 0, the default
+    #[serde(default)]
+    pub push_debounce_seconds: u64,
+    /// Size classes shown in the announcements of opened PRs
+    #[serde(default)]
+    pub pr_sizes: PrSizeThresholds,
+    /// Only announce failed workflow runs, successful ones are announced too if disabled
+    #[serde(default = "default_workflow_failures_only")]
+    pub workflow_failures_only: bool,
+    /// Announce the events without a dedicated message (e.g. `watch`) with a generic one instead of
+    /// rejecting them
+    #[serde(default)]
+    pub generic_events: bool,
+    /// Announce the actions a handler doesn't support yet (e.g. new ones added by GitHub) with a
+    /// generic message instead of ignoring them
+    #[serde(default)]
+    pub announce_unknown_actions: bool,
+    /// Regexes matching the protected branches, where pushes changing submodules or LFS settings
+    /// are flagged. Nothing is flagged if empty
+    #[serde(default, with = "serde_regex")]
+    pub protected_branches: Vec<Regex>,
+    /// Base URL of the GitHub REST API, `https://api.github.com/` by default. A trailing slash is
+    /// added if missing, e.g. `https://ghe.example.org/api/v3` is read as
+    /// `https://ghe.example.org/api/v3/`, otherwise its last segment would be dropped when joining
+    /// paths to it
+    #[serde(default = "default_github_api_url")]
+    pub api_url: Url,
+    /// Base URL of the GitHub web interface, `https://github.com/` by default, used for the links to
+    /// organization pages and user profiles. The links to repositories follow their `html_url`,
+    /// e.g. on GitHub Enterprise. A trailing slash is added if missing, like for `api_url`
+    #[serde(default = "default_github_web_url")]
+    pub web_url: Url,
+    /// Token used to authenticate to the GitHub API, needed for private repos
+    pub api_token: Option<String>,
+    /// Sensitive paths of some repos, pushes and PRs touching them are escalated
+    #[serde(default)]
+    pub watched_paths: Vec<WatchedPaths>,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving escalated messages,
+    /// security advisories and vulnerability alerts, they stay in their usual room if unset
+    pub security_room: Option<String>,
+    /// Ecosystems of the packages used by the organization (e.g. `pip` or `npm`), the security
+    /// advisories are only announced if they affect one of them or one of `advisory_packages`
+    #[serde(default)]
+    pub advisory_ecosystems: Vec<String>,
+    /// Packages used by the organization whose security advisories are announced, whatever their
+    /// ecosystem
+    #[serde(default)]
+    pub advisory_packages: Vec<String>,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving the changes of the
+    /// organization's project boards, the default room is used if unset
+    pub planning_room: Option<String>,
+    /// Copy of [`ProloloConfig::user_mapping`], filled when the config file is loaded
+    #[serde(skip)]
+    pub user_mapping: HashMap<String, UserId>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WatchedPaths {
+    /// The regex matching the full names of the repos these paths apply to
+    #[serde(with = "serde_regex")]
+    pub repo: Regex,
+    /// Watched files, or directories if they end with a `/`, e.g. `.github/workflows/`
+    pub paths: Vec<String>,
+}
+
+impl WatchedPaths {
+    fn watches(&self, file: &str) -> bool {
+        self.paths.iter().any(|path| {
+            if path.ends_with('/') {
+                file.starts_with(path.as_str())
+            } else {
+                file == path
+            }
+        })
+    }
+}
+
+/// Largest number of changed lines (additions and deletions) of each PR size class, bigger PRs
+/// are `XL`
+#[derive(Debug, Deserialize, Clone)]
+pub struct PrSizeThresholds {
+    pub xs: u64,
+    pub s: u64,
+    pub m: u64,
+    pub l: u64,
+}
+
+impl PrSizeThresholds {
+    /// The size class of a PR changing `lines` lines
+    pub fn classify(&self, lines: u64) -> &'static str {
+        if lines <= self.xs {
+            "XS"
+        } else if lines <= self.s {
+            "S"
+        } else if lines <= self.m {
+            "M"
+        } else if lines <= self.l {
+            "L"
+        } else {
+            "XL"
+        }
+    }
+}
+
+impl Default for PrSizeThresholds {
+    fn default() -> Self {
+        Self {
+            xs: 10,
+            s: 100,
+            m: 500,
+            l: 1000,
+        }
+    }
+}
+
+impl GitHubConfig {
+    /// Whether some paths of a repo are watched, `repo` being its full name
+    pub fn watches_repo(&self, repo: &str) -> bool {
+        self.watched_paths
+            .iter()
+            .any(|watched| watched.repo.is_match(repo))
+    }
+
+    /// Filters the watched files of a repo among `files`, without duplicates
+    pub fn watched_files<'a>(
+        &self,
+        repo: &str,
+        files: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        let watched: Vec<&WatchedPaths> = self
+            .watched_paths
+            .iter()
+            .filter(|watched| watched.repo.is_match(repo))
+            .collect();
+
+        let mut res: Vec<String> = Vec::new();
+        for file in files {
+            let is_new = !res.iter().any(|f| f == file);
+            if is_new && watched.iter().any(|watched| watched.watches(file)) {
+                res.push(file.to_string());
+            }
+        }
+        res
+    }
+}
+
+impl Default for GitHubConfig {
+    fn default() -> Self {
+        Self {
+            star_threshold: default_star_threshold(),
+            short_hash_length: default_short_hash_length(),
+            branch_symbol: default_branch_symbol(),
+            push_commits: default_push_commits(),
+            push_debounce_seconds: 0,
+            pr_sizes: PrSizeThresholds::default(),
+            workflow_failures_only: default_workflow_failures_only(),
+            generic_events: false,
+            announce_unknown_actions: false,
+            protected_branches: Vec::new(),
+            api_url: default_github_api_url(),
+            web_url: default_github_web_url(),
+            api_token: None,
+            watched_paths: Vec::new(),
+            security_room: None,
+            advisory_ecosystems: Vec::new(),
+            advisory_packages: Vec::new(),
+            planning_room: None,
+            user_mapping: HashMap::new(),
+        }
+    }
+}
+
+fn default_push_commits() -> usize {
+    5
+}
+
+fn default_star_threshold() -> u64 {
+    1
+}
+
+fn default_short_hash_length() -> usize {
+    7
+}
+
+fn default_branch_symbol() -> String {
+    "⊶".to_string()
+}
+
+fn default_workflow_failures_only() -> bool {
+    true
+}
+
+/// `Url::join` replaces the last segment of a base without a trailing slash, the API and web URLs
+/// are only used as bases
+fn add_trailing_slashes(config: &mut GitHubConfig) {
+    for url in [&mut config.api_url, &mut config.web_url] {
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+    }
+}
+
+fn default_github_api_url() -> Url {
+    Url::parse("https://api.github.com/").expect("valid URL")
+}
+
+fn default_github_web_url() -> Url {
+    Url::parse("https://github.com/").expect("valid URL")
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct LeaderboardConfig {
+    /// Post the previous month's leaderboard every time a new month starts
+    #[serde(default)]
+    pub monthly_post: bool,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving the monthly post, the
+    /// default room is used if unset
+    pub room: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MailConfig {
+    /// The loopback address the SMTP listener binds to, e.g. `127.0.0.1:2525`
+    pub listen: SocketAddr,
+    /// Mails are relayed to the room of the first matching rule, and dropped if no rule matches
+    pub rules: Vec<MailRule>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MailRule {
+    /// The room name as used in [`ProloloConfig::matrix_rooms`]
+    pub room: String,
+    /// The regex matched against the envelope sender, any sender matches if unset
+    #[serde(default, with = "serde_regex")]
+    pub sender: Option<Regex>,
+    /// The regex matched against the mail subject, any subject matches if unset
+    #[serde(default, with = "serde_regex")]
+    pub subject: Option<Regex>,
+    /// The severity of the relayed messages, `normal` by default
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+impl MailRule {
+    pub fn matches(&self, sender: &str, subject: &str) -> bool {
+        self.sender.as_ref().map_or(true, |re| re.is_match(sender))
+            && self
+                .subject
+                .as_ref()
+                .map_or(true, |re| re.is_match(subject))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct XmppConfig {
+    /// The `host:port` of the XMPP server's component port
+    pub server: String,
+    /// The JID of the component, as declared on the XMPP server
+    pub component: String,
+    /// The component secret shared with the XMPP server
+    pub secret: String,
+    /// The nickname used in MUCs
+    #[serde(default = "default_xmpp_nickname")]
+    pub nickname: String,
+    /// Mappings from room names (as used in [`ProloloConfig::matrix_rooms`]) to the JID of the MUC
+    /// mirroring their announcements
+    pub rooms: HashMap<String, String>,
+}
+
+fn default_xmpp_nickname() -> String {
+    "prololo".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PushConfig {
+    /// The push notification service, `ntfy` by default
+    #[serde(default)]
+    pub service: PushService,
+    /// For ntfy, the URL of the topic. For Gotify, the URL of the server
+    pub url: Url,
+    /// The ntfy access token, or the Gotify application token
+    pub token: Option<String>,
+    /// Messages less severe than this aren't pushed, `high` by default
+    #[serde(default = "default_push_min_severity")]
+    pub min_severity: Severity,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PushService {
+    Ntfy,
+    Gotify,
+}
+
+impl Default for PushService {
+    fn default() -> Self {
+        Self::Ntfy
+    }
+}
+
+fn default_push_min_severity() -> Severity {
+    Severity::High
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MentionMode {
+    /// Relay mentions verbatim
+    Keep,
+    /// Remove the `@` of mentions
+    Strip,
+    /// Replace mentions with a link to the user's GitHub profile
+    Link,
+}
+
+impl Default for MentionMode {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ImageConfig {
+    /// Images bigger than this many bytes aren't attached, 2 MiB by default
+    #[serde(default = "default_image_max_size")]
+    pub max_size: usize,
+    /// Prefixes of the URLs of the images that can be downloaded, GitHub's image hosts by default.
+    /// The URLs come from untrusted payloads, they must not reach internal services
+    #[serde(default = "default_image_hosts")]
+    pub allowed_urls: Vec<Url>,
+}
+
+impl ImageConfig {
+    pub fn allows(&self, url: &Url) -> bool {
+        self.allowed_urls.iter().any(|allowed| {
+            url.scheme() == allowed.scheme()
+                && url.host() == allowed.host()
+                && url.port_or_known_default() == allowed.port_or_known_default()
+                && url.path().starts_with(allowed.path())
+        })
+    }
+}
+
+fn default_image_max_size() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_image_hosts() -> Vec<Url> {
+    [
+        "https://user-images.githubusercontent.com/",
+        "https://private-user-images.githubusercontent.com/",
+        "https://github.com/user-attachments/",
+    ]
+    .iter()
+    .map(|url| Url::parse(url).expect("valid URL"))
+    .collect()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TimeoutsConfig {
+    /// Seconds to wait for an answer of the homeserver, 10 by default. The long polling of the
+    /// syncs comes on top of it
+    #[serde(default = "default_matrix_request_timeout")]
+    pub matrix_request_seconds: u64,
+    /// Seconds a Matrix operation (e.g. sending a message or logging in) can take, including the
+    /// retries of the failed requests, 60 by default
+    #[serde(default = "default_matrix_timeout")]
+    pub matrix_seconds: u64,
+    /// Seconds a request to another service (e.g. the GitHub API or a push server) can take, 30 by
+    /// default
+    #[serde(default = "default_http_timeout")]
+    pub http_seconds: u64,
+}
+
+impl TimeoutsConfig {
+    pub fn matrix_request(&self) -> Duration {
+        Duration::from_secs(self.matrix_request_seconds)
+    }
+
+    pub fn matrix(&self) -> Duration {
+        Duration::from_secs(self.matrix_seconds)
+    }
+
+    pub fn http(&self) -> Duration {
+        Duration::from_secs(self.http_seconds)
+    }
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            matrix_request_seconds: default_matrix_request_timeout(),
+            matrix_seconds: default_matrix_timeout(),
+            http_seconds: default_http_timeout(),
+        }
+    }
+}
+
+fn default_matrix_request_timeout() -> u64 {
+    10
+}
+
+fn default_matrix_timeout() -> u64 {
+    60
+}
+
+fn default_http_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failed sends to a room before its messages are held back, 3 by default
+    #[serde(default = "default_breaker_failures")]
+    pub failures: u32,
+    /// Seconds during which the messages of a room are held back before trying again, 300 by
+    /// default
+    #[serde(default = "default_breaker_cooldown")]
+    pub cooldown_seconds: u64,
+    /// What happens to the held back messages, `drop` by default
+    #[serde(default)]
+    pub held_messages: HeldMessages,
+    /// Number of messages buffered for each room, the oldest ones are dropped first. 100 by
+    /// default
+    #[serde(default = "default_breaker_buffer_size")]
+    pub buffer_size: usize,
+}
+
+impl CircuitBreakerConfig {
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_seconds)
+    }
+}
+
+fn default_breaker_failures() -> u32 {
+    3
+}
+
+fn default_breaker_cooldown() -> u64 {
+    5 * 60
+}
+
+fn default_breaker_buffer_size() -> usize {
+    100
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HeldMessages {
+    /// The messages are lost
+    Drop,
+    /// The messages are sent once the room works again, without being archived nor mirrored
+    Buffer,
+}
+
+impl Default for HeldMessages {
+    fn default() -> Self {
+        Self::Drop
+    }
+}
+
+/// A new set of transformation rules, validated against real traffic before replacing
+/// [`ProloloConfig::rules`]. The events are still announced with the current rules, the messages
+/// that would change are posted to the review room instead.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CanaryConfig {
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving the differences
+    pub review_room: String,
+    /// The rules being validated
+    #[serde(default)]
+    pub rules: Vec<TransformRule>,
+}
+
+/// Faults injected on purpose, never set it outside of tests
+#[doc(hidden)]
+#[cfg(any(test, feature = "chaos"))]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ChaosConfig {
+    /// Probability that sending a message to Matrix fails
+    #[serde(default)]
+    pub send_failure_rate: f64,
+    /// Milliseconds to wait before parsing each payload
+    #[serde(default)]
+    pub parse_delay_ms: u64,
+    /// Probability that the sync loop is interrupted after a sync, and restarted
+    #[serde(default)]
+    pub sync_drop_rate: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReviewsConfig {
+    /// Hours a PR can wait for its first review before its code owners are reminded, 24 by default
+    #[serde(default = "default_review_delay_hours")]
+    pub delay_hours: u64,
+}
+
+fn default_review_delay_hours() -> u64 {
+    24
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuditLogConfig {
+    /// The organization whose audit log is polled
+    pub org: String,
+    /// Token allowed to read the audit log, used instead of [`GitHubConfig::api_token`]
+    pub token: String,
+    /// Minutes between two polls, 5 by default
+    #[serde(default = "default_audit_log_interval")]
+    pub interval_minutes: u64,
+    /// The relayed actions, the authorizations of OAuth apps, changes of two-factor authentication
+    /// settings and added deploy keys by default
+    #[serde(default = "default_audit_log_actions")]
+    pub actions: Vec<String>,
+}
+
+impl AuditLogConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_minutes * 60)
+    }
+}
+
+fn default_audit_log_interval() -> u64 {
+    5
+}
+
+fn default_audit_log_actions() -> Vec<String> {
+    vec![
+        "oauth_authorization.create".to_string(),
+        "org.oauth_app_access_approved".to_string(),
+        "two_factor_authentication.disabled".to_string(),
+        "org.disable_two_factor_requirement".to_string(),
+        "org.enable_two_factor_requirement".to_string(),
+        "public_key.create".to_string(),
+    ]
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TwoFactorConfig {
+    /// The organization whose members are checked
+    pub org: String,
+    /// Token of an owner of the organization, only owners can see who has two-factor
+    /// authentication. [`GitHubConfig::api_token`] is used if unset
+    pub token: Option<String>,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving the report, it should
+    /// be private
+    pub room: String,
+    /// Day of the week of the report (e.g. `Mon` or `Friday`), Monday by default
+    #[serde(default = "default_two_factor_weekday")]
+    pub weekday: Weekday,
+}
+
+fn default_two_factor_weekday() -> Weekday {
+    Weekday::Mon
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BrowserConfig {
+    /// Origins of the pages allowed to call the Prologin site endpoints, e.g.
+    /// `https://dashboard.prologin.org`, or `*` for any page
+    pub allowed_origins: Vec<String>,
+    /// Token of the pages, accepted instead of `prolosite_secret` on the endpoints listed in
+    /// `routes` only. It can be given in the `Authorization` header or, for the pages that can't
+    /// set headers, in a `token` query parameter: it then ends up in the access logs
+    pub token: Option<String>,
+    /// The endpoints accepting `token`, none by default
+    #[serde(default)]
+    pub routes: Vec<BrowserRoute>,
+}
+
+/// An endpoint of the Prologin site that pages can call with [`BrowserConfig::token`]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BrowserRoute {
+    Forum,
+    NewSchool,
+}
+
+impl BrowserRoute {
+    /// The name of the route handling this endpoint
+    pub fn route_name(self) -> &'static str {
+        match self {
+            Self::Forum => "forum",
+            Self::NewSchool => "new_school",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeliveriesConfig {
+    /// The organization owning the webhook
+    pub org: String,
+    /// The ID of the webhook sending events to the bot, shown in its settings URL
+    pub hook_id: u64,
+    /// Token of an owner of the organization, [`GitHubConfig::api_token`] is used if unset
+    pub token: Option<String>,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) alerted when deliveries fail
+    pub room: String,
+    /// Minutes between two checks, 10 by default
+    #[serde(default = "default_deliveries_interval")]
+    pub interval_minutes: u64,
+}
+
+impl DeliveriesConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_minutes * 60)
+    }
+}
+
+fn default_deliveries_interval() -> u64 {
+    10
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StaleConfig {
+    /// Full names of the checked repos, each list is posted in the repo's room
+    pub repos: Vec<String>,
+    /// Open PRs and issues older than this many days are listed, 30 by default
+    #[serde(default = "default_stale_days")]
+    pub days: u64,
+    /// Day of the week of the post (e.g. `Mon` or `Friday`), Monday by default
+    #[serde(default = "default_stale_weekday")]
+    pub weekday: Weekday,
+}
+
+fn default_stale_days() -> u64 {
+    30
+}
+
+fn default_stale_weekday() -> Weekday {
+    Weekday::Mon
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeploymentsConfig {
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) where the status message is
+    /// pinned
+    pub room: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IncidentsConfig {
+    /// Matrix users invited to the room of every incident
+    #[serde(default)]
+    pub on_call: Vec<UserId>,
+    /// Matrix users allowed to open and close incidents besides the on-call people
+    #[serde(default)]
+    pub allowed_users: Vec<UserId>,
+    /// Room names (as used in [`ProloloConfig::matrix_rooms`]) where anyone can open and close
+    /// incidents
+    #[serde(default)]
+    pub rooms: Vec<String>,
+    /// Message posted in the room of new incidents, where `{title}` and `{sender}` are replaced.
+    /// `templates/incident.txt` of the assets is used if unset
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetentionConfig {
+    /// Archived messages older than this number of days are removed
+    pub max_age_days: Option<u64>,
+    /// Maximum size of the archive in bytes, the oldest messages are removed first
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct QueueConfig {
+    /// e.g. `redis://localhost:6379/0`
+    pub redis_url: String,
+    /// The Redis list holding the pending events, `prololo:events` by default
+    #[serde(default = "default_queue_key")]
+    pub key: String,
+}
+
+fn default_queue_key() -> String {
+    "prololo:events".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReleaseTrain {
+    /// The repo's full name
+    pub repo: String,
+    /// Day of the week of the releases (e.g. `Mon` or `Friday`)
+    pub weekday: Weekday,
+    /// Only release on the n-th `weekday` of the month (e.g. 1 for every first Monday), from 1 to
+    /// 5. Releases are weekly if unset
+    pub week: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TransformRule {
+    /// The source of the payload, any source matches if unset
+    pub source: Option<PayloadSource>,
+    /// The event type (e.g. `push` for GitHub, `django` for the Prologin site), any event matches
+    /// if unset
+    pub event: Option<String>,
+    /// Conditions on the payload's values, all of them must match
+    #[serde(default, rename = "match")]
+    pub matches: Vec<PointerMatch>,
+    /// Don't announce matching payloads at all
+    #[serde(default)]
+    pub drop: bool,
+    /// The room name (as used in [`ProloloConfig::matrix_rooms`]) receiving the message
+    pub room: Option<String>,
+    /// The severity of the message
+    pub severity: Option<Severity>,
+    /// Text replacing the message, where each `{/json/pointer}` is replaced by the payload's value
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PointerMatch {
+    /// A JSON pointer into the payload, e.g. `/sender/login`
+    pub pointer: String,
+    /// The regex matched against the value found at `pointer`
+    #[serde(with = "serde_regex")]
+    pub regex: Regex,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadSource {
+    #[serde(rename = "github")]
+    GitHub,
+    #[serde(rename = "prolosite")]
+    ProloSite,
+}
+
 impl ProloloConfig {
     pub fn find_room_for(&self, repo: String) -> anyhow::Result<&RoomId> {
         let matched = self
@@ -52,15 +1098,18 @@ impl ProloloConfig {
             .find(|dest| dest.regex.is_match(&repo));
 
         match matched {
-            Some(dest) => self
-                .matrix_rooms
-                .get(&dest.room)
-                .map(|room| &room.id)
-                .ok_or_else(|| anyhow!("destination points to unknown room {}", dest.room)),
+            Some(dest) => self.room_by_name(&dest.room),
             None => self.default_room(),
         }
     }
 
+    pub fn room_by_name(&self, name: &str) -> anyhow::Result<&RoomId> {
+        self.matrix_rooms
+            .get(name)
+            .map(|room| &room.id)
+            .ok_or_else(|| anyhow!("unknown room {}", name))
+    }
+
     pub fn default_room(&self) -> anyhow::Result<&RoomId> {
         self.matrix_rooms
             .values()
@@ -69,3 +1118,172 @@ impl ProloloConfig {
             .ok_or_else(|| anyhow!("no default room provided!"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(prefix: &str, state_dir: &str, session_file: &str) -> String {
+        format!(
+            "{{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_rooms: {{}}, destinations: [], \
+             github_secret: secret, prolosite_secret: secret, path_prefix: '{}', \
+             matrix_state_dir: {}, matrix_session_file: {}}}",
+            prefix, state_dir, session_file
+        )
+    }
+
+    fn parse(yaml: &str) -> anyhow::Result<Vec<ProloloConfig>> {
+        parse_profiles(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let single = parse(&profile("/", "state", "session.yaml")).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].path_prefix, "/");
+
+        let profiles = |second: String| {
+            format!(
+                "profiles: [{}, {}]",
+                profile("/", "state", "session.yaml"),
+                second
+            )
+        };
+
+        let both = parse(&profiles(profile("/alumni", "alumni", "alumni.yaml"))).unwrap();
+        assert_eq!(both.len(), 2);
+        assert_eq!(both[1].path_prefix, "/alumni");
+
+        // the second profile would receive the events of the first one
+        assert!(parse(&profiles(profile("/", "alumni", "alumni.yaml"))).is_err());
+        // the second profile would overwrite the session of the first one
+        assert!(parse(&profiles(profile("/alumni", "alumni", "session.yaml"))).is_err());
+    }
+
+    #[test]
+    fn test_release_trains() {
+        let with_week = |week: u32| {
+            let yaml = profile("/", "state", "session.yaml").replace(
+                "path_prefix:",
+                &format!(
+                    "release_trains: [{{repo: prologin/site, weekday: Mon, week: {}}}], path_prefix:",
+                    week
+                ),
+            );
+            parse(&yaml)
+        };
+
+        assert!(with_week(1).is_ok());
+        assert!(with_week(5).is_ok());
+        assert!(with_week(0).is_err());
+        assert!(with_week(6).is_err());
+    }
+
+    #[test]
+    fn test_audit_log_room() {
+        let with_github = |github: &str| {
+            let yaml = profile("/", "state", "session.yaml").replace(
+                "path_prefix:",
+                &format!(
+                    "audit_log: {{org: prologin, token: secret}}, github: {}, path_prefix:",
+                    github
+                ),
+            );
+            parse(&yaml)
+        };
+
+        assert!(with_github("{security_room: security}").is_ok());
+        assert!(with_github("{}").is_err());
+    }
+
+    #[test]
+    fn test_push_url() {
+        let with_push = |url: &str| {
+            let yaml = profile("/", "state", "session.yaml").replace(
+                "path_prefix:",
+                &format!("push: {{service: gotify, url: '{}'}}, path_prefix:", url),
+            );
+            parse(&yaml)
+        };
+
+        assert!(with_push("https://push.prologin.org").is_ok());
+        assert!(with_push("mailto:root@prologin.org").is_err());
+    }
+
+    #[test]
+    fn test_github_urls() {
+        let with_github = |github: &str| {
+            let yaml = profile("/", "state", "session.yaml")
+                .replace("path_prefix:", &format!("github: {}, path_prefix:", github));
+            parse(&yaml).unwrap().remove(0).github
+        };
+
+        let github = with_github(
+            "{api_url: 'https://ghe.example.org/api/v3', web_url: 'https://ghe.example.org'}",
+        );
+        assert_eq!(github.api_url.as_str(), "https://ghe.example.org/api/v3/");
+        assert_eq!(
+            github.api_url.join("repos/prologin/site").unwrap().as_str(),
+            "https://ghe.example.org/api/v3/repos/prologin/site"
+        );
+        assert_eq!(github.web_url.as_str(), "https://ghe.example.org/");
+
+        let github = with_github("{api_url: 'https://ghe.example.org/api/v3/'}");
+        assert_eq!(github.api_url.as_str(), "https://ghe.example.org/api/v3/");
+        assert_eq!(github.web_url.as_str(), "https://github.com/");
+    }
+
+    #[test]
+    fn test_mail_listener() {
+        let with_mail = |listen: &str| {
+            let yaml = profile("/", "state", "session.yaml").replace(
+                "path_prefix:",
+                &format!("mail: {{listen: '{}', rules: []}}, path_prefix:", listen),
+            );
+            parse(&yaml)
+        };
+
+        assert!(with_mail("127.0.0.1:2525").is_ok());
+        assert!(with_mail("[::1]:2525").is_ok());
+        assert!(with_mail("0.0.0.0:2525").is_err());
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let default = parse(&profile("/", "state", "session.yaml")).unwrap();
+        assert_eq!(default[0].timeouts.matrix(), Duration::from_secs(60));
+        assert_eq!(default[0].timeouts.http(), Duration::from_secs(30));
+
+        let yaml = profile("/", "state", "session.yaml").replace(
+            "path_prefix:",
+            "timeouts: {matrix_seconds: 5, http_seconds: 2}, path_prefix:",
+        );
+        let custom = parse(&yaml).unwrap();
+        assert_eq!(custom[0].timeouts.matrix_request(), Duration::from_secs(10));
+        assert_eq!(custom[0].timeouts.matrix(), Duration::from_secs(5));
+        assert_eq!(custom[0].timeouts.http(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_image_urls() {
+        let images: ImageConfig = serde_yaml::from_str("{}").unwrap();
+        let allows = |url: &str| images.allows(&Url::parse(url).unwrap());
+
+        assert!(allows(
+            "https://user-images.githubusercontent.com/42/crash.png"
+        ));
+        assert!(allows("https://github.com/user-attachments/assets/1234"));
+        assert!(!allows(
+            "https://github.com/prologin/site/raw/main/logo.png"
+        ));
+        assert!(!allows(
+            "http://user-images.githubusercontent.com/42/crash.png"
+        ));
+        assert!(!allows(
+            "https://user-images.githubusercontent.com.evil.com/crash.png"
+        ));
+        assert!(!allows("http://169.254.169.254/latest/meta-data/"));
+        assert!(!allows("http://localhost:8000/api/activity"));
+    }
+}
@@ -0,0 +1,452 @@
+//! A minimal client for the GitHub REST API, used to complete webhook payloads with information
+//! they don't contain.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{de::IgnoredAny, Deserialize};
+use url::Url;
+
+use crate::{config::GitHubConfig, webhooks::github::Verification};
+
+const USER_AGENT: &str = concat!("prololo/", env!("CARGO_PKG_VERSION"));
+
+pub struct GitHubApi {
+    client: reqwest::Client,
+    base_url: Url,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Comparison {
+    pub html_url: Url,
+    /// Number of commits in the head that aren't in the base
+    pub ahead_by: u64,
+    /// The commits in the head that aren't in the base, oldest first and truncated by GitHub for
+    /// huge comparisons
+    #[serde(default)]
+    pub commits: Vec<ComparedCommit>,
+    /// The changed files, truncated by GitHub for huge comparisons
+    #[serde(default)]
+    pub files: Vec<ChangedFile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ComparedCommit {
+    pub sha: String,
+    pub commit: CommitDetails,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitDetails {
+    pub message: String,
+    pub verification: Option<Verification>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangedFile {
+    pub filename: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenIssue {
+    pub number: u64,
+    pub title: String,
+    pub html_url: Url,
+    pub created_at: DateTime<Utc>,
+    /// Only set for PRs, that GitHub considers as issues
+    pub pull_request: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueSearch {
+    /// Number of matches, only the first page is in `items`
+    pub total_count: u64,
+    pub items: Vec<FoundIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FoundIssue {
+    pub number: u64,
+    pub title: String,
+    pub html_url: Url,
+    /// `open` or `closed`
+    pub state: String,
+    /// Only set for PRs, that GitHub considers as issues
+    pub pull_request: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Tag {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RepositoryInfo {
+    pub default_branch: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogEntry {
+    /// Milliseconds since the epoch
+    #[serde(rename = "@timestamp")]
+    pub timestamp: i64,
+    pub action: String,
+    pub actor: Option<String>,
+    /// The user affected by the action, e.g. whose two-factor authentication was disabled
+    pub user: Option<String>,
+    /// The full name of the repository affected by the action
+    pub repo: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrgMember {
+    pub login: String,
+    pub html_url: Url,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HookDelivery {
+    pub id: u64,
+    /// The outcome as described by GitHub, e.g. `OK` or `Invalid HTTP Response: 502`
+    pub status: String,
+    /// The HTTP status of the response, 0 if there was none (e.g. TLS errors or timeouts)
+    pub status_code: u16,
+    pub event: String,
+}
+
+impl HookDelivery {
+    pub fn failed(&self) -> bool {
+        !(200..300).contains(&self.status_code)
+    }
+}
+
+impl GitHubApi {
+    pub fn new(config: &GitHubConfig, timeout: Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            base_url: config.api_url.clone(),
+            token: config.api_token.clone(),
+        })
+    }
+
+    fn get(&self, path: &str) -> anyhow::Result<reqwest::RequestBuilder> {
+        self.get_as(path, "application/vnd.github.v3+json")
+    }
+
+    /// Like [`GitHubApi::get`], for a specific media type
+    fn get_as(&self, path: &str, accept: &str) -> anyhow::Result<reqwest::RequestBuilder> {
+        let url = self
+            .base_url
+            .join(path)
+            .with_context(|| format!("invalid API path {}", path))?;
+
+        let request = self
+            .client
+            .get(url)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .header(reqwest::header::ACCEPT, accept);
+
+        Ok(match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+
+    /// Compares two commits of a repository, `repo` being its full name.
+    pub async fn compare(&self, repo: &str, base: &str, head: &str) -> anyhow::Result<Comparison> {
+        let comparison = self
+            .get(&format!("repos/{}/compare/{}...{}", repo, base, head))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(comparison)
+    }
+
+    /// Lists the files changed by a PR, `repo` being the repository's full name. Only the first 100
+    /// files are returned.
+    pub async fn pull_request_files(
+        &self,
+        repo: &str,
+        number: u64,
+    ) -> anyhow::Result<Vec<ChangedFile>> {
+        let files = self
+            .get(&format!(
+                "repos/{}/pulls/{}/files?per_page=100",
+                repo, number
+            ))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(files)
+    }
+
+    /// Whether a PR was reviewed at least once, `repo` being the repository's full name.
+    pub async fn has_reviews(&self, repo: &str, number: u64) -> anyhow::Result<bool> {
+        let reviews: Vec<IgnoredAny> = self
+            .get(&format!(
+                "repos/{}/pulls/{}/reviews?per_page=1",
+                repo, number
+            ))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(!reviews.is_empty())
+    }
+
+    /// Gets the content of a file on the default branch, `repo` being the repository's full name.
+    pub async fn file_content(&self, repo: &str, path: &str) -> anyhow::Result<Option<String>> {
+        let response = self
+            .get_as(
+                &format!("repos/{}/contents/{}", repo, path),
+                "application/vnd.github.v3.raw",
+            )?
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.text().await?))
+    }
+
+    /// Lists the oldest open issues and PRs of a repo, `repo` being its full name. Only the first
+    /// 100 are returned.
+    pub async fn oldest_open_issues(&self, repo: &str) -> anyhow::Result<Vec<OpenIssue>> {
+        let issues = self
+            .get(&format!(
+                "repos/{}/issues?state=open&sort=created&direction=asc&per_page=100",
+                repo
+            ))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(issues)
+    }
+
+    /// Searches the issues and PRs of a repo, `repo` being its full name, with GitHub's search
+    /// syntax (e.g. `is:open crash`). Only the `per_page` best matches are returned.
+    pub async fn search_issues(
+        &self,
+        repo: &str,
+        query: &str,
+        per_page: usize,
+    ) -> anyhow::Result<IssueSearch> {
+        let query = format!("repo:{} {}", repo, query);
+        let query: String = url::form_urlencoded::byte_serialize(query.as_bytes()).collect();
+        let search = self
+            .get(&format!("search/issues?q={}&per_page={}", query, per_page))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(search)
+    }
+
+    /// Gets a repository, `repo` being its full name.
+    pub async fn repository(&self, repo: &str) -> anyhow::Result<RepositoryInfo> {
+        let repository = self
+            .get(&format!("repos/{}", repo))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(repository)
+    }
+
+    /// Gets the newest tag of a repo, `repo` being its full name. Tags are listed by GitHub in
+    /// reverse order of their names, versions being compared as such.
+    pub async fn latest_tag(&self, repo: &str) -> anyhow::Result<Option<Tag>> {
+        let tags: Vec<Tag> = self
+            .get(&format!("repos/{}/tags?per_page=1", repo))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(tags.into_iter().next())
+    }
+
+    /// Lists the latest releases of a repo, including drafts, newest first. Only the first 30 are
+    /// returned.
+    pub async fn releases(&self, repo: &str) -> anyhow::Result<Vec<Release>> {
+        let releases = self
+            .get(&format!("repos/{}/releases", repo))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(releases)
+    }
+
+    /// Lists the latest entries of an organization's audit log for an action, newest first. Only
+    /// the first 100 are returned.
+    pub async fn audit_log(&self, org: &str, action: &str) -> anyhow::Result<Vec<AuditLogEntry>> {
+        let entries = self
+            .get(&format!(
+                "orgs/{}/audit-log?phrase=action:{}&order=desc&per_page=100",
+                org, action
+            ))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(entries)
+    }
+
+    /// Lists the members of an organization without two-factor authentication, which only its
+    /// owners can do.
+    pub async fn members_without_2fa(&self, org: &str) -> anyhow::Result<Vec<OrgMember>> {
+        let mut members = Vec::new();
+        let mut path = format!("orgs/{}/members?filter=2fa_disabled&per_page=100", org);
+
+        loop {
+            let response = self.get(&path)?.send().await?.error_for_status()?;
+            let next = next_page(response.headers());
+            members.extend(response.json::<Vec<OrgMember>>().await?);
+
+            match next {
+                // the token is only sent to the API
+                Some(url) if url.origin() == self.base_url.origin() => path = url.into(),
+                Some(url) => anyhow::bail!("unexpected next page {}", url),
+                None => return Ok(members),
+            }
+        }
+    }
+
+    /// Lists the latest deliveries of a webhook of an organization, newest first. Only the first
+    /// 100 are returned.
+    pub async fn hook_deliveries(
+        &self,
+        org: &str,
+        hook_id: u64,
+    ) -> anyhow::Result<Vec<HookDelivery>> {
+        let deliveries = self
+            .get(&format!(
+                "orgs/{}/hooks/{}/deliveries?per_page=100",
+                org, hook_id
+            ))?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Gets the release of a tag, `repo` being the repository's full name.
+    pub async fn release_by_tag(&self, repo: &str, tag: &str) -> anyhow::Result<Option<Release>> {
+        let response = self
+            .get(&format!("repos/{}/releases/tags/{}", repo, tag))?
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+}
+
+/// The URL of the next page of a paginated response, from its `Link` header
+fn next_page(headers: &reqwest::header::HeaderMap) -> Option<Url> {
+    let links = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+    links.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#);
+        if !is_next {
+            return None;
+        }
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        Url::parse(url).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let api = GitHubApi::new(
+            &GitHubConfig {
+                api_token: Some("secret".to_string()),
+                ..Default::default()
+            },
+            Duration::from_secs(30),
+        )
+        .unwrap();
+
+        let request = api
+            .get("repos/prologin/site/compare/abc...def")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.github.com/repos/prologin/site/compare/abc...def"
+        );
+        assert_eq!(request.headers()["Authorization"], "Bearer secret");
+    }
+
+    #[test]
+    fn test_next_page() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(next_page(&headers), None);
+
+        headers.insert(
+            reqwest::header::LINK,
+            r#"<https://api.github.com/organizations/42/members?per_page=100&page=2>; rel="next", <https://api.github.com/organizations/42/members?per_page=100&page=3>; rel="last""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            next_page(&headers).unwrap().as_str(),
+            "https://api.github.com/organizations/42/members?per_page=100&page=2"
+        );
+
+        headers.insert(
+            reqwest::header::LINK,
+            r#"<https://api.github.com/organizations/42/members?per_page=100&page=1>; rel="prev", <https://api.github.com/organizations/42/members?per_page=100&page=1>; rel="first""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(next_page(&headers), None);
+    }
+}
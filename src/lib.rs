@@ -0,0 +1,31 @@
+//! Prololo, the Matrix bot announcing the events of the Prologin GitHub organization and website.
+//!
+//! The binary runs the bot, the library is used by the benchmarks and by the programs embedding
+//! the bot through [`builder::ProloloBuilder`].
+
+pub mod assets;
+
+pub mod backup;
+
+pub mod bot;
+
+pub mod builder;
+
+#[cfg(any(test, feature = "chaos"))]
+pub mod chaos;
+
+pub mod config;
+
+pub mod github_api;
+
+pub mod loadtest;
+
+pub mod mail;
+
+pub mod push;
+
+pub mod queue;
+
+pub mod webhooks;
+
+pub mod xmpp;
@@ -0,0 +1,307 @@
+//! Synthetic traffic to size the queue and the rate limits: `prololo loadtest` fires signed
+//! payloads at a running instance, or hands them directly to the parsing pipeline of the process.
+//!
+//! Over HTTP, the latency is the time the endpoints take to accept an event. In the pipeline, it
+//! is the time until the parsed event would reach the bot, Matrix isn't involved.
+
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use hmac::{Hmac, Mac, NewMac};
+use matrix_sdk::uuid::Uuid;
+use serde_json::json;
+use sha2::Sha256;
+use tokio::{
+    sync::mpsc::unbounded_channel,
+    time::{interval, Instant},
+};
+use url::Url;
+
+use crate::{
+    config::ProloloConfig,
+    webhooks::{
+        github::{X_GITHUB_DELIVERY, X_GITHUB_EVENT},
+        parse_deliveries,
+        prolosite::ProloSiteEventType,
+        rules::event_name,
+        GitHubEventType, ParseSettings, RawDelivery, RawEvent,
+    },
+};
+
+/// Outcome of a load test
+pub struct Report {
+    pub sent: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+    /// Latencies of the accepted events, sorted
+    pub latencies: Vec<Duration>,
+}
+
+impl Report {
+    fn new(sent: usize, elapsed: Duration, mut latencies: Vec<Duration>) -> Self {
+        latencies.sort_unstable();
+        Self {
+            sent,
+            failed: sent - latencies.len(),
+            elapsed,
+            latencies,
+        }
+    }
+
+    /// The latency below which `percent`% of the accepted events were handled
+    pub fn percentile(&self, percent: usize) -> Duration {
+        match self.latencies.len() {
+            0 => Duration::ZERO,
+            len => self.latencies[(len * percent / 100).min(len - 1)],
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} events in {:.1}s ({:.1}/s), {} failed, latency p50 {:?}, p95 {:?}, p99 {:?}, max {:?}",
+            self.sent,
+            self.elapsed.as_secs_f64(),
+            self.sent as f64 / self.elapsed.as_secs_f64(),
+            self.failed,
+            self.percentile(50),
+            self.percentile(95),
+            self.percentile(99),
+            self.latencies.last().copied().unwrap_or_default(),
+        )
+    }
+}
+
+/// The `i`-th synthetic event, cycling through the events the bot receives the most
+pub fn synthetic_event(i: usize) -> RawDelivery {
+    let sender = json!({
+        "login": format!("user{}", i % 50),
+        "id": i % 50,
+        "html_url": format!("https://github.com/user{}", i % 50),
+        "avatar_url": format!("https://avatars.githubusercontent.com/u/{}", i % 50),
+        "type": "User",
+    });
+    let repository = json!({
+        "name": format!("repo{}", i % 10),
+        "full_name": format!("prologin/repo{}", i % 10),
+        "html_url": format!("https://github.com/prologin/repo{}", i % 10),
+    });
+    let commit = |j: usize| {
+        json!({
+            "id": format!("{:040x}", i * 10 + j),
+            "url": format!("https://github.com/prologin/repo{}/commit/{:040x}", i % 10, i * 10 + j),
+            "distinct": true,
+            "message": format!("Commit {} of push {}\n\nWith a body.", j, i),
+            "modified": ["README.md"],
+        })
+    };
+
+    let event = match i % 4 {
+        0 | 1 => RawEvent::GitHub {
+            event_type: GitHubEventType::Push,
+            payload: json!({
+                "repository": repository,
+                "sender": sender,
+                "commits": (0..3).map(commit).collect::<Vec<_>>(),
+                "head_commit": commit(2),
+                "forced": false,
+                "created": false,
+                "ref": "refs/heads/main",
+                "before": format!("{:040x}", i * 10),
+                "after": format!("{:040x}", i * 10 + 2),
+                "compare": format!("https://github.com/prologin/repo{}/compare/a...b", i % 10),
+            })
+            .to_string(),
+        },
+        2 => RawEvent::GitHub {
+            event_type: GitHubEventType::Issues,
+            payload: json!({
+                "action": "opened",
+                "repository": repository,
+                "sender": sender,
+                "issue": {
+                    "number": i,
+                    "html_url": format!("https://github.com/prologin/repo{}/issues/{}", i % 10, i),
+                    "title": format!("Issue number {}", i),
+                },
+            })
+            .to_string(),
+        },
+        _ => RawEvent::ProloSite {
+            event_type: ProloSiteEventType::Forum,
+            payload: json!({
+                "username": format!("user{}", i % 50),
+                "forum": "Général",
+                "title": format!("Thread number {}", i),
+                "url": format!("https://prologin.org/forum/{}", i),
+            }),
+        },
+    };
+
+    RawDelivery {
+        id: Some(format!("loadtest-{}", i)),
+        event,
+    }
+}
+
+/// Sends `events` synthetic events at `rate` events per second to the endpoints of the bot at
+/// `base_url`, as configured by `config`. The events are announced like real ones, this is meant
+/// for staging instances.
+pub async fn fire(
+    base_url: &Url,
+    config: &ProloloConfig,
+    events: usize,
+    rate: u32,
+) -> anyhow::Result<Report> {
+    if rate == 0 {
+        bail!("the rate must be positive");
+    }
+    let client = reqwest::Client::builder()
+        .timeout(config.timeouts.http())
+        .build()?;
+    let base_url = base_url
+        .join(&format!("{}/", config.path_prefix.trim_matches('/')))
+        .context("invalid path prefix")?;
+
+    let mut ticks = interval(Duration::from_secs(1) / rate);
+    let mut requests = Vec::with_capacity(events);
+    let start = Instant::now();
+    for i in 0..events {
+        ticks.tick().await;
+        let request = signed_request(&client, &base_url, config, synthetic_event(i))?;
+        requests.push(tokio::spawn(async move {
+            let sent = Instant::now();
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => Some(sent.elapsed()),
+                Err(_) => None,
+            }
+        }));
+    }
+
+    let mut latencies = Vec::with_capacity(events);
+    for request in requests {
+        latencies.extend(request.await?);
+    }
+
+    Ok(Report::new(events, start.elapsed(), latencies))
+}
+
+fn signed_request(
+    client: &reqwest::Client,
+    base_url: &Url,
+    config: &ProloloConfig,
+    delivery: RawDelivery,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    // the queue skips the IDs it already saw, the runs mustn't share them
+    let id = Uuid::new_v4().to_string();
+    Ok(match delivery.event {
+        RawEvent::GitHub {
+            event_type,
+            payload,
+        } => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(config.github_secret.as_bytes())
+                .expect("HMAC accepts keys of any size");
+            mac.update(payload.as_bytes());
+            let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+            client
+                .post(base_url.join("api/webhooks/github")?)
+                .header(X_GITHUB_EVENT, event_name(&event_type))
+                .header(X_GITHUB_DELIVERY, id)
+                .header("X-Hub-Signature-256", signature)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload)
+        }
+        RawEvent::ProloSite {
+            event_type,
+            payload,
+        } => {
+            let path = format!("api/webhooks/prolosite/{}", event_name(&event_type));
+            client
+                .post(base_url.join(&path)?)
+                .header(reqwest::header::AUTHORIZATION, &config.prolosite_secret)
+                .json(&payload)
+        }
+    })
+}
+
+/// Hands `events` synthetic events at `rate` events per second to the parsing pipeline, without
+/// any bot or endpoint.
+pub async fn run_pipeline(
+    config: &ProloloConfig,
+    events: usize,
+    rate: u32,
+) -> anyhow::Result<Report> {
+    if rate == 0 {
+        bail!("the rate must be positive");
+    }
+    let (raw_sender, raw_receiver) = unbounded_channel();
+    let (sender, mut receiver) = unbounded_channel();
+    tokio::spawn(parse_deliveries(
+        raw_receiver,
+        ParseSettings::from_config(config),
+        sender,
+    ));
+
+    let collector = tokio::spawn(async move {
+        let mut received = Vec::new();
+        while let Some(delivery) = receiver.recv().await {
+            received.push((delivery.id, Instant::now()));
+        }
+        received
+    });
+
+    let mut ticks = interval(Duration::from_secs(1) / rate);
+    let mut sent = Vec::with_capacity(events);
+    let start = Instant::now();
+    for i in 0..events {
+        ticks.tick().await;
+        sent.push(Instant::now());
+        raw_sender.send(synthetic_event(i))?;
+    }
+    // the pipeline stops once it handled all the events
+    drop(raw_sender);
+
+    let latencies = collector
+        .await?
+        .into_iter()
+        .filter_map(|(id, received)| {
+            let i: usize = id?.strip_prefix("loadtest-")?.parse().ok()?;
+            Some(received - sent[i])
+        })
+        .collect();
+
+    Ok(Report::new(events, start.elapsed(), latencies))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_events() {
+        for i in 0..4 {
+            let delivery = synthetic_event(i).parse(&[]).unwrap();
+            assert!(delivery.is_some(), "event {} should parse", i);
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let latencies = (1..=100).rev().map(Duration::from_millis).collect();
+        let report = Report::new(102, Duration::from_secs(2), latencies);
+
+        assert_eq!(report.failed, 2);
+        assert_eq!(report.percentile(50), Duration::from_millis(51));
+        assert_eq!(report.percentile(99), Duration::from_millis(100));
+        assert_eq!(
+            report.to_string(),
+            "102 events in 2.0s (51.0/s), 2 failed, latency p50 51ms, p95 96ms, p99 100ms, max 100ms"
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! A minimal SMTP listener relaying incoming mails (e.g. cron `MAILTO` output) to Matrix.
+//!
+//! Only the subset of SMTP needed by local MTAs and cron daemons is supported: no
+//! authentication, no TLS, no MIME decoding. It can only listen on a loopback address.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use tokio::{
+    io::{
+        AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+        BufReader,
+    },
+    net::TcpListener,
+    sync::mpsc::UnboundedSender,
+};
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+    bot::Severity,
+    config::MailConfig,
+    webhooks::{Delivery, Event},
+};
+
+/// Mails bigger than this are rejected
+const MAX_MAIL_SIZE: usize = 1024 * 1024;
+/// Longer lines close the session, SMTP limits them to 1000 bytes
+const MAX_LINE_LENGTH: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct MailEvent {
+    /// Envelope sender of the mail
+    pub sender: String,
+    pub subject: String,
+    pub body: String,
+    /// The room name of the rule matching this mail
+    pub room: String,
+    pub severity: Severity,
+}
+
+pub async fn run_smtp_listener(
+    config: MailConfig,
+    sender: UnboundedSender<Delivery>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(config.listen)
+        .await
+        .with_context(|| format!("couldn't bind SMTP listener to {}", config.listen))?;
+    info!("SMTP listener bound to {}", config.listen);
+
+    let config = Arc::new(config);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        debug!("new SMTP connection from {}", peer);
+
+        let config = config.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_session(stream, &config, &sender).await {
+                warn!("SMTP session with {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_session<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    config: &MailConfig,
+    sender: &UnboundedSender<Delivery>,
+) -> anyhow::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut envelope_sender: Option<String> = None;
+    let mut line = String::new();
+
+    stream.write_all(b"220 prololo ESMTP\r\n").await?;
+
+    loop {
+        if read_line(&mut stream, &mut line).await? == 0 {
+            return Ok(());
+        }
+        trace!("SMTP command: {}", line.trim_end());
+
+        let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+        let reply: &[u8] = match command.as_str() {
+            "HELO" | "EHLO" => b"250 prololo\r\n",
+            "MAIL" => {
+                envelope_sender = Some(parse_path(&line));
+                b"250 OK\r\n"
+            }
+            "RCPT" => b"250 OK\r\n",
+            "DATA" => {
+                let envelope_sender = match envelope_sender.take() {
+                    Some(sender) => sender,
+                    None => {
+                        stream
+                            .write_all(b"503 MAIL command needed first\r\n")
+                            .await?;
+                        continue;
+                    }
+                };
+
+                stream
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await?;
+                match read_data(&mut stream).await? {
+                    Some(data) => {
+                        relay_mail(envelope_sender, &data, config, sender);
+                        b"250 OK\r\n"
+                    }
+                    None => b"552 Message size exceeds limit\r\n",
+                }
+            }
+            "RSET" => {
+                envelope_sender = None;
+                b"250 OK\r\n"
+            }
+            "NOOP" => b"250 OK\r\n",
+            "QUIT" => {
+                stream.write_all(b"221 Bye\r\n").await?;
+                return Ok(());
+            }
+            _ => b"502 Command not implemented\r\n",
+        };
+        stream.write_all(reply).await?;
+    }
+}
+
+/// Reads a line into `line`, failing if it is longer than [`MAX_LINE_LENGTH`]. Returns 0 at the end
+/// of the stream.
+async fn read_line<S: AsyncBufRead + Unpin>(
+    stream: &mut S,
+    line: &mut String,
+) -> anyhow::Result<usize> {
+    line.clear();
+    let read = stream.take(MAX_LINE_LENGTH as u64).read_line(line).await?;
+    if read == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        bail!("line longer than {} bytes", MAX_LINE_LENGTH);
+    }
+
+    Ok(read)
+}
+
+/// Extracts the address from a `MAIL FROM:<address>` command
+fn parse_path(line: &str) -> String {
+    let path = line.split_once(':').map_or("", |(_, path)| path).trim();
+    let path = path.split_whitespace().next().unwrap_or_default();
+    path.trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+/// Reads the mail content up to the terminating `.` line, returns `None` if it is too big.
+async fn read_data<S: AsyncBufRead + Unpin>(stream: &mut S) -> anyhow::Result<Option<String>> {
+    let mut data = String::new();
+    let mut too_big = false;
+    let mut line = String::new();
+
+    loop {
+        if read_line(stream, &mut line).await? == 0 {
+            bail!("connection closed during DATA");
+        }
+
+        let content = line.trim_end_matches(&['\r', '\n'][..]);
+        if content == "." {
+            break;
+        }
+
+        if too_big || data.len() + line.len() > MAX_MAIL_SIZE {
+            too_big = true;
+            continue;
+        }
+
+        // undo dot-stuffing
+        data.push_str(content.strip_prefix('.').unwrap_or(content));
+        data.push('\n');
+    }
+
+    Ok(if too_big { None } else { Some(data) })
+}
+
+/// Splits a raw mail into its subject and body.
+fn parse_mail(data: &str) -> (String, String) {
+    let (headers, body) = data.split_once("\n\n").unwrap_or((data, ""));
+
+    let mut subject = String::new();
+    let mut in_subject = false;
+    for header in headers.lines() {
+        if header.starts_with(|c: char| c == ' ' || c == '\t') {
+            // folded header continuation
+            if in_subject {
+                subject.push(' ');
+                subject.push_str(header.trim());
+            }
+            continue;
+        }
+
+        in_subject = false;
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("subject") {
+                subject = value.trim().to_string();
+                in_subject = true;
+            }
+        }
+    }
+
+    (subject, body.trim().to_string())
+}
+
+fn relay_mail(
+    envelope_sender: String,
+    data: &str,
+    config: &MailConfig,
+    sender: &UnboundedSender<Delivery>,
+) {
+    let (subject, body) = parse_mail(data);
+
+    let rule = match config
+        .rules
+        .iter()
+        .find(|rule| rule.matches(&envelope_sender, &subject))
+    {
+        Some(rule) => rule,
+        None => {
+            debug!(
+                "no rule matched mail `{}` from {}, dropping it",
+                subject, envelope_sender
+            );
+            return;
+        }
+    };
+
+    info!("received mail `{}` from {}", subject, envelope_sender);
+    sender
+        .send(
+            Event::Mail(MailEvent {
+                sender: envelope_sender,
+                subject,
+                body,
+                room: rule.room.clone(),
+                severity: rule.severity,
+            })
+            .into(),
+        )
+        .expect("mpsc channel was closed / dropped");
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+    use tokio::{io::duplex, sync::mpsc::unbounded_channel};
+
+    use crate::config::MailRule;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_mail() {
+        let (subject, body) = parse_mail(
+            "From: root@prologin.org\nSubject: Cron <root@server> backup\n  failed\n\nline 1\nline 2\n",
+        );
+
+        assert_eq!(subject, "Cron <root@server> backup failed");
+        assert_eq!(body, "line 1\nline 2");
+    }
+
+    #[tokio::test]
+    async fn test_smtp_session() {
+        let config = MailConfig {
+            listen: "127.0.0.1:2525".parse().unwrap(),
+            rules: vec![MailRule {
+                room: "ops".to_string(),
+                sender: Some(Regex::new("^root@").unwrap()),
+                subject: None,
+                severity: Severity::Normal,
+            }],
+        };
+        let (sender, mut receiver) = unbounded_channel();
+        let (client, server) = duplex(4096);
+
+        let session = tokio::spawn(async move { handle_session(server, &config, &sender).await });
+
+        let (client_read, mut client_write) = tokio::io::split(client);
+        client_write
+            .write_all(
+                b"EHLO localhost\r\n\
+                MAIL FROM:<root@server>\r\n\
+                RCPT TO:<prololo@server>\r\n\
+                DATA\r\n\
+                Subject: backup failed\r\n\
+                \r\n\
+                ..hidden file missing\r\n\
+                .\r\n\
+                QUIT\r\n",
+            )
+            .await
+            .unwrap();
+
+        session.await.unwrap().unwrap();
+
+        let mut replies = String::new();
+        let mut client_read = BufReader::new(client_read);
+        while client_read.read_line(&mut replies).await.unwrap() != 0 {}
+        assert!(replies.starts_with("220 "));
+        assert!(replies.ends_with("221 Bye\r\n"));
+
+        match receiver.recv().await {
+            Some(Delivery {
+                event: Event::Mail(mail),
+                ..
+            }) => {
+                assert_eq!(mail.sender, "root@server");
+                assert_eq!(mail.subject, "backup failed");
+                assert_eq!(mail.body, ".hidden file missing");
+                assert_eq!(mail.room, "ops");
+            }
+            _ => panic!("should have received a mail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_long_line() {
+        let config = MailConfig {
+            listen: "127.0.0.1:2525".parse().unwrap(),
+            rules: Vec::new(),
+        };
+        let (sender, _receiver) = unbounded_channel();
+        let (client, server) = duplex(4096);
+
+        let session = tokio::spawn(async move { handle_session(server, &config, &sender).await });
+
+        let (_client_read, mut client_write) = tokio::io::split(client);
+        let line = format!("EHLO {}\r\n", "a".repeat(MAX_LINE_LENGTH));
+        // the session stops reading in the middle of the line
+        let _ = client_write.write_all(line.as_bytes()).await;
+
+        assert!(session.await.unwrap().is_err());
+    }
+}
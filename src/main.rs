@@ -1,24 +1,14 @@
-use std::fs::File;
-use std::io::BufReader;
 use std::path::PathBuf;
 
 use anyhow::Context;
-use clap::Parser;
-use rocket::routes;
-use tokio::sync::mpsc::unbounded_channel;
+use clap::{Parser, Subcommand};
+use url::Url;
 
-mod bot;
-use bot::Prololo;
-
-mod config;
-use config::ProloloConfig;
-
-mod webhooks;
-use webhooks::{
-    github::GitHubSecret,
-    github_webhook,
-    prolosite::{django, forum, impersonate, new_school, ProlositeSecret},
-    EventSender,
+use prololo::{
+    backup::{backup, profile_backup_path, restore},
+    builder::{ProfileBuilder, ProloloBuilder, Role},
+    config::load_profiles,
+    loadtest,
 };
 
 #[derive(Parser)]
@@ -27,6 +17,42 @@ struct Opts {
     /// Configuration file for prololo
     #[clap(short, long, parse(from_os_str))]
     config: PathBuf,
+    /// Which parts of the bot run in this process, the others need a queue in the config
+    #[clap(long, arg_enum, default_value = "all")]
+    role: Role,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Copies the Matrix session and the state directory to a new directory, while the bot is
+    /// stopped
+    Backup {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Restores a backup, on a host where the bot never ran
+    Restore {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Sends synthetic events to the endpoints of the first profile, and reports the throughput
+    /// and the latency. The events are announced, don't run it against a production instance
+    Loadtest {
+        /// Number of events sent
+        #[clap(long, default_value = "10000")]
+        events: usize,
+        /// Events sent per second
+        #[clap(long, default_value = "200")]
+        rate: u32,
+        /// Where the bot is running
+        #[clap(long, default_value = "http://localhost:8000")]
+        url: Url,
+        /// Hand the events to the parsing pipeline of this process instead of a running bot
+        #[clap(long)]
+        direct: bool,
+    },
 }
 
 #[rocket::main]
@@ -34,28 +60,53 @@ async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
     let opts = Opts::parse();
-    let config_file = File::open(&opts.config)
-        .with_context(|| format!("couldn't open {}:", opts.config.display()))?;
-    let config: ProloloConfig = serde_yaml::from_reader(BufReader::new(config_file))
-        .context("couldn't parse config file")?;
-
-    let (sender, receiver) = unbounded_channel();
-    let github_secret = config.github_secret.clone();
-    let prolosite_secret = config.prolosite_secret.clone();
+    let configs = load_profiles(&opts.config)?;
 
-    let prololo = Prololo::new(config).context("failed to create prololo bot")?;
-    prololo.init().await.context("failed to init prololo bot")?;
-    tokio::spawn(async move { prololo.run(receiver).await });
+    let single = configs.len() == 1;
+    match opts.command {
+        Some(Command::Backup { path }) => {
+            for config in &configs {
+                backup(
+                    &config.matrix_session_file,
+                    &config.matrix_state_dir,
+                    &profile_backup_path(&path, &config.path_prefix, single),
+                )?;
+            }
+            return Ok(());
+        }
+        Some(Command::Restore { path }) => {
+            for config in &configs {
+                restore(
+                    &profile_backup_path(&path, &config.path_prefix, single),
+                    &config.matrix_session_file,
+                    &config.matrix_state_dir,
+                )?;
+            }
+            return Ok(());
+        }
+        Some(Command::Loadtest {
+            events,
+            rate,
+            url,
+            direct,
+        }) => {
+            let config = configs.first().context("no profile in the configuration")?;
+            let report = if direct {
+                loadtest::run_pipeline(config, events, rate).await?
+            } else {
+                loadtest::fire(&url, config, events, rate).await?
+            };
+            println!("{}", report);
+            return Ok(());
+        }
+        None => {}
+    }
 
-    let rocket = rocket::build()
-        .mount(
-            "/",
-            routes![github_webhook, django, forum, new_school, impersonate],
-        )
-        .manage(EventSender(sender))
-        .manage(GitHubSecret(github_secret))
-        .manage(ProlositeSecret(prolosite_secret));
-    rocket.launch().await.map_err(|err| anyhow::anyhow!(err))
+    let mut builder = ProloloBuilder::new().role(opts.role);
+    for config in configs {
+        builder = builder.profile(ProfileBuilder::new(config));
+    }
+    builder.build().await?.run().await
 }
 
 #[test]
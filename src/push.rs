@@ -0,0 +1,134 @@
+//! Mobile push notifications through an [ntfy](https://ntfy.sh) topic or a
+//! [Gotify](https://gotify.net) server, for people on call.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::{
+    bot::Severity,
+    config::{PushConfig, PushService},
+};
+
+pub struct PushNotifier {
+    client: reqwest::Client,
+    config: PushConfig,
+}
+
+impl PushNotifier {
+    pub fn new(config: PushConfig, timeout: Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            config,
+        })
+    }
+
+    /// Messages less severe than this aren't pushed
+    pub fn min_severity(&self) -> Severity {
+        self.config.min_severity
+    }
+
+    fn request(&self, body: &str) -> anyhow::Result<reqwest::RequestBuilder> {
+        Ok(match self.config.service {
+            PushService::Ntfy => {
+                let request = self
+                    .client
+                    .post(self.config.url.clone())
+                    .header("Title", "prololo")
+                    .header("Priority", "high")
+                    .body(body.to_string());
+
+                match &self.config.token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            PushService::Gotify => {
+                let mut url = self.config.url.clone();
+                url.path_segments_mut()
+                    .map_err(|()| anyhow!("invalid Gotify URL {}", self.config.url))?
+                    .pop_if_empty()
+                    .push("message");
+
+                let request = self.client.post(url).json(&json!({
+                    "title": "prololo",
+                    "message": body,
+                    "priority": 8,
+                }));
+
+                match &self.config.token {
+                    Some(token) => request.header("X-Gotify-Key", token),
+                    None => request,
+                }
+            }
+        })
+    }
+
+    /// Sends a push notification in the background.
+    pub fn notify(&self, body: &str) {
+        let request = match self.request(body) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("couldn't send push notification: {:#}", e);
+                return;
+            }
+        };
+        tokio::spawn(async move {
+            match request.send().await.and_then(|res| res.error_for_status()) {
+                Ok(_) => debug!("sent push notification"),
+                Err(e) => warn!("couldn't send push notification: {}", e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::*;
+
+    #[test]
+    fn test_gotify_request() {
+        let notifier = PushNotifier::new(
+            PushConfig {
+                service: PushService::Gotify,
+                url: Url::parse("https://push.prologin.org/").unwrap(),
+                token: Some("secret".to_string()),
+                min_severity: Severity::High,
+            },
+            Duration::from_secs(30),
+        )
+        .unwrap();
+
+        let request = notifier.request("django crash").unwrap().build().unwrap();
+
+        assert_eq!(request.url().as_str(), "https://push.prologin.org/message");
+        assert_eq!(request.headers()["X-Gotify-Key"], "secret");
+    }
+
+    #[test]
+    fn test_ntfy_request() {
+        let notifier = PushNotifier::new(
+            PushConfig {
+                service: PushService::Ntfy,
+                url: Url::parse("https://ntfy.sh/prololo").unwrap(),
+                token: None,
+                min_severity: Severity::High,
+            },
+            Duration::from_secs(30),
+        )
+        .unwrap();
+
+        let request = notifier.request("django crash").unwrap().build().unwrap();
+
+        assert_eq!(request.url().as_str(), "https://ntfy.sh/prololo");
+        assert!(request.headers().get("Authorization").is_none());
+        assert_eq!(
+            request.body().and_then(|body| body.as_bytes()),
+            Some(&b"django crash"[..])
+        );
+    }
+}
@@ -0,0 +1,577 @@
+//! Redis list between the webhook endpoints and the Matrix sender, so that they can run as
+//! separate processes: events keep being accepted while the sender restarts, and several instances
+//! of the endpoints can run behind a load balancer.
+//!
+//! The raw payloads go through the queue, the transformation rules are applied and the payloads
+//! are parsed by the sender. An event stays in a processing list until the bot is done with it,
+//! and is put back in the queue if the sender crashed in the meantime. The retries are announced
+//! once thanks to the delivery ID, used both to skip the events already handled and as the Matrix
+//! transaction ID.
+//!
+//! The events the bot fails to handle (e.g. when the homeserver is down) are put back at the end of
+//! the queue after a delay. After a few failed attempts, they are moved to a dead letter list.
+//!
+//! The next events are handed over without waiting for the bot to acknowledge the previous ones.
+//! The pushes combined by the debouncer are acknowledged once their combined message is sent.
+//!
+//! This needs Redis 6.2 or later, and a single sender per queue.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use matrix_sdk::uuid::Uuid;
+use redis::aio::{Connection, ConnectionManager};
+use tokio::sync::{mpsc::UnboundedSender, oneshot, Mutex, Semaphore};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::QueueConfig,
+    webhooks::{Delivery, ParseSettings, RawDelivery},
+};
+
+/// Time to wait before reconnecting to Redis after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How long the IDs of the handled events are remembered, in seconds
+const DONE_TTL: u64 = 24 * 60 * 60;
+/// Events handed over to the bot and not handled yet, e.g. pushes held by the debouncer
+const MAX_IN_FLIGHT: u32 = 256;
+/// Time to wait before queuing again an event the bot failed to handle
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Failed attempts after which an event is moved to the dead letter list
+const MAX_ATTEMPTS: u32 = 5;
+
+fn processing_key(key: &str) -> String {
+    format!("{}:processing", key)
+}
+
+fn attempts_key(key: &str) -> String {
+    format!("{}:attempts", key)
+}
+
+fn dead_key(key: &str) -> String {
+    format!("{}:dead", key)
+}
+
+fn done_key(key: &str, id: &str) -> String {
+    format!("{}:done:{}", key, id)
+}
+
+/// The producing side of the queue, used by the webhook endpoints
+pub struct Queue {
+    connection: ConnectionManager,
+    key: String,
+}
+
+impl Queue {
+    pub async fn connect(config: &QueueConfig) -> anyhow::Result<Self> {
+        let client = redis::Client::open(config.redis_url.as_str())?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .with_context(|| format!("couldn't connect to {}", config.redis_url))?;
+
+        Ok(Self {
+            connection,
+            key: config.key.clone(),
+        })
+    }
+
+    pub async fn push(&self, mut delivery: RawDelivery) -> anyhow::Result<()> {
+        // the ID must be set before the event enters the queue to be the same for all the retries
+        if delivery.id.is_none() {
+            delivery.id = Some(Uuid::new_v4().to_string());
+        }
+        let value = serde_json::to_string(&delivery)?;
+        // the connection manager is a cheap handle on a shared connection
+        let mut connection = self.connection.clone();
+        redis::cmd("LPUSH")
+            .arg(&self.key)
+            .arg(value)
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// The lists of a queue as seen by the consumer, a trait so that the recovery of the interrupted
+/// events can be tested without a Redis server
+#[rocket::async_trait]
+trait ProcessingList: Send + Sync + 'static {
+    /// Moves an event left in the processing list by a previous run back to the queue
+    async fn recover(&self) -> anyhow::Result<Option<String>>;
+
+    /// Waits for the next event of the queue, and moves it to the processing list
+    async fn pop(&self) -> anyhow::Result<String>;
+
+    /// Whether the event with this delivery ID was already handled
+    async fn is_done(&self, id: &str) -> anyhow::Result<bool>;
+
+    /// Removes an event from the processing list, remembering that it was handled if it has an ID
+    async fn acknowledge(&self, id: Option<&str>, value: &str) -> anyhow::Result<()>;
+
+    /// Counts a failed attempt at handling an event, returns the number of failed attempts so far
+    async fn fail(&self, id: Option<&str>, value: &str) -> anyhow::Result<u32>;
+
+    /// Moves an event from the processing list to the end of the queue
+    async fn requeue(&self, value: &str) -> anyhow::Result<()>;
+
+    /// Moves an event from the processing list to the dead letter list
+    async fn bury(&self, id: Option<&str>, value: &str) -> anyhow::Result<()>;
+}
+
+struct RedisList {
+    key: String,
+    processing: String,
+    attempts: String,
+    dead: String,
+    /// Blocking pops need their own connection
+    blocking: Mutex<Connection>,
+    connection: ConnectionManager,
+}
+
+impl RedisList {
+    async fn connect(config: &QueueConfig) -> anyhow::Result<Self> {
+        let client = redis::Client::open(config.redis_url.as_str())?;
+        Ok(Self {
+            key: config.key.clone(),
+            processing: processing_key(&config.key),
+            attempts: attempts_key(&config.key),
+            dead: dead_key(&config.key),
+            blocking: Mutex::new(client.get_async_connection().await?),
+            connection: ConnectionManager::new(client).await?,
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl ProcessingList for RedisList {
+    async fn recover(&self) -> anyhow::Result<Option<String>> {
+        Ok(redis::cmd("LMOVE")
+            .arg(&self.processing)
+            .arg(&self.key)
+            .arg("RIGHT")
+            .arg("RIGHT")
+            .query_async(&mut self.connection.clone())
+            .await?)
+    }
+
+    async fn pop(&self) -> anyhow::Result<String> {
+        Ok(redis::cmd("BLMOVE")
+            .arg(&self.key)
+            .arg(&self.processing)
+            .arg("RIGHT")
+            .arg("LEFT")
+            .arg(0)
+            .query_async(&mut *self.blocking.lock().await)
+            .await?)
+    }
+
+    async fn is_done(&self, id: &str) -> anyhow::Result<bool> {
+        Ok(redis::cmd("EXISTS")
+            .arg(done_key(&self.key, id))
+            .query_async(&mut self.connection.clone())
+            .await?)
+    }
+
+    async fn acknowledge(&self, id: Option<&str>, value: &str) -> anyhow::Result<()> {
+        let mut connection = self.connection.clone();
+        if let Some(id) = id {
+            redis::cmd("SET")
+                .arg(done_key(&self.key, id))
+                .arg(1)
+                .arg("EX")
+                .arg(DONE_TTL)
+                .query_async::<_, ()>(&mut connection)
+                .await?;
+        }
+        redis::pipe()
+            .atomic()
+            .cmd("LREM")
+            .arg(&self.processing)
+            .arg(1)
+            .arg(value)
+            .cmd("HDEL")
+            .arg(&self.attempts)
+            .arg(id.unwrap_or(value))
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn fail(&self, id: Option<&str>, value: &str) -> anyhow::Result<u32> {
+        Ok(redis::cmd("HINCRBY")
+            .arg(&self.attempts)
+            .arg(id.unwrap_or(value))
+            .arg(1)
+            .query_async(&mut self.connection.clone())
+            .await?)
+    }
+
+    async fn requeue(&self, value: &str) -> anyhow::Result<()> {
+        // the events are popped from the right, the other ones go first
+        redis::pipe()
+            .atomic()
+            .cmd("LPUSH")
+            .arg(&self.key)
+            .arg(value)
+            .cmd("LREM")
+            .arg(&self.processing)
+            .arg(1)
+            .arg(value)
+            .query_async::<_, ()>(&mut self.connection.clone())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn bury(&self, id: Option<&str>, value: &str) -> anyhow::Result<()> {
+        redis::pipe()
+            .atomic()
+            .cmd("LPUSH")
+            .arg(&self.dead)
+            .arg(value)
+            .cmd("LREM")
+            .arg(&self.processing)
+            .arg(1)
+            .arg(value)
+            .cmd("HDEL")
+            .arg(&self.attempts)
+            .arg(id.unwrap_or(value))
+            .query_async::<_, ()>(&mut self.connection.clone())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Waits for the bot to be done with an event, then acknowledges it, or queues it again if the bot
+/// failed to handle it
+async fn follow<L: ProcessingList>(
+    list: &L,
+    id: Option<String>,
+    value: String,
+    done: oneshot::Receiver<()>,
+    retry_delay: Duration,
+) -> anyhow::Result<()> {
+    let id = id.as_deref();
+    if done.await.is_ok() {
+        return list.acknowledge(id, &value).await;
+    }
+
+    let attempts = list.fail(id, &value).await?;
+    if attempts >= MAX_ATTEMPTS {
+        warn!(
+            "giving up on event {:?} after {} attempts, moving it to the dead letter list",
+            id, attempts
+        );
+        return list.bury(id, &value).await;
+    }
+    info!(
+        "retrying event {:?} in {}s ({} failed attempts)",
+        id,
+        retry_delay.as_secs(),
+        attempts
+    );
+    tokio::time::sleep(retry_delay).await;
+    list.requeue(&value).await
+}
+
+async fn pop_events<L: ProcessingList>(
+    list: Arc<L>,
+    settings: &ParseSettings,
+    in_flight: &Arc<Semaphore>,
+    retry_delay: Duration,
+    sender: &UnboundedSender<Delivery>,
+) -> anyhow::Result<()> {
+    // the events handed over by a previous run are only recovered once the bot is done with them,
+    // or failed to handle them
+    drop(in_flight.acquire_many(MAX_IN_FLIGHT).await?);
+
+    // the events being handled when the sender stopped are handled again first
+    while let Some(value) = list.recover().await? {
+        info!("retrying interrupted event: {}", value);
+    }
+    info!("waiting for events on the queue");
+
+    loop {
+        let value = list.pop().await?;
+        debug!("popped event from the queue: {}", value);
+
+        let parsed = match serde_json::from_str::<RawDelivery>(&value) {
+            Ok(delivery) => settings.parse(delivery).await,
+            Err(e) => Err(e.into()),
+        };
+        match parsed {
+            Ok(None) => info!("queued event dropped by a transformation rule"),
+            Ok(Some(mut delivery)) => {
+                let already_done = match &delivery.id {
+                    Some(id) => list.is_done(id).await?,
+                    None => false,
+                };
+
+                if already_done {
+                    info!("skipping already handled event {:?}", delivery.id);
+                } else {
+                    let permit = in_flight.clone().acquire_owned().await?;
+                    let id = delivery.id.clone();
+                    let (done_sender, done_receiver) = oneshot::channel();
+                    delivery.done = Some(done_sender);
+                    if sender.send(delivery).is_err() {
+                        // the event stays in the processing list for the next run
+                        return Ok(());
+                    }
+
+                    let list = list.clone();
+                    tokio::spawn(async move {
+                        // the event stays in the processing list if the sender crashes before
+                        // handling it
+                        let followed = follow(&*list, id, value, done_receiver, retry_delay);
+                        if let Err(e) = followed.await {
+                            warn!("couldn't acknowledge queued event: {:#}", e);
+                        }
+                        drop(permit);
+                    });
+                    continue;
+                }
+            }
+            Err(e) => warn!("dropping invalid queued event: {:#}\n{}", e, value),
+        }
+
+        list.acknowledge(None, &value).await?;
+    }
+}
+
+/// Hands the events of the queue over to the bot, until it stops.
+pub async fn consume(
+    config: QueueConfig,
+    settings: ParseSettings,
+    sender: UnboundedSender<Delivery>,
+) {
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT as usize));
+    loop {
+        let popped = match RedisList::connect(&config).await {
+            Ok(list) => {
+                let list = Arc::new(list);
+                pop_events(list, &settings, &in_flight, RETRY_DELAY, &sender).await
+            }
+            Err(e) => Err(e),
+        };
+        match popped {
+            Ok(()) => {
+                info!("the bot stopped, no longer consuming the queue");
+                return;
+            }
+            Err(e) => warn!("couldn't read the queue {}: {:#}", config.key, e),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet, VecDeque};
+
+    use tokio::sync::mpsc;
+
+    use crate::webhooks::Event;
+
+    use super::*;
+
+    /// The lists of a queue, ending the consumer once the queue is empty
+    #[derive(Default)]
+    struct MemoryList(std::sync::Mutex<MemoryLists>);
+
+    #[derive(Default)]
+    struct MemoryLists {
+        queue: VecDeque<String>,
+        processing: VecDeque<String>,
+        done: HashSet<String>,
+        attempts: HashMap<String, u32>,
+        dead: Vec<String>,
+    }
+
+    impl MemoryLists {
+        fn remove_processing(&mut self, value: &str) {
+            if let Some(index) = self.processing.iter().position(|v| v == value) {
+                self.processing.remove(index);
+            }
+        }
+    }
+
+    #[rocket::async_trait]
+    impl ProcessingList for MemoryList {
+        async fn recover(&self) -> anyhow::Result<Option<String>> {
+            let mut lists = self.0.lock().unwrap();
+            let value = lists.processing.pop_back();
+            if let Some(value) = &value {
+                lists.queue.push_back(value.clone());
+            }
+            Ok(value)
+        }
+
+        async fn pop(&self) -> anyhow::Result<String> {
+            let mut lists = self.0.lock().unwrap();
+            let value = lists.queue.pop_back().context("empty queue")?;
+            lists.processing.push_front(value.clone());
+            Ok(value)
+        }
+
+        async fn is_done(&self, id: &str) -> anyhow::Result<bool> {
+            Ok(self.0.lock().unwrap().done.contains(id))
+        }
+
+        async fn acknowledge(&self, id: Option<&str>, value: &str) -> anyhow::Result<()> {
+            let mut lists = self.0.lock().unwrap();
+            if let Some(id) = id {
+                lists.done.insert(id.to_string());
+            }
+            lists.remove_processing(value);
+            lists.attempts.remove(id.unwrap_or(value));
+            Ok(())
+        }
+
+        async fn fail(&self, id: Option<&str>, value: &str) -> anyhow::Result<u32> {
+            let mut lists = self.0.lock().unwrap();
+            let attempts = lists
+                .attempts
+                .entry(id.unwrap_or(value).to_string())
+                .or_default();
+            *attempts += 1;
+            Ok(*attempts)
+        }
+
+        async fn requeue(&self, value: &str) -> anyhow::Result<()> {
+            let mut lists = self.0.lock().unwrap();
+            lists.queue.push_front(value.to_string());
+            lists.remove_processing(value);
+            Ok(())
+        }
+
+        async fn bury(&self, id: Option<&str>, value: &str) -> anyhow::Result<()> {
+            let mut lists = self.0.lock().unwrap();
+            lists.dead.push(value.to_string());
+            lists.remove_processing(value);
+            lists.attempts.remove(id.unwrap_or(value));
+            Ok(())
+        }
+    }
+
+    /// Hands the events of the queue over until it is empty, once the previous ones are followed
+    async fn run(
+        list: &Arc<MemoryList>,
+        in_flight: &Arc<Semaphore>,
+    ) -> mpsc::UnboundedReceiver<Delivery> {
+        let settings = ParseSettings {
+            rules: Vec::new(),
+            canary: None,
+            chaos: None,
+        };
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let popped = pop_events(list.clone(), &settings, in_flight, Duration::ZERO, &sender);
+        assert!(popped.await.is_err());
+        receiver
+    }
+
+    fn queued(id: &str) -> String {
+        format!(
+            r#"{{"id":"{}","source":"prolosite","event_type":"forum","payload":{{"forum":"Général","title":"Hello","url":"https://prologin.org/forum/1","username":"alice"}}}}"#,
+            id
+        )
+    }
+
+    #[tokio::test]
+    async fn test_redelivery() {
+        let list = Arc::new(MemoryList::default());
+        let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT as usize));
+        list.0
+            .lock()
+            .unwrap()
+            .queue
+            .extend([queued("b"), queued("a")]);
+
+        // the sender crashes after being handed "a", before handling it
+        let mut receiver = run(&list, &in_flight).await;
+        let a = receiver.recv().await.unwrap();
+        assert_eq!(a.id.as_deref(), Some("a"));
+        let b = receiver.recv().await.unwrap();
+        assert_eq!(b.id.as_deref(), Some("b"));
+        b.done.unwrap().send(()).unwrap();
+        // "b" is acknowledged before the crash
+        drop(in_flight.acquire_many(MAX_IN_FLIGHT - 1).await.unwrap());
+
+        // only "a" is handed over again by the next process
+        let restarted = Arc::new(Semaphore::new(MAX_IN_FLIGHT as usize));
+        let mut receiver = run(&list, &restarted).await;
+        let recovered = receiver.recv().await.unwrap();
+        assert_eq!(recovered.id.as_deref(), Some("a"));
+        recovered.done.unwrap().send(()).unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        // an event queued again once handled is skipped
+        list.0.lock().unwrap().queue.push_back(queued("a"));
+        let mut receiver = run(&list, &restarted).await;
+        assert!(receiver.try_recv().is_err());
+
+        let lists = list.0.lock().unwrap();
+        assert!(lists.queue.is_empty());
+        assert!(lists.processing.is_empty());
+        drop(a);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let list = Arc::new(MemoryList::default());
+        let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT as usize));
+        list.0.lock().unwrap().queue.push_back(queued("a"));
+
+        // the first send fails, the event is announced when it is handed over again
+        let mut announced = Vec::new();
+        for send_fails in [true, false] {
+            let mut receiver = run(&list, &in_flight).await;
+            let delivery = receiver.recv().await.unwrap();
+            if !send_fails {
+                announced.extend(delivery.id);
+                delivery.done.unwrap().send(()).unwrap();
+            }
+            assert!(receiver.try_recv().is_err());
+        }
+        let mut receiver = run(&list, &in_flight).await;
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(announced, vec!["a"]);
+        {
+            let lists = list.0.lock().unwrap();
+            assert!(lists.done.contains("a"));
+            assert!(lists.processing.is_empty());
+            assert!(lists.attempts.is_empty());
+        }
+
+        // an event failing every time ends up in the dead letter list
+        list.0.lock().unwrap().queue.push_back(queued("b"));
+        for _ in 0..MAX_ATTEMPTS {
+            let mut receiver = run(&list, &in_flight).await;
+            assert_eq!(receiver.recv().await.unwrap().id.as_deref(), Some("b"));
+        }
+        let mut receiver = run(&list, &in_flight).await;
+        assert!(receiver.try_recv().is_err());
+        let lists = list.0.lock().unwrap();
+        assert_eq!(lists.dead, vec![queued("b")]);
+        assert!(lists.queue.is_empty());
+        assert!(lists.processing.is_empty());
+        assert!(lists.attempts.is_empty());
+    }
+
+    #[test]
+    fn test_queued_delivery() {
+        let value = r#"{"id":"72d3162e-cc78-11e3-81ab-4c9367dc0958","source":"prolosite","event_type":"forum","payload":{"forum":"Général","title":"Hello","url":"https://prologin.org/forum/1","username":"alice"}}"#;
+        let delivery: RawDelivery = serde_json::from_str(value).unwrap();
+        assert_eq!(serde_json::to_string(&delivery).unwrap(), value);
+
+        let delivery = delivery.parse(&[]).unwrap().unwrap();
+        assert_eq!(
+            delivery.id.as_deref(),
+            Some("72d3162e-cc78-11e3-81ab-4c9367dc0958")
+        );
+        assert!(matches!(delivery.event, Event::ProloSite(_)));
+    }
+}
@@ -1,16 +1,246 @@
-use tokio::sync::mpsc::UnboundedSender;
+use std::{convert::Infallible, time::Duration};
+
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    time::Instant,
+};
+use tracing::{info, warn};
+
+#[cfg(any(test, feature = "chaos"))]
+use crate::{chaos, config::ChaosConfig};
+use crate::{
+    config::{CanaryConfig, PayloadSource, ProloloConfig, TransformRule},
+    mail::MailEvent,
+};
+
+pub mod activity;
+
+mod body;
+
+pub mod cors;
 
 pub mod github;
-pub use github::{github_webhook, GitHubEvent};
+pub use github::{github_webhook, GitHubEvent, GitHubEventType};
 
 pub mod prolosite;
-pub(crate) use prolosite::ProloSiteEvent;
+pub(crate) use prolosite::{ProloSiteEvent, ProloSiteEventType};
 
-pub struct EventSender(pub UnboundedSender<Event>);
+pub mod openapi;
 
-#[allow(clippy::large_enum_variant)]
+pub mod render;
+
+pub mod status;
+
+pub mod profile;
+
+pub mod rules;
+use rules::{apply_rules, event_name, has_rules_for, Overrides, Verdict};
+
+/// Time the endpoints have to answer, half of the 10 seconds GitHub waits before giving up on a
+/// delivery. Anything that can be slow happens after answering
+pub const LATENCY_BUDGET: Duration = Duration::from_secs(5);
+
+/// When the endpoint started handling the request, used to enforce [`LATENCY_BUDGET`]
+#[derive(Clone, Copy)]
+pub struct Received(pub Instant);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Received {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(*request.local_cache(|| Received(Instant::now())))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum RawEvent {
+    GitHub {
+        event_type: GitHubEventType,
+        payload: String,
+    },
+    ProloSite {
+        event_type: ProloSiteEventType,
+        payload: serde_json::Value,
+    },
+}
+
+/// An event as received by the endpoints, before the transformation rules and the parsing
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RawDelivery {
+    /// The `X-GitHub-Delivery` header, or a random ID for the events going through the queue
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub event: RawEvent,
+}
+
+impl RawDelivery {
+    /// Applies the transformation rules, then parses the payload. Returns `None` if a rule dropped
+    /// the event.
+    pub fn parse(self, rules: &[TransformRule]) -> anyhow::Result<Option<Delivery>> {
+        let overrides = match self.verdict(rules)? {
+            Verdict::Keep(overrides) => overrides,
+            Verdict::Drop => return Ok(None),
+        };
+
+        let event = match self.event {
+            RawEvent::GitHub {
+                event_type,
+                payload,
+            } => Event::GitHub(event_type.parse_payload(payload)?),
+            RawEvent::ProloSite {
+                event_type,
+                payload,
+            } => Event::ProloSite(event_type.parse_payload(&payload)?),
+        };
+
+        Ok(Some(Delivery {
+            id: self.id,
+            event,
+            overrides,
+            canary: None,
+            done: None,
+        }))
+    }
+
+    /// Like [`RawDelivery::parse`], also applying the rules of the canary if there is one, so that
+    /// the bot can compare both messages. The events dropped by the current rules aren't compared.
+    pub fn parse_with_canary(
+        self,
+        rules: &[TransformRule],
+        canary: Option<&CanaryConfig>,
+    ) -> anyhow::Result<Option<Delivery>> {
+        let verdict = match canary {
+            Some(canary) => Some(self.verdict(&canary.rules)?),
+            None => None,
+        };
+        let mut delivery = self.parse(rules)?;
+        if let Some(delivery) = &mut delivery {
+            delivery.canary = verdict;
+        }
+        Ok(delivery)
+    }
+
+    /// What the first matching rule does with the event
+    fn verdict(&self, rules: &[TransformRule]) -> anyhow::Result<Verdict> {
+        Ok(match &self.event {
+            RawEvent::GitHub {
+                event_type,
+                payload,
+            } => {
+                let event = event_name(event_type);
+                // building a `Value` of the whole payload costs more than parsing the event itself
+                if has_rules_for(rules, PayloadSource::GitHub, &event) {
+                    apply_rules(
+                        rules,
+                        PayloadSource::GitHub,
+                        &event,
+                        &serde_json::from_str(payload)?,
+                    )
+                } else {
+                    Verdict::Keep(Overrides::default())
+                }
+            }
+            RawEvent::ProloSite {
+                event_type,
+                payload,
+            } => apply_rules(
+                rules,
+                PayloadSource::ProloSite,
+                &event_name(event_type),
+                payload,
+            ),
+        })
+    }
+}
+
+/// The settings of a bot used to turn the raw events into deliveries, wherever they are parsed
+#[derive(Clone)]
+pub struct ParseSettings {
+    pub rules: Vec<TransformRule>,
+    pub canary: Option<CanaryConfig>,
+    #[cfg(any(test, feature = "chaos"))]
+    pub chaos: Option<ChaosConfig>,
+}
+
+impl ParseSettings {
+    pub fn from_config(config: &ProloloConfig) -> Self {
+        Self {
+            rules: config.rules.clone(),
+            canary: config.canary.clone(),
+            #[cfg(any(test, feature = "chaos"))]
+            chaos: config.chaos.clone(),
+        }
+    }
+
+    /// Applies the rules to an event, see [`RawDelivery::parse_with_canary`].
+    pub async fn parse(&self, delivery: RawDelivery) -> anyhow::Result<Option<Delivery>> {
+        #[cfg(any(test, feature = "chaos"))]
+        chaos::delay_parse(self.chaos.as_ref()).await;
+
+        delivery.parse_with_canary(&self.rules, self.canary.as_ref())
+    }
+}
+
+/// Parses the events received by the endpoints, in order, and hands them over to the bot.
+pub async fn parse_deliveries(
+    mut deliveries: UnboundedReceiver<RawDelivery>,
+    settings: ParseSettings,
+    sender: UnboundedSender<Delivery>,
+) {
+    while let Some(delivery) = deliveries.recv().await {
+        let id = delivery.id.clone();
+        match settings.parse(delivery).await {
+            Ok(Some(delivery)) => {
+                if sender.send(delivery).is_err() {
+                    break;
+                }
+            }
+            Ok(None) => info!("event {:?} dropped by a transformation rule", id),
+            Err(e) => warn!("couldn't parse payload of event {:?}: {:#}", id, e),
+        }
+    }
+}
+
+/// An event along with the changes requested by the transformation rules
 #[derive(Debug)]
+pub struct Delivery {
+    /// Identifies the delivery across retries, so that it is announced only once
+    pub id: Option<String>,
+    pub event: Event,
+    pub overrides: Overrides,
+    /// What the rules of the canary do with the event, if there is a canary
+    pub canary: Option<Verdict>,
+    /// Notified once the bot is done with the event, to remove it from the queue
+    pub done: Option<oneshot::Sender<()>>,
+}
+
+impl From<Event> for Delivery {
+    fn from(event: Event) -> Self {
+        Self {
+            id: None,
+            event,
+            overrides: Overrides::default(),
+            canary: None,
+            done: None,
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
 pub enum Event {
     GitHub(GitHubEvent),
     ProloSite(ProloSiteEvent),
+    Mail(MailEvent),
 }
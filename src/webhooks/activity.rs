@@ -0,0 +1,255 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use chrono::Datelike;
+use rocket::{
+    http::{ContentType, Status},
+    response::status::Custom,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    bot::{
+        archive::{ArchivedKind, ArchivedMessage},
+        metrics::Metrics,
+    },
+    webhooks::{profile::Profile, prolosite::AuthorizationHeader},
+};
+
+/// Number of announcements about a repo triggered by someone during a week
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+pub struct ActivityRow {
+    /// ISO week, e.g. `2021-W50`
+    pub week: String,
+    pub repo: String,
+    /// GitHub login, missing for events triggered by GitHub apps
+    pub author: Option<String>,
+    pub messages: u64,
+}
+
+fn activity(messages: &[ArchivedMessage]) -> Vec<ActivityRow> {
+    let mut counts: BTreeMap<(String, &str, Option<&str>), u64> = BTreeMap::new();
+    for message in messages {
+        let (repo, author) = match &message.kind {
+            ArchivedKind::Announcement {
+                repo: Some(repo),
+                author,
+                ..
+            } => (repo, author),
+            _ => continue,
+        };
+        let week = message.time.iso_week();
+        let week = format!("{}-W{:02}", week.year(), week.week());
+
+        *counts.entry((week, repo, author.as_deref())).or_default() += 1;
+    }
+
+    counts
+        .into_iter()
+        .map(|((week, repo, author), messages)| ActivityRow {
+            week,
+            repo: repo.to_string(),
+            author: author.map(str::to_string),
+            messages,
+        })
+        .collect()
+}
+
+fn to_csv(rows: &[ActivityRow]) -> String {
+    let mut csv = "week,repo,author,messages\n".to_string();
+    for row in rows {
+        writeln!(
+            csv,
+            "{},{},{},{}",
+            row.week,
+            row.repo,
+            row.author.as_deref().unwrap_or_default(),
+            row.messages
+        )
+        .unwrap();
+    }
+
+    csv
+}
+
+/// Number of events received from each source and failed events during a day
+#[derive(Debug, PartialEq, Serialize, JsonSchema)]
+pub struct MetricsRow {
+    /// UTC day, e.g. `2021-12-13`
+    pub day: String,
+    pub github: u64,
+    pub prolosite: u64,
+    pub mail: u64,
+    pub errors: u64,
+}
+
+fn metrics_rows(metrics: &Metrics) -> Vec<MetricsRow> {
+    metrics
+        .days()
+        .map(|(day, metrics)| {
+            let events = |source: &str| metrics.events.get(source).copied().unwrap_or(0);
+            MetricsRow {
+                day: day.to_string(),
+                github: events("github"),
+                prolosite: events("prolosite"),
+                mail: events("mail"),
+                errors: metrics.errors,
+            }
+        })
+        .collect()
+}
+
+fn metrics_to_csv(rows: &[MetricsRow]) -> String {
+    let mut csv = "day,github,prolosite,mail,errors\n".to_string();
+    for row in rows {
+        writeln!(
+            csv,
+            "{},{},{},{},{}",
+            row.day, row.github, row.prolosite, row.mail, row.errors
+        )
+        .unwrap();
+    }
+
+    csv
+}
+
+/// Exports the number of announcements per week, repo and author, as JSON or CSV.
+#[rocket::get("/api/activity?<format>")]
+pub fn activity_export(
+    _token: AuthorizationHeader,
+    format: Option<&str>,
+    profile: &Profile,
+) -> Result<(ContentType, String), Custom<String>> {
+    info!("received activity export request");
+
+    let archive = profile.archive.as_ref().ok_or_else(|| {
+        Custom(
+            Status::NotFound,
+            "the archive is kept by the instance running the bot".to_string(),
+        )
+    })?;
+    let messages = archive.all().map_err(|e| {
+        warn!("couldn't read the archive: {:#}", e);
+        Custom(
+            Status::InternalServerError,
+            format!("couldn't read the archive: {}", e),
+        )
+    })?;
+    let rows = activity(&messages);
+
+    match format {
+        None | Some("json") => Ok((
+            ContentType::JSON,
+            serde_json::to_string(&rows).expect("activity rows are always serializable"),
+        )),
+        Some("csv") => Ok((ContentType::CSV, to_csv(&rows))),
+        Some(format) => Err(Custom(
+            Status::BadRequest,
+            format!("unknown format `{}`, expected `json` or `csv`", format),
+        )),
+    }
+}
+
+/// Exports the daily event counters kept by the bot, as JSON or CSV.
+#[rocket::get("/api/metrics?<format>")]
+pub fn metrics_export(
+    _token: AuthorizationHeader,
+    format: Option<&str>,
+    profile: &Profile,
+) -> Result<(ContentType, String), Custom<String>> {
+    info!("received metrics export request");
+
+    let activity = profile.activity.as_ref().ok_or_else(|| {
+        Custom(
+            Status::NotFound,
+            "the metrics are kept by the instance running the bot".to_string(),
+        )
+    })?;
+    let rows = metrics_rows(&activity.metrics());
+
+    match format {
+        None | Some("json") => Ok((
+            ContentType::JSON,
+            serde_json::to_string(&rows).expect("metrics rows are always serializable"),
+        )),
+        Some("csv") => Ok((ContentType::CSV, metrics_to_csv(&rows))),
+        Some(format) => Err(Custom(
+            Status::BadRequest,
+            format!("unknown format `{}`, expected `json` or `csv`", format),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use chrono::{DateTime, TimeZone, Utc};
+    use matrix_sdk::ruma::{EventId, RoomId, UserId};
+
+    use crate::bot::Severity;
+
+    use super::*;
+
+    #[test]
+    fn test_activity() {
+        let message = |time: &str, kind: ArchivedKind| ArchivedMessage {
+            time: DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc),
+            room: RoomId::try_from("!abc:prologin.org").unwrap(),
+            event_id: EventId::try_from("$event:prologin.org").unwrap(),
+            kind,
+            body: "[site] something happened".to_string(),
+        };
+        let announcement = |repo: Option<&str>, author: Option<&str>| ArchivedKind::Announcement {
+            repo: repo.map(str::to_string),
+            author: author.map(str::to_string),
+            severity: Severity::Normal,
+        };
+        let messages = vec![
+            // 2021-12-13 is the Monday of the 50th week
+            message(
+                "2021-12-13T10:00:00Z",
+                announcement(Some("prologin/site"), Some("alice")),
+            ),
+            message(
+                "2021-12-19T10:00:00Z",
+                announcement(Some("prologin/site"), Some("alice")),
+            ),
+            message(
+                "2021-12-20T10:00:00Z",
+                announcement(Some("prologin/site"), None),
+            ),
+            message("2021-12-20T11:00:00Z", announcement(None, None)),
+            message(
+                "2021-12-20T12:00:00Z",
+                ArchivedKind::Chat {
+                    sender: UserId::try_from("@bob:prologin.org").unwrap(),
+                },
+            ),
+        ];
+
+        let rows = activity(&messages);
+        assert_eq!(
+            to_csv(&rows),
+            "week,repo,author,messages\n2021-W50,prologin/site,alice,2\n2021-W51,prologin/site,,1\n"
+        );
+    }
+
+    #[test]
+    fn test_metrics_rows() {
+        let day = Utc.ymd(2021, 12, 13).and_hms(10, 0, 0);
+        let mut metrics = Metrics::default();
+        metrics.record_event("github", day);
+        metrics.record_event("github", day);
+        metrics.record_event("mail", day + chrono::Duration::days(1));
+        metrics.record_error(day + chrono::Duration::days(1));
+
+        assert_eq!(
+            metrics_to_csv(&metrics_rows(&metrics)),
+            "day,github,prolosite,mail,errors\n2021-12-13,2,0,0,0\n2021-12-14,0,0,1,1\n"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Reading of the webhook bodies, which some proxies and senders compress with gzip or deflate.
+//! The size limit applies to the body as received and once decompressed.
+
+use std::io::{self, Read};
+
+use anyhow::anyhow;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use rocket::{
+    data::{ByteUnit, FromData, Outcome},
+    http::Status,
+    Data, Request,
+};
+use tracing::trace;
+
+const CONTENT_ENCODING: &str = "Content-Encoding";
+
+/// Limit of the bodies without a `json` limit in the Rocket configuration
+pub(crate) const LIMIT: ByteUnit = ByteUnit::Mebibyte(1);
+
+fn too_large() -> (Status, anyhow::Error) {
+    trace!("payload was too big");
+    let eof = io::ErrorKind::UnexpectedEof;
+    (
+        Status::PayloadTooLarge,
+        io::Error::new(eof, "data limit exceeded").into(),
+    )
+}
+
+/// Decompresses a body according to its `Content-Encoding`, reading at most `limit` bytes
+fn decode(
+    body: Vec<u8>,
+    encoding: Option<&str>,
+    limit: u64,
+) -> Result<Vec<u8>, (Status, anyhow::Error)> {
+    let reader: Box<dyn Read> = match encoding.map(str::trim) {
+        None | Some("identity") => return Ok(body),
+        Some(encoding) if encoding.eq_ignore_ascii_case("gzip") => {
+            Box::new(GzDecoder::new(&body[..]))
+        }
+        // HTTP's deflate is actually the zlib format
+        Some(encoding) if encoding.eq_ignore_ascii_case("deflate") => {
+            Box::new(ZlibDecoder::new(&body[..]))
+        }
+        Some(encoding) => {
+            trace!("unsupported content encoding `{}`", encoding);
+            return Err((
+                Status::UnsupportedMediaType,
+                anyhow!("unsupported content encoding {}", encoding),
+            ));
+        }
+    };
+
+    // one more byte than the limit tells a body that fits from one that was cut
+    let mut decoded = Vec::new();
+    reader
+        .take(limit + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| {
+            (
+                Status::BadRequest,
+                anyhow!("couldn't decompress body: {}", e),
+            )
+        })?;
+    if decoded.len() as u64 > limit {
+        return Err(too_large());
+    }
+
+    Ok(decoded)
+}
+
+/// Reads the whole body of a request as text, decompressing it if needed
+pub(crate) async fn read_body(
+    request: &Request<'_>,
+    data: Data<'_>,
+    limit: ByteUnit,
+) -> Result<String, (Status, anyhow::Error)> {
+    let body = match data.open(limit).into_bytes().await {
+        Ok(bytes) if bytes.is_complete() => bytes.into_inner(),
+        Ok(_) => return Err(too_large()),
+        Err(e) => return Err((Status::BadRequest, e.into())),
+    };
+
+    let encoding = request.headers().get_one(CONTENT_ENCODING);
+    let body = decode(body, encoding, limit.as_u64())?;
+    String::from_utf8(body).map_err(|e| (Status::BadRequest, e.into()))
+}
+
+/// A JSON body, possibly compressed
+pub struct JsonBody(pub serde_json::Value);
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for JsonBody {
+    type Error = anyhow::Error;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
+        let limit = request.limits().get("json").unwrap_or(LIMIT);
+        let body = match read_body(request, data, limit).await {
+            Ok(body) => body,
+            Err(failure) => return Outcome::Failure(failure),
+        };
+
+        match serde_json::from_str(&body) {
+            Ok(value) => Outcome::Success(JsonBody(value)),
+            Err(e) => Outcome::Failure((Status::UnprocessableEntity, e.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let body = br#"{"zen": "Keep it logically awesome."}"#.to_vec();
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&body).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(decode(gzip, Some("gzip"), 1024).unwrap(), body);
+
+        let mut deflate = ZlibEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(&body).unwrap();
+        let deflate = deflate.finish().unwrap();
+        assert_eq!(decode(deflate, Some("deflate"), 1024).unwrap(), body);
+
+        assert_eq!(decode(body.clone(), None, 1024).unwrap(), body);
+        assert_eq!(
+            decode(body.clone(), Some("br"), 1024).unwrap_err().0,
+            Status::UnsupportedMediaType
+        );
+        assert_eq!(
+            decode(body, Some("gzip"), 1024).unwrap_err().0,
+            Status::BadRequest
+        );
+
+        // the limit applies to the decompressed body
+        let mut bomb = GzEncoder::new(Vec::new(), Compression::best());
+        bomb.write_all(&[b' '; 4096]).unwrap();
+        let bomb = bomb.finish().unwrap();
+        assert!(bomb.len() < 1024);
+        assert_eq!(
+            decode(bomb, Some("gzip"), 1024).unwrap_err().0,
+            Status::PayloadTooLarge
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! CORS headers for the Prologin site endpoints, so that dashboards and static pages can post to
+//! them directly from a browser. The other endpoints are only called by servers.
+
+use std::path::PathBuf;
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Method, Status},
+    Request, Response,
+};
+
+use crate::{config::BrowserConfig, webhooks::profile::Profile};
+
+/// The routes browsers can call, the others are only called by the Prologin site
+const BROWSER_ROUTES: &[&str] = &["forum", "new_school", "prolosite_preflight"];
+
+impl BrowserConfig {
+    fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+}
+
+/// Answers the preflight requests of the browsers, the headers are added by [`Cors`]
+#[rocket::options("/api/webhooks/prolosite/<_path..>")]
+pub fn prolosite_preflight(_path: PathBuf) -> Status {
+    Status::NoContent
+}
+
+/// Adds the CORS headers to the responses of the [`BROWSER_ROUTES`], for the origins allowed by
+/// the profile of the route
+pub struct Cors;
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) => origin,
+            None => return,
+        };
+        let route = request.route().and_then(|route| route.name.as_deref());
+        if !route.map_or(false, |route| BROWSER_ROUTES.contains(&route)) {
+            return;
+        }
+        let profile = match request.guard::<&Profile>().await.succeeded() {
+            Some(profile) => profile,
+            None => return,
+        };
+        match &profile.browser {
+            Some(browser) if browser.allows(origin) => {}
+            _ => return,
+        }
+
+        response.set_raw_header("Access-Control-Allow-Origin", origin.to_string());
+        response.set_raw_header("Vary", "Origin");
+        if request.method() == Method::Options {
+            response.set_raw_header("Access-Control-Allow-Methods", "POST");
+            response.set_raw_header(
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type",
+            );
+            response.set_raw_header("Access-Control-Max-Age", "86400");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Header},
+        local::asynchronous::Client,
+        routes,
+    };
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+    use crate::{
+        config::ProloloConfig,
+        webhooks::{
+            profile::{EventSink, Profiles},
+            prolosite::{django, forum},
+        },
+    };
+
+    #[rocket::async_test]
+    async fn test_browser_requests() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            "{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_state_dir: state, matrix_rooms: {}, \
+             destinations: [], github_secret: secret, prolosite_secret: secret, \
+             browser: {allowed_origins: ['https://dashboard.prologin.org'], \
+             token: dashboard, routes: [forum]}}",
+        )
+        .unwrap();
+        let (sender, _receiver) = unbounded_channel();
+        let profile = Profile::new(&config, EventSink::Bot(sender), None, None);
+        let rocket = rocket::build()
+            .mount("/", routes![django, forum, prolosite_preflight])
+            .attach(Cors)
+            .manage(Profiles(vec![profile]));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let response = client
+            .options("/api/webhooks/prolosite/forum")
+            .header(Header::new("Origin", "https://dashboard.prologin.org"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://dashboard.prologin.org")
+        );
+
+        let post = |uri: &'static str, origin: &'static str| {
+            client
+                .post(uri)
+                .header(ContentType::JSON)
+                .header(Header::new("Origin", origin))
+                .body(r#"{"username": "alice", "title": "Hello", "url": "https://prologin.org/forum/1"}"#)
+                .dispatch()
+        };
+        let response = post(
+            "/api/webhooks/prolosite/forum?token=dashboard",
+            "https://dashboard.prologin.org",
+        )
+        .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            Some("https://dashboard.prologin.org")
+        );
+
+        let response = post(
+            "/api/webhooks/prolosite/forum?token=dashboard",
+            "https://evil.example.com",
+        )
+        .await;
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+
+        let response = post(
+            "/api/webhooks/prolosite/forum?token=hunter2",
+            "https://dashboard.prologin.org",
+        )
+        .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // the secret of the site isn't accepted in the query
+        let response = post(
+            "/api/webhooks/prolosite/forum?token=secret",
+            "https://dashboard.prologin.org",
+        )
+        .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // the token of the pages only works on the listed endpoints
+        let response = client
+            .post("/api/webhooks/prolosite/django")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", "dashboard"))
+            .body(r#"{"request": {}, "exception": "ValueError"}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client
+            .post("/api/webhooks/prolosite/forum")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", "dashboard"))
+            .body(
+                r#"{"username": "alice", "title": "Hello", "url": "https://prologin.org/forum/1"}"#,
+            )
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
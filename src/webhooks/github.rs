@@ -1,4 +1,10 @@
-use rocket::{http::Status, State};
+use std::convert::Infallible;
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
 use tracing::{info, trace, warn};
 
 mod events;
@@ -7,36 +13,220 @@ pub use events::*;
 mod signing;
 use signing::SignedGitHubPayload;
 
-use crate::webhooks::{Event, EventSender};
+use crate::webhooks::{profile::Profile, RawDelivery, RawEvent, Received};
 
 pub const X_GITHUB_EVENT: &str = "X-GitHub-Event";
+pub const X_GITHUB_DELIVERY: &str = "X-GitHub-Delivery";
+
+/// The unique ID of a delivery, kept when it is redelivered
+pub struct GitHubDelivery(Option<String>);
 
-pub struct GitHubSecret(pub String);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for GitHubDelivery {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = request.headers().get_one(X_GITHUB_DELIVERY);
+        Outcome::Success(GitHubDelivery(id.map(str::to_string)))
+    }
+}
 
 #[rocket::post("/api/webhooks/github", data = "<payload>")]
-pub fn github_webhook(
+pub async fn github_webhook(
+    received: Received,
     event: GitHubEventType,
+    delivery: GitHubDelivery,
     payload: SignedGitHubPayload,
-    sender: &State<EventSender>,
+    profile: &Profile,
 ) -> Status {
     info!("received event {:?} with signed payload", event);
     trace!("payload: {}", payload.0);
 
-    let event = match event.parse_payload(&payload) {
-        Ok(event) => event,
-        Err(e) => {
-            warn!(
-                "couldn't parse payload for event {:?}: {}\n{}",
-                event, e, payload.0
-            );
+    // the payload itself is parsed after answering, to stay within the latency budget
+    if matches!(event, GitHubEventType::Unknown) {
+        warn!("can't handle event of unknown type");
+        return Status::BadRequest;
+    }
+    if let GitHubEventType::Generic(name) = &event {
+        if !profile.github_config.generic_events {
+            warn!("can't handle {} event, generic events are disabled", name);
             return Status::BadRequest;
         }
+    }
+
+    let delivery = RawDelivery {
+        id: delivery.0,
+        event: RawEvent::GitHub {
+            event_type: event,
+            payload: payload.0,
+        },
+    };
+    profile.forward(received, delivery).await
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac, NewMac};
+    use rocket::{
+        http::{ContentType, Header},
+        local::asynchronous::Client,
+        routes,
+    };
+    use serde_json::json;
+    use sha2::Sha256;
+    use tokio::{sync::mpsc::unbounded_channel, time::Instant};
+
+    use crate::{
+        config::ProloloConfig,
+        webhooks::{
+            profile::{EventSink, Profiles},
+            Event, LATENCY_BUDGET,
+        },
     };
 
-    sender
-        .0
-        .send(Event::GitHub(event))
-        .expect("mpsc channel was closed / dropped");
+    use super::*;
 
-    Status::Ok
+    #[rocket::async_test]
+    async fn test_github_webhook_latency() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            "{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_state_dir: state, matrix_rooms: {}, \
+             destinations: [], github_secret: secret, prolosite_secret: secret}",
+        )
+        .unwrap();
+        let (sender, mut receiver) = unbounded_channel();
+        let profile = Profile::new(&config, EventSink::Bot(sender), None, None);
+        let rocket = rocket::build()
+            .mount("/", routes![github_webhook])
+            .manage(Profiles(vec![profile]));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let commit = |i: usize| {
+            json!({
+                "id": format!("{:040x}", i),
+                "url": format!("https://github.com/prologin/site/commit/{:040x}", i),
+                "distinct": true,
+                "message": format!("Commit number {}\n\n{}", i, "A long body. ".repeat(100)),
+                "modified": ["prologin/settings.py"],
+            })
+        };
+        let payload = json!({
+            "repository": {
+                "name": "site",
+                "full_name": "prologin/site",
+                "html_url": "https://github.com/prologin/site",
+            },
+            "sender": {
+                "login": "alice",
+                "id": 42,
+                "html_url": "https://github.com/alice",
+                "avatar_url": "https://avatars.githubusercontent.com/u/42",
+                "type": "User",
+            },
+            "commits": (0..300).map(commit).collect::<Vec<_>>(),
+            "head_commit": commit(299),
+            "forced": false,
+            "created": false,
+            "ref": "refs/heads/main",
+            "before": format!("{:040x}", 0),
+            "after": format!("{:040x}", 299),
+            "compare": "https://github.com/prologin/site/compare/0000000...000012b",
+        })
+        .to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(payload.as_bytes());
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        let start = Instant::now();
+        let response = client
+            .post("/api/webhooks/github")
+            .header(ContentType::JSON)
+            .header(Header::new(X_GITHUB_EVENT, "push"))
+            .header(Header::new(
+                X_GITHUB_DELIVERY,
+                "72d3162e-cc78-11e3-81ab-4c9367dc0958",
+            ))
+            .header(Header::new("X-Hub-Signature-256", signature))
+            .body(&payload)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(start.elapsed() < LATENCY_BUDGET);
+
+        // the payload is parsed after answering
+        let delivery = receiver.recv().await.unwrap().parse(&[]).unwrap().unwrap();
+        assert_eq!(
+            delivery.id.as_deref(),
+            Some("72d3162e-cc78-11e3-81ab-4c9367dc0958")
+        );
+        match delivery.event {
+            Event::GitHub(GitHubEvent::Push(push)) => assert_eq!(push.commits.len(), 300),
+            _ => panic!("should have received a push"),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn test_github_webhook_form() {
+        let config: ProloloConfig = serde_yaml::from_str(
+            "{matrix_homeserver: 'https://matrix.prologin.org', matrix_username: prololo, \
+             matrix_password: hunter2, matrix_state_dir: state, matrix_rooms: {}, \
+             destinations: [], github_secret: secret, prolosite_secret: secret}",
+        )
+        .unwrap();
+        let (sender, mut receiver) = unbounded_channel();
+        let profile = Profile::new(&config, EventSink::Bot(sender), None, None);
+        let rocket = rocket::build()
+            .mount("/", routes![github_webhook])
+            .manage(Profiles(vec![profile]));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        let payload = json!({
+            "action": "created",
+            "repository": {
+                "name": "site",
+                "full_name": "prologin/site",
+                "html_url": "https://github.com/prologin/site",
+                "stargazers_count": 100,
+            },
+            "sender": {
+                "login": "alice",
+                "id": 42,
+                "html_url": "https://github.com/alice",
+                "avatar_url": "https://avatars.githubusercontent.com/u/42",
+                "type": "User",
+            },
+        })
+        .to_string();
+        let form = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("payload", &payload)
+            .finish();
+        // the signature covers the encoded form, not the payload
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(form.as_bytes());
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        let request = |signature: String| {
+            client
+                .post("/api/webhooks/github")
+                .header(ContentType::Form)
+                .header(Header::new(X_GITHUB_EVENT, "star"))
+                .header(Header::new("X-Hub-Signature-256", signature))
+                .body(&form)
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(payload.as_bytes());
+        let payload_signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        let response = request(payload_signature).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = request(signature).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let delivery = receiver.recv().await.unwrap().parse(&[]).unwrap().unwrap();
+        match delivery.event {
+            Event::GitHub(GitHubEvent::Star(star)) => {
+                assert_eq!(star.repository.stargazers_count, 100)
+            }
+            _ => panic!("should have received a star"),
+        }
+    }
 }
@@ -1,72 +1,256 @@
-use std::fmt::Display;
+use std::fmt::{Display, Write};
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 use url::Url;
 
 use crate::bot::utils::shorten_content;
 
+/// Declares the `action` of an event as an enum, with the names used in the payloads. The actions
+/// that aren't listed are deserialized as `Unknown` with their name, so that new actions added by
+/// GitHub don't make the whole payload invalid.
+macro_rules! actions {
+    ($name:ident { $($variant:ident => $action:literal,)* }) => {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $action,)*
+                    Self::Unknown(action) => action,
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let action = String::deserialize(deserializer)?;
+                Ok(match action.as_str() {
+                    $($action => Self::$variant,)*
+                    _ => Self::Unknown(action),
+                })
+            }
+        }
+
+        impl schemars::JsonSchema for $name {
+            fn schema_name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+                // unknown actions are valid too, the known ones are only examples
+                let mut schema = String::json_schema(gen).into_object();
+                schema.metadata().examples = vec![$(serde_json::Value::from($action),)*];
+                schema.into()
+            }
+        }
+    };
+}
+
+mod check_run;
+mod check_suite;
 mod commit_comment;
 mod create;
+mod deploy_key;
+mod deployment;
+mod deployment_status;
 mod fork;
+mod generic;
+mod gollum;
 mod issue_comment;
 mod issues;
+mod label;
 mod membership;
+mod merge_group;
+mod meta;
 mod organization;
 mod ping;
+mod projects_v2;
+mod projects_v2_item;
+mod public;
 mod pull_request;
 mod pull_request_review;
 mod pull_request_review_comment;
 mod push;
+mod release;
 mod repository;
+mod repository_ruleset;
+mod repository_vulnerability_alert;
+mod security_advisory;
+mod star;
+mod text;
 mod types;
+mod workflow_job;
+mod workflow_run;
 
+pub use check_run::*;
+pub use check_suite::*;
 pub use commit_comment::*;
 pub use create::*;
+pub use deploy_key::*;
+pub use deployment::*;
+pub use deployment_status::*;
 pub use fork::*;
+pub use generic::*;
+pub use gollum::*;
 pub use issue_comment::*;
 pub use issues::*;
+pub use label::*;
 pub use membership::*;
+pub use merge_group::*;
+pub use meta::*;
 pub use organization::*;
 pub use ping::*;
+pub use projects_v2::*;
+pub use projects_v2_item::*;
+pub use public::*;
 pub use pull_request::*;
 pub use pull_request_review::*;
 pub use pull_request_review_comment::*;
 pub use push::*;
+pub use release::*;
 pub use repository::*;
+pub use repository_ruleset::*;
+pub use repository_vulnerability_alert::*;
+pub use security_advisory::*;
+pub use star::*;
+pub use text::*;
 pub use types::*;
+pub use workflow_job::*;
+pub use workflow_run::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum GitHubEvent {
+    CheckRun(CheckRunEvent),
+    CheckSuite(CheckSuiteEvent),
     CommitComment(CommitCommentEvent),
     Create(CreateEvent),
+    DeployKey(DeployKeyEvent),
+    Deployment(DeploymentEvent),
+    DeploymentStatus(DeploymentStatusEvent),
     Fork(ForkEvent),
+    Generic(GenericEvent),
+    Gollum(GollumEvent),
     IssueComment(IssueCommentEvent),
     Issues(IssuesEvent),
+    Label(LabelEvent),
     Membership(MembershipEvent),
+    MergeGroup(MergeGroupEvent),
+    Meta(MetaEvent),
     Organization(OrganizationEvent),
     Ping(PingEvent),
+    ProjectsV2(ProjectsV2Event),
+    ProjectsV2Item(ProjectsV2ItemEvent),
+    Public(PublicEvent),
     PullRequest(PullRequestEvent),
     PullRequestReview(PullRequestReviewEvent),
     PullRequestReviewComment(PullRequestReviewCommentEvent),
     Push(PushEvent),
+    Release(ReleaseEvent),
     Repository(RepositoryEvent),
+    RepositoryRuleset(RepositoryRulesetEvent),
+    RepositoryVulnerabilityAlert(RepositoryVulnerabilityAlertEvent),
+    SecurityAdvisory(SecurityAdvisoryEvent),
+    Star(StarEvent),
+    WorkflowJob(WorkflowJobEvent),
+    WorkflowRun(WorkflowRunEvent),
+}
+
+impl GitHubEvent {
+    /// The user who triggered the event, `None` for the CI events, triggered by GitHub apps, and
+    /// for global security advisories
+    pub fn sender(&self) -> Option<&GitHubUser> {
+        match self {
+            Self::CommitComment(event) => Some(&event.sender),
+            Self::Create(event) => Some(&event.sender),
+            Self::DeployKey(event) => Some(&event.sender),
+            Self::Deployment(event) => Some(&event.sender),
+            Self::DeploymentStatus(event) => Some(&event.sender),
+            Self::Fork(event) => Some(&event.sender),
+            Self::Generic(event) => event.sender.as_ref(),
+            Self::Gollum(event) => Some(&event.sender),
+            Self::IssueComment(event) => Some(&event.sender),
+            Self::Issues(event) => Some(&event.sender),
+            Self::Label(event) => Some(&event.sender),
+            Self::Membership(event) => Some(&event.sender),
+            Self::MergeGroup(event) => Some(&event.sender),
+            Self::Meta(event) => Some(&event.sender),
+            Self::Organization(event) => Some(&event.sender),
+            Self::Ping(event) => Some(&event.sender),
+            Self::ProjectsV2(event) => Some(&event.sender),
+            Self::ProjectsV2Item(event) => Some(&event.sender),
+            Self::Public(event) => Some(&event.sender),
+            Self::PullRequest(event) => Some(&event.sender),
+            Self::PullRequestReview(event) => Some(&event.sender),
+            Self::PullRequestReviewComment(event) => Some(&event.sender),
+            Self::Push(event) => Some(&event.sender),
+            Self::Release(event) => Some(&event.sender),
+            Self::Repository(event) => Some(&event.sender),
+            Self::RepositoryRuleset(event) => Some(&event.sender),
+            Self::RepositoryVulnerabilityAlert(event) => Some(&event.sender),
+            Self::Star(event) => Some(&event.sender),
+            Self::CheckRun(_)
+            | Self::CheckSuite(_)
+            | Self::WorkflowJob(_)
+            | Self::WorkflowRun(_)
+            | Self::SecurityAdvisory(_) => None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum RefType {
     Branch,
     Tag,
 }
 
-#[derive(Debug, Deserialize)]
+/// A user or an organization, as found in all the payloads
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct GitHubUser {
     pub login: String,
     pub id: u64,
     pub html_url: Url,
+    pub avatar_url: Url,
+    #[serde(rename = "type")]
+    pub kind: UserKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+pub enum UserKind {
+    User,
+    Bot,
+    Organization,
+    #[serde(other)]
+    Other,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Organization {
+    pub login: String,
+}
+
+impl Organization {
+    /// The page of a project of the organization, or the list of its projects
+    pub fn projects_url(&self, web_url: &Url, number: Option<u64>) -> Result<Url, url::ParseError> {
+        match number {
+            Some(number) => web_url.join(&format!("orgs/{}/projects/{}", self.login, number)),
+            None => web_url.join(&format!("orgs/{}/projects", self.login)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Repository {
     pub name: String,
     pub full_name: String,
@@ -74,23 +258,53 @@ pub struct Repository {
 }
 
 impl Repository {
-    pub fn ref_url(&self, r#ref: &str) -> Result<Url, url::ParseError> {
+    /// A page of the repository, relative to its `html_url` so that it works on any GitHub
+    /// instance
+    pub fn page_url(&self, path: &str) -> Result<Url, url::ParseError> {
         Url::parse(&format!(
-            "https://github.com/{}/tree/{}",
-            self.full_name, r#ref
+            "{}/{}",
+            self.html_url.as_str().trim_end_matches('/'),
+            path
         ))
     }
+
+    pub fn ref_url(&self, r#ref: &str) -> Result<Url, url::ParseError> {
+        self.page_url(&format!("tree/{}", r#ref))
+    }
+
+    /// The page listing the labels of the repository
+    pub fn labels_url(&self) -> Result<Url, url::ParseError> {
+        self.page_url("labels")
+    }
+
+    /// The page listing the checks of a commit
+    pub fn checks_url(&self, sha: &str) -> Result<Url, url::ParseError> {
+        self.page_url(&format!("commit/{}/checks", sha))
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Issue {
     pub number: u64,
     pub html_url: Url,
     pub title: String,
     pub milestone: Option<Milestone>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
     // an issue can be a PR, in this case the object contains a `pull_request` key with urls to the
     // PR
     pub pull_request: Option<PullRequestLinks>,
+    /// Why the issue was closed, or reopened
+    pub state_reason: Option<IssueStateReason>,
+}
+
+actions! {
+    IssueStateReason {
+        Completed => "completed",
+        NotPlanned => "not_planned",
+        Duplicate => "duplicate",
+        Reopened => "reopened",
+    }
 }
 
 impl Display for Issue {
@@ -99,35 +313,71 @@ impl Display for Issue {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Milestone {
     pub title: String,
+    pub open_issues: Option<u64>,
+    pub closed_issues: Option<u64>,
+}
+
+impl Milestone {
+    /// The closed issues and PRs of the milestone, and their total, if the payload has the counts
+    pub fn progress(&self) -> Option<(u64, u64)> {
+        match (self.open_issues, self.closed_issues) {
+            (Some(open), Some(closed)) if open + closed > 0 => Some((closed, open + closed)),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// A comment on an issue, a PR, a commit or a line of a diff
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Comment {
     pub html_url: Url,
     pub body: String,
     pub commit_id: Option<String>,
     pub pull_request_review_id: Option<u64>,
     pub path: Option<String>,
+    /// Position in the diff, replaced by `line` in the current payloads and unset when the diff
+    /// changed since the comment
     pub position: Option<u64>,
+    /// The last line commented on
+    pub line: Option<u64>,
+    /// The first line of a comment on several lines
+    pub start_line: Option<u64>,
+    pub side: Option<DiffSide>,
 }
 
 impl Comment {
+    /// Where the comment was left in a file, `None` if it isn't on a file
     pub fn location(&self) -> Option<String> {
-        self.path.as_ref().map(|path| {
-            format!(
-                "on file {} @ {}",
-                path,
-                self.position
-                    .expect("comment on file without specific position"),
-            )
-        })
+        let path = self.path.as_ref()?;
+
+        let mut location = format!("on file {}", path);
+        match (self.start_line, self.line.or(self.position)) {
+            (Some(start), Some(end)) if start != end => {
+                write!(location, " @ {}-{}", start, end).unwrap()
+            }
+            (_, Some(line)) => write!(location, " @ {}", line).unwrap(),
+            (_, None) => {}
+        }
+        if self.side == Some(DiffSide::Left) {
+            location.push_str(" (previous version)");
+        }
+
+        Some(location)
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// The side of a diff, `Left` being the version before the changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DiffSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PullRequest {
     pub number: u64,
     pub html_url: Url,
@@ -137,6 +387,42 @@ pub struct PullRequest {
     pub base: PrRef,
     pub head: PrRef,
     pub merged: Option<bool>,
+    pub merged_by: Option<GitHubUser>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// The size of the diff, only sent in `pull_request` events
+    pub additions: Option<u64>,
+    pub deletions: Option<u64>,
+    pub changed_files: Option<u64>,
+    /// Set while the PR is merged automatically once its checks pass
+    pub auto_merge: Option<AutoMerge>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct AutoMerge {
+    pub enabled_by: GitHubUser,
+    /// `merge`, `squash` or `rebase`
+    pub merge_method: String,
+}
+
+impl PullRequest {
+    /// The size of the diff, e.g. `+120 −30, 7 files`
+    pub fn diff_stats(&self) -> Option<String> {
+        let (additions, deletions, changed_files) =
+            (self.additions?, self.deletions?, self.changed_files?);
+        let files = if changed_files == 1 { "file" } else { "files" };
+        Some(format!(
+            "+{} \u{2212}{}, {} {}",
+            additions, deletions, changed_files, files
+        ))
+    }
+
+    /// The number of changed lines, additions and deletions
+    pub fn changed_lines(&self) -> Option<u64> {
+        Some(self.additions? + self.deletions?)
+    }
 }
 
 impl Display for PullRequest {
@@ -151,17 +437,19 @@ impl Display for PullRequest {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PrRef {
     pub r#ref: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PullRequestLinks {
+    #[allow(dead_code)]
     pub html_url: Url,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[allow(dead_code)]
 pub struct Team {
     pub name: String,
     pub id: u64,
@@ -170,3 +458,117 @@ pub struct Team {
     pub permission: String,
     pub html_url: Url,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions() {
+        let action: PullRequestAction =
+            serde_json::from_str(r#""review_request_removed""#).unwrap();
+        assert_eq!(action, PullRequestAction::ReviewRequestRemoved);
+        assert_eq!(action.to_string(), "review_request_removed");
+
+        let action: PullRequestAction = serde_json::from_str(r#""teleported""#).unwrap();
+        assert_eq!(action, PullRequestAction::Unknown("teleported".to_string()));
+        assert_eq!(action.to_string(), "teleported");
+    }
+
+    #[test]
+    fn test_generic_event() {
+        let event_type: GitHubEventType = serde_json::from_str(r#""push""#).unwrap();
+        assert!(matches!(event_type, GitHubEventType::Push));
+
+        // the name is kept as is, e.g. for the queue and the transformation rules
+        let event_type: GitHubEventType = serde_json::from_str(r#""watch""#).unwrap();
+        assert_eq!(serde_json::to_string(&event_type).unwrap(), r#""watch""#);
+
+        let payload = r#"{"action":"started","sender":{"login":"alice","id":42,"html_url":"https://github.com/alice","avatar_url":"https://avatars.githubusercontent.com/u/42","type":"User"}}"#;
+        match event_type.parse_payload(payload.to_string()).unwrap() {
+            GitHubEvent::Generic(event) => {
+                assert_eq!(event.name, "watch");
+                assert_eq!(event.action.as_deref(), Some("started"));
+                assert!(event.repository.is_none());
+            }
+            _ => panic!("should be a generic event"),
+        }
+    }
+
+    #[test]
+    fn test_comment_location() {
+        let comment = |path: Option<&str>, position, start_line, line, side| Comment {
+            html_url: Url::parse("https://github.com/prologin/site/pull/42#discussion_r1").unwrap(),
+            body: "LGTM".to_string(),
+            commit_id: None,
+            pull_request_review_id: None,
+            path: path.map(str::to_string),
+            position,
+            line,
+            start_line,
+            side,
+        };
+
+        assert_eq!(comment(None, None, None, None, None).location(), None);
+        assert_eq!(
+            comment(Some("README.md"), Some(3), None, None, None).location(),
+            Some("on file README.md @ 3".to_string())
+        );
+        assert_eq!(
+            comment(
+                Some("README.md"),
+                None,
+                Some(10),
+                Some(12),
+                Some(DiffSide::Right)
+            )
+            .location(),
+            Some("on file README.md @ 10-12".to_string())
+        );
+        assert_eq!(
+            comment(
+                Some("README.md"),
+                None,
+                None,
+                Some(12),
+                Some(DiffSide::Left)
+            )
+            .location(),
+            Some("on file README.md @ 12 (previous version)".to_string())
+        );
+        // outdated comments don't have a position anymore
+        assert_eq!(
+            comment(Some("README.md"), None, None, None, None).location(),
+            Some("on file README.md".to_string())
+        );
+    }
+
+    #[test]
+    fn test_enterprise_urls() {
+        let repository = Repository {
+            name: "site".to_string(),
+            full_name: "prologin/site".to_string(),
+            html_url: Url::parse("https://git.prologin.org/prologin/site").unwrap(),
+        };
+        assert_eq!(
+            repository.ref_url("main").unwrap().as_str(),
+            "https://git.prologin.org/prologin/site/tree/main"
+        );
+        assert_eq!(
+            repository.checks_url("deadbeef").unwrap().as_str(),
+            "https://git.prologin.org/prologin/site/commit/deadbeef/checks"
+        );
+
+        let organization = Organization {
+            login: "prologin".to_string(),
+        };
+        let web_url = Url::parse("https://git.prologin.org/").unwrap();
+        assert_eq!(
+            organization
+                .projects_url(&web_url, Some(3))
+                .unwrap()
+                .as_str(),
+            "https://git.prologin.org/orgs/prologin/projects/3"
+        );
+    }
+}
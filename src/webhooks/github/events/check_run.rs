@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{workflow_run::is_failure, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CheckRunEvent {
+    pub action: CheckRunAction,
+    pub check_run: CheckRun,
+    pub repository: Repository,
+}
+
+actions! {
+    CheckRunAction {
+        Created => "created",
+        Completed => "completed",
+        Rerequested => "rerequested",
+        RequestedAction => "requested_action",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CheckRun {
+    pub name: String,
+    /// Only set once the check is completed
+    pub conclusion: Option<String>,
+    pub html_url: Url,
+    /// The page of the integration with the check's details
+    pub details_url: Option<Url>,
+    pub app: App,
+    pub check_suite: CheckSuiteRef,
+}
+
+impl CheckRun {
+    pub fn failed(&self) -> bool {
+        is_failure(self.conclusion.as_deref())
+    }
+}
+
+/// The GitHub App reporting a check
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct App {
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CheckSuiteRef {
+    pub head_branch: Option<String>,
+}
@@ -0,0 +1,47 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{workflow_run::is_failure, App, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CheckSuiteEvent {
+    pub action: CheckSuiteAction,
+    pub check_suite: CheckSuite,
+    pub repository: Repository,
+}
+
+actions! {
+    CheckSuiteAction {
+        Completed => "completed",
+        Requested => "requested",
+        Rerequested => "rerequested",
+    }
+}
+
+/// All the checks run by an integration on a commit
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CheckSuite {
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    /// Only set once all the checks are completed
+    pub conclusion: Option<String>,
+    pub app: App,
+    pub head_commit: Option<CheckSuiteCommit>,
+}
+
+impl CheckSuite {
+    pub fn failed(&self) -> bool {
+        is_failure(self.conclusion.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CheckSuiteCommit {
+    pub message: String,
+}
+
+impl CheckSuiteCommit {
+    pub fn title(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
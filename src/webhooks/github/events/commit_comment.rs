@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{Comment, GitHubUser, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct CommitCommentEvent {
     pub sender: GitHubUser,
     pub repository: Repository,
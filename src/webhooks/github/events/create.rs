@@ -1,11 +1,16 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{GitHubUser, RefType, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct CreateEvent {
     pub r#ref: String,
     pub ref_type: RefType,
     pub repository: Repository,
     pub sender: GitHubUser,
+    /// For tags, the notes of the matching release if there's one. Filled from the GitHub API by
+    /// the bot, it's not part of the payload
+    #[serde(skip)]
+    pub release_notes: Option<String>,
 }
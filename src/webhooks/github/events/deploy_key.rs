@@ -0,0 +1,27 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DeployKeyEvent {
+    pub action: DeployKeyAction,
+    pub key: DeployKey,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    DeployKeyAction {
+        Created => "created",
+        Deleted => "deleted",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DeployKey {
+    pub id: u64,
+    pub title: String,
+    /// Keys that aren't read only can push to the repository
+    pub read_only: bool,
+}
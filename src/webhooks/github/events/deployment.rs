@@ -0,0 +1,26 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DeploymentEvent {
+    pub action: DeploymentAction,
+    pub deployment: Deployment,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    DeploymentAction {
+        Created => "created",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Deployment {
+    /// The deployed branch, tag or commit
+    pub r#ref: String,
+    pub sha: String,
+    pub environment: String,
+}
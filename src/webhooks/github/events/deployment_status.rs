@@ -0,0 +1,45 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{Deployment, GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DeploymentStatusEvent {
+    pub action: DeploymentStatusAction,
+    pub deployment_status: DeploymentStatus,
+    pub deployment: Deployment,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    DeploymentStatusAction {
+        Created => "created",
+    }
+}
+
+actions! {
+    DeploymentState {
+        Pending => "pending",
+        Queued => "queued",
+        InProgress => "in_progress",
+        Success => "success",
+        Failure => "failure",
+        Error => "error",
+        Inactive => "inactive",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct DeploymentStatus {
+    pub state: DeploymentState,
+}
+
+impl DeploymentStatus {
+    pub fn failed(&self) -> bool {
+        matches!(
+            self.state,
+            DeploymentState::Failure | DeploymentState::Error
+        )
+    }
+}
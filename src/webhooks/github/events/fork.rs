@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{GitHubUser, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct ForkEvent {
     pub forkee: Repository,
     pub repository: Repository,
@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Organization, Repository};
+
+/// The events sent by GitHub that don't have a dedicated message yet, announced with a generic one
+/// if [`crate::config::GitHubConfig::generic_events`] is enabled
+pub const GENERIC_EVENTS: &[&str] = &[
+    "branch_protection_configuration",
+    "branch_protection_rule",
+    "code_scanning_alert",
+    "delete",
+    "dependabot_alert",
+    "discussion",
+    "discussion_comment",
+    "installation",
+    "installation_repositories",
+    "member",
+    "milestone",
+    "org_block",
+    "package",
+    "page_build",
+    "registry_package",
+    "secret_scanning_alert",
+    "sponsorship",
+    "status",
+    "team",
+    "team_add",
+    "watch",
+    "workflow_dispatch",
+];
+
+/// The fields shared by most events
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct GenericEvent {
+    /// The name of the event, which isn't part of the payload
+    #[serde(skip)]
+    pub name: String,
+    /// Not all events have actions, and their values aren't known in advance
+    pub action: Option<String>,
+    pub repository: Option<Repository>,
+    pub organization: Option<Organization>,
+    pub sender: Option<GitHubUser>,
+}
@@ -0,0 +1,26 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct GollumEvent {
+    pub pages: Vec<WikiPage>,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WikiPage {
+    pub title: String,
+    pub action: WikiPageAction,
+    pub html_url: Url,
+}
+
+actions! {
+    WikiPageAction {
+        Created => "created",
+        Edited => "edited",
+    }
+}
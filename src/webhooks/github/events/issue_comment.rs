@@ -1,12 +1,21 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{Comment, GitHubUser, Issue, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct IssueCommentEvent {
     pub sender: GitHubUser,
     pub repository: Repository,
     pub issue: Issue,
-    pub action: String,
+    pub action: IssueCommentAction,
     pub comment: Comment,
 }
+
+actions! {
+    IssueCommentAction {
+        Created => "created",
+        Edited => "edited",
+        Deleted => "deleted",
+    }
+}
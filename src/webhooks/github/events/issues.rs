@@ -1,24 +1,47 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{GitHubUser, Issue, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct IssuesEvent {
     pub repository: Repository,
     pub sender: GitHubUser,
     pub issue: Issue,
     pub changes: Option<IssueChanges>,
     pub assignee: Option<GitHubUser>,
-    pub action: String,
+    pub action: IssuesAction,
 }
 
-#[derive(Debug, Deserialize)]
+actions! {
+    IssuesAction {
+        Opened => "opened",
+        Edited => "edited",
+        Deleted => "deleted",
+        Pinned => "pinned",
+        Unpinned => "unpinned",
+        Closed => "closed",
+        Reopened => "reopened",
+        Assigned => "assigned",
+        Unassigned => "unassigned",
+        Labeled => "labeled",
+        Unlabeled => "unlabeled",
+        Locked => "locked",
+        Unlocked => "unlocked",
+        Transferred => "transferred",
+        Milestoned => "milestoned",
+        Demilestoned => "demilestoned",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct IssueChanges {
     pub title: Option<IssueChangesFrom>,
     pub body: Option<IssueChangesFrom>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct IssueChangesFrom {
+    #[allow(dead_code)]
     pub from: String,
 }
@@ -0,0 +1,40 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct LabelEvent {
+    pub action: LabelAction,
+    pub label: Label,
+    /// Previous values of the edited fields
+    pub changes: Option<LabelChanges>,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    LabelAction {
+        Created => "created",
+        Edited => "edited",
+        Deleted => "deleted",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Label {
+    pub name: String,
+    /// Hexadecimal color code, without the leading `#`
+    pub color: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct LabelChanges {
+    pub name: Option<PreviousValue>,
+    pub color: Option<PreviousValue>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct PreviousValue {
+    pub from: String,
+}
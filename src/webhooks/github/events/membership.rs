@@ -1,11 +1,19 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{GitHubUser, Team};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct MembershipEvent {
-    pub action: String,
+    pub action: MembershipAction,
     pub member: GitHubUser,
     pub team: Team,
     pub sender: GitHubUser,
 }
+
+actions! {
+    MembershipAction {
+        Added => "added",
+        Removed => "removed",
+    }
+}
@@ -0,0 +1,46 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{CheckSuiteCommit, GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MergeGroupEvent {
+    pub action: MergeGroupAction,
+    pub merge_group: MergeGroup,
+    /// Why the group was destroyed: `merged`, `invalidated` or `dequeued`
+    pub reason: Option<String>,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    MergeGroupAction {
+        ChecksRequested => "checks_requested",
+        Destroyed => "destroyed",
+    }
+}
+
+/// PRs of a merge queue tested together on a temporary branch before being merged
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MergeGroup {
+    pub head_sha: String,
+    pub head_ref: String,
+    pub base_ref: String,
+    pub head_commit: Option<CheckSuiteCommit>,
+}
+
+impl MergeGroup {
+    /// The temporary branch of the group
+    pub fn head_branch(&self) -> &str {
+        branch_name(&self.head_ref)
+    }
+
+    /// The branch the group is merged into
+    pub fn base_branch(&self) -> &str {
+        branch_name(&self.base_ref)
+    }
+}
+
+fn branch_name(r#ref: &str) -> &str {
+    r#ref.strip_prefix("refs/heads/").unwrap_or(r#ref)
+}
@@ -0,0 +1,34 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{GitHubUser, Organization, Repository};
+
+/// Sent when the webhook sending it is deleted, it is then the last event of the webhook
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MetaEvent {
+    pub action: MetaAction,
+    pub hook_id: u64,
+    pub hook: Hook,
+    /// Unset for the webhooks of organizations
+    pub repository: Option<Repository>,
+    pub organization: Option<Organization>,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    MetaAction {
+        Deleted => "deleted",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Hook {
+    pub events: Vec<String>,
+    pub config: HookConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct HookConfig {
+    pub url: Option<Url>,
+}
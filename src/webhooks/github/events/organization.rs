@@ -1,10 +1,11 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::GitHubUser;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct OrganizationEvent {
-    pub action: String,
+    pub action: OrganizationAction,
     pub sender: GitHubUser,
 
     // When 'invitation', 'user' should be set
@@ -15,12 +16,22 @@ pub struct OrganizationEvent {
     pub membership: Option<OrganizationMembership>,
 }
 
-#[derive(Debug, Deserialize)]
+actions! {
+    OrganizationAction {
+        MemberInvited => "member_invited",
+        MemberAdded => "member_added",
+        MemberRemoved => "member_removed",
+        Renamed => "renamed",
+        Deleted => "deleted",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct OrganizationInvitation {
     pub role: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct OrganizationMembership {
     pub role: String,
     pub user: GitHubUser,
@@ -0,0 +1,28 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Organization};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ProjectsV2Event {
+    pub action: ProjectsV2Action,
+    pub projects_v2: ProjectV2,
+    pub organization: Organization,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    ProjectsV2Action {
+        Created => "created",
+        Edited => "edited",
+        Closed => "closed",
+        Reopened => "reopened",
+        Deleted => "deleted",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ProjectV2 {
+    pub number: u64,
+    pub title: String,
+}
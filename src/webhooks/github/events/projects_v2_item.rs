@@ -0,0 +1,74 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::webhooks::github::events::{GitHubUser, Organization};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ProjectsV2ItemEvent {
+    pub action: ProjectsV2ItemAction,
+    pub projects_v2_item: ProjectV2Item,
+    pub changes: Option<ProjectV2ItemChanges>,
+    pub organization: Organization,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    ProjectsV2ItemAction {
+        Created => "created",
+        Edited => "edited",
+        Archived => "archived",
+        Restored => "restored",
+        Deleted => "deleted",
+        Converted => "converted",
+        Reordered => "reordered",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ProjectV2Item {
+    /// `Issue`, `PullRequest` or `DraftIssue`
+    pub content_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ProjectV2ItemChanges {
+    pub field_value: Option<FieldValueChange>,
+}
+
+/// The change of a field of an item, e.g. its status. The values are only sent by GitHub for some
+/// field types
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct FieldValueChange {
+    pub field_name: Option<String>,
+    pub project_number: Option<u64>,
+    #[serde(default)]
+    pub from: Value,
+    #[serde(default)]
+    pub to: Value,
+}
+
+impl FieldValueChange {
+    /// Displayable value, either a single select option (`{"name": ...}`), an iteration
+    /// (`{"title": ...}`) or a plain value
+    fn value_name(value: &Value) -> Option<String> {
+        match value {
+            Value::Object(object) => object
+                .get("name")
+                .or_else(|| object.get("title"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            Value::String(value) => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn old_value(&self) -> Option<String> {
+        Self::value_name(&self.from)
+    }
+
+    pub fn new_value(&self) -> Option<String> {
+        Self::value_name(&self.to)
+    }
+}
@@ -0,0 +1,12 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+/// Legacy event sent when a private repository is made public, along with the `publicized` action
+/// of the `repository` event
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct PublicEvent {
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
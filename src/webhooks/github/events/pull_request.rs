@@ -1,12 +1,43 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{GitHubUser, PullRequest, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PullRequestEvent {
     pub repository: Repository,
     pub sender: GitHubUser,
     pub pull_request: PullRequest,
     pub assignee: Option<GitHubUser>,
-    pub action: String,
+    pub action: PullRequestAction,
+    /// Why auto-merge was disabled, e.g. because the base branch changed
+    pub reason: Option<String>,
+    /// Files changed by the PR, only for repos with watched paths. Filled from the GitHub API by
+    /// the bot, it's not part of the payload
+    #[serde(skip)]
+    pub changed_files: Vec<String>,
+}
+
+actions! {
+    PullRequestAction {
+        Opened => "opened",
+        Edited => "edited",
+        Closed => "closed",
+        Reopened => "reopened",
+        Synchronize => "synchronize",
+        Assigned => "assigned",
+        Unassigned => "unassigned",
+        ReviewRequested => "review_requested",
+        ReviewRequestRemoved => "review_request_removed",
+        Labeled => "labeled",
+        Unlabeled => "unlabeled",
+        Locked => "locked",
+        Unlocked => "unlocked",
+        ConvertedToDraft => "converted_to_draft",
+        ReadyForReview => "ready_for_review",
+        AutoMergeEnabled => "auto_merge_enabled",
+        AutoMergeDisabled => "auto_merge_disabled",
+        Enqueued => "enqueued",
+        Dequeued => "dequeued",
+    }
 }
@@ -1,18 +1,27 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 use url::Url;
 
 use crate::webhooks::github::events::{GitHubUser, PullRequest, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PullRequestReviewEvent {
     pub repository: Repository,
     pub sender: GitHubUser,
     pub pull_request: PullRequest,
     pub review: Review,
-    pub action: String,
+    pub action: PullRequestReviewAction,
 }
 
-#[derive(Debug, Deserialize)]
+actions! {
+    PullRequestReviewAction {
+        Submitted => "submitted",
+        Edited => "edited",
+        Dismissed => "dismissed",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Review {
     pub state: String,
     pub user: GitHubUser,
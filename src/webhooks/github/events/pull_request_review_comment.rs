@@ -1,12 +1,21 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{Comment, GitHubUser, PullRequest, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PullRequestReviewCommentEvent {
     pub repository: Repository,
     pub sender: GitHubUser,
     pub pull_request: PullRequest,
-    pub action: String,
+    pub action: PullRequestReviewCommentAction,
     pub comment: Comment,
 }
+
+actions! {
+    PullRequestReviewCommentAction {
+        Created => "created",
+        Edited => "edited",
+        Deleted => "deleted",
+    }
+}
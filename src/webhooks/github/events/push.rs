@@ -1,9 +1,13 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 use url::Url;
 
-use crate::webhooks::github::events::{GitHubUser, Repository};
+use crate::webhooks::github::events::{GitHubUser, Repository, Text};
 
-#[derive(Debug, Deserialize)]
+/// GitHub only lists this many commits in push payloads, the oldest ones are left out
+pub const PAYLOAD_COMMITS_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct PushEvent {
     pub repository: Repository,
     pub sender: GitHubUser,
@@ -12,15 +16,92 @@ pub struct PushEvent {
     pub forced: bool,
     pub created: bool,
     pub r#ref: String,
+    /// The head of the branch before the push
+    pub before: String,
+    /// The head of the branch after the push
+    pub after: String,
     pub compare: Url,
+    /// For force pushes, the number of commits that aren't reachable from the branch anymore.
+    /// Filled from the GitHub API by the bot, it's not part of the payload
+    #[serde(skip)]
+    pub discarded_commits: Option<u64>,
+    /// Changed files that need special attention (submodules, LFS settings) on protected branches.
+    /// Filled from the GitHub API by the bot, it's not part of the payload
+    #[serde(skip)]
+    pub flagged_files: Vec<String>,
+    /// The number of consecutive pushes combined into this event by the bot, `None` for a single
+    /// push. It's not part of the payload
+    #[serde(skip)]
+    pub pushes: Option<usize>,
+    /// The real number of pushed commits when the payload's list was truncated. Filled from the
+    /// GitHub API by the bot, it's not part of the payload
+    #[serde(skip)]
+    pub total_commits: Option<u64>,
+}
+
+impl PushEvent {
+    /// The pushed branch, `None` for tags
+    pub fn branch(&self) -> Option<&str> {
+        self.r#ref.strip_prefix("refs/heads/")
+    }
+
+    /// Whether the ref didn't exist before the push, its old head is then the null commit
+    pub fn is_new_ref(&self) -> bool {
+        self.before.chars().all(|c| c == '0')
+    }
+
+    /// Whether the push deleted the ref, its new head is then the null commit
+    pub fn is_deleted_ref(&self) -> bool {
+        self.after.chars().all(|c| c == '0')
+    }
+
+    /// Whether the commit list may have been cut by GitHub, the API then knows the real count
+    pub fn may_be_truncated(&self) -> bool {
+        self.commits.len() >= PAYLOAD_COMMITS_LIMIT
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct Commit {
-    pub id: String,
+    pub id: Text,
     pub url: Url,
     pub distinct: bool,
-    pub message: String,
+    pub message: Text,
+    #[serde(default)]
+    pub added: Vec<Text>,
+    #[serde(default)]
+    pub removed: Vec<Text>,
+    #[serde(default)]
+    pub modified: Vec<Text>,
+    pub author: Option<CommitUser>,
+    pub committer: Option<CommitUser>,
+    /// Whether GitHub could verify the signature of the commit. Push payloads usually leave it out,
+    /// the bot then gets it from the GitHub API for some pushes
+    #[serde(default)]
+    pub verification: Option<Verification>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Verification {
+    pub verified: bool,
+    /// Why the signature is or isn't verified, e.g. `valid` or `unsigned`
+    pub reason: String,
+}
+
+/// The author or committer of a commit, as recorded by git
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CommitUser {
+    pub name: Text,
+    /// The GitHub login, if the email belongs to an account
+    pub username: Option<Text>,
+}
+
+impl CommitUser {
+    /// Whether this is the GitHub user `login`, going by the name for emails without an account
+    pub fn is(&self, login: &str) -> bool {
+        let name = self.username.as_deref().unwrap_or(&self.name);
+        name.eq_ignore_ascii_case(login)
+    }
 }
 
 impl Commit {
@@ -30,4 +111,25 @@ impl Commit {
             .next()
             .expect("body has at least one line")
     }
+
+    /// The paths of all the files added, removed or modified by the commit
+    pub fn changed_files(&self) -> impl Iterator<Item = &str> {
+        self.added
+            .iter()
+            .chain(&self.removed)
+            .chain(&self.modified)
+            .map(|path| &**path)
+    }
+
+    /// Whether the commit has a signature verified by GitHub
+    pub fn is_signed(&self) -> bool {
+        self.verification
+            .as_ref()
+            .map_or(false, |verification| verification.verified)
+    }
+
+    /// Who wrote the commit, the committer if the author is unknown
+    pub fn written_by(&self) -> Option<&CommitUser> {
+        self.author.as_ref().or(self.committer.as_ref())
+    }
 }
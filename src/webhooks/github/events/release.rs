@@ -0,0 +1,38 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ReleaseEvent {
+    pub action: ReleaseAction,
+    pub release: Release,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+    /// The tag of the release published before this one, if there's one. Filled from the GitHub
+    /// API by the bot, it's not part of the payload
+    #[serde(skip)]
+    pub previous_tag: Option<String>,
+}
+
+actions! {
+    ReleaseAction {
+        Created => "created",
+        Deleted => "deleted",
+        Edited => "edited",
+        Prereleased => "prereleased",
+        Published => "published",
+        Released => "released",
+        Unpublished => "unpublished",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    pub html_url: Url,
+    pub prerelease: bool,
+}
@@ -1,26 +1,41 @@
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::webhooks::github::events::{GitHubUser, Repository};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct RepositoryEvent {
-    pub action: String,
+    pub action: RepositoryAction,
     pub repository: Repository,
     pub sender: GitHubUser,
     pub changes: Option<RepositoryChanges>,
 }
 
-#[derive(Debug, Deserialize)]
+actions! {
+    RepositoryAction {
+        Created => "created",
+        Deleted => "deleted",
+        Archived => "archived",
+        Unarchived => "unarchived",
+        Edited => "edited",
+        Renamed => "renamed",
+        Transferred => "transferred",
+        Publicized => "publicized",
+        Privatized => "privatized",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct RepositoryChanges {
     pub repository: RepositoryChangesName,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct RepositoryChangesName {
     pub name: RepositoryChangesNameFrom,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct RepositoryChangesNameFrom {
     pub from: String,
 }
@@ -0,0 +1,62 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{GitHubUser, Organization, PreviousValue, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RepositoryRulesetEvent {
+    pub action: RepositoryRulesetAction,
+    pub repository_ruleset: Ruleset,
+    /// Previous values of the edited fields
+    pub changes: Option<RulesetChanges>,
+    /// Unset for the rulesets of the organization
+    pub repository: Option<Repository>,
+    pub organization: Option<Organization>,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    RepositoryRulesetAction {
+        Created => "created",
+        Edited => "edited",
+        Deleted => "deleted",
+    }
+}
+
+/// Rules protecting branches or tags, replacing the branch protection rules
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Ruleset {
+    pub id: u64,
+    pub name: String,
+    /// `branch`, `tag` or `push`
+    pub target: Option<String>,
+    /// `active`, `evaluate` (rules are only reported) or `disabled`
+    pub enforcement: String,
+}
+
+impl Ruleset {
+    /// The settings page of the ruleset, in the repository or in the organization
+    pub fn html_url(
+        &self,
+        repository: Option<&Repository>,
+        organization: Option<&Organization>,
+        web_url: &Url,
+    ) -> Option<Url> {
+        let url = match (repository, organization) {
+            (Some(repository), _) => repository.page_url(&format!("rules/{}", self.id)),
+            (None, Some(organization)) => web_url.join(&format!(
+                "organizations/{}/settings/rules/{}",
+                organization.login, self.id
+            )),
+            (None, None) => return None,
+        };
+        url.ok()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RulesetChanges {
+    pub name: Option<PreviousValue>,
+    pub enforcement: Option<PreviousValue>,
+}
@@ -0,0 +1,34 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RepositoryVulnerabilityAlertEvent {
+    pub action: RepositoryVulnerabilityAlertAction,
+    pub alert: VulnerabilityAlert,
+    pub repository: Repository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    RepositoryVulnerabilityAlertAction {
+        Create => "create",
+        Dismiss => "dismiss",
+        Resolve => "resolve",
+        Reopen => "reopen",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct VulnerabilityAlert {
+    pub affected_package_name: String,
+    pub affected_range: String,
+    /// The CVE or GHSA ID of the vulnerability
+    pub external_identifier: String,
+    pub external_reference: Url,
+    pub fixed_in: Option<String>,
+    /// `low`, `moderate`, `high` or `critical`
+    pub severity: String,
+}
@@ -0,0 +1,52 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SecurityAdvisoryEvent {
+    pub action: SecurityAdvisoryAction,
+    pub security_advisory: SecurityAdvisory,
+}
+
+actions! {
+    SecurityAdvisoryAction {
+        Published => "published",
+        Updated => "updated",
+        Performed => "performed",
+        Withdrawn => "withdrawn",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SecurityAdvisory {
+    pub ghsa_id: String,
+    pub summary: String,
+    /// `low`, `moderate`, `high` or `critical`
+    pub severity: String,
+    #[serde(default)]
+    pub vulnerabilities: Vec<Vulnerability>,
+}
+
+impl SecurityAdvisory {
+    pub fn html_url(&self, web_url: &Url) -> Result<Url, url::ParseError> {
+        web_url.join(&format!("advisories/{}", self.ghsa_id))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Vulnerability {
+    pub package: Package,
+    pub vulnerable_version_range: String,
+    pub first_patched_version: Option<PatchedVersion>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct Package {
+    pub ecosystem: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct PatchedVersion {
+    pub identifier: String,
+}
@@ -0,0 +1,25 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::webhooks::github::events::{GitHubUser, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct StarEvent {
+    pub action: StarAction,
+    pub repository: StarredRepository,
+    pub sender: GitHubUser,
+}
+
+actions! {
+    StarAction {
+        Created => "created",
+        Deleted => "deleted",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct StarredRepository {
+    #[serde(flatten)]
+    pub repository: Repository,
+    pub stargazers_count: u64,
+}
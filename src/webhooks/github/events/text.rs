@@ -0,0 +1,196 @@
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Display},
+    ops::{Deref, Range},
+    sync::Arc,
+};
+
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer,
+};
+
+thread_local! {
+    /// The payload being parsed by [`parse_body`] on this thread
+    static BODY: RefCell<Option<Arc<String>>> = RefCell::new(None);
+}
+
+/// A string of a payload. The ones without escape sequences are a range of the payload they were
+/// parsed from, which stays alive as long as they do, instead of a copy. Strings parsed outside of
+/// [`parse_body`] are always copied.
+#[derive(Clone)]
+pub struct Text(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Borrowed {
+        body: Arc<String>,
+        range: Range<usize>,
+    },
+    Owned(Box<str>),
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match &self.0 {
+            Repr::Borrowed { body, range } => &body[range.clone()],
+            Repr::Owned(text) => text,
+        }
+    }
+}
+
+impl AsRef<str> for Text {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Text {}
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        &**self == other
+    }
+}
+
+impl PartialEq<&str> for Text {
+    fn eq(&self, other: &&str) -> bool {
+        &**self == *other
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Self(Repr::Owned(text.into()))
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Self(Repr::Owned(text.into_boxed_str()))
+    }
+}
+
+struct TextVisitor;
+
+impl<'de> Visitor<'de> for TextVisitor {
+    type Value = Text;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Text, E> {
+        let borrowed = BODY.with(|body| {
+            let body = body.borrow();
+            let body = body.as_ref()?;
+            // the string is only borrowed if it is part of the payload being parsed
+            let start = (v.as_ptr() as usize).checked_sub(body.as_ptr() as usize)?;
+            let range = start..start + v.len();
+            (range.end <= body.len()).then(|| Repr::Borrowed {
+                body: body.clone(),
+                range,
+            })
+        });
+        Ok(Text(borrowed.unwrap_or_else(|| Repr::Owned(v.into()))))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Text, E> {
+        Ok(v.into())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Text, E> {
+        Ok(v.into())
+    }
+}
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(TextVisitor)
+    }
+}
+
+impl JsonSchema for Text {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        String::schema_name()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+/// Restores the payload parsed before, if [`parse_body`] is called while parsing another one
+struct BodyGuard(Option<Arc<String>>);
+
+impl Drop for BodyGuard {
+    fn drop(&mut self) {
+        BODY.with(|body| *body.borrow_mut() = self.0.take());
+    }
+}
+
+/// Calls `parse` with the content of `body`, the [`Text`]s it parses from it borrow from `body`.
+pub(crate) fn parse_body<T>(body: String, parse: impl FnOnce(&str) -> T) -> T {
+    let body = Arc::new(body);
+    let _guard = BodyGuard(BODY.with(|current| current.replace(Some(body.clone()))));
+    parse(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Payload {
+        plain: Text,
+        escaped: Text,
+    }
+
+    fn is_borrowed(text: &Text) -> bool {
+        matches!(text.0, Repr::Borrowed { .. })
+    }
+
+    #[test]
+    fn test_parse_body() {
+        let json = r#"{"plain": "Fix the forum", "escaped": "Fix the \"forum\""}"#;
+
+        let payload: Payload =
+            parse_body(json.to_string(), |body| serde_json::from_str(body).unwrap());
+        assert_eq!(payload.plain, "Fix the forum");
+        assert!(is_borrowed(&payload.plain));
+        assert_eq!(payload.escaped, "Fix the \"forum\"");
+        assert!(!is_borrowed(&payload.escaped));
+
+        // strings parsed from anything else are copied
+        let payload: Payload = parse_body(String::new(), |_| serde_json::from_str(json).unwrap());
+        assert_eq!(payload.plain, "Fix the forum");
+        assert!(!is_borrowed(&payload.plain));
+        let payload: Payload = serde_json::from_str(json).unwrap();
+        assert!(!is_borrowed(&payload.plain));
+    }
+}
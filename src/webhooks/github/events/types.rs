@@ -4,54 +4,108 @@ use rocket::{
     request::{FromRequest, Outcome},
     Request,
 };
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
-use crate::webhooks::github::{GitHubEvent, SignedGitHubPayload, X_GITHUB_EVENT};
+use crate::webhooks::github::{
+    parse_body, GenericEvent, GitHubEvent, GENERIC_EVENTS, X_GITHUB_EVENT,
+};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum GitHubEventType {
+    CheckRun,
+    CheckSuite,
     CommitComment,
     Create,
+    DeployKey,
+    Deployment,
+    DeploymentStatus,
     Fork,
+    Gollum,
     IssueComment,
     Issues,
+    Label,
     Membership,
+    MergeGroup,
+    Meta,
     Organization,
     Ping,
+    ProjectsV2,
+    ProjectsV2Item,
+    Public,
     PullRequest,
     PullRequestReview,
     PullRequestReviewComment,
     Push,
+    Release,
     Repository,
+    RepositoryRuleset,
+    RepositoryVulnerabilityAlert,
+    SecurityAdvisory,
+    Star,
+    WorkflowJob,
+    WorkflowRun,
     Unknown,
+    /// One of the [`GENERIC_EVENTS`], by name
+    #[serde(untagged)]
+    Generic(String),
 }
 
 impl GitHubEventType {
-    pub(crate) fn parse_payload(
-        &self,
-        payload: &SignedGitHubPayload,
-    ) -> anyhow::Result<GitHubEvent> {
+    /// Parses a payload, the [`Text`](crate::webhooks::github::Text)s of the event keep it alive
+    pub fn parse_payload(&self, payload: String) -> anyhow::Result<GitHubEvent> {
+        parse_body(payload, |payload| self.parse_str(payload))
+    }
+
+    fn parse_str(&self, payload: &str) -> anyhow::Result<GitHubEvent> {
         Ok(match self {
-            Self::CommitComment => GitHubEvent::CommitComment(serde_json::from_str(&payload.0)?),
-            Self::Create => GitHubEvent::Create(serde_json::from_str(&payload.0)?),
-            Self::Fork => GitHubEvent::Fork(serde_json::from_str(&payload.0)?),
-            Self::IssueComment => GitHubEvent::IssueComment(serde_json::from_str(&payload.0)?),
-            Self::Issues => GitHubEvent::Issues(serde_json::from_str(&payload.0)?),
-            Self::Membership => GitHubEvent::Membership(serde_json::from_str(&payload.0)?),
-            Self::Organization => GitHubEvent::Organization(serde_json::from_str(&payload.0)?),
-            Self::Ping => GitHubEvent::Ping(serde_json::from_str(&payload.0)?),
-            Self::PullRequest => GitHubEvent::PullRequest(serde_json::from_str(&payload.0)?),
+            Self::CheckRun => GitHubEvent::CheckRun(serde_json::from_str(payload)?),
+            Self::CheckSuite => GitHubEvent::CheckSuite(serde_json::from_str(payload)?),
+            Self::CommitComment => GitHubEvent::CommitComment(serde_json::from_str(payload)?),
+            Self::Create => GitHubEvent::Create(serde_json::from_str(payload)?),
+            Self::DeployKey => GitHubEvent::DeployKey(serde_json::from_str(payload)?),
+            Self::Deployment => GitHubEvent::Deployment(serde_json::from_str(payload)?),
+            Self::DeploymentStatus => GitHubEvent::DeploymentStatus(serde_json::from_str(payload)?),
+            Self::Fork => GitHubEvent::Fork(serde_json::from_str(payload)?),
+            Self::Gollum => GitHubEvent::Gollum(serde_json::from_str(payload)?),
+            Self::IssueComment => GitHubEvent::IssueComment(serde_json::from_str(payload)?),
+            Self::Issues => GitHubEvent::Issues(serde_json::from_str(payload)?),
+            Self::Label => GitHubEvent::Label(serde_json::from_str(payload)?),
+            Self::Membership => GitHubEvent::Membership(serde_json::from_str(payload)?),
+            Self::MergeGroup => GitHubEvent::MergeGroup(serde_json::from_str(payload)?),
+            Self::Meta => GitHubEvent::Meta(serde_json::from_str(payload)?),
+            Self::Organization => GitHubEvent::Organization(serde_json::from_str(payload)?),
+            Self::Ping => GitHubEvent::Ping(serde_json::from_str(payload)?),
+            Self::ProjectsV2 => GitHubEvent::ProjectsV2(serde_json::from_str(payload)?),
+            Self::ProjectsV2Item => GitHubEvent::ProjectsV2Item(serde_json::from_str(payload)?),
+            Self::Public => GitHubEvent::Public(serde_json::from_str(payload)?),
+            Self::PullRequest => GitHubEvent::PullRequest(serde_json::from_str(payload)?),
             Self::PullRequestReview => {
-                GitHubEvent::PullRequestReview(serde_json::from_str(&payload.0)?)
+                GitHubEvent::PullRequestReview(serde_json::from_str(payload)?)
             }
             Self::PullRequestReviewComment => {
-                GitHubEvent::PullRequestReviewComment(serde_json::from_str(&payload.0)?)
+                GitHubEvent::PullRequestReviewComment(serde_json::from_str(payload)?)
+            }
+            Self::Push => GitHubEvent::Push(serde_json::from_str(payload)?),
+            Self::Release => GitHubEvent::Release(serde_json::from_str(payload)?),
+            Self::Repository => GitHubEvent::Repository(serde_json::from_str(payload)?),
+            Self::RepositoryRuleset => {
+                GitHubEvent::RepositoryRuleset(serde_json::from_str(payload)?)
             }
-            Self::Push => GitHubEvent::Push(serde_json::from_str(&payload.0)?),
-            Self::Repository => GitHubEvent::Repository(serde_json::from_str(&payload.0)?),
+            Self::RepositoryVulnerabilityAlert => {
+                GitHubEvent::RepositoryVulnerabilityAlert(serde_json::from_str(payload)?)
+            }
+            Self::SecurityAdvisory => GitHubEvent::SecurityAdvisory(serde_json::from_str(payload)?),
+            Self::Star => GitHubEvent::Star(serde_json::from_str(payload)?),
+            Self::WorkflowJob => GitHubEvent::WorkflowJob(serde_json::from_str(payload)?),
+            Self::WorkflowRun => GitHubEvent::WorkflowRun(serde_json::from_str(payload)?),
             Self::Unknown => bail!("unknown event type"),
+            Self::Generic(name) => GitHubEvent::Generic(GenericEvent {
+                name: name.clone(),
+                ..serde_json::from_str(payload)?
+            }),
         })
     }
 }
@@ -79,6 +133,10 @@ impl<'r> FromRequest<'r> for GitHubEventType {
         let event_type_json_value =
             serde_json::to_value(event_type).expect("`String` serialization should never fail");
         let event_type = match serde_json::from_value::<GitHubEventType>(event_type_json_value) {
+            Ok(GitHubEventType::Generic(name)) if !GENERIC_EVENTS.contains(&name.as_str()) => {
+                warn!("received unknown event type: {}", name);
+                GitHubEventType::Unknown
+            }
             Ok(ev_type) => ev_type,
             Err(e) => {
                 warn!("received unknown event type: {}, {}", event_type, e);
@@ -0,0 +1,39 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::{workflow_run::is_failure, Repository};
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WorkflowJobEvent {
+    pub action: WorkflowJobAction,
+    pub workflow_job: WorkflowJob,
+    pub repository: Repository,
+}
+
+actions! {
+    WorkflowJobAction {
+        Queued => "queued",
+        Waiting => "waiting",
+        InProgress => "in_progress",
+        Completed => "completed",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WorkflowJob {
+    pub name: String,
+    /// Not sent by older GitHub Enterprise versions
+    pub workflow_name: Option<String>,
+    pub head_branch: Option<String>,
+    /// Only set once the job is completed
+    pub conclusion: Option<String>,
+    /// Link to the job's log
+    pub html_url: Url,
+}
+
+impl WorkflowJob {
+    pub fn failed(&self) -> bool {
+        is_failure(self.conclusion.as_deref())
+    }
+}
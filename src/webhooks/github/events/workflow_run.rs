@@ -0,0 +1,45 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::webhooks::github::events::Repository;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WorkflowRunEvent {
+    pub action: WorkflowRunAction,
+    pub workflow_run: WorkflowRun,
+    pub repository: Repository,
+}
+
+actions! {
+    WorkflowRunAction {
+        Requested => "requested",
+        InProgress => "in_progress",
+        Completed => "completed",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WorkflowRun {
+    pub name: String,
+    pub run_number: u64,
+    /// Not set for runs on tags or pull requests from forks
+    pub head_branch: Option<String>,
+    /// Only set once the run is completed
+    pub conclusion: Option<String>,
+    pub html_url: Url,
+}
+
+impl WorkflowRun {
+    pub fn failed(&self) -> bool {
+        is_failure(self.conclusion.as_deref())
+    }
+}
+
+/// Whether the conclusion of a workflow run or job is a failure worth announcing
+pub(crate) fn is_failure(conclusion: Option<&str>) -> bool {
+    matches!(
+        conclusion,
+        Some("failure" | "timed_out" | "startup_failure")
+    )
+}
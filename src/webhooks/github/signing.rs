@@ -1,14 +1,16 @@
-use std::io;
-
 use anyhow::anyhow;
 use rocket::{
-    data::{ByteUnit, FromData, Outcome},
-    http::{ContentType, Status},
-    Data, Request, State,
+    data::{FromData, Outcome},
+    http::Status,
+    Data, Request,
 };
 use tracing::trace;
+use url::form_urlencoded;
 
-use crate::webhooks::github::GitHubSecret;
+use crate::webhooks::{
+    body::{read_body, LIMIT},
+    profile::Profile,
+};
 
 const X_GITHUB_SIGNATURE: &str = "X-Hub-Signature-256";
 
@@ -41,9 +43,15 @@ fn validate_signature(secret: &str, signature: &str, data: &str) -> bool {
     }
 }
 
-pub struct SignedGitHubPayload(pub String);
+/// Decodes the JSON payload of a form-encoded delivery. The signature covers the whole form, it
+/// must be checked before
+fn form_payload(form: &str) -> Option<String> {
+    form_urlencoded::parse(form.as_bytes())
+        .find(|(key, _)| key == "payload")
+        .map(|(_, value)| value.into_owned())
+}
 
-const LIMIT: ByteUnit = ByteUnit::Mebibyte(1);
+pub struct SignedGitHubPayload(pub String);
 
 // Tracking issue for chaining Data guards to avoid reimplementing all this:
 // https://github.com/SergioBenitez/Rocket/issues/775
@@ -54,14 +62,18 @@ impl<'r> FromData<'r> for SignedGitHubPayload {
     async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self> {
         trace!("received payload on GitHub webhook endpoint: {:?}", request);
 
-        let json_ct = ContentType::new("application", "json");
-        if request.content_type() != Some(&json_ct) {
-            trace!(
-                "content type `{:?}` wasn't json, stopping here...",
-                request.content_type()
-            );
-            return Outcome::Failure((Status::BadRequest, anyhow!("wrong content type")));
-        }
+        // the webhooks can also be configured to send the JSON in the `payload` field of a form
+        let is_form = match request.content_type() {
+            Some(ct) if ct.is_json() => false,
+            Some(ct) if ct.is_form() => true,
+            _ => {
+                trace!(
+                    "content type `{:?}` wasn't json nor a form, stopping here...",
+                    request.content_type()
+                );
+                return Outcome::Failure((Status::BadRequest, anyhow!("wrong content type")));
+            }
+        };
 
         let signatures = request
             .headers()
@@ -75,29 +87,34 @@ impl<'r> FromData<'r> for SignedGitHubPayload {
             ));
         }
 
+        // the signature covers the payload as sent by GitHub, before any compression by a proxy
         let size_limit = request.limits().get("json").unwrap_or(LIMIT);
-        let content = match data.open(size_limit).into_string().await {
-            Ok(s) if s.is_complete() => s.into_inner(),
-            Ok(_) => {
-                let eof = io::ErrorKind::UnexpectedEof;
-                trace!("payload was too big");
-                return Outcome::Failure((
-                    Status::PayloadTooLarge,
-                    io::Error::new(eof, "data limit exceeded").into(),
-                ));
-            }
-            Err(e) => return Outcome::Failure((Status::BadRequest, e.into())),
+        let content = match read_body(request, data, size_limit).await {
+            Ok(content) => content,
+            Err(failure) => return Outcome::Failure(failure),
         };
 
         let signature = signatures[0];
-        let secret = request.guard::<&State<GitHubSecret>>().await.unwrap();
+        let profile = match request.guard::<&Profile>().await.succeeded() {
+            Some(profile) => profile,
+            None => return Outcome::Failure((Status::NotFound, anyhow!("unknown profile"))),
+        };
 
-        if !validate_signature(&secret.0, signature, &content) {
+        if !validate_signature(&profile.github_secret, signature, &content) {
             trace!("signature validation failed, stopping here...");
             return Outcome::Failure((Status::BadRequest, anyhow!("couldn't verify signature")));
         }
 
         trace!("validated GitHub payload");
-        Outcome::Success(SignedGitHubPayload(content))
+        if !is_form {
+            return Outcome::Success(SignedGitHubPayload(content));
+        }
+        match form_payload(&content) {
+            Some(payload) => Outcome::Success(SignedGitHubPayload(payload)),
+            None => {
+                trace!("form had no payload field, stopping here...");
+                Outcome::Failure((Status::BadRequest, anyhow!("missing payload field")))
+            }
+        }
     }
 }
@@ -0,0 +1,272 @@
+use rocket::serde::json::Json;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use crate::webhooks::{
+    activity::{ActivityRow, MetricsRow},
+    github::{
+        CheckRunEvent, CheckSuiteEvent, CommitCommentEvent, CreateEvent, DeployKeyEvent,
+        DeploymentEvent, ForkEvent, GenericEvent, GollumEvent, IssueCommentEvent, IssuesEvent,
+        LabelEvent, MembershipEvent, MergeGroupEvent, MetaEvent, OrganizationEvent, PingEvent,
+        ProjectsV2Event, ProjectsV2ItemEvent, PublicEvent, PullRequestEvent,
+        PullRequestReviewCommentEvent, PullRequestReviewEvent, PushEvent, ReleaseEvent,
+        RepositoryEvent, RepositoryRulesetEvent, RepositoryVulnerabilityAlertEvent,
+        SecurityAdvisoryEvent, StarEvent, WorkflowJobEvent, WorkflowRunEvent, X_GITHUB_EVENT,
+    },
+    prolosite::{DjangoErrorPayload, ForumPayload, ImpersonatePayload, NewSchoolPayload},
+    render::{RenderRequest, RenderedMessage},
+};
+
+/// Describes how a request to an endpoint is authenticated
+enum Auth {
+    /// HMAC signature of the body, as sent by GitHub
+    GitHubSignature,
+    /// The Prologin site secret in the `Authorization` header
+    ProloSiteSecret,
+}
+
+fn header(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "header",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+fn operation(summary: &str, auth: Auth, body: Value, response: Value) -> Value {
+    let mut parameters = vec![];
+    match auth {
+        Auth::GitHubSignature => {
+            parameters.push(header(X_GITHUB_EVENT, "Name of the GitHub event"));
+            parameters.push(header(
+                "X-Hub-Signature-256",
+                "`sha256=` followed by the hex HMAC-SHA256 of the body",
+            ));
+        }
+        Auth::ProloSiteSecret => {
+            parameters.push(header("Authorization", "The Prologin site secret"));
+        }
+    }
+
+    json!({
+        "post": {
+            "summary": summary,
+            "parameters": parameters,
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": body } },
+            },
+            "responses": {
+                "200": response,
+                "400": { "description": "Invalid authentication or event type" },
+                "503": { "description": "The event couldn't be queued in time" },
+            },
+        }
+    })
+}
+
+fn schema_for<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    serde_json::to_value(gen.subschema_for::<T>()).expect("schemas are always serializable")
+}
+
+/// Builds the OpenAPI document describing all the endpoints served by prololo.
+pub fn openapi_document() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let empty_response = json!({
+        "description": "The event was accepted, invalid payloads are dropped afterwards",
+    });
+
+    let github_events = vec![
+        schema_for::<CheckRunEvent>(&mut gen),
+        schema_for::<CheckSuiteEvent>(&mut gen),
+        schema_for::<CommitCommentEvent>(&mut gen),
+        schema_for::<CreateEvent>(&mut gen),
+        schema_for::<DeployKeyEvent>(&mut gen),
+        schema_for::<DeploymentEvent>(&mut gen),
+        schema_for::<ForkEvent>(&mut gen),
+        schema_for::<GenericEvent>(&mut gen),
+        schema_for::<GollumEvent>(&mut gen),
+        schema_for::<IssueCommentEvent>(&mut gen),
+        schema_for::<IssuesEvent>(&mut gen),
+        schema_for::<LabelEvent>(&mut gen),
+        schema_for::<MembershipEvent>(&mut gen),
+        schema_for::<MergeGroupEvent>(&mut gen),
+        schema_for::<MetaEvent>(&mut gen),
+        schema_for::<OrganizationEvent>(&mut gen),
+        schema_for::<PingEvent>(&mut gen),
+        schema_for::<ProjectsV2Event>(&mut gen),
+        schema_for::<ProjectsV2ItemEvent>(&mut gen),
+        schema_for::<PublicEvent>(&mut gen),
+        schema_for::<PullRequestEvent>(&mut gen),
+        schema_for::<PullRequestReviewEvent>(&mut gen),
+        schema_for::<PullRequestReviewCommentEvent>(&mut gen),
+        schema_for::<PushEvent>(&mut gen),
+        schema_for::<ReleaseEvent>(&mut gen),
+        schema_for::<RepositoryEvent>(&mut gen),
+        schema_for::<RepositoryRulesetEvent>(&mut gen),
+        schema_for::<RepositoryVulnerabilityAlertEvent>(&mut gen),
+        schema_for::<SecurityAdvisoryEvent>(&mut gen),
+        schema_for::<StarEvent>(&mut gen),
+        schema_for::<WorkflowJobEvent>(&mut gen),
+        schema_for::<WorkflowRunEvent>(&mut gen),
+    ];
+
+    let mut paths = Map::new();
+    paths.insert(
+        "/api/webhooks/github".to_string(),
+        operation(
+            "GitHub webhook, the payload depends on the event type",
+            Auth::GitHubSignature,
+            json!({ "oneOf": github_events }),
+            empty_response.clone(),
+        ),
+    );
+    paths.insert(
+        "/api/webhooks/prolosite/django".to_string(),
+        operation(
+            "Django crash on the Prologin site",
+            Auth::ProloSiteSecret,
+            schema_for::<DjangoErrorPayload>(&mut gen),
+            empty_response.clone(),
+        ),
+    );
+    paths.insert(
+        "/api/webhooks/prolosite/forum".to_string(),
+        operation(
+            "New thread on the Prologin forum",
+            Auth::ProloSiteSecret,
+            schema_for::<ForumPayload>(&mut gen),
+            empty_response.clone(),
+        ),
+    );
+    paths.insert(
+        "/api/webhooks/prolosite/new-school".to_string(),
+        operation(
+            "New school added on the Prologin site",
+            Auth::ProloSiteSecret,
+            schema_for::<NewSchoolPayload>(&mut gen),
+            empty_response.clone(),
+        ),
+    );
+    paths.insert(
+        "/api/webhooks/prolosite/impersonate".to_string(),
+        operation(
+            "Impersonation started or stopped on the Prologin site",
+            Auth::ProloSiteSecret,
+            schema_for::<ImpersonatePayload>(&mut gen),
+            empty_response,
+        ),
+    );
+    paths.insert(
+        "/api/render".to_string(),
+        operation(
+            "Preview the message generated for an event, without sending it",
+            Auth::ProloSiteSecret,
+            schema_for::<RenderRequest>(&mut gen),
+            json!({
+                "description": "The rendered message, or null if the event isn't announced",
+                "content": {
+                    "application/json": { "schema": schema_for::<Option<RenderedMessage>>(&mut gen) },
+                },
+            }),
+        ),
+    );
+    paths.insert(
+        "/api/activity".to_string(),
+        json!({
+            "get": {
+                "summary": "Number of announcements per week, repo and author",
+                "parameters": [
+                    header("Authorization", "The Prologin site secret"),
+                    {
+                        "name": "format",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "string", "enum": ["json", "csv"], "default": "json" },
+                    },
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The activity, sorted by week, repo and author",
+                        "content": {
+                            "application/json": { "schema": schema_for::<Vec<ActivityRow>>(&mut gen) },
+                            "text/csv": { "schema": { "type": "string" } },
+                        },
+                    },
+                    "400": { "description": "Invalid authentication or format" },
+                },
+            }
+        }),
+    );
+
+    paths.insert(
+        "/api/metrics".to_string(),
+        json!({
+            "get": {
+                "summary": "Number of events per day and source, and of failed events",
+                "parameters": [
+                    header("Authorization", "The Prologin site secret"),
+                    {
+                        "name": "format",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "string", "enum": ["json", "csv"], "default": "json" },
+                    },
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The daily counters of the last year, sorted by day",
+                        "content": {
+                            "application/json": { "schema": schema_for::<Vec<MetricsRow>>(&mut gen) },
+                            "text/csv": { "schema": { "type": "string" } },
+                        },
+                    },
+                    "400": { "description": "Invalid authentication or format" },
+                    "404": { "description": "The bot runs in another process" },
+                },
+            }
+        }),
+    );
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "prololo",
+            "description": "Matrix bot relaying information about Prologin's GitHub repositories, website...",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": { "schemas": gen.take_definitions() },
+    })
+}
+
+#[rocket::get("/api/openapi.json")]
+pub fn openapi() -> Json<Value> {
+    Json(openapi_document())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_document() {
+        let document = openapi_document();
+
+        assert!(document["paths"]["/api/webhooks/github"]["post"].is_object());
+        assert!(document["paths"]["/api/render"]["post"].is_object());
+        assert!(document["paths"]["/api/activity"]["get"].is_object());
+        assert!(document["paths"]["/api/metrics"]["get"].is_object());
+
+        // every referenced schema should be defined
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        for name in ["PushEvent", "ForumPayload", "RenderRequest", "GitHubUser"] {
+            assert!(schemas.contains_key(name), "missing schema {}", name);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Each bot running in the process has its own copy of the endpoints, mounted under the path
+//! prefix of its profile.
+
+use std::{future::Future, path::PathBuf, sync::Arc};
+
+use anyhow::anyhow;
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, warn};
+
+use crate::{
+    bot::{activity::Activity, archive::Archive},
+    config::{BrowserConfig, GitHubConfig, ProloloConfig},
+    queue::Queue,
+    webhooks::{RawDelivery, Received, LATENCY_BUDGET},
+};
+
+/// Where the endpoints send the events they receive
+pub enum EventSink {
+    /// The bot running in the same process, see [`crate::webhooks::parse_deliveries`]
+    Bot(UnboundedSender<RawDelivery>),
+    /// The shared queue, read by the bot wherever it runs
+    Queue(Queue),
+    /// A sink provided by a program embedding the bot, see [`crate::builder::ProloloBuilder`]
+    Custom(Box<dyn DeliverySink>),
+}
+
+/// Receives the events of the endpoints instead of the bot or the queue, e.g. to store them in
+/// another system
+#[rocket::async_trait]
+pub trait DeliverySink: Send + Sync {
+    /// Takes an event before the endpoint answers, it should be quick
+    async fn push(&self, delivery: RawDelivery) -> anyhow::Result<()>;
+}
+
+/// What the endpoints of a bot need to authenticate and forward the events they receive
+pub struct Profile {
+    /// The path prefix, without its trailing slash
+    prefix: String,
+    sink: EventSink,
+    /// The Matrix user of the bot, shown on the status page
+    pub name: String,
+    /// Secret used to verify HMAC signature of GitHub webhooks
+    pub github_secret: String,
+    /// Secret token used in Authorization header for Prologin site hooks
+    pub prolosite_secret: String,
+    pub github_config: GitHubConfig,
+    pub browser: Option<BrowserConfig>,
+    /// Missing on instances that only receive webhooks, the archive is kept by the bot
+    pub archive: Option<Arc<Archive>>,
+    /// Missing on instances that only receive webhooks, like the archive
+    pub activity: Option<Arc<Activity>>,
+    /// Overrides of the embedded assets, e.g. the status page template
+    pub assets_dir: Option<PathBuf>,
+}
+
+impl Profile {
+    pub fn new(
+        config: &ProloloConfig,
+        sink: EventSink,
+        archive: Option<Arc<Archive>>,
+        activity: Option<Arc<Activity>>,
+    ) -> Self {
+        Self {
+            prefix: config.path_prefix.trim_end_matches('/').to_string(),
+            sink,
+            name: config.matrix_username.clone(),
+            github_secret: config.github_secret.clone(),
+            prolosite_secret: config.prolosite_secret.clone(),
+            github_config: config.github.clone(),
+            browser: config.browser.clone(),
+            archive,
+            activity,
+            assets_dir: config.assets_dir.clone(),
+        }
+    }
+
+    /// Hands an event over to the bot, the queue or a custom sink, the parsing happens after answering.
+    pub async fn forward(&self, received: Received, delivery: RawDelivery) -> Status {
+        let status = match &self.sink {
+            EventSink::Bot(sender) => {
+                sender
+                    .send(delivery)
+                    .expect("mpsc channel was closed / dropped");
+                Status::Ok
+            }
+            EventSink::Queue(queue) => {
+                push_within_budget(received, "queue", queue.push(delivery)).await
+            }
+            EventSink::Custom(sink) => {
+                push_within_budget(received, "sink", sink.push(delivery)).await
+            }
+        };
+
+        let elapsed = received.0.elapsed();
+        if elapsed > LATENCY_BUDGET {
+            warn!("answered webhook after {:?}", elapsed);
+        } else {
+            debug!("answered webhook after {:?}", elapsed);
+        }
+        status
+    }
+}
+
+/// Waits for an event to be stored, but not past the latency budget of the webhooks
+async fn push_within_budget(
+    received: Received,
+    sink: &str,
+    push: impl Future<Output = anyhow::Result<()>>,
+) -> Status {
+    let deadline = received.0 + LATENCY_BUDGET;
+    match tokio::time::timeout_at(deadline, push).await {
+        Ok(Ok(())) => Status::Ok,
+        Ok(Err(e)) => {
+            warn!("couldn't push event to the {}: {:#}", sink, e);
+            Status::ServiceUnavailable
+        }
+        Err(_) => {
+            warn!("pushing event to the {} took too long", sink);
+            Status::ServiceUnavailable
+        }
+    }
+}
+
+pub struct Profiles(pub Vec<Profile>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Profile {
+    type Error = anyhow::Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // the routes are mounted once per profile, at its prefix
+        let base = request
+            .route()
+            .map_or("", |route| route.uri.base())
+            .trim_end_matches('/');
+        let profiles = request
+            .rocket()
+            .state::<Profiles>()
+            .expect("profiles are always managed");
+
+        match profiles.0.iter().find(|profile| profile.prefix == base) {
+            Some(profile) => Outcome::Success(profile),
+            None => Outcome::Failure((
+                Status::NotFound,
+                anyhow!("no profile uses the prefix {}", base),
+            )),
+        }
+    }
+}
@@ -4,18 +4,17 @@ use anyhow::anyhow;
 use rocket::{
     http::Status,
     request::{FromRequest, Outcome},
-    serde::json::Json,
-    State,
 };
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use tracing::{info, trace};
 use url::Url;
 
-use crate::webhooks::{Event, EventSender};
+use crate::webhooks::{body::JsonBody, profile::Profile, RawDelivery, RawEvent, Received};
 
 const AUTHORIZATION: &str = "Authorization";
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ProloSiteEvent {
     Error(DjangoErrorPayload),
     Forum(ForumPayload),
@@ -23,27 +22,84 @@ pub enum ProloSiteEvent {
     Impersonate(ImpersonatePayload),
 }
 
-pub struct ProlositeSecret(pub String);
+/// The kind of payload sent by the Prologin site, named after the matching webhook route
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProloSiteEventType {
+    Django,
+    Forum,
+    NewSchool,
+    Impersonate,
+}
 
-pub(crate) struct AuthorizationHeader<'r>(&'r str);
+impl ProloSiteEventType {
+    pub(crate) fn parse_payload(
+        &self,
+        payload: &serde_json::Value,
+    ) -> anyhow::Result<ProloSiteEvent> {
+        Ok(match self {
+            Self::Django => ProloSiteEvent::Error(Deserialize::deserialize(payload)?),
+            Self::Forum => ProloSiteEvent::Forum(Deserialize::deserialize(payload)?),
+            Self::NewSchool => ProloSiteEvent::NewSchool(Deserialize::deserialize(payload)?),
+            Self::Impersonate => ProloSiteEvent::Impersonate(Deserialize::deserialize(payload)?),
+        })
+    }
+}
+
+/// Compares secrets in a time that doesn't depend on where they differ
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub struct AuthorizationHeader<'r>(#[allow(dead_code)] &'r str);
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for AuthorizationHeader<'r> {
     type Error = anyhow::Error;
 
     async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
-        let authorization = request.headers().get(AUTHORIZATION).collect::<Vec<_>>();
-        if authorization.len() != 1 {
-            trace!("couldn't locate {} header", AUTHORIZATION);
-            return Outcome::Failure((
-                Status::BadRequest,
-                anyhow!("request needs an authorization header"),
-            ));
-        }
-        let authorization = authorization[0];
-        let auth_secret = &request.guard::<&State<ProlositeSecret>>().await.unwrap().0;
+        let profile = match request.guard::<&Profile>().await {
+            Outcome::Success(profile) => profile,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(()) => return Outcome::Forward(()),
+        };
+        // the token of the pages only works on some endpoints, unlike the secret of the site
+        let route = request.route().and_then(|route| route.name.as_deref());
+        let browser_token = profile.browser.as_ref().and_then(|browser| {
+            let allowed = browser
+                .routes
+                .iter()
+                .any(|allowed| Some(allowed.route_name()) == route);
+            browser.token.as_deref().filter(|_| allowed)
+        });
+        let matches_token =
+            |given: &str| browser_token.map_or(false, |token| constant_time_eq(given, token));
 
-        if authorization != auth_secret {
+        let authorization = request.headers().get(AUTHORIZATION).collect::<Vec<_>>();
+        // pages that can't set headers may give their token in the query instead, but not the
+        // secret of the site
+        let query_token = request.query_value::<&str>("token").and_then(Result::ok);
+        let (authorization, valid) = match (authorization.as_slice(), query_token) {
+            ([authorization], _) => (
+                *authorization,
+                constant_time_eq(authorization, &profile.prolosite_secret)
+                    || matches_token(authorization),
+            ),
+            ([], Some(token)) => (token, matches_token(token)),
+            _ => {
+                trace!("couldn't locate {} header", AUTHORIZATION);
+                return Outcome::Failure((
+                    Status::BadRequest,
+                    anyhow!("request needs an authorization header"),
+                ));
+            }
+        };
+
+        if !valid {
             trace!("secret validation failed, stopping here...");
             return Outcome::Failure((Status::BadRequest, anyhow!("secret doesn't match")));
         }
@@ -53,37 +109,45 @@ impl<'r> FromRequest<'r> for AuthorizationHeader<'r> {
     }
 }
 
+/// Forwards a payload to the bot, it is parsed after answering.
+async fn forward(
+    received: Received,
+    event_type: ProloSiteEventType,
+    payload: serde_json::Value,
+    profile: &Profile,
+) -> Status {
+    trace!("payload: {}", payload);
+
+    let delivery = RawDelivery {
+        id: None,
+        event: RawEvent::ProloSite {
+            event_type,
+            payload,
+        },
+    };
+    profile.forward(received, delivery).await
+}
+
 #[rocket::post("/api/webhooks/prolosite/django", format = "json", data = "<payload>")]
-pub(crate) fn django(
-    _token: AuthorizationHeader,
-    payload: Json<DjangoErrorPayload>,
-    sender: &State<EventSender>,
-) {
+pub async fn django(
+    received: Received,
+    _token: AuthorizationHeader<'_>,
+    payload: JsonBody,
+    profile: &Profile,
+) -> Status {
     info!("received django error");
-    trace!("payload: {:?}", payload.0);
-    sender
-        .0
-        .send(Event::ProloSite(ProloSiteEvent::Error(
-            payload.into_inner(),
-        )))
-        .expect("mspc channel was closed / dropped");
+    forward(received, ProloSiteEventType::Django, payload.0, profile).await
 }
 
 #[rocket::post("/api/webhooks/prolosite/forum", format = "json", data = "<payload>")]
-pub(crate) fn forum(
-    _token: AuthorizationHeader,
-    payload: Json<ForumPayload>,
-    sender: &State<EventSender>,
-) {
+pub async fn forum(
+    received: Received,
+    _token: AuthorizationHeader<'_>,
+    payload: JsonBody,
+    profile: &Profile,
+) -> Status {
     info!("received forum update");
-    trace!("payload: {:?}", payload.0);
-
-    sender
-        .0
-        .send(Event::ProloSite(ProloSiteEvent::Forum(
-            payload.into_inner(),
-        )))
-        .expect("mspc channel was closed / dropped");
+    forward(received, ProloSiteEventType::Forum, payload.0, profile).await
 }
 
 #[rocket::post(
@@ -91,20 +155,14 @@ pub(crate) fn forum(
     format = "json",
     data = "<payload>"
 )]
-pub(crate) fn new_school(
-    _token: AuthorizationHeader,
-    payload: Json<NewSchoolPayload>,
-    sender: &State<EventSender>,
-) {
+pub async fn new_school(
+    received: Received,
+    _token: AuthorizationHeader<'_>,
+    payload: JsonBody,
+    profile: &Profile,
+) -> Status {
     info!("received new school update");
-    trace!("payload: {:?}", payload.0);
-
-    sender
-        .0
-        .send(Event::ProloSite(ProloSiteEvent::NewSchool(
-            payload.into_inner(),
-        )))
-        .expect("mspc channel was closed / dropped");
+    forward(received, ProloSiteEventType::NewSchool, payload.0, profile).await
 }
 
 #[rocket::post(
@@ -112,43 +170,43 @@ pub(crate) fn new_school(
     format = "json",
     data = "<payload>"
 )]
-pub(crate) fn impersonate(
-    _token: AuthorizationHeader,
-    payload: Json<ImpersonatePayload>,
-    sender: &State<EventSender>,
-) {
+pub async fn impersonate(
+    received: Received,
+    _token: AuthorizationHeader<'_>,
+    payload: JsonBody,
+    profile: &Profile,
+) -> Status {
     info!("received impersonate notice");
-    trace!("payload: {:?}", payload.0);
-
-    sender
-        .0
-        .send(Event::ProloSite(ProloSiteEvent::Impersonate(
-            payload.into_inner(),
-        )))
-        .expect("mspc channel was closed / dropped");
+    forward(
+        received,
+        ProloSiteEventType::Impersonate,
+        payload.0,
+        profile,
+    )
+    .await
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct DjangoErrorPayload {
     pub(crate) request: Request,
     pub(crate) exception: Exception,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub(crate) struct Request {
     pub(crate) user: Option<String>,
     pub(crate) method: String,
     pub(crate) path: PathBuf,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub(crate) struct Exception {
     pub(crate) value: String,
     #[allow(dead_code)]
     pub(crate) trace: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct ForumPayload {
     pub(crate) username: String,
     pub(crate) forum: String,
@@ -156,20 +214,20 @@ pub struct ForumPayload {
     pub(crate) url: Url,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct NewSchoolPayload {
     pub(crate) name: String,
     pub(crate) url: Url,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct ImpersonatePayload {
     pub(crate) event: String,
     pub(crate) hijacker: User,
     pub(crate) hijacked: User,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct User {
     pub(crate) username: String,
     pub(crate) url: Url,
@@ -0,0 +1,130 @@
+use rocket::{response::status::BadRequest, serde::json::Json};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, trace};
+
+use crate::{
+    bot::format_event,
+    config::GitHubConfig,
+    webhooks::{
+        github::GitHubEventType,
+        profile::Profile,
+        prolosite::{AuthorizationHeader, ProloSiteEventType},
+        Event,
+    },
+};
+
+/// An event to format, as it would be received on the matching webhook endpoint
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "source")]
+pub enum RenderRequest {
+    #[serde(rename = "github")]
+    GitHub {
+        event_type: GitHubEventType,
+        payload: serde_json::Value,
+    },
+    #[serde(rename = "prolosite")]
+    ProloSite {
+        event_type: ProloSiteEventType,
+        payload: serde_json::Value,
+    },
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RenderedMessage {
+    pub plain: String,
+    pub html: String,
+    /// The repository used to pick the destination room, if any
+    pub repo: Option<String>,
+    /// The destination room name, if the event doesn't use the repo based routing
+    pub room: Option<String>,
+}
+
+/// Formats an event the same way the bot would, returns `None` if it wouldn't be announced.
+fn render(
+    request: RenderRequest,
+    github_config: &GitHubConfig,
+) -> anyhow::Result<Option<RenderedMessage>> {
+    let event = match request {
+        RenderRequest::GitHub {
+            event_type,
+            payload,
+        } => Event::GitHub(event_type.parse_payload(payload.to_string())?),
+        RenderRequest::ProloSite {
+            event_type,
+            payload,
+        } => Event::ProloSite(event_type.parse_payload(&payload)?),
+    };
+
+    let response = match format_event(event, github_config)? {
+        Some(response) => response,
+        None => return Ok(None),
+    };
+
+    let (plain, html) = response.message.into_bodies();
+    Ok(Some(RenderedMessage {
+        plain,
+        html,
+        repo: response.repo,
+        room: response.room,
+    }))
+}
+
+/// Previews the message generated for an event, without sending it to Matrix.
+#[rocket::post("/api/render", format = "json", data = "<request>")]
+pub fn render_preview(
+    _token: AuthorizationHeader,
+    request: Json<RenderRequest>,
+    profile: &Profile,
+) -> Result<Json<Option<RenderedMessage>>, BadRequest<String>> {
+    info!("received render request");
+    trace!("request: {:?}", request.0);
+
+    render(request.into_inner(), &profile.github_config)
+        .map(Json)
+        .map_err(|e| BadRequest(Some(format!("couldn't render event: {}", e))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prolosite() {
+        let request: RenderRequest = serde_json::from_str(
+            r#"{
+                "source": "prolosite",
+                "event_type": "new-school",
+                "payload": {
+                    "name": "proloschool",
+                    "url": "https://prologin.org/admin/schools/school/3/change/"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let rendered = render(request, &GitHubConfig::default())
+            .unwrap()
+            .expect("should have a rendered message");
+
+        assert_eq!(
+            rendered.plain,
+            "[🎓 school] New school added: proloschool ⋅ https://prologin.org/admin/schools/school/3/change/"
+        );
+        assert_eq!(
+            rendered.html,
+            r#"<b>[🎓 school]</b> New school added: <a href="https://prologin.org/admin/schools/school/3/change/">proloschool</a>"#
+        );
+        assert!(rendered.repo.is_none());
+    }
+
+    #[test]
+    fn test_render_invalid_payload() {
+        let request: RenderRequest = serde_json::from_str(
+            r#"{ "source": "github", "event_type": "ping", "payload": { "zen": "hi" } }"#,
+        )
+        .unwrap();
+
+        assert!(render(request, &GitHubConfig::default()).is_err());
+    }
+}
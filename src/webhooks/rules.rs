@@ -0,0 +1,207 @@
+//! Config-defined rules adjusting how incoming payloads are announced. They are evaluated on the raw
+//! JSON payload, before it is parsed and handed to the bot.
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    bot::{message_builder::MessageBuilder, Response, Severity},
+    config::{PayloadSource, TransformRule},
+};
+
+/// Changes made by a matching rule to the message announcing an event
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Overrides {
+    pub room: Option<String>,
+    pub severity: Option<Severity>,
+    /// The rendered template replacing the message
+    pub text: Option<String>,
+}
+
+impl Overrides {
+    pub fn apply(self, response: &mut Response) {
+        if let Some(room) = self.room {
+            response.room = Some(room);
+        }
+        if let Some(severity) = self.severity {
+            response.severity = severity;
+        }
+        if let Some(text) = self.text {
+            let mut message = MessageBuilder::new();
+            write!(message, "{}", text).unwrap();
+            response.message = message;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    Drop,
+    Keep(Overrides),
+}
+
+impl TransformRule {
+    fn applies_to(&self, source: PayloadSource, event: &str) -> bool {
+        self.source.map_or(true, |s| s == source)
+            && self.event.as_deref().map_or(true, |e| e == event)
+    }
+
+    fn matches(&self, source: PayloadSource, event: &str, payload: &Value) -> bool {
+        self.applies_to(source, event)
+            && self.matches.iter().all(|m| {
+                payload
+                    .pointer(&m.pointer)
+                    .map_or(false, |value| m.regex.is_match(&value_to_string(value)))
+            })
+    }
+}
+
+/// Whether some rule may match the events of this type, otherwise the payload doesn't need to be
+/// parsed into a [`Value`] to apply the rules
+pub fn has_rules_for(rules: &[TransformRule], source: PayloadSource, event: &str) -> bool {
+    rules.iter().any(|rule| rule.applies_to(source, event))
+}
+
+/// Finds the first rule matching a payload, and decides what to do with it.
+pub fn apply_rules(
+    rules: &[TransformRule],
+    source: PayloadSource,
+    event: &str,
+    payload: &Value,
+) -> Verdict {
+    let rule = match rules
+        .iter()
+        .find(|rule| rule.matches(source, event, payload))
+    {
+        Some(rule) => rule,
+        None => return Verdict::Keep(Overrides::default()),
+    };
+    debug!("payload for event {} matched rule {:?}", event, rule);
+
+    if rule.drop {
+        return Verdict::Drop;
+    }
+
+    Verdict::Keep(Overrides {
+        room: rule.room.clone(),
+        severity: rule.severity,
+        text: rule
+            .template
+            .as_deref()
+            .map(|template| render_template(template, payload)),
+    })
+}
+
+/// The name of an event type, as used in [`TransformRule::event`]
+pub fn event_name<T: Serialize>(event_type: &T) -> String {
+    match serde_json::to_value(event_type) {
+        Ok(Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Replaces each `{/json/pointer}` in the template with the value found in the payload, or with
+/// nothing if there's no such value.
+fn render_template(template: &str, payload: &Value) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{/") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        rendered.push_str(&rest[..start]);
+        if let Some(value) = payload.pointer(&rest[start + 1..end]) {
+            rendered.push_str(&value_to_string(value));
+        }
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rules(yaml: &str) -> Vec<TransformRule> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_render_template() {
+        let payload = json!({ "sender": { "login": "alice" }, "forced": true });
+
+        assert_eq!(
+            render_template(
+                "{/sender/login} pushed (forced: {/forced}){/missing} {",
+                &payload
+            ),
+            "alice pushed (forced: true) {"
+        );
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let rules = rules(
+            r#"
+- source: github
+  event: push
+  match:
+    - pointer: /repository/full_name
+      regex: "^prologin/secret-"
+  drop: true
+- event: push
+  match:
+    - pointer: /ref
+      regex: "^refs/heads/main$"
+  room: ops
+  severity: high
+  template: "{/sender/login} pushed to main"
+"#,
+        );
+        let secret = json!({ "repository": { "full_name": "prologin/secret-stuff" } });
+        let main = json!({
+            "ref": "refs/heads/main",
+            "repository": { "full_name": "prologin/site" },
+            "sender": { "login": "alice" },
+        });
+
+        assert!(has_rules_for(&rules, PayloadSource::GitHub, "push"));
+        assert!(has_rules_for(&rules, PayloadSource::ProloSite, "push"));
+        assert!(!has_rules_for(&rules, PayloadSource::GitHub, "issues"));
+
+        assert!(matches!(
+            apply_rules(&rules, PayloadSource::GitHub, "push", &secret),
+            Verdict::Drop
+        ));
+        assert!(matches!(
+            apply_rules(&rules, PayloadSource::GitHub, "issues", &secret),
+            Verdict::Keep(Overrides { room: None, .. })
+        ));
+
+        match apply_rules(&rules, PayloadSource::GitHub, "push", &main) {
+            Verdict::Keep(overrides) => {
+                assert_eq!(overrides.room.as_deref(), Some("ops"));
+                assert_eq!(overrides.severity, Some(Severity::High));
+                assert_eq!(overrides.text.as_deref(), Some("alice pushed to main"));
+            }
+            Verdict::Drop => panic!("payload shouldn't be dropped"),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use chrono::Utc;
+use rocket::{http::Status, response::content::Html, response::status::Custom};
+
+use crate::{assets, webhooks::profile::Profile};
+
+/// A minimal page telling whether the bot is alive, meant to be opened in a browser.
+#[rocket::get("/")]
+pub fn status_page(profile: &Profile) -> Result<Html<String>, Custom<String>> {
+    let activity = profile.activity.as_ref().ok_or_else(|| {
+        Custom(
+            Status::NotFound,
+            "the status is kept by the instance running the bot".to_string(),
+        )
+    })?;
+
+    let template = assets::load(profile.assets_dir.as_deref(), "templates/status.html")
+        .map_err(|e| Custom(Status::InternalServerError, format!("{:#}", e)))?;
+
+    Ok(Html(activity.status_page(
+        &template,
+        &profile.name,
+        Utc::now(),
+    )))
+}
@@ -0,0 +1,284 @@
+//! XMPP gateway mirroring announcements to multi-user chats, connected as an external component
+//! ([XEP-0114](https://xmpp.org/extensions/xep-0114.html)).
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, bail, Context};
+use matrix_sdk::ruma::RoomId;
+use regex::Regex;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+use tracing::{debug, info, trace, warn};
+
+use crate::config::{ProloloConfig, XmppConfig};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// A message to send to a MUC
+#[derive(Debug)]
+struct MucMessage {
+    muc: String,
+    body: String,
+}
+
+pub struct XmppGateway {
+    sender: UnboundedSender<MucMessage>,
+    /// The MUC mirroring each Matrix room
+    mucs: HashMap<RoomId, String>,
+}
+
+impl XmppGateway {
+    /// Starts the connection to the XMPP server in the background.
+    pub fn start(config: &ProloloConfig, xmpp: XmppConfig) -> anyhow::Result<Self> {
+        let mucs = xmpp
+            .rooms
+            .iter()
+            .map(|(room, muc)| Ok((config.room_by_name(room)?.clone(), muc.clone())))
+            .collect::<anyhow::Result<_>>()
+            .context("invalid XMPP room mapping")?;
+
+        let (sender, receiver) = unbounded_channel();
+        tokio::spawn(async move { run_component(xmpp, receiver).await });
+
+        Ok(Self { sender, mucs })
+    }
+
+    /// Sends a copy of a message posted in a Matrix room to its MUC, if it has one.
+    pub fn mirror(&self, room_id: &RoomId, body: &str) {
+        if let Some(muc) = self.mucs.get(room_id) {
+            let message = MucMessage {
+                muc: muc.clone(),
+                body: body.to_string(),
+            };
+            if self.sender.send(message).is_err() {
+                warn!("XMPP gateway stopped, can't mirror message to {}", muc);
+            }
+        }
+    }
+}
+
+async fn run_component(config: XmppConfig, mut messages: UnboundedReceiver<MucMessage>) {
+    loop {
+        match TcpStream::connect(&config.server).await {
+            Ok(stream) => match serve_component(stream, &config, &mut messages).await {
+                // all senders were dropped, nothing left to mirror
+                Ok(()) => return,
+                Err(e) => warn!("XMPP connection to {} failed: {:#}", config.server, e),
+            },
+            Err(e) => warn!("couldn't connect to XMPP server {}: {}", config.server, e),
+        }
+
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn serve_component<S: AsyncRead + AsyncWrite>(
+    stream: S,
+    config: &XmppConfig,
+    messages: &mut UnboundedReceiver<MucMessage>,
+) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buffer = String::new();
+
+    writer
+        .write_all(
+            format!(
+                "<stream:stream xmlns='jabber:component:accept' \
+                xmlns:stream='http://etherx.jabber.org/streams' to='{}'>",
+                escape_xml(&config.component)
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    read_until(&mut reader, &mut buffer, |buf| stream_id(buf).is_some()).await?;
+    let id = stream_id(&buffer).expect("stream id was found");
+    buffer.clear();
+
+    writer
+        .write_all(
+            format!(
+                "<handshake>{}</handshake>",
+                handshake_digest(&id, &config.secret)
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    read_until(&mut reader, &mut buffer, |buf| {
+        buf.contains("<handshake") || buf.contains("<stream:error")
+    })
+    .await?;
+    if buffer.contains("<stream:error") {
+        bail!("handshake refused by the server: {}", buffer);
+    }
+    info!("connected to XMPP server {}", config.server);
+
+    for muc in config.rooms.values() {
+        let presence = format!(
+            "<presence from='{}' to='{}/{}'><x xmlns='http://jabber.org/protocol/muc'/></presence>",
+            escape_xml(&config.component),
+            escape_xml(muc),
+            escape_xml(&config.nickname)
+        );
+        writer.write_all(presence.as_bytes()).await?;
+    }
+
+    // incoming stanzas are of no interest, but must be consumed to keep the connection flowing
+    let drain = async {
+        let mut chunk = [0; 4096];
+        loop {
+            match reader.read(&mut chunk).await {
+                Ok(0) => return anyhow!("connection closed by the server"),
+                Ok(n) => trace!("XMPP received: {}", String::from_utf8_lossy(&chunk[..n])),
+                Err(e) => return e.into(),
+            }
+        }
+    };
+    tokio::pin!(drain);
+
+    loop {
+        let message = tokio::select! {
+            e = &mut drain => return Err(e),
+            message = messages.recv() => match message {
+                Some(message) => message,
+                None => return Ok(()),
+            },
+        };
+
+        debug!("mirroring message to MUC {}", message.muc);
+        writer
+            .write_all(message_stanza(&config.component, &message).as_bytes())
+            .await?;
+    }
+}
+
+async fn read_until<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut String,
+    done: impl Fn(&str) -> bool,
+) -> anyhow::Result<()> {
+    let mut chunk = [0; 4096];
+    while !done(buffer) {
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("connection closed by the server");
+        }
+        buffer.push_str(&String::from_utf8_lossy(&chunk[..n]));
+    }
+    Ok(())
+}
+
+/// Finds the id of the stream opened by the server
+fn stream_id(buffer: &str) -> Option<String> {
+    let re = Regex::new(r#"<stream:stream[^>]*\sid=['"]([^'"]+)['"]"#).expect("valid regex");
+    re.captures(buffer).map(|captures| captures[1].to_string())
+}
+
+fn handshake_digest(stream_id: &str, secret: &str) -> String {
+    use sha1::{Digest, Sha1};
+
+    hex::encode(Sha1::digest(format!("{}{}", stream_id, secret).as_bytes()))
+}
+
+fn message_stanza(component: &str, message: &MucMessage) -> String {
+    format!(
+        "<message from='{}' to='{}' type='groupchat'><body>{}</body></message>",
+        escape_xml(component),
+        escape_xml(&message.muc),
+        escape_xml(&message.body)
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncBufReadExt, BufReader};
+
+    use super::*;
+
+    #[test]
+    fn test_handshake_digest() {
+        assert_eq!(
+            handshake_digest("3BF96D32", "sesame"),
+            "7a98dc4c9e92493d7fd66a25364c862637789c45"
+        );
+    }
+
+    #[test]
+    fn test_message_stanza() {
+        let message = MucMessage {
+            muc: "annonces@conference.prologin.org".to_string(),
+            body: "[repo] <b> & co".to_string(),
+        };
+
+        assert_eq!(
+            message_stanza("prololo.prologin.org", &message),
+            "<message from='prololo.prologin.org' to='annonces@conference.prologin.org' type='groupchat'><body>[repo] &lt;b&gt; &amp; co</body></message>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_component() {
+        let config = XmppConfig {
+            server: "localhost:5347".to_string(),
+            component: "prololo.prologin.org".to_string(),
+            secret: "sesame".to_string(),
+            nickname: "prololo".to_string(),
+            rooms: vec![(
+                "announces".to_string(),
+                "annonces@conference.prologin.org".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let (sender, mut receiver) = unbounded_channel();
+        let (client, server) = duplex(4096);
+
+        sender
+            .send(MucMessage {
+                muc: "annonces@conference.prologin.org".to_string(),
+                body: "hello".to_string(),
+            })
+            .unwrap();
+        drop(sender);
+
+        let component =
+            tokio::spawn(async move { serve_component(client, &config, &mut receiver).await });
+
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+        let mut received = vec![];
+
+        server_read.read_until(b'>', &mut received).await.unwrap();
+        server_write
+            .write_all(
+                b"<stream:stream xmlns:stream='http://etherx.jabber.org/streams' id='3BF96D32'>",
+            )
+            .await
+            .unwrap();
+        server_read.read_until(b'>', &mut received).await.unwrap();
+        server_read.read_until(b'>', &mut received).await.unwrap();
+        server_write.write_all(b"<handshake/>").await.unwrap();
+
+        component.await.unwrap().unwrap();
+        server_read.read_to_end(&mut received).await.unwrap();
+
+        let received = String::from_utf8(received).unwrap();
+        assert!(
+            received.contains("<handshake>7a98dc4c9e92493d7fd66a25364c862637789c45</handshake>")
+        );
+        assert!(received.contains("to='annonces@conference.prologin.org/prololo'"));
+        assert!(received.ends_with("<body>hello</body></message>"));
+    }
+}